@@ -0,0 +1,329 @@
+//! 本地归档的校验清单：导出某段时间内文件的大小与 SHA-256，供收到数据副本的一方核对完整性
+
+use crate::checksum::{ChecksumAlgorithm, hash_files};
+use crate::himawari::HsdFilename;
+use crate::layout::RemoteLayout;
+use crate::product::ProductType;
+use crate::report::REPORTS_DIR;
+use crate::storage::QUARANTINE_DIR;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 清单中的一个文件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ArchiveEntry {
+    /// 相对于归档根目录的路径，以 `/` 分隔
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    /// 本地文件名按模板重写过时，对应的远程文件名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_name: Option<String>,
+}
+
+/// 归档校验清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub product: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
+    pub total_bytes: u64,
+    pub files: Vec<ArchiveEntry>,
+    /// 全部文件条目的 SHA-256，用于发现清单本身被改动或损坏
+    pub digest: String,
+}
+
+impl ArchiveManifest {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// 按 `root` 下的文件逐一核对大小与校验和
+    pub fn verify(&self, root: &Path) -> VerifyReport {
+        let mut report = VerifyReport {
+            root: root.to_path_buf(),
+            digest_valid: entries_digest(&self.files) == self.digest,
+            ..VerifyReport::default()
+        };
+        // 大小相符的文件再计算校验和，按 CPU 核心数同时计算
+        let mut pending = Vec::new();
+        for entry in &self.files {
+            let path = root.join(&entry.path);
+            match fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => {
+                    if metadata.len() != entry.size {
+                        report.size_mismatch.push(entry.path.clone());
+                    } else {
+                        pending.push((entry, path));
+                    }
+                }
+                _ => report.missing.push(entry.path.clone()),
+            }
+        }
+        let paths: Vec<PathBuf> = pending.iter().map(|(_, path)| path.clone()).collect();
+        let checksums = hash_files(&paths, ChecksumAlgorithm::Sha256, |done| {
+            if done % PROGRESS_EVERY == 0 {
+                tracing::info!("已核对 {}/{} 个文件", done, paths.len());
+            }
+        });
+        for ((entry, _), checksum) in pending.into_iter().zip(checksums) {
+            match checksum {
+                Ok(sha256) if sha256 == entry.sha256 => report.verified += 1,
+                Ok(_) => report.checksum_mismatch.push(entry.path.clone()),
+                Err(e) => {
+                    tracing::warn!("读取文件失败 {}: {}", entry.path, e);
+                    report.unreadable.push(entry.path.clone());
+                }
+            }
+        }
+        report
+    }
+}
+
+/// 每处理多少个文件输出一次进度
+const PROGRESS_EVERY: usize = 500;
+
+/// 按清单核对归档的结果
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub root: PathBuf,
+    /// 大小与校验和都一致的文件数
+    pub verified: usize,
+    pub missing: Vec<String>,
+    pub size_mismatch: Vec<String>,
+    pub checksum_mismatch: Vec<String>,
+    pub unreadable: Vec<String>,
+    /// 清单的条目摘要是否与记录的一致
+    pub digest_valid: bool,
+}
+
+/// 需要从服务器重新获取的文件：对应的远程路径（无法确定时为空）及其时次
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchItem {
+    pub path: String,
+    pub slot: Option<NaiveDateTime>,
+    pub remote_path: Option<String>,
+}
+
+impl VerifyReport {
+    pub fn all_passed(&self) -> bool {
+        self.digest_valid
+            && self.missing.is_empty()
+            && self.size_mismatch.is_empty()
+            && self.checksum_mismatch.is_empty()
+            && self.unreadable.is_empty()
+    }
+
+    /// 缺失、损坏或无法读取的文件
+    pub fn failed_paths(&self) -> impl Iterator<Item = &String> {
+        self.missing
+            .iter()
+            .chain(&self.size_mismatch)
+            .chain(&self.checksum_mismatch)
+            .chain(&self.unreadable)
+    }
+
+    /// 为缺失与损坏的文件确定远程路径，用于从服务器补齐
+    ///
+    /// HSD 文件按文件名中的观测时间确定时次；其他产品在清单时间段内按文件名模式匹配时次。
+    /// 文件名重写过的文件按清单记录的远程文件名确定。
+    pub fn fetch_list(
+        &self,
+        manifest: &ArchiveManifest,
+        product: &ProductType,
+        layout: &dyn RemoteLayout,
+        areas: &[String],
+    ) -> Vec<FetchItem> {
+        let slots = product
+            .time_list(manifest.start, manifest.end)
+            .unwrap_or_default();
+        self.failed_paths()
+            .map(|path| {
+                let filename = manifest
+                    .files
+                    .iter()
+                    .find(|entry| &entry.path == path)
+                    .and_then(|entry| entry.remote_name.as_deref())
+                    .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(path));
+                let slot = match HsdFilename::parse(filename) {
+                    Some(hsd) if product.has_bands => Some(hsd.datetime),
+                    _ => slots.iter().copied().find(|slot| {
+                        areas
+                            .iter()
+                            .any(|area| product.matches(filename, slot, area))
+                    }),
+                };
+                let remote_path = slot.map(|slot| {
+                    format!(
+                        "{}/{}",
+                        layout.directory(product, &slot).trim_end_matches('/'),
+                        filename
+                    )
+                });
+                FetchItem {
+                    path: path.clone(),
+                    slot,
+                    remote_path,
+                }
+            })
+            .collect()
+    }
+
+    pub fn print_report(&self) {
+        println!("=== 归档核对结果 ({}) ===", self.root.display());
+        if !self.digest_valid {
+            println!("清单摘要不一致：清单文件已被改动或损坏，以下结果不可信");
+        }
+        println!("校验通过: {} 个文件", self.verified);
+        for (label, paths) in [
+            ("缺失", &self.missing),
+            ("大小不一致", &self.size_mismatch),
+            ("校验和不一致", &self.checksum_mismatch),
+            ("无法读取", &self.unreadable),
+        ] {
+            if !paths.is_empty() {
+                println!("{}: {} 个文件", label, paths.len());
+                for path in paths {
+                    println!("  {}", path);
+                }
+            }
+        }
+        if self.all_passed() {
+            println!("归档完整，与清单一致");
+        }
+    }
+}
+
+/// 为 `root` 下属于该产品、观测时间在 `start` 到 `end`（含）之间的文件生成校验清单
+///
+/// HSD 文件按文件名中的观测时间筛选；其他产品按文件名模式与各时次、观测区域匹配。
+/// 未完成的下载、来源信息文件、被取代或因冲突改名的旧文件、隔离区与运行报告中的文件不会被匹配。`renamed` 为按文件名模板重写过的
+/// 本地文件对应的远程文件名（见 [`crate::manifest::Manifest::renamed_files`]），这些文件按远程文件名筛选。
+pub fn export_archive(
+    root: &Path,
+    product: &ProductType,
+    areas: &[String],
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    renamed: &HashMap<PathBuf, String>,
+) -> Result<ArchiveManifest, Box<dyn Error>> {
+    let slots = product.time_list(start, end)?;
+    let mut paths = Vec::new();
+    collect_files(root, &mut paths)?;
+    let excluded = [root.join(QUARANTINE_DIR), root.join(REPORTS_DIR)];
+    paths.retain(|path| !excluded.iter().any(|dir| path.starts_with(dir)));
+
+    let mut selected: Vec<(String, PathBuf)> = paths
+        .into_iter()
+        .filter(|path| {
+            let local_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if is_auxiliary_file(&local_name) {
+                return false;
+            }
+            let filename = renamed
+                .get(path)
+                .map_or(local_name.as_ref(), String::as_str);
+            match HsdFilename::parse(filename) {
+                Some(hsd) if product.has_bands => {
+                    hsd.datetime >= start && hsd.datetime <= end && areas.contains(&hsd.area)
+                }
+                _ => slots.iter().any(|slot| {
+                    areas
+                        .iter()
+                        .any(|area| product.matches(filename, slot, area))
+                }),
+            }
+        })
+        .map(|path| (relative_path(root, &path), path))
+        .collect();
+    selected.sort();
+
+    let paths: Vec<PathBuf> = selected.iter().map(|(_, path)| path.clone()).collect();
+    let checksums = hash_files(&paths, ChecksumAlgorithm::Sha256, |done| {
+        if done % PROGRESS_EVERY == 0 {
+            tracing::info!("已计算校验和 {}/{} 个文件", done, paths.len());
+        }
+    });
+    let mut files = Vec::with_capacity(selected.len());
+    for ((relative, path), sha256) in selected.into_iter().zip(checksums) {
+        files.push(ArchiveEntry {
+            size: fs::metadata(&path)?.len(),
+            sha256: sha256?,
+            remote_name: renamed.get(&path).cloned(),
+            path: relative,
+        });
+    }
+
+    Ok(ArchiveManifest {
+        product: product.name.clone(),
+        start,
+        end,
+        created_at: Utc::now(),
+        total_bytes: files.iter().map(|entry| entry.size).sum(),
+        digest: entries_digest(&files),
+        files,
+    })
+}
+
+/// 未完成的下载、来源信息文件、被取代的旧版本与因冲突改名保留的文件，不属于归档数据
+fn is_auxiliary_file(filename: &str) -> bool {
+    filename.ends_with(".downloading")
+        || filename.ends_with(".meta.json")
+        || filename.contains(".superseded-")
+        || filename.contains(".conflict-")
+}
+
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), paths)?;
+        } else if file_type.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 按顺序对每个条目的 `路径\t大小\t校验和\n` 计算 SHA-256
+fn entries_digest(files: &[ArchiveEntry]) -> String {
+    let mut hasher = Sha256::new();
+    for entry in files {
+        hasher.update(format!("{}\t{}\t{}\n", entry.path, entry.size, entry.sha256).as_bytes());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,286 @@
+//! 归档自检：按观测时间表与配置的波段、观测区域核对最近一段时间的本地归档，列出超过宽限期仍不完整的时次。
+//! 适合由 cron 或 systemd 定时器每天运行一次 `audit` 命令；`check` 命令核对任意时间段并可导出缺少的分段
+
+use crate::config::Config;
+use crate::himawari::{self, HsdFilename, SegmentKey};
+use crate::manifest::Manifest;
+use crate::report::REPORTS_DIR;
+use crate::retention::collect_files;
+use crate::storage::{LocalFileStorage, QUARANTINE_DIR};
+use crate::timelist::latest_published_slot;
+use chrono::{Duration, NaiveDateTime, Utc};
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 一个文件不全的时次与观测区域
+#[derive(Debug, Clone)]
+pub struct IncompleteSlot {
+    pub datetime: NaiveDateTime,
+    pub area: String,
+    pub found: usize,
+    pub expected: usize,
+    /// 缺少分段文件的波段，仅按波段拆分的产品
+    pub missing_bands: Vec<String>,
+    /// 各波段缺少的分段，与 `missing_bands` 一一对应
+    pub missing_segments: Vec<MissingSegments>,
+}
+
+/// 一个波段缺少的分段
+#[derive(Debug, Clone)]
+pub struct MissingSegments {
+    pub band: String,
+    /// 缺少的分段编号，从 1 开始
+    pub segments: Vec<u32>,
+    pub total_segments: u32,
+}
+
+impl std::fmt::Display for MissingSegments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.segments.len() as u32 == self.total_segments {
+            return write!(f, "{} 全部 {} 个分段", self.band, self.total_segments);
+        }
+        let segments: Vec<String> = self
+            .segments
+            .iter()
+            .map(|segment| format!("S{:02}{:02}", segment, self.total_segments))
+            .collect();
+        write!(f, "{} {}", self.band, segments.join(", "))
+    }
+}
+
+/// 一次归档自检的结果
+#[derive(Debug, Clone)]
+pub struct AuditReport {
+    pub product: String,
+    /// 核对的第一个与最后一个时次
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// 核对的时次与观测区域组合数
+    pub checked_slots: usize,
+    pub incomplete: Vec<IncompleteSlot>,
+}
+
+impl AuditReport {
+    pub fn is_complete(&self) -> bool {
+        self.incomplete.is_empty()
+    }
+
+    /// 不完整的时次，按时间排列，多个观测区域不完整的时次只列一次
+    pub fn incomplete_slots(&self) -> Vec<NaiveDateTime> {
+        self.incomplete
+            .iter()
+            .map(|slot| slot.datetime)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// 不完整的时次中缺少的全部分段，仅按波段拆分的产品
+    pub fn missing_segment_keys(&self) -> HashSet<SegmentKey> {
+        self.incomplete
+            .iter()
+            .flat_map(|slot| {
+                slot.missing_segments.iter().flat_map(move |missing| {
+                    missing.segments.iter().map(move |segment| {
+                        (
+                            slot.datetime,
+                            missing.band.clone(),
+                            slot.area.clone(),
+                            *segment,
+                        )
+                    })
+                })
+            })
+            .collect()
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "=== {} 归档自检 {} 至 {} ===",
+            self.product,
+            self.start.format("%Y-%m-%d %H:%M"),
+            self.end.format("%Y-%m-%d %H:%M")
+        );
+        println!(
+            "核对 {} 个时次，{} 个不完整",
+            self.checked_slots,
+            self.incomplete.len()
+        );
+        for slot in &self.incomplete {
+            let mut line = format!(
+                "  {} {}  {}/{} 个文件",
+                slot.datetime.format("%Y-%m-%d %H:%M"),
+                slot.area,
+                slot.found,
+                slot.expected
+            );
+            if !slot.missing_segments.is_empty() {
+                let missing: Vec<String> = slot
+                    .missing_segments
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                line.push_str(&format!("，缺少 {}", missing.join("；")));
+            }
+            println!("{}", line);
+        }
+    }
+
+    /// 将缺少的文件写入 CSV，每个缺少的分段一行，`filename` 为按观测时间推定的 HSD 文件名；
+    /// 不按波段拆分的产品每个不完整的时次一行，波段与分段为空
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut csv = String::from("datetime,area,band,segment,total_segments,filename\n");
+        for slot in &self.incomplete {
+            let datetime = slot.datetime.format("%Y-%m-%d %H:%M");
+            if slot.missing_segments.is_empty() {
+                csv.push_str(&format!("{},{},,,,\n", datetime, slot.area));
+                continue;
+            }
+            for missing in &slot.missing_segments {
+                let expected = HsdFilename::expected(&slot.datetime, &missing.band, &slot.area);
+                for segment in &missing.segments {
+                    let filename = expected
+                        .iter()
+                        .find(|hsd| hsd.segment == *segment)
+                        .map(ToString::to_string)
+                        .unwrap_or_default();
+                    csv.push_str(&format!(
+                        "{},{},{},{},{},{}\n",
+                        datetime,
+                        slot.area,
+                        missing.band,
+                        segment,
+                        missing.total_segments,
+                        filename
+                    ));
+                }
+            }
+        }
+        fs::write(path, csv)
+    }
+}
+
+/// 自检的时间范围：观测时间在最近 `hours` 小时内，且距今已超过 `grace_minutes` 分钟、应当已经下载完成的时次
+pub fn audit_window(hours: u64, grace_minutes: u64) -> (NaiveDateTime, NaiveDateTime) {
+    let end = latest_published_slot(grace_minutes as i64);
+    let start = Utc::now().naive_utc() - Duration::hours(hours as i64);
+    (start, end)
+}
+
+/// 核对 `start` 到 `end`（含）之间每个时次与观测区域在本地归档中的文件数。
+///
+/// 按波段拆分的产品逐一核对配置的波段（未设置时为全部波段）应有的分段文件；其他产品按文件名模式匹配，
+/// 文件数少于 `expected_files_per_slot`（未设置时为 1）视为不完整。全圆盘的例行维护时次不核对。
+/// 隔离区、运行报告、暂存目录、入站暂存区、未完成的下载与来源信息文件不计入
+pub fn audit_archive(
+    config: &Config,
+    storage: &LocalFileStorage,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<AuditReport, Box<dyn Error>> {
+    let product = config.product()?;
+    let slots = if start <= end {
+        product.time_list(start, end)?
+    } else {
+        Vec::new()
+    };
+    let renamed = Manifest::load(&storage.base_path)
+        .map(|manifest| manifest.renamed_files())
+        .unwrap_or_default();
+
+    let mut excluded = vec![
+        storage.base_path.join(QUARANTINE_DIR),
+        storage.base_path.join(REPORTS_DIR),
+    ];
+    excluded.extend(storage.staging_path.iter().cloned());
+    excluded.extend(storage.spool_path.iter().cloned());
+    let mut files = Vec::new();
+    collect_files(&storage.base_path, &excluded, &mut files)?;
+
+    // 文件名按模板重写过的按下载清单中的远程文件名核对
+    let filenames: Vec<String> = files
+        .iter()
+        .filter_map(|file| {
+            let local_name = file.file_name()?.to_string_lossy().to_string();
+            if local_name.ends_with(&storage.temp_suffix) || local_name.ends_with(".meta.json") {
+                return None;
+            }
+            Some(renamed.get(file).cloned().unwrap_or(local_name))
+        })
+        .collect();
+
+    let bands: Vec<String> = if config.download.bands.is_empty() {
+        himawari::BANDS.map(String::from).to_vec()
+    } else {
+        config.download.bands.clone()
+    };
+    let segments: HashSet<SegmentKey> = filenames
+        .iter()
+        .filter_map(|filename| HsdFilename::parse(filename))
+        .map(|hsd| hsd.segment_key())
+        .collect();
+
+    let mut report = AuditReport {
+        product: product.name.clone(),
+        start: slots.first().copied().unwrap_or(start),
+        end: slots.last().copied().unwrap_or(end),
+        checked_slots: 0,
+        incomplete: Vec::new(),
+    };
+    for slot in &slots {
+        for area in &config.download.areas {
+            if area == "FLDK" && himawari::is_housekeeping_slot(slot) {
+                continue;
+            }
+            report.checked_slots += 1;
+            let (mut found, mut expected) = (0, 0);
+            let (mut missing_bands, mut missing_segments) = (Vec::new(), Vec::new());
+            if product.has_bands {
+                for band in &bands {
+                    let wanted = HsdFilename::expected(slot, band, area);
+                    let missing: Vec<u32> = wanted
+                        .iter()
+                        .filter(|hsd| !segments.contains(&hsd.segment_key()))
+                        .map(|hsd| hsd.segment)
+                        .collect();
+                    found += wanted.len() - missing.len();
+                    expected += wanted.len();
+                    if !missing.is_empty() {
+                        missing_bands.push(band.clone());
+                        missing_segments.push(MissingSegments {
+                            band: band.clone(),
+                            segments: missing,
+                            total_segments: wanted.len() as u32,
+                        });
+                    }
+                }
+            } else {
+                found = filenames
+                    .iter()
+                    .filter(|filename| product.matches(filename, slot, area))
+                    .count();
+                expected = config.download.expected_files_per_slot.unwrap_or(1);
+            }
+            if found < expected {
+                report.incomplete.push(IncompleteSlot {
+                    datetime: *slot,
+                    area: area.clone(),
+                    found,
+                    expected,
+                    missing_bands,
+                    missing_segments,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,186 @@
+//! 查询服务器上某个月实际存在的日期与小时目录，用于了解数据提供方的保留期限
+
+use crate::config::Config;
+use crate::layout::RemoteLayout;
+use crate::product::ProductType;
+use crate::transport::RemoteConnection;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+
+/// 某个月的远程数据可用情况
+#[derive(Debug, Clone)]
+pub struct AvailabilityReport {
+    pub product: String,
+    /// 月份的第一天
+    pub month: NaiveDate,
+    /// 目录布局是否按小时（或更细）分目录；为 false 时只能确定日期是否存在
+    pub hourly: bool,
+    /// 存在数据目录的日期及其中存在目录的小时（UTC）
+    pub days: BTreeMap<NaiveDate, BTreeSet<u32>>,
+    /// 已经过去但服务器上没有目录的日期
+    pub missing_days: Vec<NaiveDate>,
+}
+
+impl AvailabilityReport {
+    pub fn print_report(&self) {
+        println!(
+            "=== {} {} 远程可用数据 ===",
+            self.product,
+            self.month.format("%Y-%m")
+        );
+        if self.days.is_empty() {
+            println!("服务器上没有该月的数据目录");
+            return;
+        }
+        for (day, hours) in &self.days {
+            if self.hourly {
+                println!(
+                    "{}  {:>2} 小时: {}",
+                    day,
+                    hours.len(),
+                    format_hour_ranges(hours)
+                );
+            } else {
+                println!("{}", day);
+            }
+        }
+        if !self.missing_days.is_empty() {
+            let missing: Vec<String> = self.missing_days.iter().map(ToString::to_string).collect();
+            println!("缺少的日期: {}", missing.join(", "));
+        }
+    }
+}
+
+/// 将小时集合格式化为区间，例如 `00-05, 08, 10-23`
+fn format_hour_ranges(hours: &BTreeSet<u32>) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &hour in hours {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == hour => *end = hour,
+            _ => ranges.push((hour, hour)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                format!("{:02}", start)
+            } else {
+                format!("{:02}-{:02}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 解析 `YYYY-MM` 格式的月份，返回该月第一天
+pub fn parse_month(month: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| format!("无效的月份 {}，格式应为 YYYY-MM", month))
+}
+
+/// 列出某个月在服务器上实际存在的日期与小时目录
+///
+/// 每天只列出一次时次目录的上级目录，时次目录出现在其中即视为该时次可用。
+pub fn list_available(
+    connection: &dyn RemoteConnection,
+    product: &ProductType,
+    layout: &dyn RemoteLayout,
+    month: NaiveDate,
+) -> Result<AvailabilityReport, Box<dyn Error>> {
+    let month = month.with_day(1).unwrap_or(month);
+    let now = Utc::now().naive_utc();
+    let slots_per_day = 24 * 60 / product.cadence_minutes;
+
+    let directory = |datetime: &NaiveDateTime| {
+        layout
+            .directory(product, datetime)
+            .trim_end_matches('/')
+            .to_string()
+    };
+    let month_start = month.and_hms_opt(0, 0, 0).unwrap_or_default();
+    if directory(&month_start) == directory(&(month_start + Duration::days(1))) {
+        return Err("当前目录布局不按日期分目录，无法列出可用日期".into());
+    }
+
+    let mut report = AvailabilityReport {
+        product: product.name.clone(),
+        month,
+        hourly: false,
+        days: BTreeMap::new(),
+        missing_days: Vec::new(),
+    };
+    let mut listings: HashMap<String, Option<HashSet<String>>> = HashMap::new();
+
+    for day in month
+        .iter_days()
+        .take_while(|day| day.month() == month.month())
+    {
+        let day_start = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+        if day_start > now {
+            break;
+        }
+
+        let slots: Vec<(NaiveDateTime, String)> = (0..slots_per_day)
+            .map(|index| {
+                day_start
+                    + Duration::minutes(
+                        product.offset_minutes.rem_euclid(product.cadence_minutes)
+                            + index * product.cadence_minutes,
+                    )
+            })
+            .map(|slot| (slot, directory(&slot)))
+            .collect();
+        if slots.iter().any(|(_, dir)| *dir != slots[0].1) {
+            report.hourly = true;
+        }
+
+        let mut hours = BTreeSet::new();
+        for (slot, dir) in &slots {
+            let Some((parent, _)) = dir.rsplit_once('/') else {
+                continue;
+            };
+            let listing = listings.entry(parent.to_string()).or_insert_with(|| {
+                connection
+                    .list_directory(if parent.is_empty() { "/" } else { parent })
+                    .ok()
+                    .map(|paths| {
+                        paths
+                            .iter()
+                            .map(|path| path.trim_end_matches('/').to_string())
+                            .collect()
+                    })
+            });
+            if listing
+                .as_ref()
+                .is_some_and(|listing| listing.contains(dir))
+            {
+                hours.insert(slot.hour());
+            }
+        }
+
+        if hours.is_empty() {
+            report.missing_days.push(day);
+        } else {
+            report.days.insert(day, hours);
+        }
+    }
+
+    Ok(report)
+}
+
+/// 按配置连接服务器，列出当前产品某个月的可用数据
+pub fn run_available(
+    config: &Config,
+    month: NaiveDate,
+) -> Result<AvailabilityReport, Box<dyn Error>> {
+    let product = config.product()?;
+    let layout = config.remote_layout();
+    let connection = config.server.transport.connect(
+        &config.get_host_with_port(),
+        &config.server.username,
+        &config.server.credentials(),
+    )?;
+    list_available(connection.as_ref(), &product, layout.as_ref(), month)
+}
@@ -0,0 +1,108 @@
+//! 存储后端：下载的文件默认保存在 `base_path` 下的本地目录；配置了其他后端（例如 S3 兼容的对象存储）时，
+//! 文件从服务器读取后直接写入后端，不经过本地磁盘。`base_path` 仍保存下载清单、状态文件与运行报告
+//!
+//! 对象的键为文件在本地归档中的相对路径（按 `organize_by_time`、观测区域子目录与文件名模板生成），
+//! 以 `/` 分隔，见 `LocalFileStorage::archive_key`。
+
+#[cfg(feature = "s3")]
+mod s3;
+
+#[cfg(feature = "s3")]
+pub use s3::*;
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::Write;
+use std::sync::Arc;
+
+/// 存储后端
+///
+/// 新的存储后端只需实现该 trait 并通过 `DownloadOptions::with_backend` 传入。
+pub trait StorageBackend: Debug + Send + Sync {
+    /// 后端名称，用于日志
+    fn name(&self) -> &str;
+
+    /// 对象的完整位置，用于日志，例如 `s3://bucket/2024/01/01/00/HS_H09_...`
+    fn location(&self, key: &str) -> String;
+
+    /// 已保存的对象大小，对象不存在时返回 `None`
+    fn size(&self, key: &str) -> Result<Option<u64>, Box<dyn Error>>;
+
+    /// 开始写入一个对象，`size` 为预期的大小；调用 [`ObjectWriter::finish`] 之前对象不可见
+    fn writer(&self, key: &str, size: u64) -> Result<Box<dyn ObjectWriter>, Box<dyn Error>>;
+}
+
+/// 写入中的对象
+pub trait ObjectWriter: Write + Send {
+    /// 写入完成，对象以完整的内容出现在后端
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>>;
+
+    /// 放弃写入，不留下不完整的对象
+    fn abort(self: Box<Self>);
+}
+
+/// 配置文件中的存储后端
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageConfig {
+    /// 保存在 `base_path` 下
+    #[default]
+    Local,
+    /// S3 兼容的对象存储（`s3` 特性）；访问密钥取自环境变量 `AWS_ACCESS_KEY_ID`、
+    /// `AWS_SECRET_ACCESS_KEY` 与可选的 `AWS_SESSION_TOKEN`
+    S3 {
+        bucket: String,
+        region: String,
+        /// 服务地址，例如 `https://minio.example.org:9000`；不设置时为 AWS 的
+        /// `https://s3.<region>.amazonaws.com`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        endpoint: Option<String>,
+        /// 对象键的前缀，例如 `himawari/`
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        prefix: String,
+    },
+}
+
+impl StorageConfig {
+    pub fn is_local(&self) -> bool {
+        *self == StorageConfig::Local
+    }
+
+    /// 检查配置，不读取访问密钥
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            StorageConfig::Local => Ok(()),
+            StorageConfig::S3 { .. } if !cfg!(feature = "s3") => {
+                Err("S3 存储后端未编译进当前程序，请启用 s3 特性后重新编译".to_string())
+            }
+            StorageConfig::S3 { bucket, region, .. } => {
+                if bucket.is_empty() || region.is_empty() {
+                    return Err("[storage] S3 存储后端需要设置 bucket 与 region".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// 创建存储后端，保存在本地时返回 `None`
+    pub fn build(&self) -> Result<Option<Arc<dyn StorageBackend>>, String> {
+        match self {
+            StorageConfig::Local => Ok(None),
+            #[cfg(feature = "s3")]
+            StorageConfig::S3 {
+                bucket,
+                region,
+                endpoint,
+                prefix,
+            } => {
+                let backend = S3Backend::from_env(bucket, region, endpoint.as_deref(), prefix)?;
+                Ok(Some(Arc::new(backend)))
+            }
+            #[cfg(not(feature = "s3"))]
+            StorageConfig::S3 { .. } => {
+                Err("S3 存储后端未编译进当前程序，请启用 s3 特性后重新编译".to_string())
+            }
+        }
+    }
+}
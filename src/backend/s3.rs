@@ -0,0 +1,447 @@
+//! S3 兼容的对象存储（AWS S3、MinIO、Ceph RGW 等）：按路径风格访问存储桶，请求使用 AWS 签名第 4 版。
+//! 不超过一个分片的文件用一次 PUT 上传，更大的文件分片上传，内存中只保留当前分片
+
+use super::{ObjectWriter, StorageBackend};
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+use ureq::http::{Request, Response, Uri};
+use ureq::{Agent, Body};
+
+/// 分片上传的分片大小；S3 要求除最后一片外每片不小于 5 MiB
+pub const PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// 请求签名的访问密钥
+#[derive(Clone)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// 临时凭据的会话令牌
+    pub session_token: Option<String>,
+}
+
+impl S3Credentials {
+    /// 从环境变量 `AWS_ACCESS_KEY_ID`、`AWS_SECRET_ACCESS_KEY` 与 `AWS_SESSION_TOKEN` 读取
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| env::var(name).map_err(|e| format!("环境变量 {}: {}", name, e));
+        Ok(Self {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+impl fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// S3 兼容的对象存储
+#[derive(Debug, Clone)]
+pub struct S3Backend {
+    bucket: String,
+    region: String,
+    /// 服务地址，不含末尾的 `/`
+    endpoint: String,
+    /// 参与签名的 `Host`，默认端口不写出
+    host: String,
+    prefix: String,
+    credentials: S3Credentials,
+    agent: Agent,
+}
+
+impl S3Backend {
+    pub fn new(
+        bucket: &str,
+        region: &str,
+        endpoint: Option<&str>,
+        prefix: &str,
+        credentials: S3Credentials,
+    ) -> Result<Self, String> {
+        if bucket.is_empty() || region.is_empty() {
+            return Err("S3 存储后端需要设置 bucket 与 region".to_string());
+        }
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+            None => format!("https://s3.{}.amazonaws.com", region),
+        };
+        let uri: Uri = endpoint
+            .parse()
+            .map_err(|e| format!("S3 服务地址 {} 无效: {}", endpoint, e))?;
+        let (Some(scheme), Some(authority)) = (uri.scheme_str(), uri.authority()) else {
+            return Err(format!("S3 服务地址 {} 需要包含协议与主机名", endpoint));
+        };
+        let default_port = match scheme {
+            "http" => 80,
+            _ => 443,
+        };
+        let host = match authority.port_u16() {
+            Some(port) if port != default_port => authority.as_str().to_string(),
+            _ => authority.host().to_string(),
+        };
+        let agent: Agent = Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_connect(Some(Duration::from_secs(30)))
+            .timeout_recv_response(Some(Duration::from_secs(300)))
+            .build()
+            .into();
+        Ok(Self {
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            endpoint,
+            host,
+            prefix: prefix.to_string(),
+            credentials,
+            agent,
+        })
+    }
+
+    /// 访问密钥取自环境变量，见 [`S3Credentials::from_env`]
+    pub fn from_env(
+        bucket: &str,
+        region: &str,
+        endpoint: Option<&str>,
+        prefix: &str,
+    ) -> Result<Self, String> {
+        let credentials =
+            S3Credentials::from_env().map_err(|e| format!("读取 S3 访问密钥失败: {}", e))?;
+        Self::new(bucket, region, endpoint, prefix, credentials)
+    }
+
+    /// 存储桶内的完整对象键
+    fn object_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// 发送签名后的请求；`query` 为按名称排序的查询参数
+    fn send(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Response<Body>, Box<dyn Error>> {
+        let path = format!("/{}/{}", self.bucket, self.object_key(key));
+        let canonical_uri = uri_encode(&path, false);
+        let canonical_query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", uri_encode(name, true), uri_encode(value, true)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let url = if canonical_query.is_empty() {
+            format!("{}{}", self.endpoint, canonical_uri)
+        } else {
+            format!("{}{}?{}", self.endpoint, canonical_uri, canonical_query)
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex(&Sha256::digest(body));
+        let mut headers = vec![
+            ("host", self.host.clone()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = sign(
+            &self.credentials,
+            &self.region,
+            &amz_date,
+            method,
+            &canonical_uri,
+            &canonical_query,
+            &headers,
+            &payload_hash,
+        );
+
+        let mut request = Request::builder().method(method).uri(&url);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        let request = request.header("authorization", authorization).body(body)?;
+        Ok(self.agent.run(request)?)
+    }
+
+    /// 发送请求，HTTP 状态不是 2xx 时返回服务端的错误信息
+    fn expect_success(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Response<Body>, Box<dyn Error>> {
+        let mut response = self.send(method, key, query, body)?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let text = response.body_mut().read_to_string().unwrap_or_default();
+        Err(format!(
+            "{} {}: HTTP {} {}",
+            method,
+            self.location(key),
+            status.as_u16(),
+            error_message(&text)
+                .unwrap_or_else(|| status.canonical_reason().unwrap_or_default().to_string())
+        )
+        .into())
+    }
+}
+
+impl StorageBackend for S3Backend {
+    fn name(&self) -> &str {
+        "s3"
+    }
+
+    fn location(&self, key: &str) -> String {
+        format!("s3://{}/{}", self.bucket, self.object_key(key))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Box<dyn Error>> {
+        let response = self.send("HEAD", key, &[], &[])?;
+        let status = response.status();
+        if status.as_u16() == 404 {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(format!("HEAD {}: HTTP {}", self.location(key), status.as_u16()).into());
+        }
+        let length = response
+            .headers()
+            .get("content-length")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("HEAD {}: 响应没有 Content-Length", self.location(key)))?;
+        Ok(Some(length))
+    }
+
+    fn writer(&self, key: &str, size: u64) -> Result<Box<dyn ObjectWriter>, Box<dyn Error>> {
+        let capacity = usize::try_from(size).unwrap_or(PART_SIZE).min(PART_SIZE);
+        Ok(Box::new(S3Writer {
+            backend: self.clone(),
+            key: key.to_string(),
+            buffer: Vec::with_capacity(capacity),
+            upload_id: None,
+            parts: Vec::new(),
+        }))
+    }
+}
+
+/// 写入中的对象：数据先写入内存中的分片，满一片后开始分片上传
+pub struct S3Writer {
+    backend: S3Backend,
+    key: String,
+    buffer: Vec<u8>,
+    /// 已开始的分片上传
+    upload_id: Option<String>,
+    /// 已上传分片的 ETag，按分片编号排列
+    parts: Vec<String>,
+}
+
+impl S3Writer {
+    fn upload_part(&mut self) -> Result<(), Box<dyn Error>> {
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let mut response =
+                    self.backend
+                        .expect_success("POST", &self.key, &[("uploads", "")], &[])?;
+                let text = response.body_mut().read_to_string()?;
+                let upload_id = xml_element(&text, "UploadId")
+                    .ok_or("创建分片上传的响应中没有 UploadId")?
+                    .to_string();
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            }
+        };
+        let part_number = (self.parts.len() + 1).to_string();
+        let response = self.backend.expect_success(
+            "PUT",
+            &self.key,
+            &[("partNumber", &part_number), ("uploadId", &upload_id)],
+            &self.buffer,
+        )?;
+        let etag = response
+            .headers()
+            .get("etag")
+            .and_then(|value| value.to_str().ok())
+            .ok_or("上传分片的响应中没有 ETag")?
+            .to_string();
+        self.parts.push(etag);
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Write for S3Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(PART_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == PART_SIZE {
+            self.upload_part()
+                .map_err(|e| io::Error::other(e.to_string()))?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ObjectWriter for S3Writer {
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        if self.upload_id.is_none() {
+            self.backend
+                .expect_success("PUT", &self.key, &[], &self.buffer)?;
+            return Ok(());
+        }
+        let result = (|| {
+            if !self.buffer.is_empty() {
+                self.upload_part()?;
+            }
+            let upload_id = self.upload_id.clone().unwrap_or_default();
+            let mut body = String::from("<CompleteMultipartUpload>");
+            for (index, etag) in self.parts.iter().enumerate() {
+                body.push_str(&format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                    index + 1,
+                    etag
+                ));
+            }
+            body.push_str("</CompleteMultipartUpload>");
+            let mut response = self.backend.expect_success(
+                "POST",
+                &self.key,
+                &[("uploadId", &upload_id)],
+                body.as_bytes(),
+            )?;
+            // 完成分片上传的请求可能返回 200 而在响应中给出错误
+            let text = response.body_mut().read_to_string()?;
+            if let Some(message) = text
+                .contains("<Error>")
+                .then(|| error_message(&text))
+                .flatten()
+            {
+                return Err(format!(
+                    "完成分片上传 {}: {}",
+                    self.backend.location(&self.key),
+                    message
+                )
+                .into());
+            }
+            Ok(())
+        })();
+        if result.is_err() {
+            self.abort();
+        }
+        result
+    }
+
+    fn abort(self: Box<Self>) {
+        if let Some(upload_id) = &self.upload_id
+            && let Err(e) =
+                self.backend
+                    .expect_success("DELETE", &self.key, &[("uploadId", upload_id)], &[])
+        {
+            tracing::warn!("取消分片上传失败，存储桶中可能留有未完成的分片: {}", e);
+        }
+    }
+}
+
+/// 计算请求的 `Authorization` 头；`headers` 为参与签名的请求头，名称为小写并按名称排序
+#[allow(clippy::too_many_arguments)]
+fn sign(
+    credentials: &S3Credentials,
+    region: &str,
+    amz_date: &str,
+    method: &str,
+    canonical_uri: &str,
+    canonical_query: &str,
+    headers: &[(&str, String)],
+    payload_hash: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method, canonical_uri, canonical_query, canonical_headers, signed_headers, payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, b"s3");
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    )
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(key).expect("HMAC 接受任意长度的密钥");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 按签名第 4 版的规则编码：保留字母、数字与 `-._~`，路径中另保留 `/`
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// XML 中第一个 `element` 元素的文本
+fn xml_element<'a>(xml: &'a str, element: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", element))? + element.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", element))?;
+    Some(&xml[start..end])
+}
+
+/// 服务端错误响应中的 `Code` 与 `Message`
+fn error_message(xml: &str) -> Option<String> {
+    let code = xml_element(xml, "Code")?;
+    Some(match xml_element(xml, "Message") {
+        Some(message) => format!("{}: {}", code, message),
+        None => code.to_string(),
+    })
+}
@@ -0,0 +1,324 @@
+//! 文件校验和：导出的校验清单固定使用 SHA-256，来源信息文件可改用更快的 BLAKE3 或 XXH3（`fast-hash` 特性）。
+//! 大文件边读取边在另一个线程中计算，BLAKE3 另外在多个核心上并行计算；多个文件按 CPU 核心数同时计算
+
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// 每次读取的块大小
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// 不小于该大小的文件读取与计算分别在两个线程中进行
+const PIPELINE_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// 校验和算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumAlgorithm {
+    /// 通用的加密散列，导出的校验清单使用
+    #[default]
+    Sha256,
+    /// 加密散列，比 SHA-256 快数倍，大文件可多核并行（`fast-hash` 特性）
+    Blake3,
+    /// XXH3 128 位非加密散列，速度最快，只适合检查传输与存储中的损坏（`fast-hash` 特性）
+    Xxh3,
+}
+
+impl ChecksumAlgorithm {
+    /// 该算法是否已编译进当前程序
+    pub fn is_available(&self) -> bool {
+        match self {
+            ChecksumAlgorithm::Sha256 => true,
+            ChecksumAlgorithm::Blake3 | ChecksumAlgorithm::Xxh3 => cfg!(feature = "fast-hash"),
+        }
+    }
+
+    /// 启用该算法所需的 Cargo 特性
+    pub fn feature(&self) -> Option<&'static str> {
+        match self {
+            ChecksumAlgorithm::Sha256 => None,
+            ChecksumAlgorithm::Blake3 | ChecksumAlgorithm::Xxh3 => Some("fast-hash"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// 按 [`name`](Self::name) 查找算法
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ChecksumAlgorithm::Sha256,
+            ChecksumAlgorithm::Blake3,
+            ChecksumAlgorithm::Xxh3,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.name() == name)
+    }
+}
+
+impl fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 一个文件的校验和，在 JSON 中写作 `"<算法>": "<十六进制>"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    /// 十六进制校验和
+    pub value: String,
+}
+
+impl Checksum {
+    /// 计算文件的校验和
+    pub fn of_file(path: &Path, algorithm: ChecksumAlgorithm) -> io::Result<Self> {
+        Ok(Self {
+            algorithm,
+            value: hash_file(path, algorithm)?,
+        })
+    }
+}
+
+impl Serialize for Checksum {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.algorithm.name(), &self.value)?;
+        map.end()
+    }
+}
+
+/// 计算文件的校验和（十六进制）
+pub fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Hasher::new(algorithm)?;
+    if file.metadata()?.len() >= PIPELINE_THRESHOLD {
+        return hash_pipelined(file, hasher);
+    }
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let bytes_read = read_chunk(&mut file, &mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hasher.finish())
+}
+
+/// 计算文件的 SHA-256 校验和
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    hash_file(path, ChecksumAlgorithm::Sha256)
+}
+
+/// 边写入边计算 SHA-256 的写入器：下载时随数据写入临时文件一并计算，完成后无需再读一遍文件
+pub struct Sha256Writer<W> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> Sha256Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    /// 计入不经过本写入器的数据，例如续传时临时文件中已有的部分
+    pub fn update_from(&mut self, mut reader: impl Read) -> io::Result<()> {
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(bytes_read) => self.hasher.update(&buffer[..bytes_read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// 已写入数据的 SHA-256 校验和（十六进制）
+    pub fn finish(self) -> String {
+        self.hasher.finish()
+    }
+}
+
+impl<W: Write> Write for Sha256Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 按 CPU 核心数同时计算多个文件的校验和，结果与 `paths` 一一对应；每算完一个文件以已完成的文件数调用 `progress`
+pub fn hash_files(
+    paths: &[PathBuf],
+    algorithm: ChecksumAlgorithm,
+    progress: impl Fn(usize) + Sync,
+) -> Vec<io::Result<String>> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let mut results: Vec<Option<io::Result<String>>> = paths.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut hashed = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else {
+                            return hashed;
+                        };
+                        hashed.push((index, hash_file(path, algorithm)));
+                        progress(completed.fetch_add(1, Ordering::Relaxed) + 1);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            match worker.join() {
+                Ok(hashed) => {
+                    for (index, result) in hashed {
+                        results[index] = Some(result);
+                    }
+                }
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("每个文件都已计算"))
+        .collect()
+}
+
+/// 读取与计算分别在两个线程中进行，两块缓冲区轮流使用，计算时不必等待磁盘读取
+fn hash_pipelined(mut file: File, mut hasher: Hasher) -> io::Result<String> {
+    let (filled_tx, filled_rx) = mpsc::sync_channel::<Vec<u8>>(1);
+    let (empty_tx, empty_rx) = mpsc::channel::<Vec<u8>>();
+    for _ in 0..2 {
+        let _ = empty_tx.send(vec![0u8; CHUNK_SIZE]);
+    }
+    thread::scope(|scope| {
+        let hashing = scope.spawn(move || {
+            for chunk in filled_rx {
+                hasher.update(&chunk);
+                let _ = empty_tx.send(chunk);
+            }
+            hasher
+        });
+        let read = (|| {
+            // 计算线程异常退出时空缓冲区的通道随之关闭
+            while let Ok(mut buffer) = empty_rx.recv() {
+                buffer.resize(CHUNK_SIZE, 0);
+                let bytes_read = read_chunk(&mut file, &mut buffer)?;
+                if bytes_read == 0 {
+                    break;
+                }
+                buffer.truncate(bytes_read);
+                if filled_tx.send(buffer).is_err() {
+                    break;
+                }
+            }
+            Ok::<_, io::Error>(())
+        })();
+        drop(filled_tx);
+        let hasher = hashing
+            .join()
+            .map_err(|_| io::Error::other("计算校验和的线程异常退出"))?;
+        read.map(|()| hasher.finish())
+    })
+}
+
+/// 读满 `buffer`，到达文件末尾时可能不满，返回读取的字节数
+fn read_chunk(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(bytes_read) => filled += bytes_read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    #[cfg(feature = "fast-hash")]
+    Blake3(Box<blake3::Hasher>),
+    #[cfg(feature = "fast-hash")]
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    fn new(algorithm: ChecksumAlgorithm) -> io::Result<Self> {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Ok(Hasher::Sha256(Sha256::new())),
+            #[cfg(feature = "fast-hash")]
+            ChecksumAlgorithm::Blake3 => Ok(Hasher::Blake3(Box::default())),
+            #[cfg(feature = "fast-hash")]
+            ChecksumAlgorithm::Xxh3 => Ok(Hasher::Xxh3(Box::default())),
+            #[cfg(not(feature = "fast-hash"))]
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "校验和算法 {} 未编译进当前程序，请启用 fast-hash 特性后重新编译",
+                    algorithm
+                ),
+            )),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            // 整块数据按 BLAKE3 的树结构分给多个核心计算
+            #[cfg(feature = "fast-hash")]
+            Hasher::Blake3(hasher) => {
+                hasher.update_rayon(data);
+            }
+            #[cfg(feature = "fast-hash")]
+            Hasher::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            #[cfg(feature = "fast-hash")]
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            #[cfg(feature = "fast-hash")]
+            Hasher::Xxh3(hasher) => format!("{:032x}", hasher.digest128()),
+        }
+    }
+}
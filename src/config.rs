@@ -1,14 +1,104 @@
+use crate::backend::StorageConfig;
+use crate::checksum::ChecksumAlgorithm;
+use crate::credentials::{CredentialProvider, CredentialSource};
+use crate::destination::DestinationConfig;
+use crate::download::{
+    ConflictPolicy, DEFAULT_PROGRESS_INTERVAL, DuplicatePolicy, ParallelStrategy,
+    ProgressReporting, mbps_to_bytes_per_sec,
+};
+use crate::himawari;
+use crate::layout::{FileRules, LayoutConfig, RemoteLayout};
+use crate::manifest::SupersededPolicy;
+use crate::naming::FilenameTemplate;
+use crate::pipeline::{Decompress, PipelineStage, StageConfig};
+use crate::product::ProductType;
+use crate::timelist::{
+    PUBLISH_DELAY_MINUTES, latest_published_slot, parse_time, time_list_aligned,
+};
+use crate::transport::{Credentials, Transport};
+use chrono::{NaiveDateTime, NaiveTime};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
+    /// 用户名，匿名访问的传输可以为空
+    #[serde(default)]
     pub username: String,
+    /// 登录密码，auth_method 为 password 时使用
+    #[serde(default)]
     pub password: String,
     pub port: u16,
+    /// 传输实现：SFTP 的 libssh2 或纯 Rust 的 russh，通过 Web 服务器下载的 http、https，
+    /// FTP 服务器的 ftp、ftps，或匿名访问公开存储桶的 s3；也可写作 `source_type`
+    #[serde(default, alias = "source_type")]
+    pub transport: Transport,
+    /// 认证方式：password（密码）或 public_key（私钥文件）
+    #[serde(default)]
+    pub auth_method: AuthMethod,
+    /// 私钥文件路径，auth_method 为 public_key 时使用
+    #[serde(default)]
+    pub private_key_path: Option<String>,
+    /// 私钥的保护口令，私钥未加密时不设置
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// 密码（私钥认证时为私钥口令）的来源：config（默认，使用上面的 password 或 passphrase）、prompt（终端输入）、
+    /// { env = "变量名" }、{ command = "命令" } 或 { keyring = "服务名" }，在启动时取得一次
+    #[serde(default, skip_serializing_if = "CredentialSource::is_config")]
+    pub credential_source: CredentialSource,
+    /// 每个已认证的 SSH 会话上最多同时打开的 SFTP 通道数；大于 1 时多个线程共享一次登录，
+    /// 用于限制认证次数的服务器
+    #[serde(default = "default_channels_per_session")]
+    pub channels_per_session: usize,
+    /// 该服务器的文件约定：接受的扩展名与判断文件已上传完成的规则
+    #[serde(default, skip_serializing_if = "FileRules::is_default")]
+    pub files: FileRules,
+}
+
+impl ServerConfig {
+    /// 按认证方式生成建立连接使用的凭据
+    pub fn credentials(&self) -> Credentials {
+        match self.auth_method {
+            AuthMethod::Password => Credentials::Password(self.password.clone()),
+            AuthMethod::PublicKey => Credentials::PublicKey {
+                private_key: PathBuf::from(self.private_key_path.clone().unwrap_or_default()),
+                passphrase: self.passphrase.clone(),
+            },
+        }
+    }
+
+    /// 从 `provider` 取得密码（私钥认证时为私钥口令）替换配置中的值，之后建立的连接使用该凭据
+    pub fn load_secret(
+        &mut self,
+        provider: &dyn CredentialProvider,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let secret = provider
+            .secret(&self.host, &self.username)
+            .map_err(|e| format!("从 {} 取得密码失败: {}", provider.name(), e))?;
+        match self.auth_method {
+            AuthMethod::Password => self.password = secret,
+            AuthMethod::PublicKey => self.passphrase = (!secret.is_empty()).then_some(secret),
+        }
+        Ok(())
+    }
+}
+
+/// SSH 认证方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// 用户名与密码
+    #[default]
+    Password,
+    /// 私钥文件，适合禁用了密码登录的服务器
+    PublicKey,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,12 +107,712 @@ pub struct DownloadConfig {
     pub base_path: String,
     pub organize_by_time: bool,
     pub keep_original_structure: bool,
+    /// 读缓冲区大小（KB）
+    #[serde(default = "default_buffer_size_kb")]
+    pub buffer_size_kb: usize,
+    /// 自适应读缓冲区上限（KB），设置后读缓冲区根据连接表现在 16 KB 与该值之间自动调整
+    #[serde(default)]
+    pub max_buffer_size_kb: Option<usize>,
+    /// 所有线程的读缓冲区合计的内存上限（MB），额度不足时线程等待其他传输结束；不设置表示不限制
+    #[serde(default)]
+    pub max_buffer_memory_mb: Option<usize>,
+    /// 所有线程合计的下载带宽上限（Mbps，兆比特/秒），避免占满共享网络的出口；不设置表示不限制
+    #[serde(default)]
+    pub max_bandwidth_mbps: Option<f64>,
+    /// 每个线程的下载带宽上限（Mbps）；不设置表示不限制
+    #[serde(default)]
+    pub max_thread_bandwidth_mbps: Option<f64>,
+    /// 要下载的波段，例如 ["B01", "B08", "B13"]；为空表示下载全部波段。只用于按波段分文件的产品
+    #[serde(default = "default_bands")]
+    pub bands: Vec<String>,
+    /// 波段下载优先级，例如 ["B13", "B03"]
+    #[serde(default)]
+    pub band_priority: Vec<String>,
+    /// 观测区域，例如 ["FLDK"] 或 ["R301", "R302"]
+    #[serde(default = "default_areas")]
+    pub areas: Vec<String>,
+    /// 每个观测区域分别存放在 `base_path` 下以区域命名的子目录中；与配置的区域个数无关，
+    /// 增减区域不会改变已有文件的位置
+    #[serde(default)]
+    pub area_subdirectories: bool,
+    /// 只下载这些分辨率的 HSD 文件，例如 ["R05", "R20"]；为空时不按分辨率筛选
+    #[serde(default)]
+    pub resolutions: Vec<String>,
+    /// 数据产品类型名称，内置 hsd、netcdf_l1、l2_cloud，或 [[products]] 中自定义的名称
+    #[serde(default = "default_product")]
+    pub product: String,
+    /// 随观测数据一并下载的辅助文件产品名称（定义在 [[products]] 中）
+    #[serde(default)]
+    pub auxiliary: Vec<String>,
+    /// 每个主机在一次运行中允许的总重试次数，不设置表示不限制
+    #[serde(default)]
+    pub retry_budget: Option<usize>,
+    /// 重试用尽仍失败的文件在运行结束前用新建立的连接再下载一遍，重试仍失败才计为失败
+    #[serde(default = "default_retry_failed_at_end")]
+    pub retry_failed_at_end: bool,
+    /// 同一主机两次建立连接之间的最小间隔（秒）
+    #[serde(default)]
+    pub min_reconnect_interval_secs: u64,
+    /// 同一连接上两次文件传输之间的间隔（毫秒），部分数据源要求批量下载时设置
+    #[serde(default)]
+    pub inter_file_delay_ms: u64,
+    /// 并行策略：file（按文件）、slot（按时次）或 band（按波段）
+    #[serde(default)]
+    pub parallel_by: ParallelStrategy,
+    /// 在每个下载的文件旁写入 `<文件名>.meta.json` 来源信息
+    #[serde(default)]
+    pub write_metadata: bool,
+    /// 来源信息文件中校验和的算法：sha256、blake3 或 xxh3（后两者需要 `fast-hash` 特性）；导出的校验清单固定使用 sha256
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// 将每个文件的下载结果记入下载目录中的 `history.sqlite`（需要 `sqlite` 特性）
+    #[serde(default)]
+    pub history: bool,
+    /// 移入归档前校验文件内容（bz2 数据流与 HSD 文件头），校验失败的文件移入 `quarantine/` 并重新下载
+    #[serde(default)]
+    pub validate_downloads: bool,
+    /// 单次运行的最长时间（秒），到达后不再开始新文件的下载；不设置表示不限制
+    #[serde(default)]
+    pub max_run_duration_secs: Option<u64>,
+    /// 传输中超过该时间（秒）没有收到数据时中止本次传输并按下载失败重试；不设置表示一直等待
+    #[serde(default)]
+    pub stall_timeout_secs: Option<u64>,
+    /// 单个文件一次传输的最长时间（秒），超过时中止并重试，重试从已写入的位置续传；不设置表示不限制
+    #[serde(default)]
+    pub max_file_duration_secs: Option<u64>,
+    /// 开始连接服务器前随机等待 0 到该值（秒），使同一时刻由 cron 启动的多个站点错开；不设置表示不等待
+    #[serde(default)]
+    pub start_jitter_secs: Option<u64>,
+    /// 在下载目录写入 `status.json` 运行状态的间隔（秒），不设置表示不写入
+    #[serde(default)]
+    pub status_interval_secs: Option<u64>,
+    /// 整时次提交的暂存目录；设置后下载先写入暂存目录，时次内所有文件下载完成后才移入 base_path
+    #[serde(default)]
+    pub staging_path: Option<String>,
+    /// 入站暂存区；设置后每次运行的下载写入其下的 `<运行编号>/` 目录，经检查后按时次移入 base_path。
+    /// 不能与 staging_path 同时设置
+    #[serde(default)]
+    pub spool_path: Option<String>,
+    /// 使用入站暂存区时，运行结束后自动将完整的时次移入 base_path；为 false 时留待 `promote` 命令处理
+    #[serde(default = "default_auto_promote")]
+    pub auto_promote: bool,
+    /// 远程文件被重新处理或改名时的处理方式：flag（仅标记）、keep（下载新版本并保留旧文件）或 replace（下载新版本并删除旧文件）
+    #[serde(default)]
+    pub on_superseded: SupersededPolicy,
+    /// 服务器重复列出同一份数据（同一时次、波段、区域与分段）时保留哪一个：
+    /// newest（修改时间最新）、largest（最大）、first（列表中最先出现）或 shortest_name（文件名最短）
+    #[serde(default)]
+    pub on_duplicate: DuplicatePolicy,
+    /// 下载完成时最终路径上已有文件（例如被另一个进程写入）的处理方式：
+    /// overwrite（覆盖）、keep_existing（保留已有文件）、keep_both（新文件加后缀另存）或 error（记为失败）
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+    /// 每个自然月（UTC）的下载量上限（GB），超过后拒绝开始新的运行；不设置表示不限制
+    #[serde(default)]
+    pub monthly_quota_gb: Option<u64>,
+    /// 每个时次、每个观测区域预期的文件数（例如 3 个波段 × 10 个分段 = 30），
+    /// 运行结束时文件数少于该值的时次会在摘要中列出；不设置表示不检查
+    #[serde(default)]
+    pub expected_files_per_slot: Option<usize>,
+    /// 未完成的下载文件超过该时长（分钟）没有写入才在运行开始时删除，避免删除另一个正在运行的实例写入中的文件；
+    /// 为 0 时全部删除
+    #[serde(default = "default_orphan_temp_minutes")]
+    pub orphan_temp_minutes: u64,
+    /// 运行开始时保留全部未完成的下载文件，下载到这些文件时从已写入的位置继续；启用后忽略 orphan_temp_minutes
+    #[serde(default)]
+    pub resume_incomplete: bool,
+    /// 距今不超过该时长（分钟）的时次目录不存在时视为尚未发布，每分钟重试一次直到出现；
+    /// 更早的时次目录不存在则在清单中记为永久缺失。为 0 时不等待
+    #[serde(default = "default_unpublished_wait_minutes")]
+    pub unpublished_wait_minutes: u64,
+    /// 单个文件下载进度的报告间隔（秒），为 0 时不报告进度
+    #[serde(default = "default_progress_interval_secs")]
+    pub progress_interval_secs: u64,
+    /// 只在下载进度达到这些百分比时报告，例如 [10, 25, 50, 75]；设置后忽略 progress_interval_secs
+    #[serde(default)]
+    pub progress_milestones: Vec<u8>,
+    /// 在终端显示总进度与每个工作线程的进度条（百分比、速度、预计剩余时间），代替逐文件的进度日志；
+    /// 标准错误不是终端时仍输出进度日志
+    #[serde(default)]
+    pub progress_bars: bool,
+    /// 运行结束后将按观测日期汇总的统计（文件数、下载量、失败数、完整度）写入该 CSV 文件；不设置表示不写入
+    #[serde(default)]
+    pub daily_summary_path: Option<String>,
+    /// 运行结束后按 node_exporter textfile collector 的格式写入 Prometheus 指标的文件，
+    /// 例如 `/var/lib/node_exporter/textfile/hsd_downloader.prom`；不设置表示不写入
+    #[serde(default)]
+    pub metrics_textfile_path: Option<String>,
+    /// 运行结束后将 JSON 与 HTML 运行报告及失败文件列表写入 `<base_path>/reports/YYYY/MM/DD/`
+    #[serde(default)]
+    pub archive_reports: bool,
+    /// 运行结束后写入统计信息、逐文件结果与波段完整性的报告文件，供下游流程读取；扩展名为 `.csv` 时
+    /// 只写入逐文件结果，否则写入 JSON。可用 `--report-file` 指定；不设置表示不写入
+    #[serde(default)]
+    pub report_file: Option<String>,
+    /// 推送运行事件的 Unix 域套接字路径，订阅进程连接后逐行读取 JSON 事件；不设置表示不推送
+    #[serde(default)]
+    pub event_socket: Option<String>,
+    /// 保存到本地时的文件名模板，例如 `{stem:lower}{ext}`；不设置表示保持远程文件名
+    #[serde(default)]
+    pub filename_template: Option<String>,
+    /// 下载后将 `.bz2` 文件解压并删除压缩文件，在其他处理步骤之前执行
+    #[serde(default)]
+    pub decompress: bool,
+    /// 文件进入下载目录后依次执行的处理步骤，例如 `["decompress", { hook = "python convert.py {path}" }]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pipeline: Vec<StageConfig>,
+    /// 执行处理步骤的线程数，与下载线程分开；未设置时为 CPU 核心数
+    #[serde(default)]
+    pub pipeline_threads: Option<usize>,
+    /// 时次从观测到下载完成的延迟超过该分钟数时输出警告并上报；不设置表示不检查
+    #[serde(default)]
+    pub latency_alert_minutes: Option<u64>,
+}
+
+fn default_product() -> String {
+    "hsd".to_string()
+}
+
+fn default_progress_interval_secs() -> u64 {
+    DEFAULT_PROGRESS_INTERVAL.as_secs()
+}
+
+fn default_channels_per_session() -> usize {
+    1
+}
+
+fn default_auto_promote() -> bool {
+    true
+}
+
+fn default_retry_failed_at_end() -> bool {
+    true
+}
+
+fn default_orphan_temp_minutes() -> u64 {
+    60
+}
+
+fn default_unpublished_wait_minutes() -> u64 {
+    60
+}
+
+fn default_bands() -> Vec<String> {
+    himawari::VISIBLE_BANDS.map(String::from).to_vec()
+}
+
+fn default_areas() -> Vec<String> {
+    vec!["FLDK".to_string()]
+}
+
+fn default_buffer_size_kb() -> usize {
+    32
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub download: DownloadConfig,
+    /// 远程服务器目录布局
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    /// 自定义数据产品类型
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub products: Vec<ProductType>,
+    /// 额外的存储目的地
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<DestinationConfig>,
+    /// 存储后端，默认保存在 `base_path` 下
+    #[serde(default, skip_serializing_if = "StorageConfig::is_local")]
+    pub storage: StorageConfig,
+    /// 日志输出
+    #[serde(default, skip_serializing_if = "LoggingConfig::is_default")]
+    pub logging: LoggingConfig,
+    /// 追踪数据导出
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// 错误上报
+    #[serde(default, skip_serializing_if = "ErrorReportingConfig::is_disabled")]
+    pub error_reporting: ErrorReportingConfig,
+    /// 邮件通知
+    #[serde(default, skip_serializing_if = "EmailConfig::is_disabled")]
+    pub email: EmailConfig,
+    /// 下载时间段；设置后不再交互输入
+    #[serde(default, skip_serializing_if = "TimeConfig::is_unset")]
+    pub time: TimeConfig,
+    /// 归档保留期
+    #[serde(default, skip_serializing_if = "RetentionConfig::is_unset")]
+    pub retention: RetentionConfig,
+    /// 持续跟踪模式（`--follow`）的轮询间隔与回看范围
+    #[serde(default, skip_serializing_if = "FollowConfig::is_default")]
+    pub follow: FollowConfig,
+    /// 命名的服务器配置档，命令行 `--profile <名称>` 选用
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// 日志输出配置
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct LoggingConfig {
+    /// 输出的最低级别：error、warn、info、debug 或 trace；命令行 `--log-level` 优先
+    #[serde(default)]
+    pub level: LogLevel,
+    /// 输出格式：human（只有消息，警告与错误带前缀）或 full（时间、级别、线程与所在的 span）
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+impl LoggingConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 日志级别
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.trim().to_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(format!(
+                "无效的日志级别 {}，可选 error、warn、info、debug、trace",
+                level
+            )),
+        }
+    }
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => tracing::Level::ERROR,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Trace => tracing::Level::TRACE,
+        }
+    }
+}
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// 与终端输出相同的可读形式：只有消息，警告与错误带前缀
+    #[default]
+    Human,
+    /// 每行带时间、级别、线程名与所在的 span，便于日志系统解析与筛选
+    Full,
+}
+
+/// 归档保留期配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RetentionConfig {
+    /// 保留最近多少天（按观测时间）的数据，更早的时次由 `prune` 删除；不设置表示不删除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_days: Option<u64>,
+    /// 每次下载运行结束后自动删除超出保留期的数据，`prune` 也无需 `--yes` 即可删除
+    #[serde(default)]
+    pub auto_prune: bool,
+}
+
+impl RetentionConfig {
+    pub fn is_unset(&self) -> bool {
+        self.keep_days.is_none() && !self.auto_prune
+    }
+}
+
+/// 持续跟踪模式配置：每轮下载最近一段时间内已发布的时次，本地已有的文件跳过
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FollowConfig {
+    /// 两轮之间的间隔（分钟），从上一轮开始时计算
+    #[serde(default = "default_follow_interval_minutes")]
+    pub interval_minutes: u64,
+    /// 每轮检查最近多少小时内的时次；较早的时次在这期间仍未下载完整时需另行回补
+    #[serde(default = "default_follow_lookback_hours")]
+    pub lookback_hours: u64,
+    /// 每天执行夜间修补的时间（UTC，`HH:MM`）：核对最近 `repair_days` 天的本地归档并补齐不完整的时次；
+    /// 不设置表示不修补
+    #[serde(default)]
+    pub repair_at: Option<String>,
+    /// 夜间修补核对最近多少天的时次
+    #[serde(default = "default_follow_repair_days")]
+    pub repair_days: u64,
+    /// 提供 Prometheus 指标端点 `/metrics` 与控制接口 `/shares` 的监听地址，例如 `127.0.0.1:9184`；
+    /// 不设置表示不提供
+    #[serde(default)]
+    pub metrics_listen: Option<String>,
+    /// 夜间修补与实时下载同时进行时，实时下载分得的带宽与连接数的权重
+    #[serde(default = "default_follow_realtime_weight")]
+    pub realtime_weight: u32,
+    /// 夜间修补分得的带宽与连接数的权重
+    #[serde(default = "default_follow_repair_weight")]
+    pub repair_weight: u32,
+}
+
+impl Default for FollowConfig {
+    fn default() -> Self {
+        Self {
+            interval_minutes: default_follow_interval_minutes(),
+            lookback_hours: default_follow_lookback_hours(),
+            repair_at: None,
+            repair_days: default_follow_repair_days(),
+            metrics_listen: None,
+            realtime_weight: default_follow_realtime_weight(),
+            repair_weight: default_follow_repair_weight(),
+        }
+    }
+}
+
+impl FollowConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 夜间修补的时间，未设置时为 `None`
+    pub fn repair_time(&self) -> Result<Option<NaiveTime>, String> {
+        self.repair_at
+            .as_deref()
+            .map(|time| {
+                NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|_| format!("[follow] repair_at 格式应为 HH:MM（UTC）: {}", time))
+            })
+            .transpose()
+    }
+}
+
+fn default_follow_interval_minutes() -> u64 {
+    10
+}
+
+fn default_follow_lookback_hours() -> u64 {
+    3
+}
+
+fn default_follow_repair_days() -> u64 {
+    7
+}
+
+fn default_follow_realtime_weight() -> u32 {
+    80
+}
+
+fn default_follow_repair_weight() -> u32 {
+    20
+}
+
+/// 服务器配置档：一个数据源的连接信息及与之相配的默认产品、波段、观测区域与目录布局。
+/// 选用后设置了的项覆盖 `[server]`、`[download]` 与 `[layout]` 中的对应项，未设置的保持不变
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_method: Option<AuthMethod>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<String>,
+    /// 密码的来源，见 `server.credential_source`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_source: Option<CredentialSource>,
+    /// 默认数据产品，见 `download.product`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bands: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub areas: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolutions: Option<Vec<String>>,
+    /// 远程服务器目录布局，写法与 `[layout]` 相同
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<LayoutConfig>,
+    /// 后处理步骤，见 `download.pipeline`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Vec<StageConfig>>,
+}
+
+/// 下载时间段配置，时间均为 UTC，格式为 `YYYY-MM-DD HH:MM:SS`，须对齐到产品的观测时间线
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TimeConfig {
+    /// 开始时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// 结束时间（含）；不设置时到最近一个应当已经发布的时次为止
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// 时次间隔（分钟），须为产品观测间隔的整数倍；不设置时使用产品的观测间隔
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cadence_minutes: Option<i64>,
+    /// 时次相对当天 0 点的偏移（分钟），须落在产品的时次上；不设置时使用产品的偏移
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_minutes: Option<i64>,
+}
+
+impl TimeConfig {
+    /// 是否未设置下载时间段
+    pub fn is_unset(&self) -> bool {
+        self.start.is_none()
+            && self.end.is_none()
+            && self.cadence_minutes.is_none()
+            && self.offset_minutes.is_none()
+    }
+
+    fn validate(&self, product: &ProductType) -> Result<(), String> {
+        if self.is_unset() {
+            return Ok(());
+        }
+        let Some(start) = &self.start else {
+            return Err("[time] 必须设置开始时间 start".to_string());
+        };
+        let start = parse_aligned(start, product)?;
+        if let Some(end) = &self.end
+            && parse_aligned(end, product)? < start
+        {
+            return Err("[time] 结束时间早于开始时间".to_string());
+        }
+        self.schedule(product)?;
+        Ok(())
+    }
+
+    /// 时次的间隔与偏移（分钟）：间隔须为产品观测间隔的正整数倍，偏移须落在产品的时次上
+    fn schedule(&self, product: &ProductType) -> Result<(i64, i64), String> {
+        let cadence = self.cadence_minutes.unwrap_or(product.cadence_minutes);
+        if cadence <= 0 || cadence % product.cadence_minutes != 0 {
+            return Err(format!(
+                "[time] 时次间隔 {} 分钟无效，须为产品 {} 观测间隔 {} 分钟的正整数倍",
+                cadence, product.name, product.cadence_minutes
+            ));
+        }
+        let offset = self.offset_minutes.unwrap_or(product.offset_minutes);
+        if (offset - product.offset_minutes).rem_euclid(product.cadence_minutes) != 0 {
+            return Err(format!(
+                "[time] 时次偏移 {} 分钟不在产品 {} 的时次上（每 {} 分钟一次，偏移 {} 分钟）",
+                offset, product.name, product.cadence_minutes, product.offset_minutes
+            ));
+        }
+        Ok((cadence, offset))
+    }
+
+    /// 按配置的时间段生成 `product` 的下载时间列表；
+    /// 配置的间隔须为产品观测间隔的整数倍，时间段不能晚于最近一个应当已经发布的时次
+    pub fn time_list(&self, product: &ProductType) -> Result<Vec<NaiveDateTime>, String> {
+        let start = parse_aligned(self.start.as_deref().unwrap_or_default(), product)?;
+        let latest = latest_published_slot(PUBLISH_DELAY_MINUTES);
+        let end = match &self.end {
+            Some(end) => parse_aligned(end, product)?,
+            None => latest,
+        };
+        if start > latest || end > latest {
+            return Err(format!(
+                "[time] 时间段晚于最近一个已发布的时次 {}",
+                latest.format("%Y-%m-%d %H:%M")
+            ));
+        }
+        let (cadence, offset) = self.schedule(product)?;
+        time_list_aligned(start, end, cadence, offset)
+    }
+}
+
+/// 解析时间并检查是否对齐到产品的观测时间线（原生产品为 10 分钟），见 [`ProductType::is_on_timeline`]
+fn parse_aligned(input: &str, product: &ProductType) -> Result<NaiveDateTime, String> {
+    let time = parse_time(input)?;
+    if !product.is_on_timeline(&time) {
+        return Err(format!(
+            "[time] 时间 {} 未对齐到产品 {} 的观测时间线（每 {} 分钟，偏移 {} 分钟）",
+            input,
+            product.name,
+            product.timeline_minutes(),
+            product
+                .offset_minutes
+                .rem_euclid(product.timeline_minutes())
+        ));
+    }
+    Ok(time)
+}
+
+/// Sentry 兼容的错误上报配置（需启用 `sentry` 特性）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ErrorReportingConfig {
+    /// 上报地址（DSN），例如 `https://<key>@sentry.example.com/<project>`；为空时不上报
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dsn: Option<String>,
+    /// 部署环境名，用于在上报服务中区分不同的部署
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+}
+
+impl ErrorReportingConfig {
+    fn is_disabled(&self) -> bool {
+        self.dsn.is_none() && self.environment.is_none()
+    }
+}
+
+/// 邮件通知配置（需启用 `email` 特性）：一次运行下载失败的文件过多或预期的时次没有文件时通过 SMTP 发信
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EmailConfig {
+    /// SMTP 服务器地址；为空时不发送
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp_host: Option<String>,
+    /// SMTP 端口，不设置时按 `security` 使用 587、465 或 25
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp_port: Option<u16>,
+    /// 连接的加密方式
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// 登录用户名，为空时不认证
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// 密码的来源，写法同 `server.credential_source`，在启动时取得一次
+    #[serde(default, skip_serializing_if = "CredentialSource::is_config")]
+    pub credential_source: CredentialSource,
+    /// 发件人，例如 `HSD 下载 <hsd@example.org>`
+    #[serde(default)]
+    pub from: String,
+    /// 收件人
+    #[serde(default)]
+    pub to: Vec<String>,
+    /// 一次运行下载失败的文件数超过该值时通知；为 0 时有文件失败即通知
+    #[serde(default)]
+    pub failed_files_threshold: usize,
+    /// 目录已列出但没有匹配文件、或目录过了发布等待期仍不存在的时次是否通知
+    #[serde(default = "default_notify_empty_slots")]
+    pub notify_empty_slots: bool,
+    /// 两封通知之间的最短间隔（分钟），避免持续跟踪时同一问题每一轮都发信
+    #[serde(default = "default_email_min_interval_minutes")]
+    pub min_interval_minutes: u64,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            smtp_host: None,
+            smtp_port: None,
+            security: SmtpSecurity::default(),
+            username: None,
+            password: None,
+            credential_source: CredentialSource::default(),
+            from: String::new(),
+            to: Vec::new(),
+            failed_files_threshold: 0,
+            notify_empty_slots: true,
+            min_interval_minutes: default_email_min_interval_minutes(),
+        }
+    }
+}
+
+impl EmailConfig {
+    pub fn is_disabled(&self) -> bool {
+        self.smtp_host.is_none()
+    }
+
+    /// 按 `credential_source` 取得 SMTP 密码
+    pub fn load_secret(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (Some(host), Some(username)) = (&self.smtp_host, &self.username) else {
+            return Ok(());
+        };
+        if let Some(provider) = self.credential_source.build()? {
+            let secret = provider
+                .secret(host, username)
+                .map_err(|e| format!("从 {} 取得 SMTP 密码失败: {}", provider.name(), e))?;
+            self.password = Some(secret);
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.is_disabled() {
+            return Ok(());
+        }
+        if !cfg!(feature = "email") {
+            return Err("邮件通知未编译进当前程序，请启用 email 特性后重新编译".to_string());
+        }
+        if self.from.is_empty() || self.to.is_empty() {
+            return Err("[email] 必须设置发件人 from 与收件人 to".to_string());
+        }
+        self.credential_source.build()?;
+        Ok(())
+    }
+}
+
+/// SMTP 连接的加密方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// 明文连接后用 STARTTLS 升级，默认端口 587
+    #[default]
+    Starttls,
+    /// 直接建立 TLS 连接，默认端口 465
+    Tls,
+    /// 不加密，默认端口 25，只适合本机或内网的中继
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::Starttls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+fn default_notify_empty_slots() -> bool {
+    true
+}
+
+fn default_email_min_interval_minutes() -> u64 {
+    60
+}
+
+/// OpenTelemetry 追踪导出配置（需启用 `otel` 特性）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP 追踪接收地址，例如 `http://localhost:4318/v1/traces`；为空时不导出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    /// 上报的服务名
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+fn default_service_name() -> String {
+    "himawari-hsd-downloader".to_string()
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: default_service_name(),
+        }
+    }
 }
 
 impl Default for Config {
@@ -33,18 +823,99 @@ impl Default for Config {
                 username: "your_username".to_string(),
                 password: "your_password".to_string(),
                 port: 22,
+                transport: Transport::default(),
+                auth_method: AuthMethod::default(),
+                private_key_path: None,
+                passphrase: None,
+                credential_source: CredentialSource::default(),
+                channels_per_session: default_channels_per_session(),
+                files: FileRules::default(),
             },
             download: DownloadConfig {
                 num_threads: 4,
                 base_path: "./himawari_data".to_string(),
                 organize_by_time: true,
+                area_subdirectories: false,
                 keep_original_structure: false,
+                buffer_size_kb: default_buffer_size_kb(),
+                max_buffer_size_kb: None,
+                max_buffer_memory_mb: None,
+                max_bandwidth_mbps: None,
+                max_thread_bandwidth_mbps: None,
+                bands: default_bands(),
+                band_priority: Vec::new(),
+                areas: default_areas(),
+                resolutions: Vec::new(),
+                product: default_product(),
+                auxiliary: Vec::new(),
+                retry_budget: None,
+                retry_failed_at_end: default_retry_failed_at_end(),
+                min_reconnect_interval_secs: 0,
+                inter_file_delay_ms: 0,
+                parallel_by: ParallelStrategy::default(),
+                write_metadata: false,
+                checksum_algorithm: ChecksumAlgorithm::default(),
+                history: false,
+                validate_downloads: false,
+                max_run_duration_secs: None,
+                stall_timeout_secs: None,
+                max_file_duration_secs: None,
+                start_jitter_secs: None,
+                status_interval_secs: None,
+                staging_path: None,
+                spool_path: None,
+                auto_promote: default_auto_promote(),
+                on_superseded: SupersededPolicy::default(),
+                on_duplicate: DuplicatePolicy::default(),
+                on_conflict: ConflictPolicy::default(),
+                monthly_quota_gb: None,
+                expected_files_per_slot: None,
+                orphan_temp_minutes: default_orphan_temp_minutes(),
+                resume_incomplete: false,
+                unpublished_wait_minutes: default_unpublished_wait_minutes(),
+                progress_interval_secs: default_progress_interval_secs(),
+                progress_milestones: Vec::new(),
+                progress_bars: false,
+                daily_summary_path: None,
+                metrics_textfile_path: None,
+                archive_reports: false,
+                report_file: None,
+                event_socket: None,
+                filename_template: None,
+                decompress: false,
+                pipeline: Vec::new(),
+                pipeline_threads: None,
+                latency_alert_minutes: None,
             },
+            layout: LayoutConfig::default(),
+            products: Vec::new(),
+            destinations: Vec::new(),
+            storage: StorageConfig::default(),
+            logging: LoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            error_reporting: ErrorReportingConfig::default(),
+            email: EmailConfig::default(),
+            time: TimeConfig::default(),
+            retention: RetentionConfig::default(),
+            follow: FollowConfig::default(),
+            profiles: BTreeMap::new(),
         }
     }
 }
 
 impl Config {
+    /// 配置了其他凭据来源时从该来源取得密码，替换配置中的值；匿名访问的传输不取得密码
+    pub fn load_secret(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.server.transport.is_anonymous() {
+            return Ok(());
+        }
+        self.email.load_secret()?;
+        match self.server.credential_source.build()? {
+            Some(provider) => self.server.load_secret(provider.as_ref()),
+            None => Ok(()),
+        }
+    }
+
     /// 从配置文件加载配置
     pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let config_content = fs::read_to_string(path)?;
@@ -138,13 +1009,82 @@ impl Config {
                 username,
                 password,
                 port,
+                transport: Transport::default(),
+                auth_method: AuthMethod::default(),
+                private_key_path: None,
+                passphrase: None,
+                credential_source: CredentialSource::default(),
+                channels_per_session: default_channels_per_session(),
+                files: FileRules::default(),
             },
             download: DownloadConfig {
                 num_threads,
                 base_path,
                 organize_by_time: true,
+                area_subdirectories: false,
                 keep_original_structure: false,
+                buffer_size_kb: default_buffer_size_kb(),
+                max_buffer_size_kb: None,
+                max_buffer_memory_mb: None,
+                max_bandwidth_mbps: None,
+                max_thread_bandwidth_mbps: None,
+                bands: default_bands(),
+                band_priority: Vec::new(),
+                areas: default_areas(),
+                resolutions: Vec::new(),
+                product: default_product(),
+                auxiliary: Vec::new(),
+                retry_budget: None,
+                retry_failed_at_end: default_retry_failed_at_end(),
+                min_reconnect_interval_secs: 0,
+                inter_file_delay_ms: 0,
+                parallel_by: ParallelStrategy::default(),
+                write_metadata: false,
+                checksum_algorithm: ChecksumAlgorithm::default(),
+                history: false,
+                validate_downloads: false,
+                max_run_duration_secs: None,
+                stall_timeout_secs: None,
+                max_file_duration_secs: None,
+                start_jitter_secs: None,
+                status_interval_secs: None,
+                staging_path: None,
+                spool_path: None,
+                auto_promote: default_auto_promote(),
+                on_superseded: SupersededPolicy::default(),
+                on_duplicate: DuplicatePolicy::default(),
+                on_conflict: ConflictPolicy::default(),
+                monthly_quota_gb: None,
+                expected_files_per_slot: None,
+                orphan_temp_minutes: default_orphan_temp_minutes(),
+                resume_incomplete: false,
+                unpublished_wait_minutes: default_unpublished_wait_minutes(),
+                progress_interval_secs: default_progress_interval_secs(),
+                progress_milestones: Vec::new(),
+                progress_bars: false,
+                daily_summary_path: None,
+                metrics_textfile_path: None,
+                archive_reports: false,
+                report_file: None,
+                event_socket: None,
+                filename_template: None,
+                decompress: false,
+                pipeline: Vec::new(),
+                pipeline_threads: None,
+                latency_alert_minutes: None,
             },
+            layout: LayoutConfig::default(),
+            products: Vec::new(),
+            destinations: Vec::new(),
+            storage: StorageConfig::default(),
+            logging: LoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            error_reporting: ErrorReportingConfig::default(),
+            email: EmailConfig::default(),
+            time: TimeConfig::default(),
+            retention: RetentionConfig::default(),
+            follow: FollowConfig::default(),
+            profiles: BTreeMap::new(),
         })
     }
 
@@ -155,23 +1095,356 @@ impl Config {
         Ok(())
     }
 
+    /// 选用名为 `name` 的配置档，用其中设置了的项覆盖服务器连接信息与默认的产品、波段、观测区域和目录布局
+    pub fn apply_profile(&mut self, name: &str) -> Result<(), String> {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            if self.profiles.is_empty() {
+                return Err(format!(
+                    "配置档 {} 不存在，配置文件中没有 [profiles] 设置",
+                    name
+                ));
+            }
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            return Err(format!(
+                "配置档 {} 不存在，可用的配置档: {}",
+                name,
+                known.join(", ")
+            ));
+        };
+        let server = &mut self.server;
+        if let Some(host) = profile.host {
+            server.host = host;
+        }
+        if let Some(port) = profile.port {
+            server.port = port;
+        }
+        if let Some(username) = profile.username {
+            server.username = username;
+        }
+        if let Some(password) = profile.password {
+            server.password = password;
+        }
+        if let Some(auth_method) = profile.auth_method {
+            server.auth_method = auth_method;
+        }
+        if profile.private_key_path.is_some() {
+            server.private_key_path = profile.private_key_path;
+        }
+        if profile.passphrase.is_some() {
+            server.passphrase = profile.passphrase;
+        }
+        if let Some(credential_source) = profile.credential_source {
+            server.credential_source = credential_source;
+        }
+        let download = &mut self.download;
+        if let Some(product) = profile.product {
+            download.product = product;
+        }
+        if let Some(bands) = profile.bands {
+            download.bands = bands;
+        }
+        if let Some(areas) = profile.areas {
+            download.areas = areas;
+        }
+        if let Some(resolutions) = profile.resolutions {
+            download.resolutions = resolutions;
+        }
+        if let Some(layout) = profile.layout {
+            self.layout = layout;
+        }
+        if let Some(pipeline) = profile.pipeline {
+            self.download.pipeline = pipeline;
+        }
+        Ok(())
+    }
+
     /// 验证配置
     pub fn validate(&self) -> Result<(), String> {
         if self.server.host.is_empty() {
             return Err("服务器地址不能为空".to_string());
         }
-        if self.server.username.is_empty() {
+        let anonymous = self.server.transport.is_anonymous();
+        if self.server.username.is_empty() && !anonymous {
             return Err("用户名不能为空".to_string());
         }
-        if self.server.password.is_empty() {
-            return Err("密码不能为空".to_string());
+        match self.server.auth_method {
+            AuthMethod::Password
+                if self.server.password.is_empty()
+                    && self.server.credential_source.is_config()
+                    && !anonymous =>
+            {
+                return Err("密码不能为空".to_string());
+            }
+            AuthMethod::PublicKey => match &self.server.private_key_path {
+                None => return Err("使用私钥认证时必须设置 private_key_path".to_string()),
+                Some(path) if !Path::new(path).is_file() => {
+                    return Err(format!("私钥文件不存在: {}", path));
+                }
+                Some(_) => {}
+            },
+            AuthMethod::Password => {}
+        }
+        if !self.server.transport.is_available() {
+            return Err(format!(
+                "传输实现 {} 未编译进当前程序，请启用 {} 特性后重新编译",
+                self.server.transport.name(),
+                self.server.transport.feature()
+            ));
+        }
+        if self.server.transport.is_http() && self.server.auth_method == AuthMethod::PublicKey {
+            return Err(format!(
+                "传输实现 {} 只支持密码认证（HTTP Basic 认证）",
+                self.server.transport.name()
+            ));
+        }
+        if anonymous && self.server.auth_method == AuthMethod::PublicKey {
+            return Err(format!(
+                "传输实现 {} 为匿名访问，auth_method 须为 password",
+                self.server.transport.name()
+            ));
+        }
+        if self.server.transport.is_ftp() && self.server.auth_method == AuthMethod::PublicKey {
+            return Err(format!(
+                "传输实现 {} 只支持密码认证",
+                self.server.transport.name()
+            ));
+        }
+        self.server.credential_source.build()?;
+        self.email.validate()?;
+        if let Some(feature) = self.download.checksum_algorithm.feature()
+            && !self.download.checksum_algorithm.is_available()
+        {
+            return Err(format!(
+                "校验和算法 {} 未编译进当前程序，请启用 {} 特性后重新编译",
+                self.download.checksum_algorithm, feature
+            ));
+        }
+        if self.download.history && !cfg!(feature = "sqlite") {
+            return Err("下载历史未编译进当前程序，请启用 sqlite 特性后重新编译".to_string());
+        }
+        if self.server.channels_per_session == 0 {
+            return Err("每个会话的通道数必须大于0".to_string());
         }
         if self.download.num_threads == 0 {
             return Err("线程数必须大于0".to_string());
         }
+        if self.download.pipeline_threads == Some(0) {
+            return Err("后处理线程数必须大于0".to_string());
+        }
+        if self.download.buffer_size_kb == 0 {
+            return Err("缓冲区大小必须大于0".to_string());
+        }
+        if let Some(max) = self.download.max_buffer_size_kb
+            && max < self.download.buffer_size_kb
+        {
+            return Err("缓冲区上限不能小于缓冲区大小".to_string());
+        }
+        if self.download.max_buffer_memory_mb == Some(0) {
+            return Err("缓冲区内存上限必须大于0".to_string());
+        }
+        if [
+            self.download.max_bandwidth_mbps,
+            self.download.max_thread_bandwidth_mbps,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|mbps| mbps_to_bytes_per_sec(mbps) == 0)
+        {
+            return Err("带宽上限必须大于0".to_string());
+        }
+        self.pipeline()?;
+        if self.follow.interval_minutes == 0 || self.follow.lookback_hours == 0 {
+            return Err("[follow] 的轮询间隔与回看范围必须大于0".to_string());
+        }
+        if self.follow.repair_time()?.is_some() && self.follow.repair_days == 0 {
+            return Err("[follow] 的 repair_days 必须大于0".to_string());
+        }
+        if self.download.areas.is_empty() {
+            return Err("观测区域不能为空".to_string());
+        }
+        // 辅助文件按各自产品的目录模板发布，只有 P-Tree 布局提供这些目录
+        if !self.download.auxiliary.is_empty() && self.layout != LayoutConfig::PTree {
+            return Err(
+                "download.auxiliary 只能与 P-Tree 目录布局（layout.type = \"p_tree\"）一起使用"
+                    .to_string(),
+            );
+        }
+        // 自定义产品的文件名可能使用其他区域编号，只检查 HSD
+        if self.download.product == "hsd"
+            && let Some(area) = self
+                .download
+                .areas
+                .iter()
+                .find(|area| !himawari::is_valid_area(area))
+        {
+            return Err(format!(
+                "观测区域 {} 无效，可选 FLDK、JP01–JP04、R301–R304、R401–R420、R501–R520",
+                area
+            ));
+        }
+        let bands = &self.download.bands;
+        if let Some(band) = bands.iter().find(|band| !himawari::is_valid_band(band)) {
+            return Err(format!("下载波段中的 {} 不是有效的 AHI 波段", band));
+        }
+        if let Some((index, band)) = bands
+            .iter()
+            .enumerate()
+            .find(|(index, band)| bands[..*index].contains(band))
+        {
+            return Err(format!("下载波段中的 {} 重复（第 {} 项）", band, index + 1));
+        }
+        if let Some(resolution) = self
+            .download
+            .resolutions
+            .iter()
+            .find(|resolution| !himawari::is_valid_resolution(resolution))
+        {
+            return Err(format!(
+                "分辨率中的 {} 无效，可选 {:?}",
+                resolution,
+                himawari::RESOLUTIONS
+            ));
+        }
+        if let Some(band) = self
+            .download
+            .band_priority
+            .iter()
+            .find(|band| !himawari::is_valid_band(band))
+        {
+            return Err(format!("波段优先级中的 {} 不是有效的 AHI 波段", band));
+        }
+        for (index, destination) in self.destinations.iter().enumerate() {
+            if destination.name().is_empty() {
+                return Err("存储目的地名称不能为空".to_string());
+            }
+            if self.destinations[..index]
+                .iter()
+                .any(|other| other.name() == destination.name())
+            {
+                return Err(format!("存储目的地名称 {} 重复", destination.name()));
+            }
+        }
+        if self.download.staging_path.is_some() && self.download.spool_path.is_some() {
+            return Err("staging_path 与 spool_path 不能同时设置".to_string());
+        }
+        self.storage.validate()?;
+        if !self.storage.is_local() {
+            let download = &self.download;
+            let unsupported = [
+                ("download.staging_path", download.staging_path.is_some()),
+                ("download.spool_path", download.spool_path.is_some()),
+                ("download.decompress", download.decompress),
+                ("download.pipeline", !download.pipeline.is_empty()),
+                ("download.write_metadata", download.write_metadata),
+                ("download.validate_downloads", download.validate_downloads),
+                ("destinations", !self.destinations.is_empty()),
+                ("follow.repair_at", self.follow.repair_at.is_some()),
+            ];
+            if let Some((name, _)) = unsupported.iter().find(|(_, set)| *set) {
+                return Err(format!(
+                    "使用 [storage] 存储后端时不能设置 {}，这些功能需要本地文件",
+                    name
+                ));
+            }
+        }
+        if self.download.expected_files_per_slot == Some(0) {
+            return Err("每个时次的预期文件数必须大于0".to_string());
+        }
+        let milestones = &self.download.progress_milestones;
+        if milestones
+            .iter()
+            .any(|&milestone| milestone == 0 || milestone > 100)
+        {
+            return Err("进度报告节点必须在 1 到 100 之间".to_string());
+        }
+        if milestones.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err("进度报告节点必须按从小到大排列且不重复".to_string());
+        }
+        if self.retention.auto_prune && self.retention.keep_days.is_none() {
+            return Err("[retention] 启用 auto_prune 时必须设置 keep_days".to_string());
+        }
+        if let Some(template) = &self.download.filename_template {
+            FilenameTemplate::parse(template).map_err(|e| e.to_string())?;
+        }
+        if self.download.monthly_quota_gb == Some(0) {
+            return Err("每月下载量上限必须大于0".to_string());
+        }
+        if self.download.status_interval_secs == Some(0) {
+            return Err("状态文件写入间隔必须大于0".to_string());
+        }
+        if self.download.stall_timeout_secs == Some(0) {
+            return Err("传输停滞超时必须大于0".to_string());
+        }
+        if self.download.max_file_duration_secs == Some(0) {
+            return Err("单个文件的最长传输时间必须大于0".to_string());
+        }
+        let product = self.product()?;
+        for product in std::iter::once(product.clone()).chain(self.auxiliary_products()?) {
+            if product.cadence_minutes <= 0 {
+                return Err(format!("产品 {} 的观测间隔必须大于0", product.name));
+            }
+        }
+        self.time.validate(&product)?;
         Ok(())
     }
 
+    /// 获取下载进度的报告方式
+    pub fn progress_reporting(&self) -> ProgressReporting {
+        if !self.download.progress_milestones.is_empty() {
+            ProgressReporting::Milestones(self.download.progress_milestones.clone())
+        } else if self.download.progress_interval_secs == 0 {
+            ProgressReporting::Off
+        } else {
+            ProgressReporting::Interval(Duration::from_secs(self.download.progress_interval_secs))
+        }
+    }
+
+    /// 远程目录布局，按服务器的文件约定筛选文件
+    pub fn remote_layout(&self) -> Arc<dyn RemoteLayout> {
+        self.layout.build_with_rules(&self.server.files)
+    }
+
+    /// 下载后处理流水线的各步骤；设置了 `decompress` 时以删除压缩文件的解压步骤开始
+    pub fn pipeline(&self) -> Result<Vec<Arc<dyn PipelineStage>>, String> {
+        let mut stages: Vec<Arc<dyn PipelineStage>> = Vec::new();
+        if self.download.decompress {
+            if self
+                .download
+                .pipeline
+                .contains(&StageConfig::Builtin("decompress".to_string()))
+            {
+                return Err(
+                    "设置了 decompress = true 时 pipeline 中不能再有 decompress 步骤".to_string(),
+                );
+            }
+            stages.push(Arc::new(Decompress {
+                remove_archive: true,
+            }));
+        }
+        for stage in &self.download.pipeline {
+            stages.push(stage.build()?);
+        }
+        Ok(stages)
+    }
+
+    /// 获取配置的数据产品类型
+    pub fn product(&self) -> Result<ProductType, String> {
+        ProductType::find(&self.download.product, &self.products)
+            .ok_or_else(|| format!("未知的产品类型: {}", self.download.product))
+    }
+
+    /// 获取配置的辅助文件产品类型
+    pub fn auxiliary_products(&self) -> Result<Vec<ProductType>, String> {
+        self.download
+            .auxiliary
+            .iter()
+            .map(|name| {
+                ProductType::find(name, &self.products)
+                    .ok_or_else(|| format!("未知的辅助文件产品: {}", name))
+            })
+            .collect()
+    }
+
     /// 获取完整的主机地址（包含端口）
     pub fn get_host_with_port(&self) -> String {
         format!("{}:{}", self.server.host, self.server.port)
@@ -0,0 +1,166 @@
+//! 凭据来源：登录密码（私钥认证时为私钥口令）可以写在配置文件中，也可以在启动时从环境变量、
+//! 交互输入、外部命令（例如 `pass`、`vault`）或系统密钥环取得，站点可以接入自己的密钥管理
+
+use crate::destination::{shell, shell_quote};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// 内置凭据来源的名称
+pub const BUILTIN_SOURCES: [&str; 2] = ["config", "prompt"];
+
+/// 凭据来源
+///
+/// 新的来源只需实现该 trait，并通过 `ServerConfig::load_secret` 取得凭据。
+pub trait CredentialProvider: Debug + Send + Sync {
+    /// 来源名称，用于日志与错误信息
+    fn name(&self) -> &str;
+
+    /// 取得 `username@host` 的密码；私钥认证时为私钥口令，私钥未加密时返回空字符串
+    fn secret(&self, host: &str, username: &str) -> Result<String, Box<dyn Error>>;
+}
+
+/// 从环境变量读取
+#[derive(Debug, Clone)]
+pub struct EnvVar {
+    pub variable: String,
+}
+
+impl CredentialProvider for EnvVar {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn secret(&self, _host: &str, _username: &str) -> Result<String, Box<dyn Error>> {
+        env::var(&self.variable).map_err(|e| format!("环境变量 {}: {}", self.variable, e).into())
+    }
+}
+
+/// 外部命令，例如 `pass show himawari/{username}` 或 `vault kv get -field=password secret/hsd`
+///
+/// `{host}` 与 `{username}` 替换为服务器地址与用户名，命令的标准输出去掉首尾空白后作为密码；
+/// 退出码非 0 视为失败。系统密钥环也可以通过 `secret-tool lookup` 等命令读取。
+#[derive(Debug, Clone)]
+pub struct ExternalCommand {
+    pub command: String,
+}
+
+impl CredentialProvider for ExternalCommand {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    fn secret(&self, host: &str, username: &str) -> Result<String, Box<dyn Error>> {
+        let command = self
+            .command
+            .replace("{host}", &shell_quote(host))
+            .replace("{username}", &shell_quote(username));
+        let output = shell(&command).output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("命令退出状态 {}: {}", output.status, stderr.trim()).into());
+        }
+        Ok(String::from_utf8(output.stdout)
+            .map_err(|_| "命令输出不是有效的 UTF-8")?
+            .trim()
+            .to_string())
+    }
+}
+
+/// 在终端输入，输入内容不回显（需要 `cli` 特性）
+#[derive(Debug, Clone, Default)]
+pub struct Prompt;
+
+impl CredentialProvider for Prompt {
+    fn name(&self) -> &str {
+        "prompt"
+    }
+
+    #[cfg(feature = "cli")]
+    fn secret(&self, host: &str, username: &str) -> Result<String, Box<dyn Error>> {
+        Ok(rpassword::prompt_password(format!(
+            "{}@{} 的密码: ",
+            username, host
+        ))?)
+    }
+
+    #[cfg(not(feature = "cli"))]
+    fn secret(&self, _host: &str, _username: &str) -> Result<String, Box<dyn Error>> {
+        Err("交互输入密码需要 cli 特性".into())
+    }
+}
+
+/// 系统密钥环中服务名为 `service`、账户为用户名的条目（`keyring` 特性）：Linux 内核密钥环、
+/// macOS 钥匙串或 Windows 凭据管理器
+#[cfg(feature = "keyring")]
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    pub service: String,
+}
+
+#[cfg(feature = "keyring")]
+impl CredentialProvider for Keyring {
+    fn name(&self) -> &str {
+        "keyring"
+    }
+
+    fn secret(&self, _host: &str, username: &str) -> Result<String, Box<dyn Error>> {
+        Ok(keyring::Entry::new(&self.service, username)?.get_password()?)
+    }
+}
+
+/// 配置文件中的凭据来源：内置来源写名称，`"config"`（默认，使用配置中的 `password` 或 `passphrase`）
+/// 或 `"prompt"`；其他来源写作 `{ env = "变量名" }`、`{ command = "命令" }` 或 `{ keyring = "服务名" }`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum CredentialSource {
+    Builtin(String),
+    Env { env: String },
+    Command { command: String },
+    Keyring { keyring: String },
+}
+
+impl Default for CredentialSource {
+    fn default() -> Self {
+        CredentialSource::Builtin("config".to_string())
+    }
+}
+
+impl CredentialSource {
+    /// 是否直接使用配置文件中的密码
+    pub fn is_config(&self) -> bool {
+        matches!(self, CredentialSource::Builtin(name) if name == "config")
+    }
+
+    /// 创建凭据来源，使用配置文件中的密码时返回 `None`
+    pub fn build(&self) -> Result<Option<Arc<dyn CredentialProvider>>, String> {
+        match self {
+            CredentialSource::Builtin(name) => match name.as_str() {
+                "config" => Ok(None),
+                "prompt" => Ok(Some(Arc::new(Prompt))),
+                _ => Err(format!(
+                    "未知的凭据来源 {}，内置来源有: {}；其他来源写作 {{ env = \"变量名\" }}、\
+                     {{ command = \"命令\" }} 或 {{ keyring = \"服务名\" }}",
+                    name,
+                    BUILTIN_SOURCES.join(", ")
+                )),
+            },
+            CredentialSource::Env { env } => Ok(Some(Arc::new(EnvVar {
+                variable: env.clone(),
+            }))),
+            CredentialSource::Command { command } => Ok(Some(Arc::new(ExternalCommand {
+                command: command.clone(),
+            }))),
+            #[cfg(feature = "keyring")]
+            CredentialSource::Keyring { keyring } => Ok(Some(Arc::new(Keyring {
+                service: keyring.clone(),
+            }))),
+            #[cfg(not(feature = "keyring"))]
+            CredentialSource::Keyring { .. } => {
+                Err("系统密钥环未编译进当前程序，请启用 keyring 特性后重新编译".to_string())
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! 额外的存储目的地：文件进入下载目录后，再并行写入配置的各个目的地（例如另一块磁盘或对象存储）
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+
+/// 存储目的地
+///
+/// 新的存储后端只需实现该 trait 并通过 `DownloadOptions::with_destinations` 传入。
+pub trait Destination: Debug + Send + Sync {
+    /// 目的地名称，用于统计与下载清单
+    fn name(&self) -> &str;
+
+    /// 写入一个文件，`relative_path` 为文件相对下载目录的路径
+    fn put(&self, local_path: &Path, relative_path: &Path) -> Result<(), Box<dyn Error>>;
+}
+
+/// 本地或挂载的目录，按下载目录的结构保存
+#[derive(Debug, Clone)]
+pub struct LocalDirectory {
+    pub name: String,
+    pub root: PathBuf,
+}
+
+impl Destination for LocalDirectory {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn put(&self, local_path: &Path, relative_path: &Path) -> Result<(), Box<dyn Error>> {
+        let target = self.root.join(relative_path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // 先写临时文件再改名，目的地中不会出现写了一半的文件
+        let mut temp_name = target.as_os_str().to_owned();
+        temp_name.push(".part");
+        let temp = PathBuf::from(temp_name);
+        fs::copy(local_path, &temp)?;
+        fs::rename(&temp, &target)?;
+        Ok(())
+    }
+}
+
+/// 通过外部命令写入，例如 `aws s3 cp {local} s3://bucket/{relative}`
+///
+/// `{local}` 替换为本地文件路径，`{relative}` 替换为相对下载目录的路径（使用 `/` 分隔），
+/// 命令退出码非 0 视为写入失败。
+#[derive(Debug, Clone)]
+pub struct CommandDestination {
+    pub name: String,
+    pub command: String,
+}
+
+impl Destination for CommandDestination {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn put(&self, local_path: &Path, relative_path: &Path) -> Result<(), Box<dyn Error>> {
+        let relative = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let command = self
+            .command
+            .replace("{local}", &shell_quote(&local_path.to_string_lossy()))
+            .replace("{relative}", &shell_quote(&relative));
+
+        let output = shell(&command).output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(format!("命令退出状态 {}: {}", output.status, stderr.trim()).into())
+        }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+pub(crate) fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(unix)]
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("\"{}\"", value)
+}
+
+/// 配置文件中的存储目的地
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DestinationConfig {
+    Local { name: String, path: String },
+    Command { name: String, command: String },
+}
+
+impl DestinationConfig {
+    pub fn name(&self) -> &str {
+        match self {
+            DestinationConfig::Local { name, .. } | DestinationConfig::Command { name, .. } => name,
+        }
+    }
+
+    pub fn build(&self) -> Arc<dyn Destination> {
+        match self {
+            DestinationConfig::Local { name, path } => Arc::new(LocalDirectory {
+                name: name.clone(),
+                root: PathBuf::from(path),
+            }),
+            DestinationConfig::Command { name, command } => Arc::new(CommandDestination {
+                name: name.clone(),
+                command: command.clone(),
+            }),
+        }
+    }
+}
+
+/// 将一个文件并行写入多个目的地，按传入顺序返回每个目的地的结果
+pub fn fan_out(
+    destinations: &[Arc<dyn Destination>],
+    local_path: &Path,
+    relative_path: &Path,
+) -> Vec<(String, Result<(), String>)> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = destinations
+            .iter()
+            .map(|destination| {
+                scope.spawn(move || {
+                    destination
+                        .put(local_path, relative_path)
+                        .map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+        destinations
+            .iter()
+            .zip(handles)
+            .map(|(destination, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|_| Err("写入线程 panic".to_string()));
+                (destination.name().to_string(), result)
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,260 @@
+use crate::config::Config;
+use crate::timelist::{PUBLISH_DELAY_MINUTES, latest_published_slot};
+use crate::transport::{ConnectStage, RemoteConnection};
+use crate::units::format_bytes;
+use std::fs;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+/// 网络连接超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// 剩余磁盘空间低于该值时视为检查失败
+const MIN_FREE_SPACE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// 单项检查结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckOutcome {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// 单项检查记录
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+    pub detail: String,
+    pub hint: Option<&'static str>,
+}
+
+/// 诊断报告
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    fn pass(&mut self, name: &'static str, detail: String) {
+        self.checks.push(CheckResult {
+            name,
+            outcome: CheckOutcome::Passed,
+            detail,
+            hint: None,
+        });
+    }
+
+    fn fail(&mut self, name: &'static str, detail: String, hint: &'static str) {
+        self.checks.push(CheckResult {
+            name,
+            outcome: CheckOutcome::Failed,
+            detail,
+            hint: Some(hint),
+        });
+    }
+
+    fn skip(&mut self, name: &'static str) {
+        self.checks.push(CheckResult {
+            name,
+            outcome: CheckOutcome::Skipped,
+            detail: "前置检查未通过".to_string(),
+            hint: None,
+        });
+    }
+
+    /// 所有检查是否均通过
+    pub fn all_passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.outcome == CheckOutcome::Passed)
+    }
+
+    pub fn print_report(&self) {
+        println!("=== 诊断报告 ===");
+        for check in &self.checks {
+            let status = match check.outcome {
+                CheckOutcome::Passed => "通过",
+                CheckOutcome::Failed => "失败",
+                CheckOutcome::Skipped => "跳过",
+            };
+            println!("[{}] {}: {}", status, check.name, check.detail);
+            if let Some(hint) = check.hint {
+                println!("       建议: {}", hint);
+            }
+        }
+    }
+}
+
+/// 按顺序执行所有诊断检查
+pub fn run_doctor(config: &Config) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    let connection = check_remote(config, &mut report);
+    check_current_slot_listing(config, connection.as_deref(), &mut report);
+    check_local_storage(&config.download.base_path, &mut report);
+
+    report
+}
+
+/// 依次检查 DNS、TCP、SSH 握手、认证与 SFTP 子系统
+fn check_remote(config: &Config, report: &mut DoctorReport) -> Option<Box<dyn RemoteConnection>> {
+    const REMOTE_CHECKS: [&str; 4] = ["TCP 连接", "SSH 握手", "用户认证", "SFTP 子系统"];
+
+    let addr = match resolve_host(&config.server.host, config.server.port) {
+        Ok(addr) => {
+            report.pass(
+                "DNS 解析",
+                format!("{} -> {}", config.server.host, addr.ip()),
+            );
+            addr
+        }
+        Err(e) => {
+            report.fail(
+                "DNS 解析",
+                format!("{}: {}", config.server.host, e),
+                "检查 server.host 是否拼写正确，以及本机 DNS 设置",
+            );
+            REMOTE_CHECKS.iter().for_each(|name| report.skip(name));
+            return None;
+        }
+    };
+
+    let tcp = match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+        Ok(tcp) => {
+            report.pass("TCP 连接", format!("已连接 {}", addr));
+            tcp
+        }
+        Err(e) => {
+            report.fail(
+                "TCP 连接",
+                format!("{}: {}", addr, e),
+                "检查 server.port 是否正确，以及防火墙/代理是否放行该端口",
+            );
+            REMOTE_CHECKS[1..].iter().for_each(|name| report.skip(name));
+            return None;
+        }
+    };
+
+    let transport = config.server.transport;
+    match transport.connect_stream(
+        tcp,
+        &config.get_host_with_port(),
+        &config.server.username,
+        &config.server.credentials(),
+        Some(CONNECT_TIMEOUT),
+    ) {
+        Ok(connection) => {
+            report.pass("SSH 握手", format!("使用 {} 完成握手", transport.name()));
+            report.pass(
+                "用户认证",
+                format!("用户 {} 认证成功", config.server.username),
+            );
+            report.pass("SFTP 子系统", "SFTP 通道已建立".to_string());
+            Some(connection)
+        }
+        Err(e) => {
+            // 失败阶段之前的步骤视为通过，之后的步骤跳过
+            let (failed, hint) = match e.stage {
+                ConnectStage::Tcp | ConnectStage::Handshake => (
+                    1,
+                    "确认该端口运行的是 SSH/SFTP 服务（P-Tree 使用 2051 端口）",
+                ),
+                ConnectStage::Auth => (2, "检查用户名和密码（或私钥与口令），确认账户未被锁定"),
+                ConnectStage::Sftp => (3, "确认账户具有 SFTP 访问权限"),
+            };
+            for name in &REMOTE_CHECKS[1..failed] {
+                report.pass(name, "通过".to_string());
+            }
+            report.fail(REMOTE_CHECKS[failed], e.to_string(), hint);
+            REMOTE_CHECKS[failed + 1..]
+                .iter()
+                .for_each(|name| report.skip(name));
+            None
+        }
+    }
+}
+
+fn resolve_host(host: &str, port: u16) -> Result<SocketAddr, std::io::Error> {
+    (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "未解析到任何地址"))
+}
+
+fn check_current_slot_listing(
+    config: &Config,
+    connection: Option<&dyn RemoteConnection>,
+    report: &mut DoctorReport,
+) {
+    const NAME: &str = "列出当前时次目录";
+
+    let Some(connection) = connection else {
+        report.skip(NAME);
+        return;
+    };
+
+    let product = match config.product() {
+        Ok(product) => product,
+        Err(e) => {
+            report.fail(
+                NAME,
+                e,
+                "检查 download.product 是否为内置或 [[products]] 中定义的产品",
+            );
+            return;
+        }
+    };
+    let remote_dir = config
+        .layout
+        .build()
+        .directory(&product, &latest_published_slot(PUBLISH_DELAY_MINUTES));
+    match connection.list_directory(&remote_dir) {
+        Ok(entries) => report.pass(
+            NAME,
+            format!("{} 中有 {} 个条目", remote_dir, entries.len()),
+        ),
+        Err(e) => report.fail(
+            NAME,
+            format!("{}: {}", remote_dir, e),
+            "数据可能尚未发布，或账户无权访问该目录；稍后重试或联系数据提供方",
+        ),
+    }
+}
+
+fn check_local_storage(base_path: &str, report: &mut DoctorReport) {
+    let base_path = Path::new(base_path);
+    let probe = base_path.join(".hsd_doctor_probe");
+
+    let writable = fs::create_dir_all(base_path)
+        .and_then(|_| fs::write(&probe, b"probe"))
+        .and_then(|_| fs::remove_file(&probe));
+    match writable {
+        Ok(()) => report.pass("本地目录可写", format!("{}", base_path.display())),
+        Err(e) => {
+            report.fail(
+                "本地目录可写",
+                format!("{}: {}", base_path.display(), e),
+                "检查 download.base_path 是否存在以及当前用户是否有写权限",
+            );
+            report.skip("剩余磁盘空间");
+            return;
+        }
+    }
+
+    match fs4::available_space(base_path) {
+        Ok(free) if free >= MIN_FREE_SPACE => {
+            report.pass("剩余磁盘空间", format!("{} 可用", format_bytes(free)))
+        }
+        Ok(free) => report.fail(
+            "剩余磁盘空间",
+            format!("仅剩 {} 可用", format_bytes(free)),
+            "HSD 文件体积较大，请清理磁盘或更换 download.base_path",
+        ),
+        Err(e) => report.fail(
+            "剩余磁盘空间",
+            e.to_string(),
+            "无法查询磁盘空间，请手动确认",
+        ),
+    }
+}
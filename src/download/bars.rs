@@ -0,0 +1,177 @@
+//! 终端进度条（`cli` 特性）：总进度一行，每个工作线程一行，显示百分比、速度与预计剩余时间
+
+use super::ProgressDisplay;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+const OVERALL_TEMPLATE: &str =
+    "总进度 [{bar:30}] {percent:>3}% {bytes}/{total_bytes} {binary_bytes_per_sec} 剩余 {eta} {msg}";
+const WORKER_TEMPLATE: &str = "{prefix:>6} [{bar:30}] {percent:>3}% {bytes}/{total_bytes} {binary_bytes_per_sec} 剩余 {eta} {wide_msg}";
+
+/// 输出到标准错误的多行进度条
+#[derive(Debug)]
+pub struct ProgressBars {
+    multi: MultiProgress,
+    state: Mutex<BarState>,
+}
+
+#[derive(Debug, Default)]
+struct BarState {
+    overall: Option<ProgressBar>,
+    files: usize,
+    finished_files: usize,
+    workers: HashMap<usize, WorkerBar>,
+    /// 正在处理的文件所在的工作线程
+    active: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+struct WorkerBar {
+    bar: ProgressBar,
+    /// 当前文件已计入总进度的字节数
+    counted: u64,
+    /// 当前文件上一次报告的进度，尚未报告时为空
+    last: Option<u64>,
+}
+
+impl Default for ProgressBars {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressBars {
+    pub fn new() -> Self {
+        Self {
+            multi: MultiProgress::with_draw_target(ProgressDrawTarget::stderr()),
+            state: Mutex::new(BarState::default()),
+        }
+    }
+
+    /// 暂时隐藏进度条执行 `f`，用于输出日志，结束后重绘
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.multi.suspend(f)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, BarState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl BarState {
+    fn update_message(&self) {
+        if let Some(overall) = &self.overall {
+            overall.set_message(format!("{}/{} 个文件", self.finished_files, self.files));
+        }
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .expect("进度条模板有效")
+        .progress_chars("=> ")
+}
+
+impl ProgressDisplay for ProgressBars {
+    fn run_started(&self, files: usize, bytes: u64) {
+        let mut state = self.state();
+        let overall = self
+            .multi
+            .add(ProgressBar::new(bytes).with_style(style(OVERALL_TEMPLATE)));
+        if let Some(previous) = state.overall.replace(overall) {
+            previous.finish_and_clear();
+            self.multi.remove(&previous);
+        }
+        state.files = files;
+        state.finished_files = 0;
+        state.update_message();
+    }
+
+    fn file_started(&self, worker: usize, remote_path: &str) {
+        let mut state = self.state();
+        let worker_bar = state.workers.entry(worker).or_insert_with(|| {
+            let bar = self
+                .multi
+                .add(ProgressBar::new(0).with_style(style(WORKER_TEMPLATE)));
+            bar.set_prefix(format!("线程 {}", worker));
+            WorkerBar {
+                bar,
+                counted: 0,
+                last: None,
+            }
+        });
+        let filename = remote_path.rsplit('/').next().unwrap_or(remote_path);
+        worker_bar.bar.set_message(filename.to_string());
+        worker_bar.bar.set_length(0);
+        worker_bar.bar.set_position(0);
+        worker_bar.counted = 0;
+        worker_bar.last = None;
+        state.active.insert(remote_path.to_string(), worker);
+    }
+
+    fn file_progress(&self, remote_path: &str, transferred: u64, total: u64) {
+        let mut state = self.state();
+        let Some(&worker) = state.active.get(remote_path) else {
+            return;
+        };
+        let overall = state.overall.clone();
+        let Some(worker_bar) = state.workers.get_mut(&worker) else {
+            return;
+        };
+        match worker_bar.last {
+            Some(last) if transferred >= last && worker_bar.bar.length() == Some(total) => {
+                let delta = transferred - last;
+                worker_bar.counted += delta;
+                if let Some(overall) = &overall {
+                    overall.inc(delta);
+                }
+            }
+            // 新一次传输（首次或重试）：续传的起点不计入总进度与速度
+            _ => {
+                worker_bar.bar.set_length(total);
+                worker_bar.bar.reset_eta();
+            }
+        }
+        worker_bar.bar.set_position(transferred);
+        worker_bar.last = Some(transferred);
+    }
+
+    fn file_finished(&self, worker: usize, remote_path: &str, size: Option<u64>) {
+        let mut state = self.state();
+        state.active.remove(remote_path);
+        state.finished_files += 1;
+        let counted = match state.workers.get_mut(&worker) {
+            Some(worker_bar) => {
+                worker_bar.last = None;
+                std::mem::take(&mut worker_bar.counted)
+            }
+            None => 0,
+        };
+        // 已存在、续传或失败的文件按列目录时的大小补足，使总进度在全部处理完时到达终点
+        if let (Some(overall), Some(size)) = (&state.overall, size) {
+            overall.inc(size.saturating_sub(counted));
+        }
+        state.update_message();
+    }
+
+    fn worker_exited(&self, worker: usize) {
+        if let Some(worker_bar) = self.state().workers.remove(&worker) {
+            worker_bar.bar.finish_and_clear();
+            self.multi.remove(&worker_bar.bar);
+        }
+    }
+
+    fn run_finished(&self) {
+        let mut state = self.state();
+        for (_, worker_bar) in state.workers.drain() {
+            worker_bar.bar.finish_and_clear();
+            self.multi.remove(&worker_bar.bar);
+        }
+        state.active.clear();
+        if let Some(overall) = state.overall.take() {
+            overall.finish_and_clear();
+            self.multi.remove(&overall);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! 读缓冲区大小：固定大小，或根据连接表现自动调整；所有连接的缓冲区合计受内存预算限制
+
+use std::sync::{Condvar, Mutex, PoisonError};
+
+/// 自适应模式下缓冲区缩小的下限
+pub const MIN_ADAPTIVE_BUFFER_SIZE: usize = 16 * 1024;
+/// 连续多少个文件的读取基本都填满缓冲区后才增大缓冲区
+const GROW_AFTER_FILES: usize = 3;
+/// 读满缓冲区的读取次数达到该比例时，视为连接能提供更多数据
+const FULL_READ_RATIO: f64 = 0.9;
+
+/// 单个连接使用的读缓冲区大小
+///
+/// 自适应模式下，连接持续以整块返回数据（高吞吐）时逐步加倍直到上限，
+/// 传输出错（链路不稳定）时减半，最低不小于 [`MIN_ADAPTIVE_BUFFER_SIZE`]。
+#[derive(Debug, Clone)]
+pub struct BufferSizer {
+    current: usize,
+    min: usize,
+    max: usize,
+    full_read_streak: usize,
+    last_used: usize,
+}
+
+impl BufferSizer {
+    /// 固定大小的缓冲区
+    pub fn fixed(size: usize) -> Self {
+        Self {
+            current: size,
+            min: size,
+            max: size,
+            full_read_streak: 0,
+            last_used: size,
+        }
+    }
+
+    /// 从 `initial` 开始、不超过 `max` 的自适应缓冲区
+    pub fn adaptive(initial: usize, max: usize) -> Self {
+        let max = max.max(initial);
+        Self {
+            current: initial,
+            min: MIN_ADAPTIVE_BUFFER_SIZE.min(initial),
+            max,
+            full_read_streak: 0,
+            last_used: initial,
+        }
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        self.min != self.max
+    }
+
+    /// 下一次传输使用的缓冲区大小
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// 最近一次传输实际使用的缓冲区大小
+    pub fn last_used(&self) -> usize {
+        self.last_used
+    }
+
+    /// 开始一次传输，返回本次使用的缓冲区大小
+    pub(crate) fn begin_transfer(&mut self) -> usize {
+        self.last_used = self.current;
+        self.current
+    }
+
+    /// 传输成功后调用，`reads` 为读取次数，`full_reads` 为其中填满缓冲区的次数
+    pub(crate) fn record_success(&mut self, reads: usize, full_reads: usize) {
+        if reads > 0 && full_reads as f64 >= reads as f64 * FULL_READ_RATIO {
+            self.full_read_streak += 1;
+            if self.full_read_streak >= GROW_AFTER_FILES && self.current < self.max {
+                self.current = (self.current * 2).min(self.max);
+                self.full_read_streak = 0;
+            }
+        } else {
+            self.full_read_streak = 0;
+        }
+    }
+
+    /// 传输出错后调用
+    pub(crate) fn record_failure(&mut self) {
+        self.current = (self.current / 2).max(self.min);
+        self.full_read_streak = 0;
+    }
+}
+
+/// 所有传输中读缓冲区的内存预算
+///
+/// 每次传输开始前申请缓冲区大小的额度，传输结束时归还；额度不足时等待其他传输结束。
+/// 单个缓冲区超过整个预算时，等到没有其他传输占用额度后再放行，避免永远等待。
+#[derive(Debug, Default)]
+pub struct BufferBudget {
+    /// 允许同时分配的缓冲区总字节数，`None` 表示不限制（仍统计峰值）
+    limit: Option<usize>,
+    usage: Mutex<BufferUsage>,
+    released: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct BufferUsage {
+    in_use: usize,
+    peak: usize,
+}
+
+impl BufferBudget {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// 申请 `size` 字节的额度，额度不足时阻塞；返回的租约销毁时归还额度
+    pub fn acquire(&self, size: usize) -> BufferLease<'_> {
+        let mut usage = self.usage.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(limit) = self.limit {
+            while usage.in_use > 0 && usage.in_use + size > limit {
+                usage = self
+                    .released
+                    .wait(usage)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+        usage.in_use += size;
+        usage.peak = usage.peak.max(usage.in_use);
+        BufferLease { budget: self, size }
+    }
+
+    /// 本次运行中同时分配的缓冲区总字节数的峰值
+    pub fn peak(&self) -> usize {
+        self.usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .peak
+    }
+}
+
+/// 一次传输占用的缓冲区额度
+#[derive(Debug)]
+pub struct BufferLease<'a> {
+    budget: &'a BufferBudget,
+    size: usize,
+}
+
+impl Drop for BufferLease<'_> {
+    fn drop(&mut self) {
+        let mut usage = self
+            .budget
+            .usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        usage.in_use -= self.size;
+        self.budget.released.notify_all();
+    }
+}
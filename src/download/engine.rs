@@ -0,0 +1,2550 @@
+//! 多线程下载引擎，通过 [`RemoteConnection`] 访问远程服务器，与具体传输实现无关
+
+use super::{
+    BufferSizer, ConflictPolicy, ConnectionStats, DayStats, DirectoryFailure, DiscoveredFiles,
+    DownloadOptions, DownloadPlan, DownloadStats, DownloadedFile, DuplicatePolicy, FailedFile,
+    FinalizeOutcome, ParallelStrategy, PostProcessPool, PostProcessQueue, RateLimiter, ShortSlot,
+    SlotLatency, Transfer, UNPUBLISHED_POLL_INTERVAL, throttle,
+};
+use crate::backend::StorageBackend;
+use crate::checksum::{Checksum, ChecksumAlgorithm, Sha256Writer};
+use crate::destination::fan_out;
+use crate::events::RunEvent;
+use crate::failure::{DownloadError, FailureKind};
+use crate::himawari::{self, HsdFilename};
+#[cfg(feature = "sqlite")]
+use crate::history::HistoryEntry;
+use crate::manifest::{Manifest, QuarantineRecord, SupersededPolicy, SupersededRecord};
+use crate::product::ProductType;
+use crate::sidecar::FileMetadata;
+use crate::status::{Heartbeat, RunStatus};
+use crate::storage::LocalFileStorage;
+use crate::transport::{
+    ConnectionTag, Credentials, PooledConnection, RemoteConnection, RemoteEntry, SessionPool,
+};
+use crate::units::{format_bytes, format_duration};
+use crate::validation::validate_file;
+use chrono::{NaiveDateTime, Utc};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 边下载边写入磁盘的安全版本；`listed_size` 为列目录时得到的大小，首次尝试时用它代替查询远程属性
+#[allow(clippy::too_many_arguments)]
+fn download_and_save_file_streaming(
+    connection: &dyn RemoteConnection,
+    host: &str,
+    remote_path: &str,
+    listed_size: Option<u64>,
+    local_storage: &LocalFileStorage,
+    options: &DownloadOptions,
+    buffer: &mut BufferSizer,
+    manifest: &Mutex<Manifest>,
+) -> Result<Transfer, Box<dyn std::error::Error>> {
+    let span = tracing::info_span!(
+        "file_transfer",
+        remote_path,
+        bytes = tracing::field::Empty,
+        retries = tracing::field::Empty,
+    );
+    let _entered = span.enter();
+
+    let max_retries = options.max_retries;
+
+    // 检查文件是否已经存在并且完整
+    if let Some((stored_path, local_size)) = options.find_stored(local_storage, remote_path) {
+        tracing::debug!(
+            "文件已存在，跳过: {} ({})",
+            stored_path.display(),
+            format_bytes(local_size)
+        );
+        return Ok(Transfer {
+            bytes: 0,
+            outcome: FinalizeOutcome::AlreadyStored,
+            sha256: None,
+        });
+    }
+
+    let local_path = local_storage.generate_download_path(remote_path);
+    let temp_path = local_storage.generate_temp_path(&local_path);
+    let key = local_storage.archive_key(remote_path);
+    let target = match &options.backend {
+        Some(backend) => backend.location(&key),
+        None => local_path.display().to_string(),
+    };
+
+    // 创建目录
+    if options.backend.is_none()
+        && let Some(parent) = local_path.parent()
+    {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut retry_count = 0;
+    let mut last_error = None;
+
+    while retry_count <= max_retries {
+        // 重试时重新查询大小，避免列目录之后文件已在服务器上改变而一直校验失败
+        let known_size = listed_size.filter(|_| retry_count == 0);
+        let result = match &options.backend {
+            Some(backend) => download_to_backend(
+                connection,
+                remote_path,
+                known_size,
+                backend.as_ref(),
+                &key,
+                buffer,
+                options,
+            ),
+            None => download_file_with_resume(
+                connection,
+                remote_path,
+                known_size,
+                &temp_path,
+                &local_path,
+                buffer,
+                options,
+            ),
+        };
+        match result {
+            Ok(transfer) => {
+                if transfer.outcome.is_conflict() {
+                    tracing::warn!(
+                        "下载期间目标文件已被写入 {}: {}",
+                        local_path.display(),
+                        transfer.outcome.description()
+                    );
+                }
+                tracing::info!("完成下载: {} ({})", target, format_bytes(transfer.bytes));
+                span.record("bytes", transfer.bytes);
+                span.record("retries", retry_count);
+                // 写入存储后端的文件没有本地副本，不写来源信息
+                if options.backend.is_some() {
+                    #[cfg(feature = "sqlite")]
+                    record_stored(remote_path, &target, transfer.bytes, options);
+                    return Ok(transfer);
+                }
+                // 最终路径上保留的是已有文件时来源信息与校验和均与其内容不符，不写入
+                if matches!(
+                    transfer.outcome,
+                    FinalizeOutcome::KeptExisting | FinalizeOutcome::KeptBoth
+                ) {
+                    return Ok(Transfer {
+                        sha256: None,
+                        ..transfer
+                    });
+                }
+                let checksum = if options.write_metadata {
+                    write_file_metadata(
+                        connection,
+                        host,
+                        remote_path,
+                        &local_path,
+                        retry_count,
+                        options.checksum_algorithm,
+                    )
+                } else {
+                    None
+                };
+                // 下载时计算的 SHA-256 记入下载清单供 verify 命令核对，没有来源信息文件的校验和时也记入下载历史
+                #[cfg(feature = "sqlite")]
+                record_downloaded(
+                    remote_path,
+                    &local_path,
+                    checksum.or_else(|| {
+                        transfer.sha256.clone().map(|value| Checksum {
+                            algorithm: ChecksumAlgorithm::Sha256,
+                            value,
+                        })
+                    }),
+                    local_storage,
+                    options,
+                );
+                #[cfg(not(feature = "sqlite"))]
+                let _ = checksum;
+                return Ok(transfer);
+            }
+            Err(e) => {
+                tracing::debug!(attempt = retry_count + 1, error = %e, "传输失败");
+                // 目标文件已存在不会因重试而改变；中止的传输不再重试；连接已不可用时在同一连接上重试没有意义，
+                // 交给工作线程重新建立连接后重试
+                let kind = FailureKind::classify(e.as_ref());
+                if matches!(kind, FailureKind::FileConflict | FailureKind::Interrupted)
+                    || kind.is_session_lost()
+                {
+                    span.record("retries", retry_count);
+                    return Err(e);
+                }
+                if kind == FailureKind::InvalidContent {
+                    quarantine(
+                        remote_path,
+                        &temp_path,
+                        &e.to_string(),
+                        local_storage,
+                        manifest,
+                    );
+                }
+                buffer.record_failure();
+                last_error = Some(e);
+                options.retry_budget.record_failure(host);
+                retry_count += 1;
+                if retry_count <= max_retries {
+                    if !options.retry_budget.try_acquire_retry(host) {
+                        tracing::warn!("主机 {} 的重试预算已耗尽，放弃: {}", host, remote_path);
+                        break;
+                    }
+                    tracing::warn!(
+                        "下载失败，重试 {}/{}: {}",
+                        retry_count,
+                        max_retries,
+                        remote_path
+                    );
+                    thread::sleep(Duration::from_secs(2));
+                }
+            }
+        }
+    }
+
+    let kind = last_error
+        .as_deref()
+        .map(FailureKind::classify)
+        .unwrap_or(FailureKind::Other);
+    span.record("retries", retry_count - 1);
+    tracing::debug!(kind = %kind, "放弃下载");
+    Err(Box::new(DownloadError {
+        kind,
+        message: format!("下载失败，已重试 {} 次: {:?}", retry_count - 1, last_error),
+    }))
+}
+
+/// 将内容校验失败的临时文件移入隔离区并记入清单，下一次重试重新下载；移动失败时删除该文件
+fn quarantine(
+    remote_path: &str,
+    temp_path: &Path,
+    reason: &str,
+    local_storage: &LocalFileStorage,
+    manifest: &Mutex<Manifest>,
+) {
+    let size = fs::metadata(temp_path).map(|m| m.len()).unwrap_or(0);
+    let quarantine_path = local_storage.generate_quarantine_path(remote_path);
+    let result = quarantine_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::rename(temp_path, &quarantine_path));
+    if let Err(e) = result {
+        tracing::warn!("移入隔离区失败 {}: {}", temp_path.display(), e);
+        let _ = fs::remove_file(temp_path);
+        return;
+    }
+    tracing::warn!(
+        "内容校验失败，已移入隔离区: {} -> {} ({})",
+        remote_path,
+        quarantine_path.display(),
+        reason
+    );
+    manifest
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .record_quarantine(QuarantineRecord {
+            remote_path: remote_path.to_string(),
+            quarantine_path,
+            size,
+            reason: reason.to_string(),
+            detected_at: Utc::now(),
+        });
+}
+
+/// 在数据文件旁写入来源信息，返回其中的校验和；失败只提示，不影响已完成的下载
+fn write_file_metadata(
+    connection: &dyn RemoteConnection,
+    host: &str,
+    remote_path: &str,
+    local_path: &Path,
+    retries: usize,
+    algorithm: ChecksumAlgorithm,
+) -> Option<Checksum> {
+    let result = connection
+        .metadata(remote_path)
+        .and_then(|remote| {
+            Ok(FileMetadata::new(
+                host,
+                remote_path,
+                &remote,
+                local_path,
+                retries,
+                algorithm,
+            )?)
+        })
+        .and_then(|metadata| {
+            metadata.write_sidecar(local_path)?;
+            Ok(metadata.checksum)
+        });
+    match result {
+        Ok(checksum) => Some(checksum),
+        Err(e) => {
+            tracing::warn!("写入来源信息失败 {}: {}", local_path.display(), e);
+            None
+        }
+    }
+}
+
+/// 将下载完成的文件记入下载历史，位置记为其在归档中的最终位置；没有来源信息文件中的校验和时另行计算。
+/// 写入失败只提示
+#[cfg(feature = "sqlite")]
+fn record_downloaded(
+    remote_path: &str,
+    download_path: &Path,
+    checksum: Option<Checksum>,
+    local_storage: &LocalFileStorage,
+    options: &DownloadOptions,
+) {
+    let Some(history) = &options.history else {
+        return;
+    };
+    let size = fs::metadata(download_path).map_or(0, |metadata| metadata.len());
+    let checksum = checksum.or_else(|| {
+        Checksum::of_file(download_path, options.checksum_algorithm)
+            .inspect_err(|e| tracing::warn!("计算校验和失败 {}: {}", download_path.display(), e))
+            .ok()
+    });
+    let entry = HistoryEntry::downloaded(
+        remote_path,
+        &local_storage.generate_local_path(remote_path),
+        size,
+        checksum,
+    );
+    if let Err(e) = history.record(&entry) {
+        tracing::warn!("写入下载历史失败 {}: {}", remote_path, e);
+    }
+}
+
+/// 将写入存储后端的文件记入下载历史，位置记为对象的位置，不计算校验和；写入失败只提示
+#[cfg(feature = "sqlite")]
+fn record_stored(remote_path: &str, location: &str, size: u64, options: &DownloadOptions) {
+    let Some(history) = &options.history else {
+        return;
+    };
+    let entry = HistoryEntry::downloaded(remote_path, Path::new(location), size, None);
+    if let Err(e) = history.record(&entry) {
+        tracing::warn!("写入下载历史失败 {}: {}", remote_path, e);
+    }
+}
+
+/// 将下载失败的文件记入下载历史；写入失败只提示
+#[cfg(feature = "sqlite")]
+fn record_failed(
+    remote_path: &str,
+    listed_size: Option<u64>,
+    error: &str,
+    local_storage: &LocalFileStorage,
+    options: &DownloadOptions,
+) {
+    let Some(history) = &options.history else {
+        return;
+    };
+    let entry = HistoryEntry::failed(
+        remote_path,
+        &local_storage.generate_local_path(remote_path),
+        listed_size.unwrap_or(0),
+        error,
+    );
+    if let Err(e) = history.record(&entry) {
+        tracing::warn!("写入下载历史失败 {}: {}", remote_path, e);
+    }
+}
+
+/// 支持断点续传的下载函数；`known_size` 为空时先查询远程文件大小。按 `options` 报告进度，
+/// 启用内容校验时在移到最终位置前校验，失败时返回 [`FailureKind::InvalidContent`] 错误并保留临时文件
+pub(crate) fn download_file_with_resume(
+    connection: &dyn RemoteConnection,
+    remote_path: &str,
+    known_size: Option<u64>,
+    temp_path: &Path,
+    final_path: &Path,
+    buffer: &mut BufferSizer,
+    options: &DownloadOptions,
+) -> Result<Transfer, Box<dyn std::error::Error>> {
+    // 获取远程文件信息
+    let remote_size = match known_size {
+        Some(size) => size,
+        None => connection.metadata(remote_path)?.size,
+    };
+
+    // 检查是否存在临时文件
+    let mut start_pos = 0u64;
+    if temp_path.exists() {
+        let temp_size = fs::metadata(temp_path)?.len();
+        if temp_size < remote_size {
+            start_pos = temp_size;
+            tracing::info!(
+                "断点续传: {} (从 {} 处开始)",
+                remote_path,
+                format_bytes(start_pos)
+            );
+        } else {
+            fs::remove_file(temp_path)?;
+        }
+    }
+
+    // 打开远程文件；读取的超时由 copy_stream 按传输时限逐次设置
+    let mut remote_file = connection.open_at(remote_path, start_pos)?;
+
+    // 打开本地临时文件，写入时计算 SHA-256；续传时先计入已下载的部分
+    let mut local_file = Sha256Writer::new(
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(start_pos > 0)
+            .truncate(start_pos == 0)
+            .open(temp_path)?,
+    );
+    if start_pos > 0 {
+        local_file.update_from(File::open(temp_path)?.take(start_pos))?;
+    }
+
+    let copied = copy_stream(
+        connection,
+        remote_file.as_mut(),
+        &mut local_file,
+        remote_path,
+        start_pos,
+        remote_size,
+        buffer,
+        options,
+    )?;
+    let total_bytes = copied.bytes;
+
+    // 收到中止请求时把已读到的数据写入磁盘，临时文件留待下次续传
+    if copied.aborted {
+        local_file.flush()?;
+        local_file.get_mut().sync_all()?;
+        return Err(Box::new(DownloadError {
+            kind: FailureKind::Interrupted,
+            message: format!(
+                "传输被中止，已写入 {}/{} 保留在 {}",
+                format_bytes(total_bytes),
+                format_bytes(remote_size),
+                temp_path.display()
+            ),
+        }));
+    }
+
+    // 确保数据写入磁盘
+    local_file.flush()?;
+    local_file.get_mut().sync_all()?;
+    let sha256 = local_file.finish();
+
+    // 验证文件大小
+    if total_bytes != remote_size {
+        return Err(format!(
+            "文件大小不匹配: 预期 {} 字节，实际 {} 字节",
+            remote_size, total_bytes
+        )
+        .into());
+    }
+
+    if options.validate_downloads {
+        let filename = remote_path.rsplit('/').next().unwrap_or(remote_path);
+        if let Err(reason) = validate_file(temp_path, filename) {
+            return Err(Box::new(DownloadError {
+                kind: FailureKind::InvalidContent,
+                message: reason,
+            }));
+        }
+    }
+
+    // 将临时文件移动到最终位置
+    let outcome = finalize_download(temp_path, final_path, options.conflict_policy)?;
+    buffer.record_success(copied.reads, copied.full_reads);
+
+    Ok(Transfer {
+        bytes: total_bytes,
+        outcome,
+        sha256: Some(sha256),
+    })
+}
+
+/// 一次流式传输读到的数据
+struct StreamCopy {
+    /// 含续传起点在内的总字节数
+    bytes: u64,
+    reads: usize,
+    full_reads: usize,
+    /// 是否因中止请求而提前停止
+    aborted: bool,
+}
+
+/// 传输看门狗：距上次收到数据超过停滞超时，或本次传输超过单个文件的最长时间时中止传输。
+/// 阻塞中的读取由传输后端的读超时结束（见 [`RemoteConnection::set_read_timeout`]），之后在这里判定为超时
+struct TransferWatchdog {
+    started: Instant,
+    last_progress: Instant,
+    stall_timeout: Option<Duration>,
+    max_duration: Option<Duration>,
+}
+
+impl TransferWatchdog {
+    fn new(options: &DownloadOptions) -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last_progress: now,
+            stall_timeout: options.stall_timeout,
+            max_duration: options.max_file_duration,
+        }
+    }
+
+    fn progress(&mut self) {
+        self.last_progress = Instant::now();
+    }
+
+    /// 下一次读取最多等待的时间：停滞超时与单个文件剩余时间中较短的一个，阻塞的读取在任一时限到达时结束。
+    /// 至少 1 毫秒，libssh2 把 0 当作不限时
+    fn read_timeout(&self) -> Option<Duration> {
+        let remaining = self.max_duration.map(|max_duration| {
+            max_duration
+                .saturating_sub(self.started.elapsed())
+                .max(Duration::from_millis(1))
+        });
+        match (self.stall_timeout, remaining) {
+            (Some(stall_timeout), Some(remaining)) => Some(stall_timeout.min(remaining)),
+            (stall_timeout, remaining) => stall_timeout.or(remaining),
+        }
+    }
+
+    /// 超过任一时限时返回错误，按下载失败重试：停滞视为连接已不可用（[`FailureKind::Timeout`]），
+    /// 超过单个文件的最长时间只说明这次传输太慢，在同一连接上续传（[`FailureKind::TransferTooLong`]）
+    fn check(&self, bytes: u64, remote_size: u64) -> Result<(), DownloadError> {
+        let (kind, reason) = if let Some(stall_timeout) = self.stall_timeout
+            && self.last_progress.elapsed() >= stall_timeout
+        {
+            (
+                FailureKind::Timeout,
+                format!("超过 {} 秒没有收到数据", stall_timeout.as_secs()),
+            )
+        } else if let Some(max_duration) = self.max_duration
+            && self.started.elapsed() >= max_duration
+        {
+            (
+                FailureKind::TransferTooLong,
+                format!("传输超过 {} 秒仍未完成", max_duration.as_secs()),
+            )
+        } else {
+            return Ok(());
+        };
+        Err(DownloadError {
+            kind,
+            message: format!(
+                "{}，已传输 {}/{}",
+                reason,
+                format_bytes(bytes),
+                format_bytes(remote_size)
+            ),
+        })
+    }
+}
+
+/// 从远程文件读取并写入 `writer`，直到文件结束或收到中止请求；按 `options` 报告进度并限速，
+/// 传输停滞或超过单个文件的最长时间时返回超时错误。`remote_file` 须由 `connection` 打开，
+/// 每次读取前按剩余时限设置连接的读超时
+#[allow(clippy::too_many_arguments)]
+fn copy_stream(
+    connection: &dyn RemoteConnection,
+    remote_file: &mut dyn Read,
+    writer: &mut dyn Write,
+    remote_path: &str,
+    start_pos: u64,
+    remote_size: u64,
+    buffer: &mut BufferSizer,
+    options: &DownloadOptions,
+) -> Result<StreamCopy, Box<dyn std::error::Error>> {
+    // 使用缓冲区进行流式传输
+    let buffer_size = buffer.begin_transfer();
+    let _lease = options.buffer_budget.acquire(buffer_size);
+    let mut read_buffer = vec![0u8; buffer_size];
+    let mut copied = StreamCopy {
+        bytes: start_pos,
+        reads: 0,
+        full_reads: 0,
+        aborted: false,
+    };
+    let mut tracker = options
+        .progress
+        .tracker(remote_path, start_pos, remote_size)
+        .with_display(options.progress_display.as_deref());
+    let thread_limit = options.thread_bandwidth_limit.map(RateLimiter::new);
+    let limiters: Vec<&RateLimiter> = options
+        .bandwidth_limit
+        .as_deref()
+        .into_iter()
+        .chain(thread_limit.as_ref())
+        .collect();
+
+    let mut watchdog = TransferWatchdog::new(options);
+
+    loop {
+        if options.shutdown.aborting() {
+            copied.aborted = true;
+            return Ok(copied);
+        }
+        watchdog.check(copied.bytes, remote_size)?;
+        connection.set_read_timeout(watchdog.read_timeout());
+        match remote_file.read(&mut read_buffer) {
+            Ok(0) => return Ok(copied), // EOF
+            Ok(bytes_read) => {
+                writer.write_all(&read_buffer[..bytes_read])?;
+                copied.bytes += bytes_read as u64;
+                copied.reads += 1;
+                if bytes_read == buffer_size {
+                    copied.full_reads += 1;
+                }
+
+                tracker.update(copied.bytes);
+                if let Some(delay) = limiters
+                    .iter()
+                    .map(|limiter| limiter.consume(bytes_read))
+                    .max()
+                {
+                    throttle(delay, &options.shutdown);
+                }
+                watchdog.progress();
+            }
+            Err(e) => {
+                watchdog.check(copied.bytes, remote_size)?;
+                return Err(Box::new(DownloadError {
+                    kind: FailureKind::classify(&e),
+                    message: format!("读取远程文件失败: {}", e),
+                }));
+            }
+        }
+    }
+}
+
+/// 将远程文件直接写入存储后端；`known_size` 为空时先查询远程文件大小。对象在全部内容写入后才出现在后端，
+/// 失败或中止时放弃写入，不支持断点续传
+fn download_to_backend(
+    connection: &dyn RemoteConnection,
+    remote_path: &str,
+    known_size: Option<u64>,
+    backend: &dyn StorageBackend,
+    key: &str,
+    buffer: &mut BufferSizer,
+    options: &DownloadOptions,
+) -> Result<Transfer, Box<dyn std::error::Error>> {
+    let remote_size = match known_size {
+        Some(size) => size,
+        None => connection.metadata(remote_path)?.size,
+    };
+    let mut remote_file = connection.open_at(remote_path, 0)?;
+    let mut writer = backend.writer(key, remote_size)?;
+
+    let copied = match copy_stream(
+        connection,
+        remote_file.as_mut(),
+        &mut writer,
+        remote_path,
+        0,
+        remote_size,
+        buffer,
+        options,
+    ) {
+        Ok(copied) => copied,
+        Err(e) => {
+            writer.abort();
+            return Err(e);
+        }
+    };
+    if copied.aborted {
+        writer.abort();
+        return Err(Box::new(DownloadError {
+            kind: FailureKind::Interrupted,
+            message: format!(
+                "传输被中止，已读取 {}/{}，未写入 {}",
+                format_bytes(copied.bytes),
+                format_bytes(remote_size),
+                backend.location(key)
+            ),
+        }));
+    }
+    if copied.bytes != remote_size {
+        writer.abort();
+        return Err(format!(
+            "文件大小不匹配: 预期 {} 字节，实际 {} 字节",
+            remote_size, copied.bytes
+        )
+        .into());
+    }
+
+    writer.finish()?;
+    buffer.record_success(copied.reads, copied.full_reads);
+    Ok(Transfer {
+        bytes: copied.bytes,
+        outcome: FinalizeOutcome::Created,
+        sha256: None,
+    })
+}
+
+/// 将下载完成的临时文件放到最终路径：以硬链接原子地创建最终路径，路径上已有文件时按策略处理；
+/// 文件系统不支持硬链接时退回为先检查再重命名
+fn finalize_download(
+    temp_path: &Path,
+    final_path: &Path,
+    policy: ConflictPolicy,
+) -> Result<FinalizeOutcome, Box<dyn std::error::Error>> {
+    let exists = match fs::hard_link(temp_path, final_path) {
+        Ok(()) => {
+            fs::remove_file(temp_path)?;
+            return Ok(FinalizeOutcome::Created);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => true,
+        Err(_) => final_path.exists(),
+    };
+    if !exists {
+        fs::rename(temp_path, final_path)?;
+        return Ok(FinalizeOutcome::Created);
+    }
+
+    match policy {
+        ConflictPolicy::Overwrite => {
+            fs::rename(temp_path, final_path)?;
+            Ok(FinalizeOutcome::Overwritten)
+        }
+        ConflictPolicy::KeepExisting => {
+            fs::remove_file(temp_path)?;
+            Ok(FinalizeOutcome::KeptExisting)
+        }
+        ConflictPolicy::KeepBoth => {
+            let kept = place_beside(temp_path, final_path)?;
+            tracing::warn!("新下载的文件另存为 {}", kept.display());
+            Ok(FinalizeOutcome::KeptBoth)
+        }
+        ConflictPolicy::Error => {
+            fs::remove_file(temp_path)?;
+            Err(Box::new(DownloadError {
+                kind: FailureKind::FileConflict,
+                message: format!("目标文件已存在: {}", final_path.display()),
+            }))
+        }
+    }
+}
+
+/// 将临时文件放到最终路径旁尚未使用的 `<文件名>.conflict-<时间>[-<序号>]`，最终路径上的已有文件保持不动；
+/// 与 [`finalize_download`] 一样以硬链接原子地占用名称，不支持硬链接时退回为先检查再重命名
+fn place_beside(
+    temp_path: &Path,
+    final_path: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let stamp = Utc::now().format("%Y%m%d%H%M%S").to_string();
+    let mut attempt = 0u32;
+    loop {
+        let mut sibling = final_path.as_os_str().to_owned();
+        sibling.push(format!(".conflict-{}", stamp));
+        if attempt > 0 {
+            sibling.push(format!("-{}", attempt));
+        }
+        let sibling = PathBuf::from(sibling);
+        attempt += 1;
+        match fs::hard_link(temp_path, &sibling) {
+            Ok(()) => {
+                fs::remove_file(temp_path)?;
+                return Ok(sibling);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(_) if sibling.exists() => continue,
+            Err(_) => {
+                fs::rename(temp_path, &sibling)?;
+                return Ok(sibling);
+            }
+        }
+    }
+}
+
+/// 读取远程目录并筛选属于该产品、观测时间、观测区域与分辨率的文件
+pub(crate) fn list_remote_files_in_directory(
+    connection: &dyn RemoteConnection,
+    remote_dir: &str,
+    target_time: &NaiveDateTime,
+    options: &DownloadOptions,
+    bands: &[String],
+) -> Result<Vec<RemoteEntry>, Box<dyn std::error::Error>> {
+    let mut matched_files = Vec::new();
+    let product = &options.product;
+
+    // 读取目录内容
+    let dir_entries = connection.list_directory_entries(remote_dir)?;
+
+    for entry in dir_entries {
+        if let Some(filename) = Path::new(&entry.path).file_name() {
+            let filename_str = filename.to_string_lossy();
+            let parsed = HsdFilename::parse(&filename_str);
+
+            // 按产品文件名模式筛选时间与观测区域
+            if !options.areas.iter().any(|area| {
+                options
+                    .layout
+                    .matches(product, &filename_str, target_time, area)
+            }) {
+                continue;
+            }
+            // 检查是否包含所需波段
+            if product.has_bands
+                && !bands.is_empty()
+                && !bands.iter().any(|band| match &parsed {
+                    Some(parsed) => parsed.band == *band,
+                    None => filename_str.contains(band),
+                })
+            {
+                continue;
+            }
+            if let Some(only_files) = &options.only_files
+                && !only_files.contains(&entry.path)
+            {
+                continue;
+            }
+            if let Some(only_segments) = &options.only_segments
+                && !parsed
+                    .as_ref()
+                    .is_some_and(|parsed| only_segments.contains(&parsed.segment_key()))
+            {
+                continue;
+            }
+            // 同一波段有多种分辨率时只保留所需的分辨率
+            if let Some(parsed) = &parsed
+                && !options.resolutions.is_empty()
+                && !options.resolutions.contains(&parsed.resolution)
+            {
+                continue;
+            }
+            matched_files.push(entry);
+        }
+    }
+
+    Ok(matched_files)
+}
+
+/// 同一份数据的规范标识：HSD 文件为时次、波段、区域与分段（忽略卫星编号、分辨率标记与扩展名后缀），
+/// 其他文件为完整的远程路径
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CanonicalKey {
+    Hsd(NaiveDateTime, String, String, u32, u32),
+    Path(String),
+}
+
+impl CanonicalKey {
+    fn of(remote_path: &str) -> Self {
+        let filename = Path::new(remote_path)
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        match HsdFilename::parse(&filename) {
+            Some(hsd) => Self::Hsd(
+                hsd.datetime,
+                hsd.band,
+                hsd.area,
+                hsd.segment,
+                hsd.total_segments,
+            ),
+            None => Self::Path(remote_path.to_string()),
+        }
+    }
+}
+
+/// 去掉目录列表中重复的同一份数据，按策略保留一个，返回保留的文件（保持列表顺序）与去掉的文件
+fn deduplicate_listing(
+    files: Vec<RemoteEntry>,
+    policy: DuplicatePolicy,
+) -> (Vec<RemoteEntry>, Vec<String>) {
+    let mut kept: Vec<RemoteEntry> = Vec::with_capacity(files.len());
+    let mut index_of: HashMap<CanonicalKey, usize> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for entry in files {
+        let key = CanonicalKey::of(&entry.path);
+        let Some(&index) = index_of.get(&key) else {
+            index_of.insert(key, kept.len());
+            kept.push(entry);
+            continue;
+        };
+        let current = &kept[index];
+        let replace = match policy {
+            DuplicatePolicy::Newest => {
+                (entry.metadata.modified, entry.metadata.size)
+                    > (current.metadata.modified, current.metadata.size)
+            }
+            DuplicatePolicy::Largest => entry.metadata.size > current.metadata.size,
+            DuplicatePolicy::First => false,
+            DuplicatePolicy::ShortestName => entry.path.len() < current.path.len(),
+        };
+        if replace {
+            duplicates.push(std::mem::replace(&mut kept[index], entry).path);
+        } else {
+            duplicates.push(entry.path);
+        }
+    }
+    (kept, duplicates)
+}
+
+/// 从文件名中提取观测时次（`YYYYMMDD_HHMM`）与波段
+fn slot_and_band(remote_path: &str) -> (String, String) {
+    let filename = Path::new(remote_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // HS_H09_20250717_0900_B03_FLDK_R05_S0101.DAT.bz2
+    let parts: Vec<&str> = filename.split('_').collect();
+    if parts.len() >= 5 {
+        (format!("{}_{}", parts[2], parts[3]), parts[4].to_string())
+    } else {
+        (String::new(), String::new())
+    }
+}
+
+/// 文件所属的时次与观测区域，用于整时次提交
+fn slot_and_area(remote_path: &str) -> (String, String) {
+    let (slot, _) = slot_and_band(remote_path);
+    let filename = Path::new(remote_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let area = filename.split('_').nth(5).unwrap_or_default().to_string();
+    (slot, area)
+}
+
+/// 整时次提交：请求的文件全部下载完成（且包含每个请求的波段）的时次从暂存目录移入归档，
+/// 不完整的时次保留在暂存目录，下次运行继续补齐；关闭运行后自动提交时全部留待 `promote` 处理
+fn commit_complete_slots(
+    requested: &[String],
+    bands: &[String],
+    options: &DownloadOptions,
+    local_storage: &LocalFileStorage,
+    post_processing: &PostProcessQueue,
+    stats: &mut DownloadStats,
+) {
+    if local_storage.staging_path.is_none() || !local_storage.promote_after_run {
+        return;
+    }
+
+    let mut slots: BTreeMap<(String, String), Vec<&String>> = BTreeMap::new();
+    for file in requested {
+        slots.entry(slot_and_area(file)).or_default().push(file);
+    }
+
+    for ((slot, area), files) in slots {
+        let missing = files
+            .iter()
+            .filter(|file| options.find_stored(local_storage, file).is_none())
+            .count();
+        let missing_bands: Vec<&String> = if options.product.has_bands {
+            bands
+                .iter()
+                .filter(|band| !files.iter().any(|file| slot_and_band(file).1 == **band))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        if missing > 0 || !missing_bands.is_empty() {
+            let mut reason = format!("缺少 {} 个文件", missing);
+            if !missing_bands.is_empty() {
+                reason.push_str(&format!("，远程缺少波段 {:?}", missing_bands));
+            }
+            tracing::info!("时次 {} {} 不完整，保留在暂存目录: {}", slot, area, reason);
+            stats.staged_slots += 1;
+            continue;
+        }
+
+        // 只处理本次从暂存目录移入的文件，已在归档中的文件之前已经处理过
+        let staged: Vec<&String> = files
+            .iter()
+            .copied()
+            .filter(|file| {
+                local_storage
+                    .generate_staged_paths(file)
+                    .iter()
+                    .any(|path| path.exists())
+            })
+            .collect();
+        let result = files
+            .iter()
+            .try_for_each(|file| local_storage.commit_staged(file));
+        match result {
+            Ok(()) => {
+                tracing::info!(
+                    "时次 {} {} 已完整，移入归档 ({} 个文件)",
+                    slot,
+                    area,
+                    files.len()
+                );
+                stats.committed_slots += 1;
+                // 原始数据已提交，处理失败只记入后处理统计
+                for file in staged {
+                    post_process(file, local_storage, post_processing);
+                }
+            }
+            Err(e) => {
+                tracing::warn!("时次 {} {} 移入归档失败: {}", slot, area, e);
+                stats.staged_slots += 1;
+            }
+        }
+    }
+}
+
+/// 文件下载完成后减少所属时次与观测区域的剩余文件数；减到 0 时该时次已完整，记录其相对观测时间的延迟
+fn complete_slot_file(
+    slot_remaining: &Mutex<HashMap<(String, String), usize>>,
+    file_slots: &HashMap<String, NaiveDateTime>,
+    remote_path: &str,
+    stats: &mut DownloadStats,
+) -> Option<SlotLatency> {
+    let key = slot_and_area(remote_path);
+    {
+        let mut slot_remaining = lock(slot_remaining);
+        let remaining = slot_remaining.get_mut(&key)?;
+        *remaining -= 1;
+        if *remaining > 0 {
+            return None;
+        }
+        slot_remaining.remove(&key);
+    }
+    let datetime = *file_slots.get(remote_path)?;
+    let completed_at = Utc::now();
+    let latency = (completed_at.naive_utc() - datetime)
+        .to_std()
+        .unwrap_or_default();
+    tracing::info!(
+        "时次 {} {} 已下载完成，距观测 {}",
+        datetime.format("%Y-%m-%d %H:%M"),
+        key.1,
+        format_duration(latency)
+    );
+    let latency = SlotLatency {
+        datetime,
+        area: key.1,
+        completed_at,
+        latency,
+    };
+    stats.slot_latencies.push(latency.clone());
+    Some(latency)
+}
+
+/// 按时次排序，同一时次内按波段优先级排序；未列出的波段排在其后并保持原有顺序
+fn order_by_band_priority(mut files: Vec<String>, band_priority: &[String]) -> Vec<String> {
+    if band_priority.is_empty() {
+        return files;
+    }
+
+    files.sort_by_cached_key(|file| {
+        let (slot, band) = slot_and_band(file);
+        let rank = band_priority
+            .iter()
+            .position(|priority| *priority == band)
+            .unwrap_or(band_priority.len());
+        (slot, rank)
+    });
+    files
+}
+
+/// 按并行策略将文件分组为工作单元，保持组内与组间的原有顺序
+fn group_work_units(files: Vec<String>, strategy: ParallelStrategy) -> Vec<Vec<String>> {
+    if strategy == ParallelStrategy::File {
+        return files.into_iter().map(|file| vec![file]).collect();
+    }
+
+    // 单元保持首次出现的顺序，按键记录单元所在的位置
+    let mut units: Vec<Vec<String>> = Vec::new();
+    let mut index_of: HashMap<String, usize> = HashMap::new();
+    for file in files {
+        let (slot, band) = slot_and_band(&file);
+        let key = match strategy {
+            ParallelStrategy::Slot => slot,
+            _ => band,
+        };
+        match index_of.get(&key) {
+            Some(&index) => units[index].push(file),
+            None => {
+                index_of.insert(key, units.len());
+                units.push(vec![file]);
+            }
+        }
+    }
+    units
+}
+
+/// 重新建立连接的次数上限
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+/// 两次重新连接之间的初始等待，每次失败后加倍
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(2);
+
+/// 重新连接的等待上限
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// 每次重新连接最多等待的时间，服务器没有响应时不会让工作线程一直阻塞
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 连接断开后为工作线程重新建立连接：先把断开的会话 `lost_session` 移出会话池，避免再在其上打开通道，
+/// 再以新的代数登录，失败时按退避间隔再试，最多 [`RECONNECT_ATTEMPTS`] 次。
+/// 收到中断请求或到达运行时长上限时放弃，返回 `None`
+fn reconnect(
+    sessions: &SessionPool,
+    lost_session: &ConnectionTag,
+    worker: usize,
+    options: &DownloadOptions,
+) -> Option<(ConnectionTag, PooledConnection)> {
+    let host = sessions.host();
+    sessions.evict(lost_session);
+    let mut backoff = RECONNECT_BACKOFF;
+    for attempt in 1..=RECONNECT_ATTEMPTS {
+        if options.shutdown.requested() || options.deadline_reached() {
+            return None;
+        }
+        options.retry_budget.wait_for_connect_slot(host);
+        let tag = ConnectionTag::next(format!("w{}", worker));
+        match sessions.connect_within(&tag, Some(RECONNECT_TIMEOUT)) {
+            Ok(pooled) => {
+                tracing::info!("线程 {} 已重新连接", tag);
+                return Some((tag, pooled));
+            }
+            Err(e) => {
+                options.retry_budget.record_failure(host);
+                tracing::warn!(
+                    "线程 {} 重新连接失败 ({}/{}): {}",
+                    tag,
+                    attempt,
+                    RECONNECT_ATTEMPTS,
+                    e
+                );
+            }
+        }
+        if attempt < RECONNECT_ATTEMPTS && !options.shutdown.sleep(backoff) {
+            return None;
+        }
+        backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+    }
+    None
+}
+
+/// 从 panic 负载中取出消息文本
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "未知错误".to_string())
+}
+
+/// 运行结束时按时次与区域统计本地已有的文件数，少于预期的时次记入统计并给出警告
+fn check_expected_counts(
+    download_list: &[NaiveDateTime],
+    requested: &[String],
+    options: &DownloadOptions,
+    local_storage: &LocalFileStorage,
+    stats: &mut DownloadStats,
+) {
+    let Some(expected) = options.expected_files_per_slot else {
+        return;
+    };
+
+    let mut by_slot: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+    for file in requested {
+        by_slot.entry(slot_and_band(file).0).or_default().push(file);
+    }
+
+    for datetime in download_list {
+        let files = by_slot
+            .get(&datetime.format("%Y%m%d_%H%M").to_string())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for area in &options.areas {
+            // 全圆盘的例行维护时次本来就没有数据
+            if area == "FLDK" && himawari::is_housekeeping_slot(datetime) {
+                continue;
+            }
+            let area_marker = format!("_{}", area);
+            let found = files
+                .iter()
+                .filter(|file| file.contains(&area_marker))
+                .filter(|file| options.find_stored(local_storage, file).is_some())
+                .count();
+            if found < expected {
+                tracing::warn!(
+                    "时次 {} 区域 {} 只有 {} 个文件，预期 {} 个",
+                    datetime.format("%Y-%m-%d %H:%M"),
+                    area,
+                    found,
+                    expected
+                );
+                stats.short_slots.push(ShortSlot {
+                    datetime: *datetime,
+                    area: area.clone(),
+                    found,
+                    expected,
+                });
+            }
+        }
+    }
+}
+
+/// 将已进入下载目录的文件交给后处理线程池；处理失败计入后处理统计，不影响下载结果
+fn post_process(remote_path: &str, local_storage: &LocalFileStorage, queue: &PostProcessQueue) {
+    queue.submit(remote_path, local_storage.generate_local_path(remote_path));
+}
+
+/// 将已进入下载目录（不含暂存目录）的文件写入尚未成功写入的存储目的地
+fn replicate(
+    remote_path: &str,
+    options: &DownloadOptions,
+    local_storage: &LocalFileStorage,
+    manifest: &Mutex<Manifest>,
+    stats: &mut DownloadStats,
+) {
+    if options.destinations.is_empty() {
+        return;
+    }
+    let local_path = local_storage.generate_local_path(remote_path);
+    let Ok(relative_path) = local_path.strip_prefix(&local_storage.base_path) else {
+        return;
+    };
+    if !local_path.exists() {
+        return;
+    }
+    let pending: Vec<_> = {
+        let manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+        options
+            .destinations
+            .iter()
+            .filter(|destination| !manifest.replicated(remote_path, destination.name()))
+            .cloned()
+            .collect()
+    };
+    if pending.is_empty() {
+        return;
+    }
+
+    let results = fan_out(&pending, &local_path, relative_path);
+    let mut manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+    for (name, result) in results {
+        let destination_stats = stats.destinations.entry(name.clone()).or_default();
+        match &result {
+            Ok(()) => destination_stats.written += 1,
+            Err(e) => {
+                destination_stats.failed += 1;
+                tracing::warn!("写入存储目的地 {} 失败 {}: {}", name, remote_path, e);
+            }
+        }
+        manifest.record_replica(remote_path, &name, &result);
+    }
+}
+
+/// 读取下载清单，损坏时从空清单开始，避免中断下载
+fn load_manifest(local_storage: &LocalFileStorage) -> Manifest {
+    Manifest::load(&local_storage.base_path).unwrap_or_else(|e| {
+        tracing::warn!("读取下载清单失败，将重新建立: {}", e);
+        let mut manifest = Manifest::default();
+        manifest.set_path(&local_storage.base_path);
+        manifest
+    })
+}
+
+/// 打开本进程中同时进行的运行共用的下载清单，损坏时从空清单开始，避免中断下载
+fn open_manifest(local_storage: &LocalFileStorage) -> Arc<Mutex<Manifest>> {
+    Manifest::open_shared(&local_storage.base_path).unwrap_or_else(|e| {
+        tracing::warn!("读取下载清单失败，将重新建立: {}", e);
+        let mut manifest = Manifest::default();
+        manifest.set_path(&local_storage.base_path);
+        Arc::new(Mutex::new(manifest))
+    })
+}
+
+/// 加锁；其他线程持锁时 panic 不影响后续使用，panic 由工作线程单独处理
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 运行结束时的清单：去掉本次已续传完成的中断记录
+fn finish_manifest(manifest: &Mutex<Manifest>) -> MutexGuard<'_, Manifest> {
+    let mut manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+    manifest.clear_finished_interrupted();
+    manifest
+}
+
+fn save_manifest(manifest: &Manifest) {
+    if let Err(e) = manifest.save() {
+        tracing::error!("保存下载清单失败: {}", e);
+    }
+}
+
+/// 按策略处理被远程新版本取代的本地文件，返回是否需要下载新版本
+fn handle_superseded(record: &SupersededRecord) -> bool {
+    tracing::info!(
+        "远程文件已更新: {} (本地 {}) -> {} ({})",
+        record.local_path.display(),
+        format_bytes(record.local_size),
+        record.new_remote_path,
+        format_bytes(record.new_size)
+    );
+    let result = match record.policy {
+        SupersededPolicy::Flag => return false,
+        SupersededPolicy::Keep => {
+            let mut kept = record.local_path.clone().into_os_string();
+            kept.push(format!(".superseded-{}", Utc::now().format("%Y%m%d%H%M%S")));
+            fs::rename(&record.local_path, &kept)
+        }
+        SupersededPolicy::Replace => fs::remove_file(&record.local_path).and_then(|_| {
+            let sidecar = crate::sidecar::sidecar_path(&record.local_path);
+            if sidecar.exists() {
+                fs::remove_file(sidecar)
+            } else {
+                Ok(())
+            }
+        }),
+    };
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!("处理旧版本文件失败 {}: {}", record.local_path.display(), e);
+            false
+        }
+    }
+}
+
+/// 列出一个时次的远程目录并归类其中的文件。目录不存在时：时次仍在发布等待期内则返回该时次以便稍后重试，
+/// 否则记为永久缺失
+fn discover_directory(
+    connection: &dyn RemoteConnection,
+    datetime: &NaiveDateTime,
+    remote_dir: &str,
+    bands: &[String],
+    options: &DownloadOptions,
+    local_storage: &LocalFileStorage,
+    discovered: &mut DiscoveredFiles,
+) -> Option<(NaiveDateTime, String)> {
+    let files =
+        match list_remote_files_in_directory(connection, remote_dir, datetime, options, bands) {
+            Ok(files) => files,
+            Err(e) if FailureKind::classify(e.as_ref()) == FailureKind::RemoteMissing => {
+                if options.awaiting_publication(datetime) {
+                    return Some((*datetime, remote_dir.to_string()));
+                }
+                tracing::warn!("目录不存在，记为缺失 {}: {}", remote_dir, e);
+                discovered
+                    .missing_directories
+                    .push((remote_dir.to_string(), *datetime));
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!("读取目录失败 {}: {}", remote_dir, e);
+                discovered.unlisted.push(*datetime);
+                discovered.failed_directories.push(DirectoryFailure {
+                    directory: remote_dir.to_string(),
+                    slot: *datetime,
+                    kind: FailureKind::classify(e.as_ref()),
+                    error: e.to_string(),
+                });
+                return None;
+            }
+        };
+
+    // 尚未上传完成的文件不下载，所属时次留待下次运行
+    let (files, not_ready): (Vec<RemoteEntry>, Vec<RemoteEntry>) = files
+        .into_iter()
+        .partition(|entry| options.layout.is_ready(entry));
+    if !not_ready.is_empty() {
+        tracing::info!(
+            "{} 中有 {} 个文件尚未上传完成，留待下次运行",
+            remote_dir,
+            not_ready.len()
+        );
+        discovered.unlisted.push(*datetime);
+        discovered
+            .not_ready
+            .extend(not_ready.into_iter().map(|entry| entry.path));
+    }
+
+    let (files, duplicates) = deduplicate_listing(files, options.duplicate_policy);
+    if !duplicates.is_empty() {
+        tracing::info!(
+            "{} 中有 {} 个重复列出的文件，已去重",
+            remote_dir,
+            duplicates.len()
+        );
+    }
+    discovered.duplicates.extend(duplicates);
+    tracing::debug!("在 {} 找到 {} 个文件", remote_dir, files.len());
+    discovered.listed_directories.push(remote_dir.to_string());
+    if files.is_empty() {
+        discovered.empty_slots.push(*datetime);
+    }
+    for entry in files {
+        let file = entry.path;
+        discovered.slots.insert(file.clone(), *datetime);
+        discovered.sizes.insert(file.clone(), entry.metadata.size);
+        // 存储后端中大小不同的对象直接重新下载并覆盖
+        if options.backend.is_some() {
+            match options.find_stored(local_storage, &file) {
+                Some((_, size)) if size == entry.metadata.size => discovered.existing.push(file),
+                Some((location, size)) => {
+                    tracing::info!(
+                        "远程文件已更新: {} ({}) -> {} ({})，将覆盖",
+                        location.display(),
+                        format_bytes(size),
+                        file,
+                        format_bytes(entry.metadata.size)
+                    );
+                    discovered.to_download.push(file);
+                }
+                None => discovered.to_download.push(file),
+            }
+            continue;
+        }
+        // 本地已有同名文件但大小不同，或已有同一观测数据的不同文件名版本，说明远程文件已被更新
+        let previous = match options.find_stored(local_storage, &file) {
+            Some((_, size)) if size == entry.metadata.size => {
+                discovered.existing.push(file);
+                continue;
+            }
+            Some((path, _)) if local_storage.is_decompressed_copy(&path, &file) => {
+                discovered.existing.push(file);
+                continue;
+            }
+            Some((path, size)) => Some((path, size, file.clone())),
+            None => local_storage
+                .find_previous_version(&file)
+                .map(|(path, size)| {
+                    let old_remote = Path::new(&file)
+                        .with_file_name(path.file_name().unwrap())
+                        .to_string_lossy()
+                        .to_string();
+                    (path, size, old_remote)
+                }),
+        };
+
+        if let Some((local_path, local_size, remote_path)) = previous {
+            let record = SupersededRecord {
+                remote_path,
+                local_path,
+                local_size,
+                new_remote_path: file.clone(),
+                new_size: entry.metadata.size,
+                detected_at: Utc::now(),
+                policy: options.superseded_policy,
+            };
+            let fetch = if options.dry_run {
+                record.policy != SupersededPolicy::Flag
+            } else {
+                handle_superseded(&record)
+            };
+            discovered.superseded.push(record);
+            if !fetch {
+                discovered.existing.push(file);
+                continue;
+            }
+        }
+
+        discovered.to_download.push(file);
+    }
+    None
+}
+
+/// 推送运行结束事件
+fn emit_run_finished(options: &DownloadOptions, stats: &DownloadStats) {
+    options.emit(RunEvent::RunFinished {
+        product: options.product.name.clone(),
+        downloaded_files: stats.downloaded_files,
+        existing_files: stats.existing_files,
+        failed_files: stats.failed_files,
+        total_bytes: stats.total_bytes,
+        elapsed_secs: stats.elapsed_time.as_secs_f64(),
+    });
+}
+
+/// 文件所属观测日期的统计，文件不在发现结果中时返回 `None`
+fn day_stats<'a>(
+    stats: &'a mut DownloadStats,
+    file_slots: &HashMap<String, NaiveDateTime>,
+    file: &str,
+) -> Option<&'a mut DayStats> {
+    let slot = file_slots.get(file)?;
+    Some(stats.days.entry(slot.date()).or_default())
+}
+
+/// 将未能下载的文件计入所属日期的失败数，并把所属时次记为未完成
+fn mark_file_pending(
+    stats: &mut DownloadStats,
+    file_slots: &HashMap<String, NaiveDateTime>,
+    file: &str,
+) {
+    if let Some(slot) = file_slots.get(file) {
+        stats.pending_slots.insert(*slot);
+        stats.days.entry(slot.date()).or_default().failed_files += 1;
+    }
+}
+
+/// 收集所有要下载的文件列表并过滤已存在的文件
+fn collect_files_to_download(
+    download_list: &[NaiveDateTime],
+    bands: &[String],
+    options: &DownloadOptions,
+    sessions: &SessionPool,
+    local_storage: &LocalFileStorage,
+) -> Result<DiscoveredFiles, Box<dyn std::error::Error>> {
+    let _span = tracing::info_span!("discover_files").entered();
+    tracing::info!("开始收集需要下载的文件列表...");
+
+    // 建立连接
+    options.retry_budget.wait_for_connect_slot(sessions.host());
+    let tag = ConnectionTag::next("discover");
+    let connection = sessions
+        .connect(&tag)
+        .inspect_err(|_| {
+            options.retry_budget.record_failure(sessions.host());
+        })?
+        .connection;
+    tracing::debug!("发现阶段连接 {} 已建立", tag);
+
+    let mut discovered = DiscoveredFiles::default();
+    // 目录不存在且时次仍在发布等待期内的时次，稍后轮询
+    let mut unpublished: Vec<(NaiveDateTime, String)> = Vec::new();
+
+    for (index, datetime) in download_list.iter().enumerate() {
+        // 收到中断请求后其余时次不再列出，记为未完成以便续传
+        if options.shutdown.requested() {
+            tracing::info!("收到中断请求，{} 个时次未列出", download_list.len() - index);
+            discovered.unlisted.extend(&download_list[index..]);
+            break;
+        }
+        let remote_dir = options.layout.directory(&options.product, datetime);
+        if let Some(pending) = discover_directory(
+            connection.as_ref(),
+            datetime,
+            &remote_dir,
+            bands,
+            options,
+            local_storage,
+            &mut discovered,
+        ) {
+            tracing::info!("目录 {} 尚未发布，稍后重试", remote_dir);
+            unpublished.push(pending);
+        }
+    }
+
+    // 近期时次的目录可能只是还没有发布：定期重新列出，直到出现或超过等待期
+    while !unpublished.is_empty()
+        && options.poll_unpublished
+        && !options.dry_run
+        && !options.deadline_reached()
+    {
+        tracing::info!(
+            "等待 {} 个尚未发布的时次，{} 秒后重试",
+            unpublished.len(),
+            UNPUBLISHED_POLL_INTERVAL.as_secs()
+        );
+        if !options.shutdown.sleep(UNPUBLISHED_POLL_INTERVAL) {
+            break;
+        }
+        unpublished.retain(|(datetime, remote_dir)| {
+            discover_directory(
+                connection.as_ref(),
+                datetime,
+                remote_dir,
+                bands,
+                options,
+                local_storage,
+                &mut discovered,
+            )
+            .is_some()
+        });
+    }
+    for (datetime, remote_dir) in unpublished {
+        tracing::info!("目录 {} 仍未发布，留待下次运行", remote_dir);
+        discovered.unlisted.push(datetime);
+        discovered.unpublished.push(datetime);
+    }
+
+    tracing::info!("已存在文件: {} 个", discovered.existing.len());
+    if !discovered.duplicates.is_empty() {
+        tracing::info!("重复列出而去掉的文件: {} 个", discovered.duplicates.len());
+    }
+    if !discovered.missing_directories.is_empty() {
+        tracing::info!(
+            "服务器上不存在的目录: {} 个",
+            discovered.missing_directories.len()
+        );
+    }
+    if !discovered.superseded.is_empty() {
+        tracing::info!("远程已更新的文件: {} 个", discovered.superseded.len());
+    }
+    if !discovered.failed_directories.is_empty() {
+        tracing::info!("读取失败的目录: {} 个", discovered.failed_directories.len());
+    }
+    if !discovered.empty_slots.is_empty() {
+        tracing::info!("没有匹配文件的时次: {} 个", discovered.empty_slots.len());
+    }
+    if !discovered.not_ready.is_empty() {
+        tracing::info!("尚未上传完成的文件: {} 个", discovered.not_ready.len());
+    }
+    tracing::info!("需要下载: {} 个", discovered.to_download.len());
+
+    Ok(discovered)
+}
+
+/// 列出下载计划：与下载时相同地发现文件并过滤本地已存在的文件，但不清理、不改动本地文件，也不开始传输
+pub fn plan_download(
+    download_list: &[NaiveDateTime],
+    bands: &[String],
+    options: &DownloadOptions,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    local_storage: &LocalFileStorage,
+) -> Result<DownloadPlan, Box<dyn std::error::Error>> {
+    let mut plan = DownloadPlan {
+        product: options.product.name.clone(),
+        ..DownloadPlan::default()
+    };
+    if download_list.is_empty() {
+        tracing::info!("下载列表为空");
+        return Ok(plan);
+    }
+
+    let mut options = options.clone();
+    options.dry_run = true;
+    let local_storage = &local_storage
+        .clone()
+        .with_recorded_locations(load_manifest(local_storage).locations_by_filename());
+    let sessions = SessionPool::new(
+        options.transport,
+        host,
+        username,
+        credentials,
+        options.channels_per_session,
+    );
+    let discovered =
+        collect_files_to_download(download_list, bands, &options, &sessions, local_storage)?;
+
+    plan.discovery = discovered.report();
+    plan.existing_files = discovered.existing.len();
+    plan.superseded_files = discovered.superseded.len();
+    plan.unlisted = discovered.unlisted.clone();
+    for file in order_by_band_priority(discovered.to_download, &options.band_priority) {
+        let (Some(slot), Some(size)) = (discovered.slots.get(&file), discovered.sizes.get(&file))
+        else {
+            continue;
+        };
+        plan.slots.entry(*slot).or_default().push((file, *size));
+    }
+    Ok(plan)
+}
+
+/// 多线程流式下载FLDK文件 - 优化版
+///
+/// 每次运行都重新发现剩余文件并放入共享队列，由当前的 `num_threads` 个线程动态领取，
+/// 续传时不依赖上一次运行的线程分配。
+pub fn download_fldk_files_streaming(
+    download_list: Vec<NaiveDateTime>,
+    bands: Vec<String>,
+    options: DownloadOptions,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    local_storage: LocalFileStorage,
+) -> Result<DownloadStats, Box<dyn std::error::Error>> {
+    let start_time = Instant::now();
+    let run_span = tracing::info_span!(
+        "download_run",
+        product = %options.product.name,
+        slots = download_list.len(),
+        threads = options.num_threads,
+        downloaded = tracing::field::Empty,
+        failed = tracing::field::Empty,
+    );
+    let _entered = run_span.enter();
+
+    if download_list.is_empty() {
+        tracing::info!("下载列表为空，跳过下载");
+        return Ok(DownloadStats::new());
+    }
+
+    // 清理未完成的下载，上次运行中断时保留的临时文件留待续传
+    let manifest = open_manifest(&local_storage);
+    let (locations, interrupted) = {
+        let manifest = lock(&manifest);
+        (
+            manifest.locations_by_filename(),
+            manifest.interrupted.clone(),
+        )
+    };
+    let local_storage = local_storage.with_recorded_locations(locations);
+    tracing::info!("清理未完成的下载文件...");
+    let cleanup_result = local_storage.cleanup_incomplete_downloads(&interrupted)?;
+    if !cleanup_result.removed.is_empty() {
+        tracing::info!("已清理 {} 个未完成的下载文件", cleanup_result.removed.len());
+    }
+
+    // 检查波段数据完整性
+    if options.product.has_bands && !bands.is_empty() && options.backend.is_none() {
+        tracing::info!("检查波段数据完整性...");
+        let report = local_storage.check_band_completeness(&download_list, &options.areas, &bands);
+        report.print_report();
+    }
+
+    if !bands.is_empty() {
+        tracing::info!("筛选波段: {:?}", bands);
+    } else {
+        tracing::info!("下载所有FLDK文件");
+    }
+    if !options.resolutions.is_empty() {
+        tracing::info!("筛选分辨率: {:?}", options.resolutions);
+    }
+
+    tracing::info!(
+        "准备下载 {} 个时间点的 {} 数据，观测区域: {:?}",
+        download_list.len(),
+        options.product.name,
+        options.areas
+    );
+
+    // 发现阶段与各工作线程共享已认证的会话，每个会话上的通道数不超过 channels_per_session
+    let sessions = Arc::new(SessionPool::new(
+        options.transport,
+        host,
+        username,
+        credentials,
+        options.channels_per_session,
+    ));
+
+    // 收集需要下载的文件
+    let discovered =
+        collect_files_to_download(&download_list, &bands, &options, &sessions, &local_storage)?;
+
+    // 本地已存在的文件计入统计，总文件数反映完整的请求集合
+    let mut initial_stats = DownloadStats::new();
+    initial_stats.discovery = discovered.report();
+    initial_stats.existing_files = discovered.existing.len();
+    initial_stats.total_files = discovered.existing.len() + discovered.to_download.len();
+    let requested: Vec<String> = discovered
+        .existing
+        .iter()
+        .chain(&discovered.to_download)
+        .cloned()
+        .collect();
+    // 每个文件所属的时次，用于按日汇总与记录未完成的时次
+    let file_slots: Arc<HashMap<String, NaiveDateTime>> = Arc::new(discovered.slots.clone());
+    let listed_sizes: Arc<HashMap<String, u64>> = Arc::new(discovered.sizes.clone());
+    // 各时次与观测区域尚未下载完成的文件数；有文件尚未上传完成或目录读取失败的时次不计延迟
+    let mut slot_remaining: HashMap<(String, String), usize> = HashMap::new();
+    for file in &discovered.to_download {
+        if file_slots
+            .get(file)
+            .is_some_and(|slot| !discovered.unlisted.contains(slot))
+        {
+            *slot_remaining.entry(slot_and_area(file)).or_default() += 1;
+        }
+    }
+    let slot_remaining = Arc::new(Mutex::new(slot_remaining));
+    initial_stats
+        .pending_slots
+        .extend(discovered.unlisted.iter().copied());
+    for file in &requested {
+        if let Some(day) = day_stats(&mut initial_stats, &file_slots, file) {
+            day.total_files += 1;
+        }
+    }
+    for file in &discovered.existing {
+        if let Some(day) = day_stats(&mut initial_stats, &file_slots, file) {
+            day.existing_files += 1;
+        }
+    }
+    if options.file_results {
+        initial_stats
+            .existing
+            .extend(discovered.existing.iter().cloned());
+    }
+
+    // 不使用暂存目录时，本次下载的文件由工作线程在下载后立即写入目的地，运行结束时只需补写其余文件
+    let replicate_at_end: Vec<String> = if local_storage.staging_path.is_none() {
+        discovered.existing.clone()
+    } else {
+        requested.clone()
+    };
+
+    {
+        let mut manifest = lock(&manifest);
+        for record in discovered.superseded {
+            manifest.record_superseded(record);
+        }
+        for remote_dir in &discovered.listed_directories {
+            manifest.clear_missing_directory(remote_dir);
+        }
+        for (remote_dir, slot) in &discovered.missing_directories {
+            manifest.record_missing_directory(remote_dir, *slot);
+        }
+    }
+    // 后处理在独立的线程池中进行，不占用下载线程
+    let post_processing = PostProcessPool::start(
+        &options.pipeline,
+        options.pipeline_threads,
+        &options.shutdown,
+    );
+
+    if discovered.to_download.is_empty() {
+        tracing::info!("没有需要下载的文件");
+        commit_complete_slots(
+            &requested,
+            &bands,
+            &options,
+            &local_storage,
+            post_processing.queue(),
+            &mut initial_stats,
+        );
+        initial_stats.post_processing = post_processing.finish();
+        check_expected_counts(
+            &download_list,
+            &requested,
+            &options,
+            &local_storage,
+            &mut initial_stats,
+        );
+        for file in &replicate_at_end {
+            replicate(
+                file,
+                &options,
+                &local_storage,
+                &manifest,
+                &mut initial_stats,
+            );
+        }
+        save_manifest(&finish_manifest(&manifest));
+        initial_stats.elapsed_time = start_time.elapsed();
+        emit_run_finished(&options, &initial_stats);
+        return Ok(initial_stats);
+    }
+
+    // 按时次与波段优先级排序后放入共享队列，由各线程依次领取
+    let files_to_download = order_by_band_priority(discovered.to_download, &options.band_priority);
+    let work_units = group_work_units(files_to_download, options.parallel_strategy);
+    let num_threads = options.num_threads.min(work_units.len());
+    let queued_files = work_units.iter().map(Vec::len).sum();
+    options.emit(RunEvent::RunStarted {
+        product: options.product.name.clone(),
+        total_files: requested.len(),
+        existing_files: discovered.existing.len(),
+        queued_files,
+    });
+    if let Some(display) = &options.progress_display {
+        let queued_bytes = work_units
+            .iter()
+            .flatten()
+            .filter_map(|file| listed_sizes.get(file))
+            .sum();
+        display.run_started(queued_files, queued_bytes);
+    }
+    let queue = Arc::new(Mutex::new(VecDeque::from(work_units)));
+    // 第一轮中失败的文件先留待运行结束前用新建立的连接重试一遍，重试仍失败才计为失败
+    let deferred: Arc<Mutex<Vec<FailedFile>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut final_pass = !options.final_retry;
+    let mut sessions = sessions;
+    let mut num_threads = num_threads;
+    let mut retried: HashSet<String> = HashSet::new();
+
+    // 定期写入 status.json，供外部监控判断运行是否卡住
+    let run_status = options
+        .status_interval
+        .map(|_| Arc::new(RunStatus::new(&local_storage.base_path, queued_files)));
+    let _heartbeat = run_status
+        .as_ref()
+        .zip(options.status_interval)
+        .map(|(status, interval)| Heartbeat::start(Arc::clone(status), interval));
+
+    // 创建共享统计信息
+    let total_stats = Arc::new(Mutex::new(initial_stats));
+    let mut escaped_panics = Vec::new();
+
+    loop {
+        let mut handles = Vec::new();
+
+        // 为每个线程创建任务
+        for thread_id in 0..num_threads {
+            let queue = Arc::clone(&queue);
+            let stats_clone = Arc::clone(&total_stats);
+            let sessions = Arc::clone(&sessions);
+            let storage_clone = local_storage.clone();
+            let options = options.clone();
+            let run_span = run_span.clone();
+            let run_status = run_status.clone();
+            let manifest = Arc::clone(&manifest);
+            let file_slots = Arc::clone(&file_slots);
+            let listed_sizes = Arc::clone(&listed_sizes);
+            let post_queue = post_processing.queue().clone();
+            let deferred = Arc::clone(&deferred);
+            let slot_remaining = Arc::clone(&slot_remaining);
+
+            let handle = thread::spawn(move || {
+                // 连接标识写入 SSH 客户端标识串与日志，便于与服务器端的会话记录对应
+                let mut tag = ConnectionTag::next(format!("w{}", thread_id));
+                let _span = tracing::info_span!(
+                    parent: &run_span,
+                    "worker",
+                    thread_id,
+                    connection = %tag
+                )
+                .entered();
+                tracing::debug!("线程 {} 开始领取下载任务", tag);
+
+                // 建立连接
+                let host = sessions.host();
+                options.retry_budget.wait_for_connect_slot(host);
+                let (mut connection, mut session) = match sessions.connect(&tag) {
+                    Ok(pooled) => {
+                        if pooled.session != tag {
+                            tracing::debug!("线程 {} 复用会话 {}", tag, pooled.session);
+                        }
+                        (pooled.connection, pooled.session)
+                    }
+                    Err(e) => {
+                        options.retry_budget.record_failure(host);
+                        tracing::error!("线程 {} 建立连接失败: {}", tag, e);
+                        return;
+                    }
+                };
+                if let Some(status) = &run_status {
+                    status.worker_connected(thread_id, &tag);
+                }
+                options.emit(RunEvent::WorkerConnected {
+                    worker: thread_id,
+                    connection: tag.to_string(),
+                });
+
+                let mut thread_stats = DownloadStats::new();
+                thread_stats
+                    .connections
+                    .insert(tag.to_string(), ConnectionStats::default());
+                let mut buffer = options.buffer_sizer();
+
+                // 从共享队列中领取工作单元直到队列为空，单元内的文件由本线程依次下载
+                let mut last_transfer_end: Option<Instant> = None;
+                // 无法重新建立连接时本线程退出，剩余的文件留在队列中交给其他线程
+                let mut connection_lost = false;
+                'dispatch: loop {
+                    if connection_lost {
+                        break;
+                    }
+                    if options.deadline_reached() {
+                        tracing::info!("线程 {} 已到达运行时长上限，不再领取新任务", tag);
+                        break;
+                    }
+                    if options.shutdown.requested() {
+                        tracing::info!("线程 {} 收到中断请求，不再领取新任务", tag);
+                        break;
+                    }
+                    let work_unit = {
+                        let mut queue = lock(&queue);
+                        let work_unit = queue.pop_front();
+                        if let Some(status) = &run_status {
+                            status.set_queue_remaining(queue.iter().map(Vec::len).sum());
+                        }
+                        work_unit
+                    };
+                    let Some(work_unit) = work_unit else {
+                        break;
+                    };
+                    let mut files = work_unit.into_iter();
+                    while let Some(file_path) = files.next() {
+                        // 同一连接上两次传输之间保持礼貌间隔；已存在而跳过的文件不产生传输，无需等待。
+                        // 等待期间收到中断请求时立即结束等待，由下面的检查把文件放回队列
+                        if let Some(last_end) = last_transfer_end
+                            && !connection_lost
+                        {
+                            let remaining =
+                                options.inter_file_delay.saturating_sub(last_end.elapsed());
+                            if !remaining.is_zero() {
+                                options.shutdown.sleep(remaining);
+                            }
+                        }
+                        // 到达运行时长上限、收到中断请求或无法重新连接后，单元内尚未开始的文件放回队列，稍后计入未完成
+                        let stop_reason = if options.deadline_reached() {
+                            Some("已到达运行时长上限")
+                        } else if options.shutdown.requested() {
+                            Some("收到中断请求")
+                        } else if connection_lost {
+                            Some("无法重新建立连接")
+                        } else {
+                            None
+                        };
+                        if let Some(reason) = stop_reason {
+                            let mut remaining = vec![file_path];
+                            remaining.extend(files);
+                            lock(&queue).push_front(remaining);
+                            tracing::info!("线程 {} {}，不再开始新文件", tag, reason);
+                            break 'dispatch;
+                        }
+
+                        if let Some(status) = &run_status {
+                            status.start_file(thread_id, &file_path);
+                        }
+                        if let Some(display) = &options.progress_display {
+                            display.file_started(thread_id, &file_path);
+                        }
+                        options.emit(RunEvent::FileStarted {
+                            worker: thread_id,
+                            file: file_path.clone(),
+                        });
+                        // 连接断开时重新建立连接后再下载，已写入临时文件的部分续传；重新连接也计入重试次数
+                        let mut reconnects = 0;
+                        let result = loop {
+                            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                                download_and_save_file_streaming(
+                                    connection.as_ref(),
+                                    host,
+                                    &file_path,
+                                    listed_sizes.get(&file_path).copied(),
+                                    &storage_clone,
+                                    &options,
+                                    &mut buffer,
+                                    &manifest,
+                                )
+                            }));
+                            let Ok(Err(e)) = &result else {
+                                break result;
+                            };
+                            if !FailureKind::classify(e.as_ref()).is_session_lost()
+                                || reconnects >= options.max_retries
+                                || !options.retry_budget.try_acquire_retry(host)
+                            {
+                                break result;
+                            }
+                            tracing::warn!("线程 {} 的连接已不可用，重新连接: {}", tag, e);
+                            let Some((new_tag, pooled)) =
+                                reconnect(&sessions, &session, thread_id, &options)
+                            else {
+                                connection_lost = true;
+                                break result;
+                            };
+                            reconnects += 1;
+                            tag = new_tag;
+                            connection = pooled.connection;
+                            session = pooled.session;
+                            thread_stats
+                                .connections
+                                .insert(tag.to_string(), ConnectionStats::default());
+                            if let Some(status) = &run_status {
+                                status.worker_connected(thread_id, &tag);
+                            }
+                            options.emit(RunEvent::WorkerConnected {
+                                worker: thread_id,
+                                connection: tag.to_string(),
+                            });
+                        };
+                        // panic 后连接状态不可信：本文件记为失败，单元内剩余文件放回队列交给其他线程，本线程退出
+                        let result = match result {
+                            Ok(result) => result,
+                            Err(payload) => {
+                                let message = format!(
+                                    "线程 {} 下载 {} 时 panic: {}",
+                                    tag,
+                                    file_path,
+                                    panic_message(payload.as_ref())
+                                );
+                                tracing::error!("{}", message);
+                                thread_stats.record_failure(
+                                    &file_path,
+                                    FailureKind::WorkerPanic,
+                                    &message,
+                                );
+                                mark_file_pending(&mut thread_stats, &file_slots, &file_path);
+                                if let Some(status) = &run_status {
+                                    status.finish_file(thread_id, None);
+                                }
+                                if let Some(display) = &options.progress_display {
+                                    display.file_finished(
+                                        thread_id,
+                                        &file_path,
+                                        listed_sizes.get(&file_path).copied(),
+                                    );
+                                }
+                                options.emit(RunEvent::FileFailed {
+                                    worker: thread_id,
+                                    file: file_path.clone(),
+                                    kind: FailureKind::WorkerPanic.to_string(),
+                                    error: message.clone(),
+                                });
+                                thread_stats.panics.push(message);
+                                let remaining: Vec<String> = files.collect();
+                                if !remaining.is_empty() {
+                                    lock(&queue).push_front(remaining);
+                                }
+                                break 'dispatch;
+                            }
+                        };
+                        if let Some(status) = &run_status {
+                            status.finish_file(
+                                thread_id,
+                                result.as_ref().ok().map(|transfer| transfer.bytes),
+                            );
+                        }
+                        if let Some(display) = &options.progress_display {
+                            display.file_finished(
+                                thread_id,
+                                &file_path,
+                                listed_sizes.get(&file_path).copied(),
+                            );
+                        }
+                        options.emit(match &result {
+                            Ok(transfer) => RunEvent::FileFinished {
+                                worker: thread_id,
+                                file: file_path.clone(),
+                                bytes: transfer.bytes,
+                                outcome: transfer.outcome,
+                            },
+                            Err(e) => RunEvent::FileFailed {
+                                worker: thread_id,
+                                file: file_path.clone(),
+                                kind: FailureKind::classify(e.as_ref()).to_string(),
+                                error: e.to_string(),
+                            },
+                        });
+                        if !matches!(result, Ok(Transfer { bytes: 0, .. })) {
+                            last_transfer_end = Some(Instant::now());
+                        }
+
+                        match result {
+                            Ok(transfer) => {
+                                let bytes = transfer.bytes;
+                                if options.file_results {
+                                    thread_stats.downloaded.push(DownloadedFile {
+                                        remote_path: file_path.clone(),
+                                        bytes,
+                                        outcome: transfer.outcome,
+                                        sha256: transfer.sha256.clone(),
+                                    });
+                                }
+                                if transfer.outcome.is_conflict() {
+                                    *thread_stats.conflicts.entry(transfer.outcome).or_default() +=
+                                        1;
+                                }
+                                if bytes > 0 {
+                                    thread_stats.downloaded_files += 1;
+                                    {
+                                        let mut manifest = lock(&manifest);
+                                        let stored_path = match &options.backend {
+                                            Some(backend) => {
+                                                PathBuf::from(backend.location(
+                                                    &storage_clone.archive_key(&file_path),
+                                                ))
+                                            }
+                                            None => storage_clone.generate_local_path(&file_path),
+                                        };
+                                        manifest.record_download(
+                                            &file_path,
+                                            &stored_path,
+                                            bytes,
+                                            transfer.sha256.clone(),
+                                        );
+                                        manifest.record_usage(bytes);
+                                    }
+                                    if buffer.is_adaptive() {
+                                        *thread_stats
+                                            .buffer_sizes
+                                            .entry(buffer.last_used())
+                                            .or_default() += 1;
+                                    }
+                                    thread_stats.total_bytes += bytes;
+                                    let connection_stats = thread_stats
+                                        .connections
+                                        .entry(tag.to_string())
+                                        .or_default();
+                                    connection_stats.files += 1;
+                                    connection_stats.bytes += bytes;
+                                    if let Some(day) =
+                                        day_stats(&mut thread_stats, &file_slots, &file_path)
+                                    {
+                                        day.downloaded_files += 1;
+                                        day.bytes += bytes;
+                                    }
+                                    // 暂存模式下文件在整时次提交后才进入下载目录，届时再写入目的地并处理；
+                                    // 写入存储后端的文件不进入下载目录
+                                    if storage_clone.staging_path.is_none()
+                                        && options.backend.is_none()
+                                    {
+                                        replicate(
+                                            &file_path,
+                                            &options,
+                                            &storage_clone,
+                                            &manifest,
+                                            &mut thread_stats,
+                                        );
+                                        post_process(&file_path, &storage_clone, &post_queue);
+                                    }
+                                } else {
+                                    thread_stats.skipped_files += 1;
+                                    if let Some(day) =
+                                        day_stats(&mut thread_stats, &file_slots, &file_path)
+                                    {
+                                        day.skipped_files += 1;
+                                    }
+                                }
+                                if let Some(latency) = complete_slot_file(
+                                    &slot_remaining,
+                                    &file_slots,
+                                    &file_path,
+                                    &mut thread_stats,
+                                ) && let Some(status) = &run_status
+                                {
+                                    status.slot_completed(&latency);
+                                }
+                            }
+                            Err(e) => {
+                                let kind = FailureKind::classify(e.as_ref());
+                                if !final_pass && kind.is_retryable() {
+                                    tracing::warn!(
+                                        "线程 {} 下载失败 {}: {}，运行结束前重试",
+                                        tag,
+                                        file_path,
+                                        e
+                                    );
+                                    lock(&deferred).push(FailedFile {
+                                        remote_path: file_path.clone(),
+                                        kind,
+                                        error: e.to_string(),
+                                    });
+                                    continue;
+                                }
+                                tracing::error!("线程 {} 下载失败 {}: {}", tag, file_path, e);
+                                thread_stats.record_failure(&file_path, kind, &e.to_string());
+                                mark_file_pending(&mut thread_stats, &file_slots, &file_path);
+                                // 中止的传输下次运行续传，不记为失败
+                                #[cfg(feature = "sqlite")]
+                                if kind != FailureKind::Interrupted {
+                                    record_failed(
+                                        &file_path,
+                                        listed_sizes.get(&file_path).copied(),
+                                        &e.to_string(),
+                                        &storage_clone,
+                                        &options,
+                                    );
+                                }
+                                // 中止时已写入的部分保留，下次运行启动清理时不删除
+                                if kind == FailureKind::Interrupted {
+                                    let temp_path = storage_clone.generate_temp_path(
+                                        &storage_clone.generate_download_path(&file_path),
+                                    );
+                                    if temp_path.exists() {
+                                        lock(&manifest).record_interrupted(&temp_path);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                tracing::info!(
+                    "线程 {} 完成，成功: {}, 跳过: {}, 失败: {}, 下载量: {}",
+                    tag,
+                    thread_stats.downloaded_files,
+                    thread_stats.skipped_files,
+                    thread_stats.failed_files,
+                    format_bytes(thread_stats.total_bytes)
+                );
+
+                if let Some(status) = &run_status {
+                    status.worker_exited(thread_id);
+                }
+                if let Some(display) = &options.progress_display {
+                    display.worker_exited(thread_id);
+                }
+                options.emit(RunEvent::WorkerExited { worker: thread_id });
+
+                // 合并统计信息
+                lock(&stats_clone).merge(&thread_stats);
+            });
+
+            handles.push((thread_id, handle));
+        }
+
+        // 等待所有线程完成；下载之外的 panic 会丢失该线程的统计，只记录 panic 信息
+        for (thread_id, handle) in handles {
+            if let Err(payload) = handle.join() {
+                let message = format!(
+                    "线程 {} panic: {}",
+                    thread_id,
+                    panic_message(payload.as_ref())
+                );
+                tracing::error!("{}", message);
+                escaped_panics.push(message);
+            }
+        }
+        if final_pass {
+            break;
+        }
+        final_pass = true;
+
+        let failed = mem::take(&mut *lock(&deferred));
+        // 中断或到达运行时长上限时不再重试，留待重试的文件直接计为失败
+        if options.shutdown.requested() || options.deadline_reached() {
+            let mut stats = lock(&total_stats);
+            for file in &failed {
+                stats.record_failure(&file.remote_path, file.kind, &file.error);
+                mark_file_pending(&mut stats, &file_slots, &file.remote_path);
+                #[cfg(feature = "sqlite")]
+                record_failed(
+                    &file.remote_path,
+                    listed_sizes.get(&file.remote_path).copied(),
+                    &file.error,
+                    &local_storage,
+                    &options,
+                );
+            }
+            break;
+        }
+        // 所有线程都无法建立连接而剩在队列中的文件一并重试
+        let mut retry_files: Vec<String> =
+            failed.into_iter().map(|file| file.remote_path).collect();
+        retry_files.extend(lock(&queue).drain(..).flatten());
+        if retry_files.is_empty() {
+            break;
+        }
+        tracing::info!(
+            "运行结束前用新的连接重试 {} 个失败的文件",
+            retry_files.len()
+        );
+        lock(&total_stats).retried_at_end = retry_files.len();
+        if let Some(display) = &options.progress_display {
+            let retry_bytes = retry_files
+                .iter()
+                .filter_map(|file| listed_sizes.get(file))
+                .sum();
+            display.run_started(retry_files.len(), retry_bytes);
+        }
+        sessions = Arc::new(SessionPool::new(
+            options.transport,
+            host,
+            username,
+            credentials,
+            options.channels_per_session,
+        ));
+        num_threads = options.num_threads.min(retry_files.len());
+        retried.extend(retry_files.iter().cloned());
+        lock(&queue).extend(group_work_units(retry_files, ParallelStrategy::File));
+    }
+    if let Some(display) = &options.progress_display {
+        display.run_finished();
+    }
+
+    let mut final_stats = Arc::try_unwrap(total_stats)
+        .unwrap()
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner);
+    final_stats.elapsed_time = start_time.elapsed();
+    final_stats.peak_buffer_bytes = options.buffer_budget.peak();
+    final_stats.panics.extend(escaped_panics);
+
+    // 到达运行时长上限，或所有线程都无法建立连接或已退出时，队列中剩余的文件计为失败
+    let unprocessed_files: Vec<String> = queue
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .drain(..)
+        .flatten()
+        .collect();
+    let unprocessed = unprocessed_files.len();
+    if unprocessed > 0 {
+        let kind = if options.shutdown.requested() {
+            tracing::warn!("{} 个文件因收到中断请求而未下载", unprocessed);
+            FailureKind::Interrupted
+        } else if options.deadline_reached() {
+            tracing::warn!("{} 个文件因到达运行时长上限而未下载", unprocessed);
+            FailureKind::TimeLimit
+        } else {
+            tracing::warn!("{} 个文件因没有可用的下载线程而未能下载", unprocessed);
+            FailureKind::NoConnection
+        };
+        for file in &unprocessed_files {
+            mark_file_pending(&mut final_stats, &file_slots, file);
+            final_stats.record_failure(file, kind, "未开始下载");
+        }
+    }
+    if final_stats.retried_at_end > 0 {
+        let still_failed = final_stats
+            .failed
+            .iter()
+            .filter(|file| retried.contains(&file.remote_path))
+            .count();
+        final_stats.recovered_at_end = final_stats.retried_at_end - still_failed;
+        tracing::info!(
+            "运行结束前重试 {} 个文件，{} 个成功",
+            final_stats.retried_at_end,
+            final_stats.recovered_at_end
+        );
+    }
+
+    commit_complete_slots(
+        &requested,
+        &bands,
+        &options,
+        &local_storage,
+        post_processing.queue(),
+        &mut final_stats,
+    );
+    // 下载耗时不含等待后处理完成的时间，两者的吞吐量分别统计
+    if post_processing.queue().pending() > 0 {
+        tracing::info!(
+            "等待后处理完成，队列中还有 {} 个文件",
+            post_processing.queue().pending()
+        );
+    }
+    final_stats.post_processing = post_processing.finish();
+    check_expected_counts(
+        &download_list,
+        &requested,
+        &options,
+        &local_storage,
+        &mut final_stats,
+    );
+    // 补写本次提交的时次、已存在的文件以及之前写入失败的目的地
+    for file in &replicate_at_end {
+        replicate(file, &options, &local_storage, &manifest, &mut final_stats);
+    }
+    save_manifest(&finish_manifest(&manifest));
+
+    run_span.record("downloaded", final_stats.downloaded_files);
+    run_span.record("failed", final_stats.failed_files);
+    emit_run_finished(&options, &final_stats);
+    final_stats.print_summary();
+    options.retry_budget.print_summary();
+    if options.shutdown.requested() {
+        let interrupted = manifest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .interrupted
+            .len();
+        tracing::info!(
+            "运行已按中断请求停止，{} 个未完成的临时文件已保留，下次运行将从断点继续",
+            interrupted
+        );
+    }
+
+    Ok(final_stats)
+}
+
+/// 下载可见光波段的FLDK文件
+pub fn download_visible_bands_streaming(
+    download_list: Vec<NaiveDateTime>,
+    options: DownloadOptions,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    local_storage: LocalFileStorage,
+) -> Result<DownloadStats, Box<dyn std::error::Error>> {
+    let visible_bands = himawari::VISIBLE_BANDS.map(String::from).to_vec();
+
+    tracing::info!("开始下载可见光波段FLDK文件 (B01-B03)");
+
+    download_fldk_files_streaming(
+        download_list,
+        visible_bands,
+        options,
+        host,
+        username,
+        credentials,
+        local_storage,
+    )
+}
+
+/// 下载所有波段的FLDK文件
+pub fn download_all_bands_streaming(
+    download_list: Vec<NaiveDateTime>,
+    options: DownloadOptions,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    local_storage: LocalFileStorage,
+) -> Result<DownloadStats, Box<dyn std::error::Error>> {
+    tracing::info!("开始下载所有波段FLDK文件");
+
+    download_fldk_files_streaming(
+        download_list,
+        vec![], // 空列表表示下载所有文件
+        options,
+        host,
+        username,
+        credentials,
+        local_storage,
+    )
+}
+
+/// 下载单个波段的FLDK文件
+pub fn download_single_band_streaming(
+    download_list: Vec<NaiveDateTime>,
+    band: &str,
+    options: DownloadOptions,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    local_storage: LocalFileStorage,
+) -> Result<DownloadStats, Box<dyn std::error::Error>> {
+    tracing::info!("开始下载波段 {} 的FLDK文件", band);
+
+    download_fldk_files_streaming(
+        download_list,
+        vec![band.to_string()],
+        options,
+        host,
+        username,
+        credentials,
+        local_storage,
+    )
+}
+
+/// 下载观测时段对应的辅助文件（定标修正表、导航校正数据等），存放在 `auxiliary/<产品名>/` 下
+pub fn download_auxiliary_files(
+    download_list: &[NaiveDateTime],
+    auxiliary: &[ProductType],
+    options: &DownloadOptions,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    local_storage: &LocalFileStorage,
+) -> Result<DownloadStats, Box<dyn std::error::Error>> {
+    let mut stats = DownloadStats::new();
+
+    for product in auxiliary {
+        // 辅助文件通常按天或按小时发布，多个观测时次可能对应同一发布时段
+        let mut periods: Vec<NaiveDateTime> = download_list
+            .iter()
+            .map(|datetime| product.period_start(datetime))
+            .collect();
+        periods.dedup();
+
+        tracing::info!(
+            "开始下载辅助文件 {} ({} 个时段)",
+            product.name,
+            periods.len()
+        );
+
+        let mut storage = local_storage.clone();
+        storage.base_path = local_storage
+            .base_path
+            .join("auxiliary")
+            .join(&product.name);
+
+        let product_stats = download_fldk_files_streaming(
+            periods,
+            vec![],
+            // 辅助文件按配置的目录布局查找，P-Tree 布局下使用其自身的目录模板
+            options.clone().with_product(product.clone()),
+            host,
+            username,
+            credentials,
+            storage,
+        )?;
+        stats.merge(&product_stats);
+        stats.elapsed_time += product_stats.elapsed_time;
+    }
+
+    Ok(stats)
+}
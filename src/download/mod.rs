@@ -0,0 +1,1455 @@
+//! 下载引擎：下载参数、统计信息、并行策略与多线程下载流程
+
+use crate::backend::StorageBackend;
+use crate::checksum::ChecksumAlgorithm;
+use crate::destination::Destination;
+use crate::events::{EventSocket, RunEvent};
+use crate::failure::FailureKind;
+use crate::himawari::SegmentKey;
+#[cfg(feature = "sqlite")]
+use crate::history::DownloadHistory;
+use crate::layout::{PTreeLayout, RemoteLayout};
+use crate::manifest::{SupersededPolicy, SupersededRecord};
+use crate::metrics::LiveMetrics;
+use crate::pipeline::PipelineStage;
+use crate::product::ProductType;
+use crate::report::csv_field;
+use crate::retry_budget::RetryBudget;
+use crate::shutdown::Shutdown;
+use crate::storage::LocalFileStorage;
+use crate::transport::Transport;
+use crate::units::{format_bytes, format_duration, format_rate};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "cli")]
+mod bars;
+mod buffer;
+mod engine;
+mod postprocess;
+mod progress;
+mod shares;
+mod throttle;
+#[cfg(feature = "cli")]
+pub use bars::*;
+pub use buffer::*;
+pub use engine::*;
+pub use postprocess::*;
+pub use progress::*;
+pub use shares::*;
+pub use throttle::*;
+
+/// 运行结束时仍下载失败的文件列表的文件名，位于下载目录下，可用 `--retry-from-file` 重新下载
+pub const FAILED_LIST_FILE: &str = "failed_files.txt";
+
+/// 读取失败文件列表：每行一个远程路径，空行与 `#` 开头的行忽略
+pub fn read_failed_list(path: &Path) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// 下载统计信息
+#[derive(Debug, Clone)]
+pub struct DownloadStats {
+    pub total_files: usize,
+    pub downloaded_files: usize,
+    pub failed_files: usize,
+    pub skipped_files: usize,
+    /// 发现阶段即确认本地已存在的文件
+    pub existing_files: usize,
+    pub total_bytes: u64,
+    pub elapsed_time: Duration,
+    /// 按原因分类的失败文件数
+    pub failures: BTreeMap<FailureKind, usize>,
+    /// 失败的文件，按记录顺序排列
+    pub failed: Vec<FailedFile>,
+    /// 启用 [`DownloadOptions::with_file_results`] 时记录的下载完成与跳过的文件，按完成顺序排列
+    pub downloaded: Vec<DownloadedFile>,
+    /// 启用 [`DownloadOptions::with_file_results`] 时记录的发现阶段即确认本地已存在的文件
+    pub existing: Vec<String>,
+    /// 运行结束前用新连接重试的文件数，与其中重试成功的文件数
+    pub retried_at_end: usize,
+    pub recovered_at_end: usize,
+    /// 工作线程 panic 的信息
+    pub panics: Vec<String>,
+    /// 启用自适应读缓冲区时，各缓冲区大小（字节）下完成的文件数
+    pub buffer_sizes: BTreeMap<usize, usize>,
+    /// 同时分配的读缓冲区总字节数的峰值
+    pub peak_buffer_bytes: usize,
+    /// 下载完成时最终路径上已有文件的次数，按处理结果分类
+    pub conflicts: BTreeMap<FinalizeOutcome, usize>,
+    /// 整时次提交模式下移入归档的时次数
+    pub committed_slots: usize,
+    /// 整时次提交模式下因不完整而保留在暂存目录的时次数
+    pub staged_slots: usize,
+    /// 各额外存储目的地的写入结果，写入失败不计入下载失败
+    pub destinations: BTreeMap<String, DestinationStats>,
+    /// 后处理流水线的执行结果，处理失败不计入下载失败
+    pub post_processing: PostProcessingStats,
+    /// 运行结束时文件数少于 `expected_files_per_slot` 的时次
+    pub short_slots: Vec<ShortSlot>,
+    /// 本次运行中文件全部下载完成的时次与观测区域，及其相对观测时间的延迟
+    pub slot_latencies: Vec<SlotLatency>,
+    /// 按连接标识（例如 `w3.g1`）统计的下载量
+    pub connections: BTreeMap<String, ConnectionStats>,
+    /// 按观测日期（UTC）汇总的文件统计
+    pub days: BTreeMap<NaiveDate, DayStats>,
+    /// 有文件未能下载或目录尚未发布的时次，下次运行需要从最早的一个继续
+    pub pending_slots: BTreeSet<NaiveDateTime>,
+    /// 发现阶段的结果：列出的目录、读取失败的目录及原因、没有匹配文件的时次
+    pub discovery: DiscoveryReport,
+}
+
+/// 发现阶段的结果
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryReport {
+    /// 成功列出的目录数
+    pub listed_directories: usize,
+    /// 列出的属于本次请求的文件数（含本地已存在的）
+    pub files_found: usize,
+    /// 读取失败的目录
+    pub failed_directories: Vec<DirectoryFailure>,
+    /// 服务器上不存在且已过发布等待期的目录及其时次
+    pub missing_directories: Vec<(String, NaiveDateTime)>,
+    /// 等待期结束时仍未发布的时次
+    pub unpublished_slots: Vec<NaiveDateTime>,
+    /// 目录已列出但没有匹配文件的时次
+    pub empty_slots: Vec<NaiveDateTime>,
+    /// 匹配但尚未上传完成、留待下次运行的文件
+    pub not_ready_files: Vec<String>,
+}
+
+/// 读取失败的远程目录
+#[derive(Debug, Clone)]
+pub struct DirectoryFailure {
+    pub directory: String,
+    pub slot: NaiveDateTime,
+    pub kind: FailureKind,
+    pub error: String,
+}
+
+impl DiscoveryReport {
+    /// 是否有目录读取失败、缺失、未发布、时次没有匹配文件或文件尚未上传完成
+    pub fn has_problems(&self) -> bool {
+        !self.failed_directories.is_empty()
+            || !self.missing_directories.is_empty()
+            || !self.unpublished_slots.is_empty()
+            || !self.empty_slots.is_empty()
+            || !self.not_ready_files.is_empty()
+    }
+
+    pub fn merge(&mut self, other: &DiscoveryReport) {
+        self.listed_directories += other.listed_directories;
+        self.files_found += other.files_found;
+        self.failed_directories
+            .extend(other.failed_directories.iter().cloned());
+        self.missing_directories
+            .extend(other.missing_directories.iter().cloned());
+        self.unpublished_slots
+            .extend(other.unpublished_slots.iter().copied());
+        self.empty_slots.extend(other.empty_slots.iter().copied());
+        self.not_ready_files
+            .extend(other.not_ready_files.iter().cloned());
+    }
+
+    /// 输出发现阶段的问题，没有问题时不输出
+    pub fn print_problems(&self) {
+        if !self.has_problems() {
+            return;
+        }
+        tracing::info!(
+            "发现阶段: 列出 {} 个目录，找到 {} 个文件",
+            self.listed_directories,
+            self.files_found
+        );
+        if !self.failed_directories.is_empty() {
+            tracing::info!("读取失败的目录 {} 个:", self.failed_directories.len());
+            for failure in &self.failed_directories {
+                tracing::info!(
+                    "  {} ({}): {} - {}",
+                    failure.directory,
+                    failure.slot.format("%Y-%m-%d %H:%M"),
+                    failure.kind,
+                    failure.error
+                );
+            }
+        }
+        if !self.missing_directories.is_empty() {
+            tracing::info!(
+                "服务器上不存在的目录 {} 个:",
+                self.missing_directories.len()
+            );
+            for (directory, slot) in &self.missing_directories {
+                tracing::info!("  {} ({})", directory, slot.format("%Y-%m-%d %H:%M"));
+            }
+        }
+        for (label, slots) in [
+            ("仍未发布的时次", &self.unpublished_slots),
+            ("没有匹配文件的时次", &self.empty_slots),
+        ] {
+            if !slots.is_empty() {
+                let formatted: Vec<String> = slots
+                    .iter()
+                    .map(|slot| slot.format("%Y-%m-%d %H:%M").to_string())
+                    .collect();
+                tracing::info!("{} {} 个: {}", label, slots.len(), formatted.join(", "));
+            }
+        }
+        if !self.not_ready_files.is_empty() {
+            tracing::info!("尚未上传完成的文件 {} 个:", self.not_ready_files.len());
+            for file in &self.not_ready_files {
+                tracing::info!("  {}", file);
+            }
+        }
+    }
+}
+
+/// 延迟告警只考虑观测后这段时间内完成的时次，更早的时次视为回补
+pub const REALTIME_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 一个时次与观测区域的文件全部下载完成的时间，及其相对观测时间（时间线开始时间）的延迟
+#[derive(Debug, Clone)]
+pub struct SlotLatency {
+    pub datetime: NaiveDateTime,
+    pub area: String,
+    pub completed_at: DateTime<Utc>,
+    pub latency: Duration,
+}
+
+/// 下载失败的文件
+#[derive(Debug, Clone)]
+pub struct FailedFile {
+    pub remote_path: String,
+    pub kind: FailureKind,
+    pub error: String,
+}
+
+impl FailedFile {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "remote_path": self.remote_path,
+            "kind": self.kind.name(),
+            "error": self.error,
+        })
+    }
+}
+
+/// 下载完成或因本地已有完整文件而跳过的文件
+#[derive(Debug, Clone)]
+pub struct DownloadedFile {
+    pub remote_path: String,
+    /// 本次传输的字节数，跳过的文件为 0
+    pub bytes: u64,
+    pub outcome: FinalizeOutcome,
+    pub sha256: Option<String>,
+}
+
+impl DownloadedFile {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "remote_path": self.remote_path,
+            "bytes": self.bytes,
+            "outcome": self.outcome.name(),
+            "sha256": self.sha256,
+        })
+    }
+}
+
+/// 逐文件结果 CSV 的表头
+pub const FILE_RESULTS_CSV_HEADER: &str = "remote_path,status,bytes,outcome,sha256,kind,error";
+
+/// 单个观测日期的文件统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DayStats {
+    /// 该日期请求的文件数
+    pub total_files: usize,
+    pub existing_files: usize,
+    pub downloaded_files: usize,
+    pub skipped_files: usize,
+    pub failed_files: usize,
+    pub bytes: u64,
+}
+
+impl DayStats {
+    /// 本地已有文件占请求文件的百分比
+    pub fn completeness(&self) -> f64 {
+        if self.total_files == 0 {
+            return 100.0;
+        }
+        let present = self.existing_files + self.downloaded_files + self.skipped_files;
+        present as f64 / self.total_files as f64 * 100.0
+    }
+}
+
+/// 单个连接的下载统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// 文件数少于预期的时次
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortSlot {
+    pub datetime: NaiveDateTime,
+    pub area: String,
+    /// 本地已有的文件数
+    pub found: usize,
+    pub expected: usize,
+}
+
+/// 单个存储目的地的写入统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DestinationStats {
+    pub written: usize,
+    pub failed: usize,
+}
+
+/// 后处理流水线的执行统计
+#[derive(Debug, Clone, Default)]
+pub struct PostProcessingStats {
+    /// 全部步骤执行成功的文件数
+    pub processed: usize,
+    /// 处理失败的文件，原始数据仍保留在归档中
+    pub failed: Vec<PostProcessFailure>,
+    /// 后处理线程数
+    pub threads: usize,
+    /// 队列中同时等待处理的文件数的峰值
+    pub peak_queue: usize,
+    /// 处理的文件的总大小
+    pub bytes: u64,
+    /// 各线程处理文件的耗时之和
+    pub busy_time: Duration,
+    /// 文件在队列中等待的时间之和
+    pub wait_time: Duration,
+    /// 从第一个文件入队到最后一个文件处理完的时长
+    pub elapsed: Duration,
+}
+
+impl PostProcessingStats {
+    /// 已处理（含失败）的文件数
+    pub fn files(&self) -> usize {
+        self.processed + self.failed.len()
+    }
+
+    pub fn merge(&mut self, other: &PostProcessingStats) {
+        self.processed += other.processed;
+        self.failed.extend(other.failed.iter().cloned());
+        self.threads = self.threads.max(other.threads);
+        self.peak_queue = self.peak_queue.max(other.peak_queue);
+        self.bytes += other.bytes;
+        self.busy_time += other.busy_time;
+        self.wait_time += other.wait_time;
+        self.elapsed += other.elapsed;
+    }
+}
+
+/// 处理失败的文件
+#[derive(Debug, Clone)]
+pub struct PostProcessFailure {
+    pub remote_path: String,
+    /// 失败的步骤名称
+    pub stage: String,
+    pub error: String,
+}
+
+impl Default for DownloadStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DownloadStats {
+    pub fn new() -> Self {
+        Self {
+            total_files: 0,
+            downloaded_files: 0,
+            failed_files: 0,
+            skipped_files: 0,
+            existing_files: 0,
+            total_bytes: 0,
+            elapsed_time: Duration::from_secs(0),
+            failures: BTreeMap::new(),
+            failed: Vec::new(),
+            downloaded: Vec::new(),
+            existing: Vec::new(),
+            retried_at_end: 0,
+            recovered_at_end: 0,
+            panics: Vec::new(),
+            buffer_sizes: BTreeMap::new(),
+            peak_buffer_bytes: 0,
+            conflicts: BTreeMap::new(),
+            committed_slots: 0,
+            staged_slots: 0,
+            destinations: BTreeMap::new(),
+            post_processing: PostProcessingStats::default(),
+            short_slots: Vec::new(),
+            slot_latencies: Vec::new(),
+            connections: BTreeMap::new(),
+            days: BTreeMap::new(),
+            pending_slots: BTreeSet::new(),
+            discovery: DiscoveryReport::default(),
+        }
+    }
+
+    /// 累加另一份统计的文件数与字节数
+    pub fn merge(&mut self, other: &DownloadStats) {
+        self.total_files += other.total_files;
+        self.downloaded_files += other.downloaded_files;
+        self.skipped_files += other.skipped_files;
+        self.existing_files += other.existing_files;
+        self.failed_files += other.failed_files;
+        self.committed_slots += other.committed_slots;
+        self.staged_slots += other.staged_slots;
+        self.total_bytes += other.total_bytes;
+        for (kind, count) in &other.failures {
+            *self.failures.entry(*kind).or_default() += count;
+        }
+        self.failed.extend(other.failed.iter().cloned());
+        self.downloaded.extend(other.downloaded.iter().cloned());
+        self.existing.extend(other.existing.iter().cloned());
+        self.retried_at_end += other.retried_at_end;
+        self.recovered_at_end += other.recovered_at_end;
+        self.panics.extend(other.panics.iter().cloned());
+        for (size, count) in &other.buffer_sizes {
+            *self.buffer_sizes.entry(*size).or_default() += count;
+        }
+        self.peak_buffer_bytes = self.peak_buffer_bytes.max(other.peak_buffer_bytes);
+        for (outcome, count) in &other.conflicts {
+            *self.conflicts.entry(*outcome).or_default() += count;
+        }
+        self.short_slots.extend(other.short_slots.iter().cloned());
+        self.slot_latencies
+            .extend(other.slot_latencies.iter().cloned());
+        for (tag, connection) in &other.connections {
+            let total = self.connections.entry(tag.clone()).or_default();
+            total.files += connection.files;
+            total.bytes += connection.bytes;
+        }
+        for (name, destination) in &other.destinations {
+            let total = self.destinations.entry(name.clone()).or_default();
+            total.written += destination.written;
+            total.failed += destination.failed;
+        }
+        self.post_processing.merge(&other.post_processing);
+        self.pending_slots
+            .extend(other.pending_slots.iter().copied());
+        self.discovery.merge(&other.discovery);
+        for (day, day_stats) in &other.days {
+            let total = self.days.entry(*day).or_default();
+            total.total_files += day_stats.total_files;
+            total.existing_files += day_stats.existing_files;
+            total.downloaded_files += day_stats.downloaded_files;
+            total.skipped_files += day_stats.skipped_files;
+            total.failed_files += day_stats.failed_files;
+            total.bytes += day_stats.bytes;
+        }
+    }
+
+    /// 记录一个失败文件及其原因
+    pub fn record_failure(&mut self, remote_path: &str, kind: FailureKind, error: &str) {
+        self.failed_files += 1;
+        *self.failures.entry(kind).or_default() += 1;
+        self.failed.push(FailedFile {
+            remote_path: remote_path.to_string(),
+            kind,
+            error: error.to_string(),
+        });
+    }
+
+    pub fn print_summary(&self) {
+        tracing::info!("=== 下载统计摘要 ===");
+        tracing::info!("总文件数: {}", self.total_files);
+        tracing::info!("成功下载: {}", self.downloaded_files);
+        tracing::info!("已存在文件: {}", self.existing_files);
+        tracing::info!("跳过文件: {}", self.skipped_files);
+        tracing::info!("失败文件: {}", self.failed_files);
+        tracing::info!("总下载量: {}", format_bytes(self.total_bytes));
+        tracing::info!("耗时: {}", format_duration(self.elapsed_time));
+        if self.elapsed_time.as_secs() > 0 {
+            tracing::info!(
+                "平均速度: {}",
+                format_rate(self.total_bytes, self.elapsed_time)
+            );
+        }
+        if !self.failures.is_empty() {
+            let mut failures: Vec<_> = self.failures.iter().collect();
+            failures.sort_by(|a, b| b.1.cmp(a.1));
+            let grouped: Vec<String> = failures
+                .iter()
+                .map(|(kind, count)| format!("{} × {}", count, kind))
+                .collect();
+            tracing::info!("失败原因: {}", grouped.join(", "));
+        }
+        if let Some(latest) = self.latest_slot_latency() {
+            tracing::info!(
+                "时次延迟: {} 个时次下载完成，最新时次 {} {} 延迟 {}，最大延迟 {}",
+                self.slot_latencies.len(),
+                latest.datetime.format("%Y-%m-%d %H:%M"),
+                latest.area,
+                format_duration(latest.latency),
+                format_duration(self.max_slot_latency().unwrap_or_default())
+            );
+        }
+        if self.retried_at_end > 0 {
+            tracing::info!(
+                "运行结束前重试: {} 个文件，{} 个成功",
+                self.retried_at_end,
+                self.recovered_at_end
+            );
+        }
+        if self.committed_slots + self.staged_slots > 0 {
+            tracing::info!(
+                "整时次提交: 移入归档 {} 个时次，保留在暂存目录 {} 个时次",
+                self.committed_slots,
+                self.staged_slots
+            );
+        }
+        for (tag, connection) in &self.connections {
+            tracing::info!(
+                "连接 {}: {} 个文件, {}",
+                tag,
+                connection.files,
+                format_bytes(connection.bytes)
+            );
+        }
+        if !self.conflicts.is_empty() {
+            let grouped: Vec<String> = self
+                .conflicts
+                .iter()
+                .map(|(outcome, count)| format!("{} × {}", count, outcome.description()))
+                .collect();
+            tracing::info!("下载期间目标文件已被写入: {}", grouped.join(", "));
+        }
+        self.discovery.print_problems();
+        if !self.short_slots.is_empty() {
+            tracing::info!("文件数不足的时次 {} 个:", self.short_slots.len());
+            for slot in &self.short_slots {
+                tracing::info!(
+                    "  {} {}: {}/{}",
+                    slot.datetime.format("%Y-%m-%d %H:%M"),
+                    slot.area,
+                    slot.found,
+                    slot.expected
+                );
+            }
+        }
+        for (name, destination) in &self.destinations {
+            tracing::info!(
+                "存储目的地 {}: 写入 {}，失败 {}",
+                name,
+                destination.written,
+                destination.failed
+            );
+        }
+        let post = &self.post_processing;
+        if post.files() > 0 {
+            tracing::info!(
+                "后处理: {} 个线程，完成 {} 个文件，失败 {} 个文件，处理量 {}",
+                post.threads,
+                post.processed,
+                post.failed.len(),
+                format_bytes(post.bytes)
+            );
+            let secs = post.elapsed.as_secs_f64();
+            tracing::info!(
+                "后处理耗时: {}，平均 {:.2} 个文件/秒 ({})，队列峰值 {} 个文件，平均等待 {}",
+                format_duration(post.elapsed),
+                if secs > 0.0 {
+                    post.files() as f64 / secs
+                } else {
+                    0.0
+                },
+                format_rate(post.bytes, post.elapsed),
+                post.peak_queue,
+                format_duration(post.wait_time / post.files() as u32)
+            );
+            for failure in &post.failed {
+                tracing::info!(
+                    "  {} ({}): {}",
+                    failure.remote_path,
+                    failure.stage,
+                    failure.error
+                );
+            }
+        }
+        if !self.buffer_sizes.is_empty() {
+            let sizes: Vec<String> = self
+                .buffer_sizes
+                .iter()
+                .map(|(size, count)| format!("{} × {}", format_bytes(*size as u64), count))
+                .collect();
+            tracing::info!("读缓冲区大小: {}", sizes.join(", "));
+        }
+        if self.peak_buffer_bytes > 0 {
+            tracing::info!(
+                "读缓冲区峰值: {}",
+                format_bytes(self.peak_buffer_bytes as u64)
+            );
+        }
+        if !self.panics.is_empty() {
+            tracing::info!("工作线程 panic {} 次:", self.panics.len());
+            for message in &self.panics {
+                tracing::info!("  {}", message);
+            }
+        }
+        // 跨越多天的回补在逐文件输出之外再给出按日汇总
+        if self.days.len() > 1 {
+            tracing::info!("=== 每日汇总 ===");
+            for (day, day_stats) in &self.days {
+                tracing::info!(
+                    "{}  完整度 {:>5.1}% ({}/{})  下载 {} 个, {}  失败 {} 个",
+                    day,
+                    day_stats.completeness(),
+                    day_stats.existing_files + day_stats.downloaded_files + day_stats.skipped_files,
+                    day_stats.total_files,
+                    day_stats.downloaded_files,
+                    format_bytes(day_stats.bytes),
+                    day_stats.failed_files
+                );
+            }
+        }
+    }
+
+    /// 观测时间最新的已完成时次的延迟
+    pub fn latest_slot_latency(&self) -> Option<&SlotLatency> {
+        self.slot_latencies
+            .iter()
+            .max_by_key(|slot| (slot.datetime, slot.completed_at))
+    }
+
+    /// 已完成时次的最大延迟
+    pub fn max_slot_latency(&self) -> Option<Duration> {
+        self.slot_latencies.iter().map(|slot| slot.latency).max()
+    }
+
+    /// 延迟超过 `threshold` 的实时时次；延迟超过 [`REALTIME_WINDOW`] 的时次视为回补的历史数据，不计入
+    pub fn late_slots(&self, threshold: Duration) -> Vec<&SlotLatency> {
+        self.slot_latencies
+            .iter()
+            .filter(|slot| slot.latency > threshold && slot.latency <= REALTIME_WINDOW)
+            .collect()
+    }
+
+    /// 将失败文件的远程路径写入 `path`，每行一个；没有失败的文件时删除 `path`，
+    /// 使该文件始终对应最近一次运行
+    pub fn write_failed_list(&self, path: &Path) -> io::Result<()> {
+        if self.failed.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut lines = String::new();
+        for file in &self.failed {
+            lines.push_str(&file.remote_path);
+            lines.push('\n');
+        }
+        fs::write(path, lines)
+    }
+
+    /// 将每日汇总写入 CSV 文件
+    pub fn write_daily_summary(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut csv = String::from(
+            "date,total_files,existing_files,downloaded_files,skipped_files,failed_files,bytes,completeness_percent\n",
+        );
+        for (day, day_stats) in &self.days {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{:.1}\n",
+                day,
+                day_stats.total_files,
+                day_stats.existing_files,
+                day_stats.downloaded_files,
+                day_stats.skipped_files,
+                day_stats.failed_files,
+                day_stats.bytes,
+                day_stats.completeness()
+            ));
+        }
+        fs::write(path, csv)
+    }
+
+    /// 统计信息与逐文件结果，用于运行报告与 `report_file`
+    pub fn to_json(&self) -> Value {
+        let failures: serde_json::Map<String, Value> = self
+            .failures
+            .iter()
+            .map(|(kind, count)| (kind.name().to_string(), json!(count)))
+            .collect();
+        let pending_slots: Vec<String> =
+            self.pending_slots.iter().map(ToString::to_string).collect();
+        let short_slots: Vec<Value> = self
+            .short_slots
+            .iter()
+            .map(|slot| {
+                json!({
+                    "slot": slot.datetime.to_string(),
+                    "area": slot.area,
+                    "found": slot.found,
+                    "expected": slot.expected,
+                })
+            })
+            .collect();
+        let post_processing_failed: Vec<Value> = self
+            .post_processing
+            .failed
+            .iter()
+            .map(|failure| {
+                json!({
+                    "remote_path": failure.remote_path,
+                    "stage": failure.stage,
+                    "error": failure.error,
+                })
+            })
+            .collect();
+        json!({
+            "total_files": self.total_files,
+            "existing_files": self.existing_files,
+            "downloaded_files": self.downloaded_files,
+            "skipped_files": self.skipped_files,
+            "failed_files": self.failed_files,
+            "retried_at_end": self.retried_at_end,
+            "recovered_at_end": self.recovered_at_end,
+            "downloaded_bytes": self.total_bytes,
+            "elapsed_secs": self.elapsed_time.as_secs_f64(),
+            "failures": failures,
+            "pending_slots": pending_slots,
+            "short_slots": short_slots,
+            "slot_latency": {
+                "completed_slots": self.slot_latencies.len(),
+                "latest_slot": self.latest_slot_latency().map(|slot| slot.datetime.to_string()),
+                "latest_secs": self.latest_slot_latency().map(|slot| slot.latency.as_secs_f64()),
+                "max_secs": self.max_slot_latency().map(|latency| latency.as_secs_f64()),
+            },
+            "failed": self.failed.iter().map(FailedFile::to_json).collect::<Vec<_>>(),
+            "downloaded": self.downloaded.iter().map(DownloadedFile::to_json).collect::<Vec<_>>(),
+            "existing": self.existing,
+            "post_processing": {
+                "threads": self.post_processing.threads,
+                "processed": self.post_processing.processed,
+                "bytes": self.post_processing.bytes,
+                "elapsed_secs": self.post_processing.elapsed.as_secs_f64(),
+                "busy_secs": self.post_processing.busy_time.as_secs_f64(),
+                "queue_wait_secs": self.post_processing.wait_time.as_secs_f64(),
+                "peak_queue": self.post_processing.peak_queue,
+                "failed": post_processing_failed,
+            },
+        })
+    }
+
+    /// 逐文件结果的 CSV 行（不含表头），列见 [`FILE_RESULTS_CSV_HEADER`]：依次为本地已存在、
+    /// 下载完成与跳过、失败的文件
+    pub fn file_result_rows(&self) -> Vec<String> {
+        let existing = self
+            .existing
+            .iter()
+            .map(|remote_path| format!("{},existing,,,,,", csv_field(remote_path)));
+        let downloaded = self.downloaded.iter().map(|file| {
+            format!(
+                "{},{},{},{},{},,",
+                csv_field(&file.remote_path),
+                if file.bytes > 0 {
+                    "downloaded"
+                } else {
+                    "skipped"
+                },
+                file.bytes,
+                file.outcome.name(),
+                file.sha256.as_deref().unwrap_or_default()
+            )
+        });
+        let failed = self.failed.iter().map(|file| {
+            format!(
+                "{},failed,,,,{},{}",
+                csv_field(&file.remote_path),
+                file.kind.name(),
+                csv_field(&file.error)
+            )
+        });
+        existing.chain(downloaded).chain(failed).collect()
+    }
+
+    /// 逐文件结果的 CSV，含表头
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{}\n", FILE_RESULTS_CSV_HEADER);
+        for row in self.file_result_rows() {
+            csv.push_str(&row);
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// 并行策略：工作线程之间按什么粒度分配任务
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParallelStrategy {
+    /// 按单个文件分配，适合回补历史数据
+    #[default]
+    File,
+    /// 每个时次由一个线程完整下载，适合实时业务尽快获得完整时次
+    Slot,
+    /// 每个波段由一个线程下载
+    Band,
+}
+
+/// 服务器在同一目录中重复列出同一份数据（例如带与不带版本后缀的两个文件名）时保留哪一个
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// 修改时间最新的，相同时取较大的
+    #[default]
+    Newest,
+    /// 文件最大的
+    Largest,
+    /// 目录列表中最先出现的
+    First,
+    /// 文件名最短的，通常是不带版本后缀的原始文件名
+    ShortestName,
+}
+
+/// 下载完成时最终路径上已经存在文件（例如另一个进程在下载期间写入了同一路径）时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 用新下载的文件替换已有文件
+    #[default]
+    Overwrite,
+    /// 保留已有文件，丢弃新下载的文件
+    KeepExisting,
+    /// 已有文件保持原路径不动，新文件另存为 `<文件名>.conflict-<时间>`
+    KeepBoth,
+    /// 保留已有文件，本文件记为失败
+    Error,
+}
+
+/// 下载完成后文件如何放到最终路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinalizeOutcome {
+    /// 下载前检查时本地已有完整文件，未下载
+    AlreadyStored,
+    /// 最终路径上没有文件，正常放入
+    Created,
+    /// 替换了下载期间出现的文件
+    Overwritten,
+    /// 下载期间出现的文件被保留，新下载的文件被丢弃
+    KeptExisting,
+    /// 下载期间出现的文件被保留，新下载的文件另存到旁边
+    KeptBoth,
+}
+
+impl FinalizeOutcome {
+    /// 是否在最终路径上遇到了已有文件
+    pub fn is_conflict(&self) -> bool {
+        matches!(
+            self,
+            Self::Overwritten | Self::KeptExisting | Self::KeptBoth
+        )
+    }
+
+    /// 报告与导出中使用的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AlreadyStored => "already_stored",
+            Self::Created => "created",
+            Self::Overwritten => "overwritten",
+            Self::KeptExisting => "kept_existing",
+            Self::KeptBoth => "kept_both",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::AlreadyStored => "本地已存在",
+            Self::Created => "已保存",
+            Self::Overwritten => "覆盖已有文件",
+            Self::KeptExisting => "保留已有文件",
+            Self::KeptBoth => "两者均保留",
+        }
+    }
+}
+
+/// 单个文件的下载结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    /// 本次传输的字节数，未下载时为 0
+    pub bytes: u64,
+    pub outcome: FinalizeOutcome,
+    /// 下载到本地的文件的 SHA-256，传输时随写入计算，记入下载清单；未下载、保留了已有文件或写入存储后端时为 `None`
+    pub sha256: Option<String>,
+}
+
+/// 默认读缓冲区大小
+pub const DEFAULT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// 重新列出尚未发布的近期目录的间隔
+pub const UNPUBLISHED_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 下载参数
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub num_threads: usize,
+    pub buffer_size: usize,
+    /// 自适应读缓冲区的上限，为空时始终使用 `buffer_size`
+    pub max_buffer_size: Option<usize>,
+    /// 所有连接的读缓冲区合计的内存预算
+    pub buffer_budget: Arc<BufferBudget>,
+    /// 所有工作线程共享的带宽限制，为空时不限速
+    pub bandwidth_limit: Option<Arc<RateLimiter>>,
+    /// 每个工作线程的带宽上限（字节/秒），为空时不限速
+    pub thread_bandwidth_limit: Option<u64>,
+    pub max_retries: usize,
+    /// 波段优先级，同一时次内靠前的波段先下载
+    pub band_priority: Vec<String>,
+    /// 观测区域，例如 FLDK、R301、R302
+    pub areas: Vec<String>,
+    /// HSD 文件的分辨率标记，例如 R05、R10、R20；为空时不按分辨率筛选
+    pub resolutions: Vec<String>,
+    /// 数据产品类型
+    pub product: ProductType,
+    /// 远程服务器目录布局
+    pub layout: Arc<dyn RemoteLayout>,
+    /// 按主机共享的重试预算
+    pub retry_budget: Arc<RetryBudget>,
+    /// 同一连接上两次文件传输之间的间隔
+    pub inter_file_delay: Duration,
+    /// 工作线程之间的任务分配粒度
+    pub parallel_strategy: ParallelStrategy,
+    /// SSH/SFTP 传输实现
+    pub transport: Transport,
+    /// 每个已认证的 SSH 会话上最多同时打开的 SFTP 通道数，发现阶段与各工作线程共享会话
+    pub channels_per_session: usize,
+    /// 是否在每个下载的文件旁写入 `<文件名>.meta.json` 来源信息
+    pub write_metadata: bool,
+    /// 来源信息文件中校验和的算法
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// 是否在移入归档前校验文件内容，校验失败的文件移入隔离区并重新下载
+    pub validate_downloads: bool,
+    /// 运行截止时间，到达后不再开始新文件的下载，进行中的文件会继续完成
+    pub deadline: Option<Instant>,
+    /// 中断请求，收到后不再开始新文件，再次收到时中止进行中的传输
+    pub shutdown: Arc<Shutdown>,
+    /// 写入 `status.json` 的间隔，为空时不写入
+    pub status_interval: Option<Duration>,
+    /// 传输中超过该时长没有收到数据时中止本次传输，为空时一直等待
+    pub stall_timeout: Option<Duration>,
+    /// 单个文件一次传输的最长时间，为空时不限制
+    pub max_file_duration: Option<Duration>,
+    /// 远程文件被更新时的处理方式
+    pub superseded_policy: SupersededPolicy,
+    /// 同一份数据被重复列出时保留哪一个
+    pub duplicate_policy: DuplicatePolicy,
+    /// 下载完成时最终路径上已有文件的处理方式
+    pub conflict_policy: ConflictPolicy,
+    /// 存储后端，为空时文件保存在本地下载目录
+    pub backend: Option<Arc<dyn StorageBackend>>,
+    /// 文件进入下载目录后额外写入的存储目的地
+    pub destinations: Vec<Arc<dyn Destination>>,
+    /// 文件进入下载目录后依次执行的处理步骤
+    pub pipeline: Vec<Arc<dyn PipelineStage>>,
+    /// 执行处理步骤的线程数，与下载线程分开
+    pub pipeline_threads: usize,
+    /// 每个时次、每个观测区域预期的文件数，运行结束时少于该数的时次会被标出
+    pub expected_files_per_slot: Option<usize>,
+    /// 单个文件下载进度的报告方式
+    pub progress: ProgressReporting,
+    /// 进度显示，例如终端进度条；设置后不再输出逐文件的进度日志
+    pub progress_display: Option<Arc<dyn ProgressDisplay>>,
+    /// 推送运行事件的 Unix 域套接字，为空时不推送
+    pub events: Option<Arc<EventSocket>>,
+    /// 由运行事件更新的累计指标，为空时不统计
+    pub live_metrics: Option<Arc<LiveMetrics>>,
+    /// 时次距今不超过该时长时，目录不存在视为尚未发布并定期重试；为零时不等待
+    pub unpublished_wait: Duration,
+    /// 是否在发现阶段等待尚未发布的目录；为 false 时直接记为未发布，留待下次运行
+    pub poll_unpublished: bool,
+    /// 下载历史，为空时不记录
+    #[cfg(feature = "sqlite")]
+    pub history: Option<Arc<DownloadHistory>>,
+    /// 只列出需要下载的文件：发现阶段不改动被远程新版本取代的本地文件，也不等待尚未发布的目录
+    pub dry_run: bool,
+    /// 重试用尽仍失败的文件是否在运行结束前用新建立的连接再下载一遍，重试仍失败才计为失败
+    pub final_retry: bool,
+    /// 是否逐个记录本地已存在、下载完成与跳过的文件，供导出逐文件结果；失败的文件总是记录
+    pub file_results: bool,
+    /// 只下载这些远程路径的文件，为空时下载发现的全部文件
+    pub only_files: Option<Arc<HashSet<String>>>,
+    /// 只下载这些分段的 HSD 文件，为空时不按分段筛选
+    pub only_segments: Option<Arc<HashSet<SegmentKey>>>,
+}
+
+impl DownloadOptions {
+    pub fn new(num_threads: usize) -> Self {
+        Self {
+            num_threads,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            max_buffer_size: None,
+            buffer_budget: Arc::new(BufferBudget::default()),
+            bandwidth_limit: None,
+            thread_bandwidth_limit: None,
+            max_retries: 3,
+            band_priority: Vec::new(),
+            areas: vec!["FLDK".to_string()],
+            resolutions: Vec::new(),
+            product: ProductType::hsd(),
+            layout: Arc::new(PTreeLayout),
+            retry_budget: Arc::new(RetryBudget::default()),
+            inter_file_delay: Duration::ZERO,
+            parallel_strategy: ParallelStrategy::default(),
+            transport: Transport::default(),
+            channels_per_session: 1,
+            write_metadata: false,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            validate_downloads: false,
+            deadline: None,
+            shutdown: Arc::new(Shutdown::new()),
+            status_interval: None,
+            stall_timeout: None,
+            max_file_duration: None,
+            superseded_policy: SupersededPolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            conflict_policy: ConflictPolicy::default(),
+            backend: None,
+            destinations: Vec::new(),
+            pipeline: Vec::new(),
+            pipeline_threads: 1,
+            expected_files_per_slot: None,
+            progress: ProgressReporting::default(),
+            progress_display: None,
+            events: None,
+            live_metrics: None,
+            unpublished_wait: Duration::from_secs(60 * 60),
+            poll_unpublished: true,
+            #[cfg(feature = "sqlite")]
+            history: None,
+            dry_run: false,
+            final_retry: true,
+            file_results: false,
+            only_files: None,
+            only_segments: None,
+        }
+    }
+
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// 启用自适应读缓冲区：从 `buffer_size` 开始，按连接表现在下限与 `max_buffer_size` 之间调整
+    pub fn with_max_buffer_size(mut self, max_buffer_size: usize) -> Self {
+        self.max_buffer_size = Some(max_buffer_size);
+        self
+    }
+
+    pub fn with_buffer_budget(mut self, buffer_budget: Arc<BufferBudget>) -> Self {
+        self.buffer_budget = buffer_budget;
+        self
+    }
+
+    /// 限制所有工作线程合计的下载速度，`limit` 可与其他下载共享
+    pub fn with_bandwidth_limit(mut self, limit: Arc<RateLimiter>) -> Self {
+        self.bandwidth_limit = Some(limit);
+        self
+    }
+
+    /// 限制每个工作线程的下载速度（字节/秒）
+    pub fn with_thread_bandwidth_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.thread_bandwidth_limit = Some(bytes_per_sec);
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_band_priority(mut self, band_priority: Vec<String>) -> Self {
+        self.band_priority = band_priority;
+        self
+    }
+
+    pub fn with_areas(mut self, areas: Vec<String>) -> Self {
+        self.areas = areas;
+        self
+    }
+
+    pub fn with_resolutions(mut self, resolutions: Vec<String>) -> Self {
+        self.resolutions = resolutions;
+        self
+    }
+
+    pub fn with_product(mut self, product: ProductType) -> Self {
+        self.product = product;
+        self
+    }
+
+    pub fn with_layout(mut self, layout: Arc<dyn RemoteLayout>) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_retry_budget(mut self, retry_budget: Arc<RetryBudget>) -> Self {
+        self.retry_budget = retry_budget;
+        self
+    }
+
+    pub fn with_inter_file_delay(mut self, inter_file_delay: Duration) -> Self {
+        self.inter_file_delay = inter_file_delay;
+        self
+    }
+
+    pub fn with_parallel_strategy(mut self, parallel_strategy: ParallelStrategy) -> Self {
+        self.parallel_strategy = parallel_strategy;
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn with_channels_per_session(mut self, channels_per_session: usize) -> Self {
+        self.channels_per_session = channels_per_session;
+        self
+    }
+
+    pub fn with_write_metadata(mut self, write_metadata: bool) -> Self {
+        self.write_metadata = write_metadata;
+        self
+    }
+
+    pub fn with_checksum_algorithm(mut self, checksum_algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum_algorithm = checksum_algorithm;
+        self
+    }
+
+    pub fn with_validate_downloads(mut self, validate_downloads: bool) -> Self {
+        self.validate_downloads = validate_downloads;
+        self
+    }
+
+    /// 从现在起计算运行时长上限；共用同一份参数的后续下载（如辅助文件）共享同一截止时间
+    pub fn with_max_run_duration(mut self, max_run_duration: Duration) -> Self {
+        self.deadline = Some(Instant::now() + max_run_duration);
+        self
+    }
+
+    /// 设置中断请求，共用同一份参数的后续下载（如辅助文件）响应同一请求
+    pub fn with_shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn with_status_interval(mut self, status_interval: Duration) -> Self {
+        self.status_interval = Some(status_interval);
+        self
+    }
+
+    /// 设置传输停滞超时：超过该时长没有收到数据时中止传输，按下载失败重试
+    pub fn with_stall_timeout(mut self, stall_timeout: Duration) -> Self {
+        self.stall_timeout = Some(stall_timeout);
+        self
+    }
+
+    /// 设置单个文件一次传输的最长时间，超过时中止传输，按下载失败重试
+    pub fn with_max_file_duration(mut self, max_file_duration: Duration) -> Self {
+        self.max_file_duration = Some(max_file_duration);
+        self
+    }
+
+    pub fn with_superseded_policy(mut self, superseded_policy: SupersededPolicy) -> Self {
+        self.superseded_policy = superseded_policy;
+        self
+    }
+
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    pub fn with_conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    /// 设置每个时次、每个观测区域预期的文件数
+    pub fn with_expected_files_per_slot(mut self, expected: usize) -> Self {
+        self.expected_files_per_slot = Some(expected);
+        self
+    }
+
+    /// 设置推送运行事件的套接字，共用同一份参数的各次下载推送到同一套接字
+    pub fn with_events(mut self, events: Arc<EventSocket>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// 设置由运行事件更新的累计指标，多轮运行共用同一份时跨轮累计
+    pub fn with_live_metrics(mut self, metrics: Arc<LiveMetrics>) -> Self {
+        self.live_metrics = Some(metrics);
+        self
+    }
+
+    /// 推送一个运行事件
+    pub fn emit(&self, event: RunEvent) {
+        if let Some(metrics) = &self.live_metrics {
+            metrics.record(&event);
+        }
+        if let Some(events) = &self.events {
+            events.emit(&event);
+        }
+    }
+
+    pub fn with_progress(mut self, progress: ProgressReporting) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// 设置进度显示，例如终端进度条
+    pub fn with_progress_display(mut self, display: Arc<dyn ProgressDisplay>) -> Self {
+        self.progress_display = Some(display);
+        self
+    }
+
+    /// 设置等待近期目录发布的时长，为零时目录不存在一律记为缺失
+    pub fn with_unpublished_wait(mut self, unpublished_wait: Duration) -> Self {
+        self.unpublished_wait = unpublished_wait;
+        self
+    }
+
+    /// 设置是否在发现阶段等待尚未发布的目录，定期轮询的调用方可关闭，由下一轮下载新发布的时次
+    pub fn with_poll_unpublished(mut self, poll_unpublished: bool) -> Self {
+        self.poll_unpublished = poll_unpublished;
+        self
+    }
+
+    /// 设置存储后端，下载的文件直接写入后端，不经过本地下载目录
+    pub fn with_backend(mut self, backend: Arc<dyn StorageBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// 设置额外的存储目的地，每个文件进入下载目录后并行写入这些目的地
+    pub fn with_destinations(mut self, destinations: Vec<Arc<dyn Destination>>) -> Self {
+        self.destinations = destinations;
+        self
+    }
+
+    /// 设置后处理流水线，本次运行下载的文件进入下载目录后依次执行这些步骤
+    pub fn with_pipeline(mut self, pipeline: Vec<Arc<dyn PipelineStage>>) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// 设置下载历史，每个文件的下载结果记入其中，发现阶段也据此查找已下载的文件
+    #[cfg(feature = "sqlite")]
+    pub fn with_history(mut self, history: Arc<DownloadHistory>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// 查找已下载完成的本地文件：先按当前的文件名规则在归档与暂存目录中查找，启用下载历史时再查历史中记录的位置。
+    /// 使用存储后端时查找后端中的对象，返回对象的位置与大小；查询失败视为不存在
+    pub fn find_stored(
+        &self,
+        local_storage: &LocalFileStorage,
+        remote_path: &str,
+    ) -> Option<(PathBuf, u64)> {
+        if let Some(backend) = &self.backend {
+            let key = local_storage.archive_key(remote_path);
+            return match backend.size(&key) {
+                Ok(size) => size
+                    .filter(|&size| size > 0)
+                    .map(|size| (PathBuf::from(backend.location(&key)), size)),
+                Err(e) => {
+                    tracing::warn!("查询 {} 失败: {}", backend.location(&key), e);
+                    None
+                }
+            };
+        }
+        let stored = local_storage.find_stored(remote_path);
+        #[cfg(feature = "sqlite")]
+        let stored = stored.or_else(|| self.history.as_ref()?.find_stored(remote_path));
+        stored
+    }
+
+    /// 设置执行处理步骤的线程数
+    pub fn with_pipeline_threads(mut self, pipeline_threads: usize) -> Self {
+        self.pipeline_threads = pipeline_threads;
+        self
+    }
+
+    /// 设置是否在运行结束前重试失败的文件
+    pub fn with_final_retry(mut self, final_retry: bool) -> Self {
+        self.final_retry = final_retry;
+        self
+    }
+
+    /// 设置是否逐个记录本地已存在、下载完成与跳过的文件；大范围回补时记录会占用较多内存
+    pub fn with_file_results(mut self, file_results: bool) -> Self {
+        self.file_results = file_results;
+        self
+    }
+
+    /// 只下载这些远程路径的文件，例如上次运行写入 [`FAILED_LIST_FILE`] 的失败文件
+    pub fn with_only_files(mut self, files: impl IntoIterator<Item = String>) -> Self {
+        self.only_files = Some(Arc::new(files.into_iter().collect()));
+        self
+    }
+
+    /// 只下载这些分段的 HSD 文件，例如归档核对发现缺少的分段；卫星编号、分辨率标记与扩展名不限
+    pub fn with_only_segments(mut self, segments: impl IntoIterator<Item = SegmentKey>) -> Self {
+        self.only_segments = Some(Arc::new(segments.into_iter().collect()));
+        self
+    }
+
+    /// 为一个连接创建读缓冲区大小控制
+    pub fn buffer_sizer(&self) -> BufferSizer {
+        match self.max_buffer_size {
+            Some(max) if max > self.buffer_size => BufferSizer::adaptive(self.buffer_size, max),
+            _ => BufferSizer::fixed(self.buffer_size),
+        }
+    }
+
+    /// 是否已超过运行截止时间
+    pub fn deadline_reached(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// 时次是否仍在发布等待期内，此时目录不存在可能只是数据尚未发布
+    pub fn awaiting_publication(&self, datetime: &NaiveDateTime) -> bool {
+        let age = Utc::now().naive_utc() - *datetime;
+        chrono::Duration::from_std(self.unpublished_wait).is_ok_and(|wait| age < wait)
+    }
+}
+
+/// 远程发现的文件，按本地是否已存在分为两类
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveredFiles {
+    pub to_download: Vec<String>,
+    pub existing: Vec<String>,
+    /// 被远程新版本取代的本地文件
+    pub superseded: Vec<SupersededRecord>,
+    /// 成功列出的远程目录
+    pub listed_directories: Vec<String>,
+    /// 服务器上不存在且已过发布等待期的目录及其时次
+    pub missing_directories: Vec<(String, NaiveDateTime)>,
+    /// 重复列出而被去掉的文件
+    pub duplicates: Vec<String>,
+    /// 列出的每个文件所属的时次
+    pub slots: HashMap<String, NaiveDateTime>,
+    /// 列目录时得到的文件大小，下载时不再逐个查询远程属性
+    pub sizes: HashMap<String, u64>,
+    /// 目录仍未发布或读取失败、本次未能列出的时次
+    pub unlisted: Vec<NaiveDateTime>,
+    /// 读取失败的目录及原因
+    pub failed_directories: Vec<DirectoryFailure>,
+    /// 等待期结束时仍未发布的时次
+    pub unpublished: Vec<NaiveDateTime>,
+    /// 目录已列出但没有匹配文件的时次
+    pub empty_slots: Vec<NaiveDateTime>,
+    /// 匹配但尚未上传完成的文件
+    pub not_ready: Vec<String>,
+}
+
+/// 下载计划：按时次列出需要下载的文件与远程大小，不传输数据
+#[derive(Debug, Clone, Default)]
+pub struct DownloadPlan {
+    pub product: String,
+    /// 每个时次需要下载的文件及其远程大小
+    pub slots: BTreeMap<NaiveDateTime, Vec<(String, u64)>>,
+    /// 本地已存在的文件数
+    pub existing_files: usize,
+    /// 本地文件将被远程新版本取代的文件数
+    pub superseded_files: usize,
+    /// 发现阶段的结果
+    pub discovery: DiscoveryReport,
+    /// 目录尚未发布或读取失败、未能列出的时次
+    pub unlisted: Vec<NaiveDateTime>,
+}
+
+impl DownloadPlan {
+    /// 需要下载的文件数
+    pub fn total_files(&self) -> usize {
+        self.slots.values().map(Vec::len).sum()
+    }
+
+    /// 需要下载的数据量（字节）
+    pub fn total_bytes(&self) -> u64 {
+        self.slots.values().flatten().map(|(_, size)| size).sum()
+    }
+
+    pub fn print_report(&self) {
+        println!("=== {} 下载计划 ===", self.product);
+        for (slot, files) in &self.slots {
+            let bytes: u64 = files.iter().map(|(_, size)| size).sum();
+            println!(
+                "{}  {} 个文件, {}",
+                slot.format("%Y-%m-%d %H:%M"),
+                files.len(),
+                format_bytes(bytes)
+            );
+            for (file, size) in files {
+                println!("  {}  {}", file, format_bytes(*size));
+            }
+        }
+        println!(
+            "需要下载 {} 个文件，共 {}；本地已存在 {} 个文件",
+            self.total_files(),
+            format_bytes(self.total_bytes()),
+            self.existing_files
+        );
+        if self.superseded_files > 0 {
+            println!(
+                "其中 {} 个文件的本地版本已被远程更新，下载时按 superseded_policy 处理",
+                self.superseded_files
+            );
+        }
+        if !self.unlisted.is_empty() {
+            println!(
+                "未能列出的时次: {} 个，实际下载量可能更多",
+                self.unlisted.len()
+            );
+        }
+    }
+}
+
+impl DiscoveredFiles {
+    /// 汇总为发现阶段的结果
+    pub fn report(&self) -> DiscoveryReport {
+        DiscoveryReport {
+            listed_directories: self.listed_directories.len(),
+            files_found: self.existing.len() + self.to_download.len(),
+            failed_directories: self.failed_directories.clone(),
+            missing_directories: self.missing_directories.clone(),
+            unpublished_slots: self.unpublished.clone(),
+            empty_slots: self.empty_slots.clone(),
+            not_ready_files: self.not_ready.clone(),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! 后处理线程池：下载线程把进入下载目录的文件放入队列，由独立的工作线程执行后处理流水线，
+//! CPU 密集的转换不占用下载线程，下载也不必等待处理完成
+
+use super::{PostProcessFailure, PostProcessingStats};
+use crate::pipeline::{PipelineStage, run_pipeline};
+use crate::shutdown::Shutdown;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+/// 等待处理的文件
+struct Job {
+    remote_path: String,
+    local_path: PathBuf,
+    queued_at: Instant,
+}
+
+/// 队列长度与首个文件入队的时间，由提交方与各工作线程共享
+#[derive(Debug, Default)]
+struct QueueMetrics {
+    waiting: AtomicUsize,
+    peak: AtomicUsize,
+    first_queued: Mutex<Option<Instant>>,
+}
+
+/// 后处理队列的提交端，可复制给各下载线程；没有配置处理步骤时提交的文件直接丢弃
+#[derive(Debug, Clone)]
+pub struct PostProcessQueue {
+    sender: Option<Sender<Job>>,
+    metrics: Arc<QueueMetrics>,
+}
+
+impl PostProcessQueue {
+    /// 队列中等待处理的文件数
+    pub fn pending(&self) -> usize {
+        self.metrics.waiting.load(Ordering::Relaxed)
+    }
+
+    /// 将已进入下载目录的文件放入队列
+    pub fn submit(&self, remote_path: &str, local_path: PathBuf) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let now = Instant::now();
+        self.metrics
+            .first_queued
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert(now);
+        let waiting = self.metrics.waiting.fetch_add(1, Ordering::Relaxed) + 1;
+        self.metrics.peak.fetch_max(waiting, Ordering::Relaxed);
+        let job = Job {
+            remote_path: remote_path.to_string(),
+            local_path,
+            queued_at: now,
+        };
+        if sender.send(job).is_err() {
+            self.metrics.waiting.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 后处理线程池
+#[derive(Debug)]
+pub struct PostProcessPool {
+    queue: PostProcessQueue,
+    workers: Vec<JoinHandle<WorkerResult>>,
+}
+
+/// 一个工作线程的统计与最后一个文件处理完的时间
+type WorkerResult = (PostProcessingStats, Option<Instant>);
+
+impl PostProcessPool {
+    /// 启动 `threads` 个工作线程；没有处理步骤时不启动线程。收到中止请求后队列中剩余的文件不再处理，记为失败
+    pub fn start(
+        stages: &[Arc<dyn PipelineStage>],
+        threads: usize,
+        shutdown: &Arc<Shutdown>,
+    ) -> Self {
+        let metrics = Arc::new(QueueMetrics::default());
+        if stages.is_empty() {
+            return Self {
+                queue: PostProcessQueue {
+                    sender: None,
+                    metrics,
+                },
+                workers: Vec::new(),
+            };
+        }
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads.max(1))
+            .map(|index| {
+                let stages = stages.to_vec();
+                let receiver = Arc::clone(&receiver);
+                let metrics = Arc::clone(&metrics);
+                let shutdown = Arc::clone(shutdown);
+                thread::Builder::new()
+                    .name(format!("post-{}", index))
+                    .spawn(move || work(&stages, &receiver, &metrics, &shutdown))
+                    .expect("创建后处理线程失败")
+            })
+            .collect();
+        Self {
+            queue: PostProcessQueue {
+                sender: Some(sender),
+                metrics,
+            },
+            workers,
+        }
+    }
+
+    /// 提交端
+    pub fn queue(&self) -> &PostProcessQueue {
+        &self.queue
+    }
+
+    /// 关闭队列并等待已入队的文件处理完，返回汇总的统计。调用前各下载线程持有的提交端应已释放
+    pub fn finish(self) -> PostProcessingStats {
+        let threads = self.workers.len();
+        let metrics = Arc::clone(&self.queue.metrics);
+        drop(self.queue);
+        let mut stats = PostProcessingStats::default();
+        let mut last_finished: Option<Instant> = None;
+        for worker in self.workers {
+            match worker.join() {
+                Ok((worker_stats, finished)) => {
+                    stats.merge(&worker_stats);
+                    last_finished = last_finished.max(finished);
+                }
+                Err(_) => tracing::error!("后处理线程异常退出"),
+            }
+        }
+        stats.threads = threads;
+        stats.peak_queue = metrics.peak.load(Ordering::Relaxed);
+        let first_queued = *metrics
+            .first_queued
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let (Some(first), Some(last)) = (first_queued, last_finished) {
+            stats.elapsed = last.saturating_duration_since(first);
+        }
+        stats
+    }
+}
+
+/// 工作线程：依次领取队列中的文件执行流水线，队列关闭且取空后退出
+fn work(
+    stages: &[Arc<dyn PipelineStage>],
+    receiver: &Mutex<Receiver<Job>>,
+    metrics: &QueueMetrics,
+    shutdown: &Shutdown,
+) -> WorkerResult {
+    let mut stats = PostProcessingStats::default();
+    let mut last_finished = None;
+    loop {
+        let job = match receiver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv()
+        {
+            Ok(job) => job,
+            Err(_) => return (stats, last_finished),
+        };
+        metrics.waiting.fetch_sub(1, Ordering::Relaxed);
+        let started = Instant::now();
+        stats.wait_time += started.duration_since(job.queued_at);
+        if shutdown.aborting() {
+            stats.failed.push(PostProcessFailure {
+                remote_path: job.remote_path,
+                stage: "queue".to_string(),
+                error: "运行已中止，未处理".to_string(),
+            });
+            continue;
+        }
+        stats.bytes += fs::metadata(&job.local_path).map_or(0, |metadata| metadata.len());
+        match run_pipeline(stages, &job.local_path) {
+            Ok(()) => stats.processed += 1,
+            Err(failure) => {
+                tracing::warn!("后处理失败 {}: {}", job.local_path.display(), failure);
+                stats.failed.push(PostProcessFailure {
+                    remote_path: job.remote_path,
+                    stage: failure.stage,
+                    error: failure.error,
+                });
+            }
+        }
+        let finished = Instant::now();
+        stats.busy_time += finished.duration_since(started);
+        last_finished = Some(finished);
+    }
+}
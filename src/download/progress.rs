@@ -0,0 +1,141 @@
+//! 单个文件的下载进度报告：按固定间隔、按百分比节点或不报告，或交给进度显示（例如终端进度条）
+
+use crate::units::format_bytes;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+/// 默认的进度报告间隔
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 下载进度的显示方式，例如终端进度条；设置后逐文件的进度日志不再输出，由它显示每次读取后的进度
+///
+/// 方法在工作线程中调用，应尽快返回。新的显示方式只需实现该 trait 并通过
+/// `DownloadOptions::with_progress_display` 传入。
+pub trait ProgressDisplay: Debug + Send + Sync {
+    /// 开始下载队列中的文件，`bytes` 为其中列目录时已知大小的文件的总字节数
+    fn run_started(&self, files: usize, bytes: u64);
+
+    /// 工作线程 `worker` 开始处理文件
+    fn file_started(&self, worker: usize, remote_path: &str);
+
+    /// 文件已传输到 `transferred` 字节，共 `total` 字节；断点续传时第一次报告的是续传的起点
+    fn file_progress(&self, remote_path: &str, transferred: u64, total: u64);
+
+    /// 文件处理结束（下载完成、已存在而跳过或失败），`size` 为列目录时得到的大小
+    fn file_finished(&self, worker: usize, remote_path: &str, size: Option<u64>);
+
+    /// 工作线程退出
+    fn worker_exited(&self, worker: usize);
+
+    /// 队列中的文件全部处理完毕
+    fn run_finished(&self);
+}
+
+/// 下载进度的报告方式；进度以 tracing 事件输出，附带文件、百分比与字节数字段
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressReporting {
+    /// 每隔固定时间报告一次
+    Interval(Duration),
+    /// 只在进度首次达到这些百分比时报告，例如 `[10, 25, 50, 75]`
+    Milestones(Vec<u8>),
+    /// 不报告进度
+    Off,
+}
+
+impl Default for ProgressReporting {
+    fn default() -> Self {
+        Self::Interval(DEFAULT_PROGRESS_INTERVAL)
+    }
+}
+
+impl ProgressReporting {
+    /// 为一次传输创建进度跟踪，`start` 为断点续传的起始字节
+    pub fn tracker(&self, remote_path: &str, start: u64, total: u64) -> ProgressTracker<'_> {
+        let next_milestone = match self {
+            Self::Milestones(milestones) => milestones
+                .iter()
+                .position(|&milestone| percent(start, total) < f64::from(milestone))
+                .unwrap_or(milestones.len()),
+            _ => 0,
+        };
+        ProgressTracker {
+            reporting: self,
+            display: None,
+            remote_path: remote_path.to_string(),
+            start,
+            total,
+            last_report_time: Instant::now(),
+            next_milestone,
+        }
+    }
+}
+
+/// 单次传输的进度跟踪
+#[derive(Debug)]
+pub struct ProgressTracker<'a> {
+    reporting: &'a ProgressReporting,
+    display: Option<&'a dyn ProgressDisplay>,
+    remote_path: String,
+    start: u64,
+    total: u64,
+    last_report_time: Instant,
+    next_milestone: usize,
+}
+
+impl<'a> ProgressTracker<'a> {
+    /// 进度交给 `display` 显示，不再按报告方式输出进度日志
+    pub fn with_display(mut self, display: Option<&'a dyn ProgressDisplay>) -> Self {
+        if let Some(display) = display {
+            display.file_progress(&self.remote_path, self.start, self.total);
+        }
+        self.display = display;
+        self
+    }
+
+    /// 已传输 `transferred` 字节，按设置决定是否报告
+    pub fn update(&mut self, transferred: u64) {
+        if let Some(display) = self.display {
+            display.file_progress(&self.remote_path, transferred, self.total);
+            return;
+        }
+        let progress = percent(transferred, self.total);
+        match self.reporting {
+            ProgressReporting::Interval(interval) => {
+                if self.last_report_time.elapsed() <= *interval {
+                    return;
+                }
+                self.last_report_time = Instant::now();
+            }
+            ProgressReporting::Milestones(milestones) => {
+                let reached = milestones[self.next_milestone..]
+                    .iter()
+                    .take_while(|&&milestone| progress >= f64::from(milestone))
+                    .count();
+                if reached == 0 {
+                    return;
+                }
+                self.next_milestone += reached;
+            }
+            ProgressReporting::Off => return,
+        }
+        tracing::info!(
+            file = %self.remote_path,
+            progress = format_args!("{:.1}", progress),
+            bytes = transferred,
+            total = self.total,
+            "下载进度 {}: {:.1}% ({}/{})",
+            self.remote_path,
+            progress,
+            format_bytes(transferred),
+            format_bytes(self.total)
+        );
+    }
+}
+
+fn percent(transferred: u64, total: u64) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        transferred as f64 / total as f64 * 100.0
+    }
+}
@@ -0,0 +1,195 @@
+//! 同一进程中同时运行的多个下载（例如持续跟踪的实时下载与夜间修补）按权重分享总带宽与连接数，
+//! 而不是谁先开始谁占满
+
+use super::RateLimiter;
+use serde::Serialize;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// 按权重分享的总带宽与连接数：每个下载开始时领取一份 [`JobLease`]，带它的限速器与可用的连接数运行。
+///
+/// 总带宽在正在运行的下载之间按权重分配，某个下载结束后它的份额立即分给其余下载；权重可在运行中
+/// 通过 [`JobShares::set_weight`] 调整，各限速器立即按新的比例限速。
+/// 连接数在下载开始时确定：没有其他正在运行或常驻的下载时可用全部连接，否则按权重分得一份，且不超过其他下载
+/// 占用后剩下的连接。常驻的下载（例如持续跟踪的实时下载）即使在两轮之间没有运行，也为它保留按权重计算的连接，
+/// 在它运行期间开始的下载不会占满连接。
+#[derive(Debug)]
+pub struct JobShares {
+    /// 总带宽（字节/秒），为空时不限速，只分配连接
+    bytes_per_sec: Option<u64>,
+    connections: usize,
+    jobs: Mutex<Vec<Job>>,
+}
+
+#[derive(Debug)]
+struct Job {
+    name: String,
+    weight: u32,
+    resident: bool,
+    running: Option<Running>,
+}
+
+#[derive(Debug)]
+struct Running {
+    limiter: Option<Arc<RateLimiter>>,
+    connections: usize,
+}
+
+/// 某个下载运行期间领取的份额，释放后份额归还给其余下载
+#[derive(Debug)]
+pub struct JobLease {
+    shares: Arc<JobShares>,
+    name: String,
+    /// 该下载的限速器，通过 `DownloadOptions::with_bandwidth_limit` 传入；没有总带宽限制时为空
+    pub limiter: Option<Arc<RateLimiter>>,
+    /// 该下载可用的连接数，即工作线程数
+    pub connections: usize,
+}
+
+/// 某个下载的权重与当前份额，由控制接口 `/shares` 输出
+#[derive(Debug, Clone, Serialize)]
+pub struct JobShare {
+    pub name: String,
+    pub weight: u32,
+    pub running: bool,
+    /// 正在运行时可用的连接数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connections: Option<usize>,
+    /// 正在运行时的限速（字节/秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_per_sec: Option<u64>,
+}
+
+impl JobShares {
+    /// 总带宽为每秒 `bytes_per_sec` 字节（为空时不限速），总连接数为 `connections`
+    pub fn new(bytes_per_sec: Option<u64>, connections: usize) -> Self {
+        Self {
+            bytes_per_sec,
+            connections: connections.max(1),
+            jobs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 登记一个以 `weight` 分享的下载；`resident` 的下载在没有运行时也为它保留连接
+    pub fn with_job(self, name: &str, weight: u32, resident: bool) -> Self {
+        self.jobs().push(Job {
+            name: name.to_string(),
+            weight,
+            resident,
+            running: None,
+        });
+        self
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, Vec<Job>> {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 开始名为 `name` 的下载，领取它的限速器与连接数；没有登记该名称或它已在运行时返回 `None`
+    pub fn start(self: &Arc<Self>, name: &str) -> Option<JobLease> {
+        let mut jobs = self.jobs();
+        let index = jobs.iter().position(|job| job.name == name)?;
+        if jobs[index].running.is_some() {
+            return None;
+        }
+        let connections = self.allowance(&jobs, index);
+        let limiter = self
+            .bytes_per_sec
+            .map(|bytes_per_sec| Arc::new(RateLimiter::new(bytes_per_sec)));
+        jobs[index].running = Some(Running {
+            limiter: limiter.clone(),
+            connections,
+        });
+        self.rebalance(&jobs);
+        Some(JobLease {
+            shares: Arc::clone(self),
+            name: name.to_string(),
+            limiter,
+            connections,
+        })
+    }
+
+    /// 调整下载的权重，带宽立即按新的比例分配，连接数在该下载下次开始时按新权重计算；
+    /// 没有该名称的下载时返回 `false`
+    pub fn set_weight(&self, name: &str, weight: u32) -> bool {
+        let mut jobs = self.jobs();
+        let Some(job) = jobs.iter_mut().find(|job| job.name == name) else {
+            return false;
+        };
+        job.weight = weight;
+        self.rebalance(&jobs);
+        true
+    }
+
+    /// 各下载的权重与当前份额
+    pub fn snapshot(&self) -> Vec<JobShare> {
+        self.jobs()
+            .iter()
+            .map(|job| JobShare {
+                name: job.name.clone(),
+                weight: job.weight,
+                running: job.running.is_some(),
+                connections: job.running.as_ref().map(|running| running.connections),
+                bytes_per_sec: job
+                    .running
+                    .as_ref()
+                    .and_then(|running| running.limiter.as_ref())
+                    .map(|limiter| limiter.bytes_per_sec()),
+            })
+            .collect()
+    }
+
+    /// 第 `index` 个下载开始时可用的连接数：与正在运行或常驻的其他下载按权重分配，
+    /// 不超过其他下载占用后剩下的连接，至少 1 个
+    fn allowance(&self, jobs: &[Job], index: usize) -> usize {
+        let sharing: Vec<&Job> = jobs
+            .iter()
+            .enumerate()
+            .filter(|(other, job)| *other == index || job.running.is_some() || job.resident)
+            .map(|(_, job)| job)
+            .collect();
+        if sharing.len() == 1 {
+            return self.connections;
+        }
+        let total_weight: u64 = sharing.iter().map(|job| job.weight as u64).sum();
+        let weighted = (self.connections as u64 * jobs[index].weight as u64)
+            .checked_div(total_weight)
+            .unwrap_or((self.connections / sharing.len()) as u64) as usize;
+        let in_use: usize = jobs
+            .iter()
+            .filter_map(|job| job.running.as_ref())
+            .map(|running| running.connections)
+            .sum();
+        weighted.min(self.connections.saturating_sub(in_use)).max(1)
+    }
+
+    /// 按权重在正在运行的下载之间重新分配总带宽；权重全为 0 时平均分配
+    fn rebalance(&self, jobs: &[Job]) {
+        let Some(bytes_per_sec) = self.bytes_per_sec else {
+            return;
+        };
+        let running: Vec<(u32, &Arc<RateLimiter>)> = jobs
+            .iter()
+            .filter_map(|job| {
+                let limiter = job.running.as_ref()?.limiter.as_ref()?;
+                Some((job.weight, limiter))
+            })
+            .collect();
+        let total_weight: u64 = running.iter().map(|(weight, _)| *weight as u64).sum();
+        for (weight, limiter) in &running {
+            let share = (bytes_per_sec * *weight as u64)
+                .checked_div(total_weight)
+                .unwrap_or(bytes_per_sec / running.len() as u64);
+            limiter.set_bytes_per_sec(share);
+        }
+    }
+}
+
+impl Drop for JobLease {
+    fn drop(&mut self) {
+        let mut jobs = self.shares.jobs();
+        if let Some(job) = jobs.iter_mut().find(|job| job.name == self.name) {
+            job.running = None;
+        }
+        self.shares.rebalance(&jobs);
+    }
+}
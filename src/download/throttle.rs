@@ -0,0 +1,106 @@
+//! 带宽限制：令牌桶限速器，全局限速由所有工作线程共享，单线程限速每次传输各用一个；
+//! 同一进程中同时运行的多个下载按权重分享总限速见 [`super::JobShares`]
+
+use crate::shutdown::Shutdown;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 空闲后允许以全速传输的时长，即令牌桶的容量
+const BURST: Duration = Duration::from_millis(250);
+/// 限速等待期间检查中止请求的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 令牌桶限速器：按 `bytes_per_sec` 的速度补充额度，额度最多积累 [`BURST`] 的量
+///
+/// 读取到数据后再扣除额度，额度可以为负（大于桶容量的一次读取），欠下的额度换算为需要等待的时间。
+/// 限速可在传输过程中调整，见 [`RateLimiter::set_bytes_per_sec`]。
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    bytes_per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// 每秒 `bytes_per_sec` 字节的限速器
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1) as f64;
+        Self {
+            bucket: Mutex::new(Bucket {
+                bytes_per_sec,
+                tokens: bytes_per_sec * BURST.as_secs_f64(),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// 每秒 `mbps` 兆比特（10^6 比特）的限速器
+    pub fn from_mbps(mbps: f64) -> Self {
+        Self::new(mbps_to_bytes_per_sec(mbps))
+    }
+
+    fn bucket(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        self.bucket.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 限速，单位为字节/秒
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bucket().bytes_per_sec as u64
+    }
+
+    /// 调整限速，之后的读取按新的限速补充额度；已积累的额度不超过新的桶容量
+    pub fn set_bytes_per_sec(&self, bytes_per_sec: u64) {
+        let mut bucket = self.bucket();
+        bucket.refill(Instant::now());
+        bucket.bytes_per_sec = bytes_per_sec.max(1) as f64;
+        bucket.tokens = bucket.tokens.min(bucket.capacity());
+    }
+
+    /// 扣除刚传输的 `bytes` 字节，返回为不超过限速还需等待的时间
+    pub fn consume(&self, bytes: usize) -> Duration {
+        let mut bucket = self.bucket();
+        bucket.refill(Instant::now());
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / bucket.bytes_per_sec)
+        }
+    }
+}
+
+impl Bucket {
+    fn capacity(&self) -> f64 {
+        self.bytes_per_sec * BURST.as_secs_f64()
+    }
+
+    /// 按经过的时间补充额度
+    fn refill(&mut self, now: Instant) {
+        let refill = now.duration_since(self.updated).as_secs_f64() * self.bytes_per_sec;
+        self.tokens = (self.tokens + refill).min(self.capacity());
+        self.updated = now;
+    }
+}
+
+/// 兆比特/秒换算为字节/秒
+pub fn mbps_to_bytes_per_sec(mbps: f64) -> u64 {
+    (mbps * 1_000_000.0 / 8.0) as u64
+}
+
+/// 按限速等待 `delay`；收到中止请求时提前返回，由传输循环保存已写入的数据
+pub(crate) fn throttle(delay: Duration, shutdown: &Shutdown) {
+    let end = Instant::now() + delay;
+    while !shutdown.aborting() {
+        let remaining = end.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return;
+        }
+        thread::sleep(remaining.min(POLL_INTERVAL));
+    }
+}
@@ -0,0 +1,281 @@
+//! 归档容量估算：按波段、分段、观测区域与时间段计算应有的文件数与大致数据量，
+//! 在开始长时间回补前规划磁盘空间。文件大小取各波段的典型值，或服务器上最近一段时间文件的平均大小
+
+use crate::config::Config;
+use crate::himawari::{self, HsdFilename};
+use crate::product::ProductType;
+use crate::snapshot::{RemoteSnapshot, take_snapshot};
+use crate::timelist::{PUBLISH_DELAY_MINUTES, latest_published_slot};
+use crate::units::format_bytes;
+use chrono::{Duration, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// 要估算的数据范围
+#[derive(Debug, Clone)]
+pub struct EstimateSelection {
+    pub product: ProductType,
+    /// 波段，为空时为全部波段；不按波段拆分的产品忽略
+    pub bands: Vec<String>,
+    /// 全圆盘的分段编号，为空时为全部分段；区域观测不分段，不受影响
+    pub segments: Vec<u32>,
+    pub areas: Vec<String>,
+    /// 第一个与最后一个时次（含）
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// 不按波段拆分的产品每个时次与观测区域的文件数
+    pub files_per_slot: usize,
+}
+
+/// 服务器上样本文件的大小，按波段与观测区域汇总
+#[derive(Debug, Clone, Default)]
+pub struct SampledSizes {
+    /// 样本时间段
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// （波段, 观测区域）的文件数与总大小，仅能解析为 HSD 文件名的文件
+    by_band_area: BTreeMap<(String, String), (usize, u64)>,
+    files: usize,
+    bytes: u64,
+}
+
+impl SampledSizes {
+    pub fn from_snapshot(snapshot: &RemoteSnapshot) -> Self {
+        let mut sizes = Self {
+            start: snapshot.start,
+            end: snapshot.end,
+            ..Self::default()
+        };
+        for (path, entry) in &snapshot.files {
+            let filename = path.rsplit('/').next().unwrap_or(path);
+            if let Some(hsd) = HsdFilename::parse(filename) {
+                let group = sizes.by_band_area.entry((hsd.band, hsd.area)).or_default();
+                group.0 += 1;
+                group.1 += entry.size;
+            }
+            sizes.files += 1;
+            sizes.bytes += entry.size;
+        }
+        sizes
+    }
+
+    /// 样本文件数
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// 波段与观测区域的文件平均大小；`band` 为空时为全部样本文件的平均大小
+    pub fn average(&self, band: Option<&str>, area: &str) -> Option<u64> {
+        let (files, bytes) = match band {
+            Some(band) => *self
+                .by_band_area
+                .get(&(band.to_string(), area.to_string()))?,
+            None => (self.files, self.bytes),
+        };
+        (files > 0).then(|| bytes / files as u64)
+    }
+}
+
+/// 文件大小的来源
+#[derive(Debug, Clone)]
+pub enum FileSizes {
+    /// 各波段 HSD 文件的典型大小，见 [`himawari::typical_file_size`]
+    Typical,
+    /// 服务器上样本文件的平均大小；样本中没有的波段与观测区域使用典型大小
+    Sampled(SampledSizes),
+}
+
+/// 一个波段（不按波段拆分的产品为观测区域）的文件数与数据量
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EstimateGroup {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// 估算结果
+#[derive(Debug, Clone)]
+pub struct ArchiveEstimate {
+    pub product: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// 时间段内的时次数
+    pub slots: usize,
+    /// 按波段汇总，不按波段拆分的产品按观测区域汇总
+    pub groups: BTreeMap<String, EstimateGroup>,
+    pub files: usize,
+    pub bytes: u64,
+    /// 没有可用大小、未计入数据量的文件数
+    pub unsized_files: usize,
+    /// 时间段的长度（按时次数乘以观测间隔）
+    pub span: Duration,
+    /// 文件大小的来源说明
+    pub basis: String,
+}
+
+impl ArchiveEstimate {
+    /// 平均每天的数据量
+    pub fn bytes_per_day(&self) -> u64 {
+        let days = self.span.num_minutes() as f64 / (24.0 * 60.0);
+        if days > 0.0 {
+            (self.bytes as f64 / days) as u64
+        } else {
+            0
+        }
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "=== {} 容量估算 {} 至 {} ===",
+            self.product,
+            self.start.format("%Y-%m-%d %H:%M"),
+            self.end.format("%Y-%m-%d %H:%M")
+        );
+        println!("文件大小: {}", self.basis);
+        for (name, group) in &self.groups {
+            println!(
+                "  {}  {} 个文件  {}",
+                name,
+                group.files,
+                format_bytes(group.bytes)
+            );
+        }
+        println!(
+            "共 {} 个时次，{} 个文件，约 {}（平均每天 {}）",
+            self.slots,
+            self.files,
+            format_bytes(self.bytes),
+            format_bytes(self.bytes_per_day())
+        );
+        if self.unsized_files > 0 {
+            println!(
+                "其中 {} 个文件没有典型大小，未计入数据量；可按服务器上的文件大小估算",
+                self.unsized_files
+            );
+        }
+    }
+}
+
+/// 计算时间段内每个时次与观测区域应有的文件数与数据量。
+///
+/// 按波段拆分的产品按波段、观测区域与分段逐一计算，全圆盘的例行维护时次不计入；
+/// 其他产品每个时次与观测区域按 `files_per_slot` 个文件计算。
+pub fn estimate_archive(
+    selection: &EstimateSelection,
+    sizes: &FileSizes,
+) -> Result<ArchiveEstimate, Box<dyn Error>> {
+    let product = &selection.product;
+    let slots = product.time_list(selection.start, selection.end)?;
+    let bands: Vec<String> = if selection.bands.is_empty() {
+        himawari::BANDS.map(String::from).to_vec()
+    } else {
+        selection.bands.clone()
+    };
+    let size_of = |band: Option<&str>, area: &str| {
+        let sampled = match sizes {
+            FileSizes::Typical => None,
+            FileSizes::Sampled(sampled) => sampled.average(band, area),
+        };
+        sampled.or_else(|| himawari::typical_file_size(band?, area))
+    };
+
+    let mut estimate = ArchiveEstimate {
+        product: product.name.clone(),
+        start: slots.first().copied().unwrap_or(selection.start),
+        end: slots.last().copied().unwrap_or(selection.end),
+        slots: slots.len(),
+        groups: BTreeMap::new(),
+        files: 0,
+        bytes: 0,
+        unsized_files: 0,
+        span: Duration::minutes(product.cadence_minutes * slots.len() as i64),
+        basis: match sizes {
+            FileSizes::Typical => "各波段的典型大小（昼夜平均的粗略值）".to_string(),
+            FileSizes::Sampled(sampled) => format!(
+                "服务器上 {} 至 {} 的 {} 个文件的平均大小",
+                sampled.start.format("%Y-%m-%d %H:%M"),
+                sampled.end.format("%Y-%m-%d %H:%M"),
+                sampled.files()
+            ),
+        },
+    };
+    let mut add = |group: &str, files: usize, size: Option<u64>| {
+        let entry = estimate.groups.entry(group.to_string()).or_default();
+        entry.files += files;
+        estimate.files += files;
+        match size {
+            Some(size) => {
+                entry.bytes += size * files as u64;
+                estimate.bytes += size * files as u64;
+            }
+            None => estimate.unsized_files += files,
+        }
+    };
+
+    for area in &selection.areas {
+        if !product.has_bands {
+            add(
+                area,
+                slots.len() * selection.files_per_slot,
+                size_of(None, area),
+            );
+            continue;
+        }
+        let total_segments = himawari::segments_per_area(area).unwrap_or(1);
+        let segments = if total_segments > 1 && !selection.segments.is_empty() {
+            selection
+                .segments
+                .iter()
+                .filter(|&&segment| (1..=total_segments).contains(&segment))
+                .count()
+        } else {
+            total_segments as usize
+        };
+        let observed = if area == "FLDK" {
+            slots
+                .iter()
+                .filter(|slot| !himawari::is_housekeeping_slot(slot))
+                .count()
+        } else {
+            slots.len()
+        };
+        for band in &bands {
+            add(band, observed * segments, size_of(Some(band), area));
+        }
+    }
+    Ok(estimate)
+}
+
+/// 按配置连接服务器，列出最近 `hours` 小时内已发布的 `areas` 文件作为样本。
+/// 可见光波段的文件大小随昼夜变化，样本宜覆盖一整天
+pub fn sample_file_sizes(
+    config: &Config,
+    areas: &[String],
+    hours: u64,
+) -> Result<SampledSizes, Box<dyn Error>> {
+    let product = config.product()?;
+    let layout = config.remote_layout();
+    let end = latest_published_slot(PUBLISH_DELAY_MINUTES);
+    let start = end - Duration::hours(hours as i64) + Duration::minutes(product.cadence_minutes);
+    let connection = config.server.transport.connect(
+        &config.get_host_with_port(),
+        &config.server.username,
+        &config.server.credentials(),
+    )?;
+    let snapshot = take_snapshot(
+        connection.as_ref(),
+        &product,
+        layout.as_ref(),
+        areas,
+        start.min(end),
+        end,
+    )?;
+    if snapshot.files.is_empty() {
+        return Err(format!(
+            "服务器上 {} 至 {} 没有可作为样本的文件",
+            start.format("%Y-%m-%d %H:%M"),
+            end.format("%Y-%m-%d %H:%M")
+        )
+        .into());
+    }
+    Ok(SampledSizes::from_snapshot(&snapshot))
+}
@@ -0,0 +1,146 @@
+//! 通过 Unix 域套接字向订阅进程实时推送运行事件（每行一个 JSON 对象）
+
+use crate::download::FinalizeOutcome;
+#[cfg(unix)]
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 推送给订阅者的运行事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RunEvent {
+    /// 一次下载运行开始，`queued_files` 为需要下载的文件数
+    RunStarted {
+        product: String,
+        total_files: usize,
+        existing_files: usize,
+        queued_files: usize,
+    },
+    /// 工作线程建立了连接
+    WorkerConnected {
+        worker: usize,
+        connection: String,
+    },
+    FileStarted {
+        worker: usize,
+        file: String,
+    },
+    /// 文件下载完成，`bytes` 为 0 表示本地已存在而跳过；`outcome` 为文件如何放到最终路径
+    FileFinished {
+        worker: usize,
+        file: String,
+        bytes: u64,
+        outcome: FinalizeOutcome,
+    },
+    FileFailed {
+        worker: usize,
+        file: String,
+        kind: String,
+        error: String,
+    },
+    WorkerExited {
+        worker: usize,
+    },
+    /// 一次下载运行结束
+    RunFinished {
+        product: String,
+        downloaded_files: usize,
+        existing_files: usize,
+        failed_files: usize,
+        total_bytes: u64,
+        elapsed_secs: f64,
+    },
+}
+
+/// 带时间戳的事件，序列化为一行 JSON
+#[cfg(unix)]
+#[derive(Serialize)]
+struct EventLine<'a> {
+    at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a RunEvent,
+}
+
+/// 事件套接字：监听指定路径，向所有已连接的订阅者广播事件
+///
+/// 订阅者只需连接并逐行读取，例如 `nc -U <路径>`；读取过慢或断开的订阅者会被移除，不影响下载。
+/// 销毁时删除套接字文件。
+#[derive(Debug)]
+pub struct EventSocket {
+    path: PathBuf,
+    #[cfg(unix)]
+    subscribers: std::sync::Arc<std::sync::Mutex<Vec<std::os::unix::net::UnixStream>>>,
+}
+
+/// 向单个订阅者写入一行事件的超时，超时的订阅者视为已失效
+#[cfg(unix)]
+const WRITE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
+
+impl EventSocket {
+    /// 在 `path` 上监听订阅连接；路径上残留的旧套接字文件会被替换
+    #[cfg(unix)]
+    pub fn bind(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        use std::os::unix::net::UnixListener;
+        use std::sync::{Arc, Mutex};
+
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let accepted = Arc::clone(&subscribers);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok() {
+                    accepted.lock().unwrap().push(stream);
+                }
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+            subscribers,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Err(format!("当前平台不支持 Unix 域套接字: {}", path.display()).into())
+    }
+
+    /// 套接字路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 向所有订阅者广播一个事件，写入失败的订阅者被移除
+    pub fn emit(&self, event: &RunEvent) {
+        #[cfg(unix)]
+        {
+            use std::io::Write;
+
+            let Ok(mut line) = serde_json::to_string(&EventLine {
+                at: Utc::now(),
+                event,
+            }) else {
+                return;
+            };
+            line.push('\n');
+            self.subscribers
+                .lock()
+                .unwrap()
+                .retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+        }
+        #[cfg(not(unix))]
+        let _ = event;
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,131 @@
+//! 只读查询远程路径：列出目录或查询单个路径的属性，以 JSON 输出，供脚本判断某个时次是否已发布等。
+//! 复用下载时的传输与认证配置，只允许访问产品的远程根目录之下的路径
+
+use crate::config::Config;
+use crate::failure::FailureKind;
+use crate::transport::{RemoteConnection, RemoteEntry};
+use serde::Serialize;
+use std::error::Error;
+
+/// 远程路径的属性
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RemotePathInfo {
+    /// 完整的远程路径
+    pub path: String,
+    pub exists: bool,
+    /// 字节数，路径不存在时为空
+    pub size: Option<u64>,
+    /// 修改时间（Unix 时间戳，秒），服务器未提供时为空
+    pub modified: Option<u64>,
+}
+
+impl From<RemoteEntry> for RemotePathInfo {
+    fn from(entry: RemoteEntry) -> Self {
+        RemotePathInfo {
+            path: entry.path,
+            exists: true,
+            size: Some(entry.metadata.size),
+            modified: entry.metadata.modified,
+        }
+    }
+}
+
+/// 将命令行给出的路径解析为 `root` 之下的完整远程路径
+///
+/// 相对路径相对于 `root`；绝对路径必须位于 `root` 之下。不允许 `..`。
+pub fn resolve_path(root: &str, path: &str) -> Result<String, String> {
+    let root = root.trim_end_matches('/');
+    let relative = if path.starts_with('/') {
+        let trimmed = path.trim_end_matches('/');
+        if trimmed == root {
+            ""
+        } else {
+            trimmed
+                .strip_prefix(root)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .ok_or_else(|| format!("路径 {} 不在远程根目录 {}/ 之下", path, root))?
+        }
+    } else {
+        path
+    };
+
+    let mut resolved = root.to_string();
+    for component in relative.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return Err(format!("路径 {} 不能包含 ..", path)),
+            component => {
+                resolved.push('/');
+                resolved.push_str(component);
+            }
+        }
+    }
+    if resolved.is_empty() {
+        resolved.push('/');
+    }
+    Ok(resolved)
+}
+
+/// 列出目录中的条目及其属性，按路径排序
+pub fn list_path(
+    connection: &dyn RemoteConnection,
+    dir: &str,
+) -> Result<Vec<RemotePathInfo>, Box<dyn Error>> {
+    let mut entries: Vec<RemotePathInfo> = connection
+        .list_directory_entries(dir)?
+        .into_iter()
+        .map(RemotePathInfo::from)
+        .collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// 查询单个路径的属性；路径不存在时 `exists` 为 false，其他错误照常返回
+pub fn stat_path(
+    connection: &dyn RemoteConnection,
+    path: &str,
+) -> Result<RemotePathInfo, Box<dyn Error>> {
+    match connection.metadata(path) {
+        Ok(metadata) => Ok(RemotePathInfo {
+            path: path.to_string(),
+            exists: true,
+            size: Some(metadata.size),
+            modified: metadata.modified,
+        }),
+        Err(e) if FailureKind::classify(e.as_ref()) == FailureKind::RemoteMissing => {
+            Ok(RemotePathInfo {
+                path: path.to_string(),
+                exists: false,
+                size: None,
+                modified: None,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// 按配置连接服务器，解析路径后执行查询
+fn with_connection<T>(
+    config: &Config,
+    path: &str,
+    query: impl FnOnce(&dyn RemoteConnection, &str) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let product = config.product()?;
+    let path = resolve_path(&config.layout.root(&product), path)?;
+    let connection = config.server.transport.connect(
+        &config.get_host_with_port(),
+        &config.server.username,
+        &config.server.credentials(),
+    )?;
+    query(connection.as_ref(), &path)
+}
+
+/// 按配置连接服务器，列出远程目录
+pub fn run_remote_ls(config: &Config, path: &str) -> Result<Vec<RemotePathInfo>, Box<dyn Error>> {
+    with_connection(config, path, list_path)
+}
+
+/// 按配置连接服务器，查询远程路径的属性
+pub fn run_remote_stat(config: &Config, path: &str) -> Result<RemotePathInfo, Box<dyn Error>> {
+    with_connection(config, path, stat_path)
+}
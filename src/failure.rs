@@ -0,0 +1,245 @@
+use crate::transport::ConnectError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// 下载失败的原因分类，用于在运行结束时按原因汇总失败文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// 远程文件不存在
+    RemoteMissing,
+    /// 远程或本地权限不足
+    PermissionDenied,
+    /// 连接被重置或断开
+    ConnectionReset,
+    /// 连接或读写超时
+    Timeout,
+    /// 单个文件的一次传输超过最长时间，连接本身仍可用
+    TransferTooLong,
+    /// 没有可用的服务器连接
+    NoConnection,
+    /// 服务器认证失败
+    AuthFailed,
+    /// 本地磁盘空间不足
+    DiskFull,
+    /// 下载过程中工作线程 panic
+    WorkerPanic,
+    /// 到达运行时长上限，未开始下载
+    TimeLimit,
+    /// 收到中断请求，未开始下载或传输被中止
+    Interrupted,
+    /// 下载完成时最终路径上已有文件，且处理方式为报错
+    FileConflict,
+    /// 大小正确但内容校验失败（bz2 损坏或 HSD 文件头无效），文件已移入隔离区
+    InvalidContent,
+    /// 其他错误
+    Other,
+}
+
+impl FailureKind {
+    /// 根据错误类型判断失败原因
+    pub fn classify(error: &(dyn Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<DownloadError>() {
+            return error.kind;
+        }
+        if let Some(error) = error.downcast_ref::<ConnectError>() {
+            return error.failure_kind();
+        }
+        if let Some(kind) = Self::from_backend(error) {
+            return kind;
+        }
+        if let Some(error) = error.downcast_ref::<io::Error>() {
+            if let Some(inner) = error.get_ref()
+                && let Some(kind) = Self::from_backend(inner)
+            {
+                return kind;
+            }
+            return Self::from_io(error);
+        }
+        Self::Other
+    }
+
+    /// 各传输后端自身的错误类型
+    #[allow(unused_variables)]
+    fn from_backend(error: &(dyn Error + 'static)) -> Option<Self> {
+        #[cfg(feature = "sftp")]
+        if let Some(error) = error.downcast_ref::<ssh2::Error>() {
+            return Some(libssh2::classify(error));
+        }
+        #[cfg(feature = "russh")]
+        if let Some(error) = error.downcast_ref::<russh_sftp::client::error::Error>() {
+            return Some(russh_sftp_classify(error));
+        }
+        #[cfg(feature = "http")]
+        if let Some(error) = error.downcast_ref::<ureq::Error>() {
+            return Some(match error {
+                ureq::Error::Io(error) => Self::from_io(error),
+                ureq::Error::Timeout(_) => FailureKind::Timeout,
+                ureq::Error::HostNotFound | ureq::Error::ConnectionFailed => {
+                    FailureKind::NoConnection
+                }
+                _ => FailureKind::Other,
+            });
+        }
+        None
+    }
+
+    fn from_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Self::RemoteMissing,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => Self::ConnectionReset,
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Self::Timeout,
+            io::ErrorKind::ConnectionRefused | io::ErrorKind::NotConnected => Self::NoConnection,
+            io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => Self::DiskFull,
+            _ => Self::Other,
+        }
+    }
+
+    /// 是否为连接或认证问题（而非单个文件的问题）
+    pub fn is_connection(&self) -> bool {
+        matches!(self, FailureKind::NoConnection | FailureKind::AuthFailed)
+    }
+
+    /// 是否说明连接已不可用（连接被重置、读写超时或连接已断开），工作线程应重新建立连接后再重试
+    pub fn is_session_lost(&self) -> bool {
+        matches!(
+            self,
+            FailureKind::ConnectionReset | FailureKind::Timeout | FailureKind::NoConnection
+        )
+    }
+
+    /// 是否值得在运行结束前换用新的连接重试：中断、到达时长上限、文件冲突、磁盘已满与导致 panic 的文件不重试
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            FailureKind::Interrupted
+                | FailureKind::TimeLimit
+                | FailureKind::FileConflict
+                | FailureKind::DiskFull
+                | FailureKind::WorkerPanic
+        )
+    }
+
+    /// 机器可读的名称，用于监控指标的标签
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureKind::RemoteMissing => "remote_missing",
+            FailureKind::PermissionDenied => "permission_denied",
+            FailureKind::ConnectionReset => "connection_reset",
+            FailureKind::Timeout => "timeout",
+            FailureKind::TransferTooLong => "transfer_too_long",
+            FailureKind::NoConnection => "no_connection",
+            FailureKind::AuthFailed => "auth_failed",
+            FailureKind::DiskFull => "disk_full",
+            FailureKind::WorkerPanic => "worker_panic",
+            FailureKind::TimeLimit => "time_limit",
+            FailureKind::Interrupted => "interrupted",
+            FailureKind::FileConflict => "file_conflict",
+            FailureKind::InvalidContent => "invalid_content",
+            FailureKind::Other => "other",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            FailureKind::RemoteMissing => "远程文件不存在",
+            FailureKind::PermissionDenied => "权限不足",
+            FailureKind::ConnectionReset => "连接被重置",
+            FailureKind::Timeout => "超时",
+            FailureKind::TransferTooLong => "传输时间过长",
+            FailureKind::NoConnection => "无可用连接",
+            FailureKind::AuthFailed => "认证失败",
+            FailureKind::DiskFull => "磁盘空间不足",
+            FailureKind::WorkerPanic => "工作线程崩溃",
+            FailureKind::TimeLimit => "超过运行时长上限",
+            FailureKind::Interrupted => "运行被中断",
+            FailureKind::FileConflict => "目标文件已存在",
+            FailureKind::InvalidContent => "内容校验失败",
+            FailureKind::Other => "其他错误",
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// 重试耗尽后的下载错误，保留最后一次失败的原因分类
+#[derive(Debug)]
+pub struct DownloadError {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.kind)
+    }
+}
+
+impl Error for DownloadError {}
+
+#[cfg(feature = "sftp")]
+mod libssh2 {
+    use super::FailureKind;
+
+    // libssh2 错误码（见 libssh2_sftp.h 与 libssh2.h）
+    const LIBSSH2_FX_NO_SUCH_FILE: i32 = 2;
+    const LIBSSH2_FX_PERMISSION_DENIED: i32 = 3;
+    const LIBSSH2_FX_NO_CONNECTION: i32 = 6;
+    const LIBSSH2_FX_CONNECTION_LOST: i32 = 7;
+    const LIBSSH2_FX_NO_SUCH_PATH: i32 = 10;
+    const LIBSSH2_FX_NO_SPACE_ON_FILESYSTEM: i32 = 14;
+    const LIBSSH2_FX_QUOTA_EXCEEDED: i32 = 15;
+    const LIBSSH2_ERROR_SOCKET_SEND: i32 = -7;
+    const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+    const LIBSSH2_ERROR_SOCKET_DISCONNECT: i32 = -13;
+    const LIBSSH2_ERROR_SOCKET_TIMEOUT: i32 = -30;
+    const LIBSSH2_ERROR_SOCKET_RECV: i32 = -43;
+
+    pub(super) fn classify(error: &ssh2::Error) -> FailureKind {
+        match error.code() {
+            ssh2::ErrorCode::SFTP(LIBSSH2_FX_NO_SUCH_FILE | LIBSSH2_FX_NO_SUCH_PATH) => {
+                FailureKind::RemoteMissing
+            }
+            ssh2::ErrorCode::SFTP(LIBSSH2_FX_PERMISSION_DENIED) => FailureKind::PermissionDenied,
+            ssh2::ErrorCode::SFTP(LIBSSH2_FX_NO_CONNECTION) => FailureKind::NoConnection,
+            ssh2::ErrorCode::SFTP(LIBSSH2_FX_CONNECTION_LOST) => FailureKind::ConnectionReset,
+            ssh2::ErrorCode::SFTP(
+                LIBSSH2_FX_NO_SPACE_ON_FILESYSTEM | LIBSSH2_FX_QUOTA_EXCEEDED,
+            ) => FailureKind::DiskFull,
+            ssh2::ErrorCode::Session(
+                LIBSSH2_ERROR_SOCKET_SEND
+                | LIBSSH2_ERROR_SOCKET_RECV
+                | LIBSSH2_ERROR_SOCKET_DISCONNECT,
+            ) => FailureKind::ConnectionReset,
+            ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT | LIBSSH2_ERROR_SOCKET_TIMEOUT) => {
+                FailureKind::Timeout
+            }
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+#[cfg(feature = "russh")]
+fn russh_sftp_classify(error: &russh_sftp::client::error::Error) -> FailureKind {
+    use russh_sftp::client::error::Error;
+    use russh_sftp::protocol::StatusCode;
+    match error {
+        Error::Status(status) => match status.status_code {
+            StatusCode::NoSuchFile => FailureKind::RemoteMissing,
+            StatusCode::PermissionDenied => FailureKind::PermissionDenied,
+            StatusCode::NoConnection => FailureKind::NoConnection,
+            StatusCode::ConnectionLost => FailureKind::ConnectionReset,
+            _ => FailureKind::Other,
+        },
+        Error::Timeout => FailureKind::Timeout,
+        _ => FailureKind::Other,
+    }
+}
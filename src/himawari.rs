@@ -0,0 +1,285 @@
+//! AHI（Advanced Himawari Imager）领域知识：波段与分辨率、观测区域的分段数与观测频次、
+//! 例行维护时次、各卫星的业务运行期，以及 HSD 文件名的解析与生成
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// AHI 的全部 16 个波段
+pub const BANDS: [&str; 16] = [
+    "B01", "B02", "B03", "B04", "B05", "B06", "B07", "B08", "B09", "B10", "B11", "B12", "B13",
+    "B14", "B15", "B16",
+];
+
+/// 可见光波段
+pub const VISIBLE_BANDS: [&str; 3] = ["B01", "B02", "B03"];
+
+/// HSD 文件名中的分辨率标记：0.5、1、2 公里
+pub const RESOLUTIONS: [&str; 3] = ["R05", "R10", "R20"];
+
+/// 一个观测时间线的长度（分钟），HSD 文件名中的时间为时间线的开始时间
+pub const TIMELINE_MINUTES: i64 = 10;
+
+/// 全圆盘不观测的例行维护时间线（UTC 时:分）
+pub const HOUSEKEEPING_SLOTS: [(u32, u32); 2] = [(2, 40), (14, 40)];
+
+/// 波段是否为 AHI 的有效波段
+pub fn is_valid_band(band: &str) -> bool {
+    BANDS.contains(&band)
+}
+
+/// 波段在星下点的原始空间分辨率（公里）
+pub fn native_resolution_km(band: &str) -> Option<f64> {
+    match band {
+        "B03" => Some(0.5),
+        "B01" | "B02" | "B04" => Some(1.0),
+        _ if is_valid_band(band) => Some(2.0),
+        _ => None,
+    }
+}
+
+/// HSD 文件名中的分辨率标记，例如 B03 为 `R05`
+pub fn resolution_marker(band: &str) -> Option<&'static str> {
+    match band {
+        "B03" => Some("R05"),
+        "B01" | "B02" | "B04" => Some("R10"),
+        _ if is_valid_band(band) => Some("R20"),
+        _ => None,
+    }
+}
+
+/// 是否为 HSD 文件名中的分辨率标记
+pub fn is_valid_resolution(resolution: &str) -> bool {
+    RESOLUTIONS.contains(&resolution)
+}
+
+/// 是否为 HSD 文件名中的观测区域：全圆盘 FLDK、日本区域 JP01–JP04、目标区域 R301–R304
+/// 或地标区域 R401–R420、R501–R520
+pub fn is_valid_area(area: &str) -> bool {
+    area_kind(area).is_some()
+}
+
+/// 观测区域的 HSD 文件分段数：全圆盘分为 10 段，区域观测不分段
+pub fn segments_per_area(area: &str) -> Option<u32> {
+    match area_kind(area)? {
+        AreaKind::FullDisk => Some(10),
+        _ => Some(1),
+    }
+}
+
+/// 观测区域在一个 10 分钟时间线内的观测次数
+pub fn observations_per_timeline(area: &str) -> Option<u32> {
+    match area_kind(area)? {
+        AreaKind::FullDisk => Some(1),
+        AreaKind::Japan | AreaKind::Target => Some(4),
+        AreaKind::Landmark => Some(20),
+    }
+}
+
+/// 观测区域的观测间隔（秒）：全圆盘 10 分钟，日本区域与目标区域 2.5 分钟，地标区域 30 秒
+pub fn cadence_seconds(area: &str) -> Option<u32> {
+    observations_per_timeline(area).map(|count| TIMELINE_MINUTES as u32 * 60 / count)
+}
+
+/// 全圆盘一个分段文件（bz2 压缩后）的典型大小（字节），按分辨率：0.5、1、2 公里。
+/// 为昼夜平均的粗略值，可见光波段白天的文件明显大于夜间
+const TYPICAL_SEGMENT_BYTES: [u64; 3] = [35_000_000, 9_000_000, 2_800_000];
+
+/// 波段与观测区域的一个 HSD 文件（bz2 压缩后）的典型大小（字节），用于估算归档容量。
+/// 区域观测的文件按区域面积与全圆盘一个分段的比例折算
+pub fn typical_file_size(band: &str, area: &str) -> Option<u64> {
+    let resolution = resolution_marker(band)?;
+    let index = RESOLUTIONS.iter().position(|&r| r == resolution)?;
+    let segment = TYPICAL_SEGMENT_BYTES[index];
+    let size = match area_kind(area)? {
+        AreaKind::FullDisk => segment,
+        AreaKind::Japan => segment * 3 / 10,
+        AreaKind::Target => segment * 8 / 100,
+        AreaKind::Landmark => segment * 4 / 100,
+    };
+    Some(size)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AreaKind {
+    FullDisk,
+    Japan,
+    Target,
+    Landmark,
+}
+
+/// 区域编号：FLDK，JP01–JP04，R301–R304，R401–R420，R501–R520
+fn area_kind(area: &str) -> Option<AreaKind> {
+    if area == "FLDK" {
+        return Some(AreaKind::FullDisk);
+    }
+    if area.len() != 4 {
+        return None;
+    }
+    let (prefix, number) = area.split_at_checked(2)?;
+    let number: u32 = number.parse().ok()?;
+    match (prefix, number) {
+        ("JP", 1..=4) => Some(AreaKind::Japan),
+        ("R3", 1..=4) => Some(AreaKind::Target),
+        ("R4" | "R5", 1..=20) => Some(AreaKind::Landmark),
+        _ => None,
+    }
+}
+
+/// 观测时次是否为全圆盘的例行维护时间线（该时次没有全圆盘数据）
+pub fn is_housekeeping_slot(datetime: &NaiveDateTime) -> bool {
+    HOUSEKEEPING_SLOTS
+        .iter()
+        .any(|&(hour, minute)| datetime.hour() == hour && datetime.minute() == minute)
+}
+
+/// 向日葵卫星及其业务运行期
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Satellite {
+    /// HSD 文件名中的卫星编号，例如 `H09`
+    pub id: &'static str,
+    pub name: &'static str,
+    /// 开始业务运行的时间（UTC）
+    pub operational_from: NaiveDateTime,
+    /// 结束业务运行的时间（UTC），仍在运行时为 `None`
+    pub operational_until: Option<NaiveDateTime>,
+}
+
+const fn utc(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+    match (
+        NaiveDate::from_ymd_opt(year, month, day),
+        NaiveTime::from_hms_opt(hour, 0, 0),
+    ) {
+        (Some(date), Some(time)) => NaiveDateTime::new(date, time),
+        _ => panic!("无效的日期"),
+    }
+}
+
+/// 按运行时间排列的业务卫星
+pub const SATELLITES: [Satellite; 2] = [
+    Satellite {
+        id: "H08",
+        name: "Himawari-8",
+        operational_from: utc(2015, 7, 7, 2),
+        operational_until: Some(utc(2022, 12, 13, 5)),
+    },
+    Satellite {
+        id: "H09",
+        name: "Himawari-9",
+        operational_from: utc(2022, 12, 13, 5),
+        operational_until: None,
+    },
+];
+
+/// 观测时间对应的业务卫星，早于 Himawari-8 业务运行时返回 `None`
+pub fn satellite_at(datetime: &NaiveDateTime) -> Option<&'static Satellite> {
+    SATELLITES.iter().find(|satellite| {
+        *datetime >= satellite.operational_from
+            && satellite
+                .operational_until
+                .is_none_or(|until| *datetime < until)
+    })
+}
+
+/// 标识一个分段文件的时次、波段、观测区域与分段编号，不含卫星编号、分辨率标记与扩展名
+pub type SegmentKey = (NaiveDateTime, String, String, u32);
+
+/// 解析后的 HSD 文件名，例如 `HS_H09_20250717_0900_B03_FLDK_R05_S0110.DAT.bz2`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HsdFilename {
+    pub satellite: String,
+    /// 时间线开始时间
+    pub datetime: NaiveDateTime,
+    pub band: String,
+    pub area: String,
+    pub resolution: String,
+    /// 分段编号，从 1 开始
+    pub segment: u32,
+    pub total_segments: u32,
+    /// 分段之后的扩展名，例如 `.DAT.bz2`
+    pub extension: String,
+}
+
+impl HsdFilename {
+    pub fn parse(filename: &str) -> Option<Self> {
+        let parts: Vec<&str> = filename.split('_').collect();
+        if parts.len() != 8 || parts[0] != "HS" {
+            return None;
+        }
+        let datetime =
+            NaiveDateTime::parse_from_str(&format!("{}{}", parts[2], parts[3]), "%Y%m%d%H%M")
+                .ok()?;
+        let segment_part = parts[7].strip_prefix('S')?;
+        let extension_start = segment_part.find('.').unwrap_or(segment_part.len());
+        let (segment, extension) = segment_part.split_at(extension_start);
+        if segment.len() != 4 {
+            return None;
+        }
+        Some(Self {
+            satellite: parts[1].to_string(),
+            datetime,
+            band: parts[4].to_string(),
+            area: parts[5].to_string(),
+            resolution: parts[6].to_string(),
+            segment: segment[0..2].parse().ok()?,
+            total_segments: segment[2..4].parse().ok()?,
+            extension: extension.to_string(),
+        })
+    }
+
+    /// 某时次、波段与区域应有的全部分段文件名；卫星编号按观测时间确定
+    pub fn expected(datetime: &NaiveDateTime, band: &str, area: &str) -> Vec<Self> {
+        let satellite = satellite_at(datetime).map_or("H09", |satellite| satellite.id);
+        let resolution = resolution_marker(band).unwrap_or("R20");
+        let total_segments = segments_per_area(area).unwrap_or(1);
+        (1..=total_segments)
+            .map(|segment| Self {
+                satellite: satellite.to_string(),
+                datetime: *datetime,
+                band: band.to_string(),
+                area: area.to_string(),
+                resolution: resolution.to_string(),
+                segment,
+                total_segments,
+                extension: ".DAT.bz2".to_string(),
+            })
+            .collect()
+    }
+
+    /// 该文件的分段标识
+    pub fn segment_key(&self) -> SegmentKey {
+        (
+            self.datetime,
+            self.band.clone(),
+            self.area.clone(),
+            self.segment,
+        )
+    }
+
+    /// 标识同一份观测数据的部分（时次、波段、区域、分段与扩展名），不含卫星编号与分辨率标记
+    pub fn observation_key(&self) -> (NaiveDateTime, &str, &str, u32, u32, &str) {
+        (
+            self.datetime,
+            &self.band,
+            &self.area,
+            self.segment,
+            self.total_segments,
+            &self.extension,
+        )
+    }
+}
+
+impl std::fmt::Display for HsdFilename {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "HS_{}_{}_{}_{}_{}_S{:02}{:02}{}",
+            self.satellite,
+            self.datetime.format("%Y%m%d_%H%M"),
+            self.band,
+            self.area,
+            self.resolution,
+            self.segment,
+            self.total_segments,
+            self.extension
+        )
+    }
+}
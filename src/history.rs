@@ -0,0 +1,275 @@
+//! 下载历史（`sqlite` 特性）：每个文件的下载结果记入下载目录中的 `history.sqlite`，
+//! 包括远程与本地路径、大小、校验和、时间与状态，可按时间与路径查询
+
+use crate::checksum::{Checksum, ChecksumAlgorithm};
+use chrono::{NaiveDateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// 下载历史数据库的文件名，位于下载目录下
+pub const HISTORY_FILE: &str = "history.sqlite";
+
+/// 记录时间的格式（UTC），按字符串比较即按时间先后
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS downloads (
+    id INTEGER PRIMARY KEY,
+    remote_path TEXT NOT NULL,
+    local_path TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    checksum_algorithm TEXT,
+    checksum TEXT,
+    recorded_at TEXT NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS downloads_remote_path ON downloads (remote_path);
+CREATE INDEX IF NOT EXISTS downloads_recorded_at ON downloads (recorded_at);
+";
+
+/// 下载结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryStatus {
+    Downloaded,
+    Failed,
+}
+
+impl HistoryStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HistoryStatus::Downloaded => "downloaded",
+            HistoryStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "downloaded" => Some(HistoryStatus::Downloaded),
+            "failed" => Some(HistoryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HistoryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 一条下载记录
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub remote_path: String,
+    /// 文件在归档中的位置
+    pub local_path: PathBuf,
+    /// 文件大小（字节），失败的记录为列目录时的远程大小
+    pub size: u64,
+    /// 未计算校验和时为空
+    pub checksum: Option<Checksum>,
+    /// 记录时间（UTC）
+    pub recorded_at: NaiveDateTime,
+    pub status: HistoryStatus,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// 下载完成的记录，时间为当前时间
+    pub fn downloaded(
+        remote_path: &str,
+        local_path: &Path,
+        size: u64,
+        checksum: Option<Checksum>,
+    ) -> Self {
+        Self {
+            remote_path: remote_path.to_string(),
+            local_path: local_path.to_path_buf(),
+            size,
+            checksum,
+            recorded_at: Utc::now().naive_utc(),
+            status: HistoryStatus::Downloaded,
+            error: None,
+        }
+    }
+
+    /// 下载失败的记录，时间为当前时间
+    pub fn failed(remote_path: &str, local_path: &Path, size: u64, error: &str) -> Self {
+        Self {
+            remote_path: remote_path.to_string(),
+            local_path: local_path.to_path_buf(),
+            size,
+            checksum: None,
+            recorded_at: Utc::now().naive_utc(),
+            status: HistoryStatus::Failed,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// 查询条件，为空的条件不限制
+#[derive(Debug, Clone, Default)]
+pub struct HistoryQuery {
+    /// 记录时间不早于（UTC）
+    pub since: Option<NaiveDateTime>,
+    /// 记录时间不晚于（UTC）
+    pub until: Option<NaiveDateTime>,
+    /// 远程路径包含的文本，例如波段 `B13` 或时次 `20240101_0000`
+    pub path_contains: Option<String>,
+    pub status: Option<HistoryStatus>,
+    /// 最多返回最近的多少条，为 0 时不限制
+    pub limit: usize,
+}
+
+/// 下载历史数据库，各工作线程共享一个连接
+#[derive(Debug)]
+pub struct DownloadHistory {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl DownloadHistory {
+    /// 打开（不存在时创建）`base_path` 下的下载历史
+    pub fn open(base_path: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(base_path)?;
+        let path = base_path.join(HISTORY_FILE);
+        let connection = Connection::open(&path)?;
+        // 同时运行的其他命令（例如 history 查询）持有锁时等待，而不是立即失败
+        connection.busy_timeout(Duration::from_secs(10))?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            path,
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// 数据库文件的路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 写入一条记录
+    pub fn record(&self, entry: &HistoryEntry) -> Result<(), Box<dyn Error>> {
+        self.connection().execute(
+            "INSERT INTO downloads
+                 (remote_path, local_path, size, checksum_algorithm, checksum, recorded_at, status, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.remote_path,
+                entry.local_path.to_string_lossy(),
+                entry.size as i64,
+                entry.checksum.as_ref().map(|checksum| checksum.algorithm.name()),
+                entry.checksum.as_ref().map(|checksum| checksum.value.as_str()),
+                entry.recorded_at.format(TIME_FORMAT).to_string(),
+                entry.status.name(),
+                entry.error,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 远程文件最近一次下载完成时保存的位置，该文件仍存在且大小大于 0 时返回路径与大小。
+    /// 之后修改了文件名模板或目录组织方式的文件据此仍能找到
+    pub fn find_stored(&self, remote_path: &str) -> Option<(PathBuf, u64)> {
+        let local_path: String = self
+            .connection()
+            .query_row(
+                "SELECT local_path FROM downloads
+                 WHERE remote_path = ?1 AND status = 'downloaded'
+                 ORDER BY id DESC LIMIT 1",
+                params![remote_path],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()??;
+        let local_path = PathBuf::from(local_path);
+        let size = fs::metadata(&local_path).ok()?.len();
+        (size > 0).then_some((local_path, size))
+    }
+
+    /// 按条件查询，结果按记录时间先后排列；设置了 `limit` 时返回最近的几条
+    pub fn query(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        let since = query
+            .since
+            .map(|since| since.format(TIME_FORMAT).to_string());
+        let until = query
+            .until
+            .map(|until| until.format(TIME_FORMAT).to_string());
+        let pattern = query
+            .path_contains
+            .as_ref()
+            .map(|text| format!("%{}%", text));
+        let limit = if query.limit == 0 {
+            -1
+        } else {
+            query.limit as i64
+        };
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT remote_path, local_path, size, checksum_algorithm, checksum, recorded_at, status, error
+             FROM downloads
+             WHERE (?1 IS NULL OR recorded_at >= ?1)
+               AND (?2 IS NULL OR recorded_at <= ?2)
+               AND (?3 IS NULL OR remote_path LIKE ?3)
+               AND (?4 IS NULL OR status = ?4)
+             ORDER BY id DESC LIMIT ?5",
+        )?;
+        let rows = statement.query_map(
+            params![
+                since,
+                until,
+                pattern,
+                query.status.map(|status| status.name()),
+                limit
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                ))
+            },
+        )?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (remote_path, local_path, size, algorithm, checksum, recorded_at, status, error) =
+                row?;
+            let checksum = algorithm.zip(checksum).and_then(|(algorithm, value)| {
+                Some(Checksum {
+                    algorithm: ChecksumAlgorithm::from_name(&algorithm)?,
+                    value,
+                })
+            });
+            entries.push(HistoryEntry {
+                remote_path,
+                local_path: PathBuf::from(local_path),
+                size: size as u64,
+                checksum,
+                recorded_at: NaiveDateTime::parse_from_str(&recorded_at, TIME_FORMAT)?,
+                status: HistoryStatus::parse(&status)
+                    .ok_or_else(|| format!("未知的下载状态: {}", status))?,
+                error,
+            });
+        }
+        entries.reverse();
+        Ok(entries)
+    }
+}
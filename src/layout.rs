@@ -0,0 +1,225 @@
+use crate::product::{ProductType, expand_template};
+use crate::transport::RemoteEntry;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 远程服务器目录布局：决定某一观测时间的文件位于哪个目录，以及目录中哪些文件属于请求的数据
+///
+/// 新的镜像布局只需实现该 trait 并通过 `DownloadOptions::with_layout` 传入，无需修改下载引擎。
+pub trait RemoteLayout: Debug + Send + Sync {
+    /// 指定观测时间对应的远程目录
+    fn directory(&self, product: &ProductType, datetime: &NaiveDateTime) -> String;
+
+    /// 文件名是否属于指定产品、观测时间与观测区域
+    fn matches(
+        &self,
+        product: &ProductType,
+        filename: &str,
+        datetime: &NaiveDateTime,
+        area: &str,
+    ) -> bool {
+        product.matches(filename, datetime, area)
+    }
+
+    /// 匹配的文件是否已上传完成、可以下载；未就绪的文件所属时次留待下次运行
+    fn is_ready(&self, _entry: &RemoteEntry) -> bool {
+        true
+    }
+}
+
+/// P-Tree 目录树：每个产品使用自身的目录模板（例如 `/jma/hsd/YYYYMM/DD/hh/`）
+#[derive(Debug, Clone, Default)]
+pub struct PTreeLayout;
+
+impl RemoteLayout for PTreeLayout {
+    fn directory(&self, product: &ProductType, datetime: &NaiveDateTime) -> String {
+        product.remote_directory(datetime)
+    }
+}
+
+/// JMA HSD 目录树：每个观测时次一个目录（`<root>/YYYY/MM/DD/hhmm/`）
+#[derive(Debug, Clone)]
+pub struct JmaHsdLayout {
+    pub root: String,
+}
+
+impl RemoteLayout for JmaHsdLayout {
+    fn directory(&self, _product: &ProductType, datetime: &NaiveDateTime) -> String {
+        expand_template(
+            &format!(
+                "{}/{{YYYY}}/{{MM}}/{{DD}}/{{hh}}{{mm}}/",
+                self.root.trim_end_matches('/')
+            ),
+            datetime,
+            "",
+        )
+    }
+}
+
+/// 扁平镜像：所有文件位于同一目录，仅依靠文件名筛选
+#[derive(Debug, Clone)]
+pub struct FlatLayout {
+    pub root: String,
+}
+
+impl RemoteLayout for FlatLayout {
+    fn directory(&self, _product: &ProductType, _datetime: &NaiveDateTime) -> String {
+        format!("{}/", self.root.trim_end_matches('/'))
+    }
+}
+
+/// 配置文件中的目录布局选择
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayoutConfig {
+    #[default]
+    PTree,
+    JmaHsd {
+        root: String,
+    },
+    Flat {
+        root: String,
+    },
+    /// AWS 开放数据的 `noaa-himawari9` 存储桶：`/AHI-L1b-<sector>/YYYY/MM/DD/hhmm/`，
+    /// `sector` 为 `FLDK`、`Japan` 或 `Target`
+    AwsOpenData {
+        #[serde(default = "default_sector")]
+        sector: String,
+    },
+}
+
+fn default_sector() -> String {
+    "FLDK".to_string()
+}
+
+impl LayoutConfig {
+    pub fn build(&self) -> Arc<dyn RemoteLayout> {
+        match self {
+            LayoutConfig::PTree => Arc::new(PTreeLayout),
+            LayoutConfig::JmaHsd { root } => Arc::new(JmaHsdLayout { root: root.clone() }),
+            LayoutConfig::Flat { root } => Arc::new(FlatLayout { root: root.clone() }),
+            LayoutConfig::AwsOpenData { sector } => Arc::new(JmaHsdLayout {
+                root: aws_open_data_root(sector),
+            }),
+        }
+    }
+
+    /// 产品数据所在的远程根目录，不以 `/` 结尾；P-Tree 布局为产品目录模板中第一个占位符之前的目录
+    pub fn root(&self, product: &ProductType) -> String {
+        let root = match self {
+            LayoutConfig::PTree => {
+                let fixed = product.path_template.split('{').next().unwrap_or_default();
+                fixed.rsplit_once('/').map_or("", |(dir, _)| dir)
+            }
+            LayoutConfig::JmaHsd { root } | LayoutConfig::Flat { root } => root,
+            LayoutConfig::AwsOpenData { sector } => return aws_open_data_root(sector),
+        };
+        root.trim_end_matches('/').to_string()
+    }
+
+    /// 按镜像的文件约定筛选文件的布局；未设置约定时与 [`LayoutConfig::build`] 相同
+    pub fn build_with_rules(&self, rules: &FileRules) -> Arc<dyn RemoteLayout> {
+        let layout = self.build();
+        if rules.is_default() {
+            return layout;
+        }
+        Arc::new(MirrorLayout {
+            inner: layout,
+            rules: rules.clone(),
+        })
+    }
+}
+
+/// AWS 开放数据存储桶中某个观测区域的根目录
+fn aws_open_data_root(sector: &str) -> String {
+    format!("/AHI-L1b-{}", sector)
+}
+
+/// 镜像的文件约定：接受的扩展名，以及判断文件是否已上传完成的规则
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FileRules {
+    /// 接受的扩展名，例如 [".DAT.bz2", ".DAT"]，依次替换产品文件名模式中自第一个 `.` 起的扩展名；
+    /// 为空时使用产品的文件名模式
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// 上传过程中附加在文件名后的后缀，例如 [".tmp"]；带这些后缀的文件视为尚未就绪
+    #[serde(default)]
+    pub partial_suffixes: Vec<String>,
+    /// 修改时间距今不足该秒数的文件视为仍在写入；为 0 时不检查
+    #[serde(default)]
+    pub settle_secs: u64,
+}
+
+impl FileRules {
+    /// 是否未设置任何约定
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 去掉上传中后缀后的文件名，文件名不带这些后缀时为 `None`
+    fn strip_partial<'a>(&self, filename: &'a str) -> Option<&'a str> {
+        self.partial_suffixes
+            .iter()
+            .find_map(|suffix| filename.strip_suffix(suffix.as_str()))
+    }
+
+    /// 文件已上传完成：文件名不带上传中后缀，且修改时间早于 `settle_secs` 之前（服务器未提供修改时间时不检查）
+    pub fn is_ready(&self, entry: &RemoteEntry) -> bool {
+        let filename = Path::new(&entry.path)
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if self.strip_partial(&filename).is_some() {
+            return false;
+        }
+        if self.settle_secs == 0 {
+            return true;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        entry
+            .metadata
+            .modified
+            .is_none_or(|modified| now.saturating_sub(modified) >= self.settle_secs)
+    }
+}
+
+/// 按镜像文件约定筛选的布局：目录由内部布局确定，文件名按约定的扩展名匹配，
+/// 上传中的文件同样匹配但不视为就绪
+#[derive(Debug)]
+pub struct MirrorLayout {
+    pub inner: Arc<dyn RemoteLayout>,
+    pub rules: FileRules,
+}
+
+impl RemoteLayout for MirrorLayout {
+    fn directory(&self, product: &ProductType, datetime: &NaiveDateTime) -> String {
+        self.inner.directory(product, datetime)
+    }
+
+    fn matches(
+        &self,
+        product: &ProductType,
+        filename: &str,
+        datetime: &NaiveDateTime,
+        area: &str,
+    ) -> bool {
+        let filename = self.rules.strip_partial(filename).unwrap_or(filename);
+        if self.rules.extensions.is_empty() {
+            return self.inner.matches(product, filename, datetime, area);
+        }
+        self.rules.extensions.iter().any(|extension| {
+            self.inner
+                .matches(&product.with_extension(extension), filename, datetime, area)
+        })
+    }
+
+    fn is_ready(&self, entry: &RemoteEntry) -> bool {
+        self.rules.is_ready(entry) && self.inner.is_ready(entry)
+    }
+}
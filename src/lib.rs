@@ -1,3 +1,54 @@
+pub mod archive;
+pub mod audit;
+pub mod availability;
+pub mod backend;
+pub mod checksum;
 pub mod config;
+pub mod credentials;
+pub mod destination;
+#[cfg(feature = "cli")]
+pub mod doctor;
+pub mod download;
+pub mod estimate;
+pub mod events;
+pub mod explore;
+pub mod failure;
+pub mod himawari;
+#[cfg(feature = "sqlite")]
+pub mod history;
+pub mod layout;
+#[cfg(feature = "cli")]
+pub mod logging;
+pub mod manifest;
+pub mod metrics;
+pub mod migrate;
+pub mod naming;
+#[cfg(feature = "email")]
+pub mod notify;
+pub mod pipeline;
+pub mod prelude;
+pub mod product;
+pub mod report;
+#[cfg(feature = "sentry")]
+pub mod reporting;
+pub mod retention;
+pub mod retry_budget;
+pub mod shutdown;
+pub mod sidecar;
+pub mod snapshot;
+#[cfg(feature = "cli")]
+pub mod speedtest;
+pub mod spool;
+pub mod status;
+pub mod storage;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod timelist;
+pub mod transport;
+pub mod units;
+pub mod validation;
+pub mod verify;
+
+// 旧的模块路径，保留一个版本
 pub mod download_files_from_list;
 pub mod get_download_time_list;
@@ -0,0 +1,158 @@
+//! 日志输出（`cli` 特性）：将下载过程中的 tracing 事件按级别过滤后输出到终端，
+//! info 及以下写入标准输出，警告与错误写入标准错误；显示进度条时日志在进度条上方输出
+
+use crate::config::{LogFormat, LogLevel, LoggingConfig};
+use crate::download::ProgressBars;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::{MakeWriter, MakeWriterExt};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// 与日志输出一同注册的其他层，例如 OTLP 追踪导出
+pub type ExtraLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// 注册全局 subscriber：按 `level` 过滤的日志输出，格式取自配置；`extra` 不受日志级别限制，
+/// 接收全部 span 与事件；设置 `bars` 时每条日志输出前先隐藏进度条。进程内只能注册一次
+pub fn init(
+    config: &LoggingConfig,
+    level: LogLevel,
+    extra: Option<ExtraLayer>,
+    bars: Option<Arc<ProgressBars>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let writer = BarAware {
+        inner: io::stderr.with_max_level(Level::WARN).or_else(io::stdout),
+        bars,
+    };
+    let filter = LevelFilter::from_level(level.into());
+    let output = match config.format {
+        LogFormat::Human => tracing_subscriber::fmt::layer()
+            .event_format(HumanFormat)
+            .with_writer(writer.clone())
+            .with_filter(filter)
+            .boxed(),
+        LogFormat::Full => tracing_subscriber::fmt::layer()
+            .with_thread_names(true)
+            .with_ansi(io::stdout().is_terminal())
+            .with_writer(writer)
+            .with_filter(filter)
+            .boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(extra)
+        .with(output)
+        .try_init()?;
+    Ok(())
+}
+
+/// 可读格式：只输出消息，警告与错误前加提示；事件附带的字段只出现在 full 格式与追踪数据中
+struct HumanFormat;
+
+impl<S, N> FormatEvent<S, N> for HumanFormat
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "错误: ")?,
+            Level::WARN => write!(writer, "警告: ")?,
+            _ => {}
+        }
+        let mut message = MessageWriter {
+            writer: writer.by_ref(),
+            result: Ok(()),
+        };
+        event.record(&mut message);
+        message.result?;
+        writeln!(writer)
+    }
+}
+
+/// 只写出事件的 `message` 字段
+struct MessageWriter<'a> {
+    writer: Writer<'a>,
+    result: fmt::Result,
+}
+
+impl Visit for MessageWriter<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.result = write!(self.writer, "{:?}", value);
+        }
+    }
+}
+
+/// 显示进度条时先把一条日志写入缓冲，再在隐藏进度条期间整条写出
+#[derive(Clone)]
+struct BarAware<M> {
+    inner: M,
+    bars: Option<Arc<ProgressBars>>,
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for BarAware<M> {
+    type Writer = BarAwareWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        BarAwareWriter::new(self.inner.make_writer(), self.bars.clone())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        BarAwareWriter::new(self.inner.make_writer_for(meta), self.bars.clone())
+    }
+}
+
+struct BarAwareWriter<W: Write> {
+    inner: W,
+    bars: Option<Arc<ProgressBars>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> BarAwareWriter<W> {
+    fn new(inner: W, bars: Option<Arc<ProgressBars>>) -> Self {
+        Self {
+            inner,
+            bars,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for BarAwareWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.bars.is_none() {
+            return self.inner.write(buf);
+        }
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(bars) = &self.bars
+            && !self.buffer.is_empty()
+        {
+            let (inner, buffer) = (&mut self.inner, &self.buffer);
+            bars.suspend(|| inner.write_all(buffer))?;
+            self.buffer.clear();
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for BarAwareWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
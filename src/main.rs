@@ -1,23 +1,697 @@
-use Himawari_HSD_downloader::download_files_from_list::download_files::{
-    LocalFileStorage, download_visible_bands_streaming,
+#![allow(non_snake_case)]
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use clap::{Parser, Subcommand};
+use himawari_hsd_downloader::archive::{ArchiveManifest, export_archive};
+use himawari_hsd_downloader::audit::{AuditReport, audit_archive, audit_window};
+use himawari_hsd_downloader::availability::{parse_month, run_available};
+use himawari_hsd_downloader::backend::StorageBackend;
+use himawari_hsd_downloader::config::{Config, LogLevel};
+use himawari_hsd_downloader::destination::DestinationConfig;
+use himawari_hsd_downloader::doctor::run_doctor;
+use himawari_hsd_downloader::download::{
+    BufferBudget, DownloadOptions, DownloadStats, FAILED_LIST_FILE, JobLease, JobShares,
+    ProgressBars, RateLimiter, download_all_bands_streaming, download_auxiliary_files,
+    download_fldk_files_streaming, download_single_band_streaming, mbps_to_bytes_per_sec,
+    plan_download, read_failed_list,
+};
+use himawari_hsd_downloader::estimate::{
+    EstimateSelection, FileSizes, estimate_archive, sample_file_sizes,
+};
+use himawari_hsd_downloader::events::EventSocket;
+use himawari_hsd_downloader::explore::{run_remote_ls, run_remote_stat};
+use himawari_hsd_downloader::failure::FailureKind;
+use himawari_hsd_downloader::himawari;
+#[cfg(feature = "sqlite")]
+use himawari_hsd_downloader::history::{
+    DownloadHistory, HISTORY_FILE, HistoryQuery, HistoryStatus,
+};
+use himawari_hsd_downloader::logging;
+use himawari_hsd_downloader::manifest::Manifest;
+use himawari_hsd_downloader::metrics::{LiveMetrics, RunMetrics, serve_metrics};
+use himawari_hsd_downloader::migrate::plan_migration;
+use himawari_hsd_downloader::naming::FilenameTemplate;
+#[cfg(feature = "email")]
+use himawari_hsd_downloader::notify::Notifier;
+use himawari_hsd_downloader::product::ProductType;
+use himawari_hsd_downloader::report::RunReport;
+use himawari_hsd_downloader::retention::plan_prune;
+use himawari_hsd_downloader::retry_budget::RetryBudget;
+use himawari_hsd_downloader::shutdown::Shutdown;
+#[cfg(unix)]
+use himawari_hsd_downloader::shutdown::install_signal_handlers;
+use himawari_hsd_downloader::snapshot::{RemoteSnapshot, run_snapshot};
+use himawari_hsd_downloader::speedtest::{SpeedtestOptions, run_speedtest};
+use himawari_hsd_downloader::spool::{new_run_id, promote};
+use himawari_hsd_downloader::storage::{BandCompletenessReport, LocalFileStorage};
+use himawari_hsd_downloader::timelist::{
+    ResumeToken, get_download_time_list_aligned, parse_time, skip_housekeeping_slots,
+    slot_from_filename,
 };
-use Himawari_HSD_downloader::get_download_time_list::get_download_time_list::get_download_time_list;
+use himawari_hsd_downloader::units::{format_bytes, format_duration, set_raw_units};
+use himawari_hsd_downloader::verify::verify_archive;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+const EXIT_CODE_HELP: &str = "退出码:
+  0  全部成功
+  2  运行完成，但部分文件下载失败或时次文件数少于预期
+  3  配置错误
+  4  连接或认证失败
+  5  运行被中止（包括按 Ctrl+C 中断）
+  6  本月下载量已超过上限（可用 --override-quota 强制运行）";
+
+/// 进程退出码，供外部脚本判断运行结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ExitStatus {
+    Success = 0,
+    PartialFailure = 2,
+    ConfigError = 3,
+    ConnectionError = 4,
+    Aborted = 5,
+    QuotaExceeded = 6,
+}
+
+/// 终端进度条，配置了 `progress_bars` 且标准错误是终端时创建，日志输出与各次下载共用
+static PROGRESS_BARS: OnceLock<Arc<ProgressBars>> = OnceLock::new();
 
-mod config;
-use config::Config;
+/// 配置了 `[storage]` 时的存储后端，启动时创建，各次下载共用
+static STORAGE_BACKEND: OnceLock<Arc<dyn StorageBackend>> = OnceLock::new();
 
-fn main() {
+/// 配置了 `[email]` 时的邮件通知，启动时创建，持续跟踪的各轮共用以限制发信频率
+#[cfg(feature = "email")]
+static NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+
+/// 本月下载量达到上限的该比例时开始警告
+const QUOTA_WARNING_RATIO: f64 = 0.8;
+
+impl ExitStatus {
+    /// 根据下载统计判断结果：所有失败均为连接问题且没有文件下载成功时视为连接失败，
+    /// 没有失败但存在文件数不足的时次或读取失败的远程目录时视为部分失败
+    fn from_stats(stats: &DownloadStats) -> Self {
+        let incomplete =
+            !stats.short_slots.is_empty() || !stats.discovery.failed_directories.is_empty();
+        if stats.failed_files == 0 && !incomplete {
+            ExitStatus::Success
+        } else if stats.failed_files == 0 {
+            ExitStatus::PartialFailure
+        } else if stats.downloaded_files == 0
+            && stats.failures.keys().all(FailureKind::is_connection)
+        {
+            ExitStatus::ConnectionError
+        } else {
+            ExitStatus::PartialFailure
+        }
+    }
+
+    /// 根据中断运行的错误判断结果
+    fn from_error(error: &(dyn Error + 'static)) -> Self {
+        if FailureKind::classify(error).is_connection() {
+            ExitStatus::ConnectionError
+        } else {
+            ExitStatus::Aborted
+        }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+#[derive(Parser)]
+#[command(version, about = "Himawari HSD 数据下载工具", after_help = EXIT_CODE_HELP)]
+struct Cli {
+    /// 配置文件路径
+    #[arg(long, default_value = "config.toml")]
+    config: String,
+
+    /// 选用配置文件 [profiles] 中的配置档，切换服务器及与之相配的默认产品、波段、观测区域与目录布局
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// 本月下载量已超过 `monthly_quota_gb` 时仍然开始下载
+    #[arg(long)]
+    override_quota: bool,
+
+    /// 只列出每个时次需要下载的文件、远程大小与总下载量，不下载、不改动本地文件
+    #[arg(long)]
+    dry_run: bool,
+
+    /// 从上次运行结束时输出的续传令牌继续回补，不再交互输入时间段
+    #[arg(long)]
+    resume_token: Option<ResumeToken>,
+
+    /// 只重新下载列表中的文件（每行一个远程路径），例如上次运行写入下载目录的 failed_files.txt
+    /// 或 verify-manifest 生成的补齐列表
+    #[arg(long, conflicts_with = "resume_token")]
+    retry_from_file: Option<String>,
+
+    /// 持续运行：按 [follow] 的间隔反复下载最近一段时间内新发布的时次，直到按 Ctrl+C 或收到 SIGTERM
+    #[arg(long, conflicts_with_all = ["resume_token", "retry_from_file", "dry_run"])]
+    follow: bool,
+
+    /// 数据量、时长与速度输出为固定的字节数、秒数与字节/秒，便于脚本解析
+    #[arg(long, global = true)]
+    raw_units: bool,
+
+    /// 运行结束后将统计信息、逐文件结果与波段完整性写入该文件（`.csv` 只写逐文件结果，其他为 JSON），
+    /// 覆盖配置中的 download.report_file
+    #[arg(long, global = true)]
+    report_file: Option<String>,
+
+    /// 日志级别：error、warn、info、debug 或 trace，覆盖配置中的 logging.level
+    #[arg(long, global = true)]
+    log_level: Option<LogLevel>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 逐项检查网络、认证与本地存储环境（存在未通过的检查时退出码为 1）
+    Doctor,
+    /// 用不同线程数和缓冲区大小下载样本文件，测量实际吞吐量
+    Speedtest {
+        /// 用于测速的波段
+        #[arg(long, default_value = "B01")]
+        band: String,
+        /// 每轮下载的样本文件数
+        #[arg(long, default_value_t = 4)]
+        files: usize,
+        /// 待比较的线程数，逗号分隔
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "1,2,4,8",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        threads: Vec<usize>,
+        /// 待比较的缓冲区大小（KB），逗号分隔
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "32,256,1024",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        buffers_kb: Vec<usize>,
+    },
+    /// 强制重新下载某个波段在一段时间内的全部文件（本地旧文件删除或重命名保留）
+    Refetch {
+        /// 要重新下载的波段，例如 B08
+        #[arg(long)]
+        band: String,
+        /// 开始时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        start: NaiveDateTime,
+        /// 结束时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"，默认与开始时间相同
+        #[arg(long, value_parser = parse_time)]
+        end: Option<NaiveDateTime>,
+        /// 将旧文件重命名为 `<文件名>.superseded-<时间>` 保留，而不是删除
+        #[arg(long)]
+        keep_old: bool,
+    },
+    /// 导出本地归档某段时间内文件的大小与 SHA-256 校验清单，供收到数据的一方核对
+    ExportManifest {
+        /// 开始时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        start: NaiveDateTime,
+        /// 结束时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        end: NaiveDateTime,
+        /// 清单输出路径
+        #[arg(long)]
+        output: String,
+    },
+    /// 按校验清单核对归档的完整性（存在缺失或不一致的文件时退出码为 1）
+    VerifyManifest {
+        /// 由 export-manifest 导出的清单
+        manifest: String,
+        /// 收到的归档所在目录，默认为配置中的 base_path
+        #[arg(long, alias = "root")]
+        base_path: Option<String>,
+        /// 将缺失与损坏文件的远程路径写入该文件（每行一个），用于从服务器补齐
+        #[arg(long)]
+        fetch_list: Option<String>,
+    },
+    /// 按下载清单中记录的 SHA-256 重新计算本地归档的校验和，列出缺失与损坏的文件（存在时退出码为 1）
+    Verify {
+        /// 将缺失与损坏文件的远程路径写入该文件（每行一个），可用 --retry-from-file 重新下载
+        #[arg(long)]
+        fetch_list: Option<String>,
+        /// 将损坏的文件移入隔离区并删除缺失与损坏文件的下载记录，之后的运行将重新下载
+        #[arg(long)]
+        quarantine: bool,
+    },
+    /// 将一段时间内的远程文件列表与之前保存的快照比较，列出新增、消失与变化的文件
+    DiffRemote {
+        /// 开始时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        start: NaiveDateTime,
+        /// 结束时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"，默认与开始时间相同
+        #[arg(long, value_parser = parse_time)]
+        end: Option<NaiveDateTime>,
+        /// 快照文件；不存在时保存当前列表作为快照
+        #[arg(long)]
+        snapshot: String,
+        /// 比较后用当前列表更新快照
+        #[arg(long)]
+        update: bool,
+    },
+    /// 将入站暂存区中完整且校验通过的时次移入归档（存在未移入的时次时退出码为 2）
+    Promote {
+        /// 只处理这些运行编号的目录，默认处理全部
+        runs: Vec<String>,
+        /// 不校验文件内容（bz2 数据流与 HSD 文件头），只检查分段是否齐全
+        #[arg(long)]
+        skip_validation: bool,
+    },
+    /// 删除观测时间超出保留期的本地数据；不加 --yes 时只列出将要删除的目录与数据量
+    Prune {
+        /// 保留最近多少天的数据，默认使用配置中的 retention.keep_days
+        #[arg(long)]
+        keep_days: Option<u64>,
+        /// 只列出将要删除的目录与数据量，不删除
+        #[arg(long)]
+        dry_run: bool,
+        /// 确认删除；配置了 retention.auto_prune = true 时可省略
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 改变目录组织方式、观测区域子目录或文件名模板后，将已下载的文件移到新布局中的位置并更新下载清单；
+    /// 不加 --yes 时只列出将要移动的文件
+    MigrateLayout {
+        /// 确认移动
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 核对最近一段时间的本地归档与观测时间表，列出超过宽限期仍不完整的时次（存在时退出码为 2）
+    Audit {
+        /// 核对最近多少小时内的时次
+        #[arg(long, default_value_t = 24)]
+        hours: u64,
+        /// 观测后经过多少分钟仍不完整才列出，较新的时次不核对
+        #[arg(long, default_value_t = 60)]
+        grace_minutes: u64,
+    },
+    /// 逐时次、波段与分段核对一段时间内的本地归档，列出缺少的分段（存在时退出码为 2）
+    Check {
+        /// 开始时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        start: NaiveDateTime,
+        /// 结束时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"，默认与开始时间相同
+        #[arg(long, value_parser = parse_time)]
+        end: Option<NaiveDateTime>,
+        /// 将缺少的分段写入该 CSV 文件，每个分段一行
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// 核对一段时间内的本地归档，只列出不完整时次的远程目录并下载缺少的分段，不重新扫描整个时间段
+    Repair {
+        /// 开始时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        start: NaiveDateTime,
+        /// 结束时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"，默认与开始时间相同
+        #[arg(long, value_parser = parse_time)]
+        end: Option<NaiveDateTime>,
+        /// 只列出需要下载的文件与下载量，不下载
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// 按记录时间查询下载历史中下载完成与失败的文件（需要 download.history = true）
+    #[cfg(feature = "sqlite")]
+    History {
+        /// 记录时间不早于（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        since: Option<NaiveDateTime>,
+        /// 记录时间不晚于（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        until: Option<NaiveDateTime>,
+        /// 只列出远程路径包含该文本的文件，例如 B13 或 20240101_0000
+        #[arg(long)]
+        path: Option<String>,
+        /// 只列出下载失败的记录
+        #[arg(long)]
+        failed: bool,
+        /// 最多列出最近的多少条，0 表示不限制
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// 列出服务器上某个月实际存在的日期与小时目录
+    Available {
+        /// 月份，格式 YYYY-MM
+        #[arg(long, value_parser = parse_month)]
+        month: NaiveDate,
+    },
+    /// 以 JSON 列出远程目录中的条目，路径相对于产品的远程根目录（也可给出根目录之下的绝对路径）
+    RemoteLs {
+        /// 远程目录，默认为根目录
+        #[arg(default_value = "")]
+        path: String,
+    },
+    /// 以 JSON 输出远程路径的大小与修改时间；路径不存在时 exists 为 false，退出码仍为 0
+    RemoteStat {
+        /// 远程路径，相对于产品的远程根目录（也可给出根目录之下的绝对路径）
+        path: String,
+    },
+    /// 估算一段时间内应有的文件数与大致数据量，用于在回补前规划磁盘空间
+    Estimate {
+        /// 开始时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        start: NaiveDateTime,
+        /// 结束时间（UTC），格式 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_time)]
+        end: NaiveDateTime,
+        /// 波段，逗号分隔，默认使用配置中的 bands
+        #[arg(long, value_delimiter = ',')]
+        bands: Vec<String>,
+        /// 全圆盘的分段编号（1-10），逗号分隔，默认全部分段
+        #[arg(long, value_delimiter = ',')]
+        segments: Vec<u32>,
+        /// 观测区域，逗号分隔，默认使用配置中的 areas
+        #[arg(long, value_delimiter = ',')]
+        areas: Vec<String>,
+        /// 连接服务器，按最近一段时间文件的实际大小估算，而不是典型大小
+        #[arg(long)]
+        live: bool,
+        /// 按实际大小估算时的样本时长（小时）
+        #[arg(long, default_value_t = 24)]
+        sample_hours: u64,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    set_raw_units(cli.raw_units);
+
+    // 以 JSON 输出的命令保持标准输出只有结果，供脚本解析
+    let json_output = prints_json(cli.command.as_ref());
     let version = env!("CARGO_PKG_VERSION");
-    println!(
-        "----------  Himawari HSD Data Downloader Version {}  ----------",
-        version
-    );
+    if !json_output {
+        println!(
+            "----------  Himawari HSD Data Downloader Version {}  ----------",
+            version
+        );
+    }
+
+    let Some(mut config) = load_config(&cli.config, cli.profile.as_deref()) else {
+        return ExitStatus::ConfigError.into();
+    };
+    if let Some(report_file) = &cli.report_file {
+        config.download.report_file = Some(report_file.clone());
+    }
+    // 只在需要连接服务器时取得密码，避免只处理本地归档的命令也要求输入
+    if connects_to_server(cli.command.as_ref())
+        && let Err(e) = config.load_secret()
+    {
+        eprintln!("{}", e);
+        return ExitStatus::ConfigError.into();
+    }
+    if connects_to_server(cli.command.as_ref()) {
+        match config.storage.build() {
+            Ok(Some(backend)) => {
+                let _ = STORAGE_BACKEND.set(backend);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("创建存储后端失败: {}", e);
+                return ExitStatus::ConfigError.into();
+            }
+        }
+        #[cfg(feature = "email")]
+        if let Some(notifier) = Notifier::new(&config.email) {
+            let _ = NOTIFIER.set(notifier);
+        }
+    }
 
-    // 配置文件路径
-    let config_path = "config.toml";
+    // 守卫在 main 结束时释放，确保剩余的追踪数据全部导出
+    #[cfg(feature = "otel")]
+    let (telemetry, extra_layer) =
+        match himawari_hsd_downloader::telemetry::layer(&config.telemetry) {
+            Ok(Some((guard, layer))) => (Some(guard), Some(Box::new(layer) as logging::ExtraLayer)),
+            Ok(None) => (None, None),
+            Err(e) => {
+                eprintln!("初始化追踪导出失败，本次运行不导出追踪数据: {}", e);
+                (None, None)
+            }
+        };
+    #[cfg(not(feature = "otel"))]
+    let extra_layer = None;
+    if config.download.progress_bars && io::stderr().is_terminal() {
+        let _ = PROGRESS_BARS.set(Arc::new(ProgressBars::new()));
+    }
+    // 低于警告的日志输出到标准输出，JSON 命令默认不输出，除非在命令行指定了级别
+    let log_level = cli.log_level.unwrap_or(if json_output {
+        LogLevel::Warn
+    } else {
+        config.logging.level
+    });
+    if let Err(e) = logging::init(
+        &config.logging,
+        log_level,
+        extra_layer,
+        PROGRESS_BARS.get().cloned(),
+    ) {
+        eprintln!("初始化日志输出失败: {}", e);
+    }
+    #[cfg(feature = "otel")]
+    if let Some(endpoint) = &config.telemetry.otlp_endpoint
+        && telemetry.is_some()
+    {
+        tracing::info!("追踪数据将导出到: {}", endpoint);
+    }
+    #[cfg(feature = "sentry")]
+    let _reporting = match himawari_hsd_downloader::reporting::init(&config) {
+        Ok(guard) => guard,
+        Err(e) => {
+            tracing::warn!("初始化错误上报失败，本次运行不上报错误: {}", e);
+            None
+        }
+    };
+    #[cfg(not(feature = "sentry"))]
+    if config.error_reporting.dsn.is_some() {
+        tracing::warn!("已配置 error_reporting.dsn，但程序未启用 sentry 功能编译，错误不会上报");
+    }
+    #[cfg(not(feature = "otel"))]
+    if config.telemetry.otlp_endpoint.is_some() {
+        tracing::warn!(
+            "已配置 telemetry.otlp_endpoint，但程序未启用 otel 功能编译，追踪数据不会导出"
+        );
+    }
 
-    // 加载配置
-    let config = match Config::load_or_create(config_path) {
+    match cli.command {
+        Some(Command::Doctor) => {
+            let report = run_doctor(&config);
+            report.print_report();
+            if report.all_passed() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Some(Command::Speedtest {
+            band,
+            files,
+            threads,
+            buffers_kb,
+        }) => {
+            let options = SpeedtestOptions {
+                band,
+                sample_files: files,
+                thread_counts: threads,
+                buffer_sizes: buffers_kb.iter().map(|kb| kb * 1024).collect(),
+            };
+            match run_speedtest(&config, &options) {
+                Ok(report) => {
+                    report.print_report();
+                    ExitStatus::Success.into()
+                }
+                Err(e) => {
+                    tracing::error!("测速失败: {}", e);
+                    ExitStatus::from_error(e.as_ref()).into()
+                }
+            }
+        }
+        Some(Command::Refetch {
+            band,
+            start,
+            end,
+            keep_old,
+        }) => run_refetch(
+            &config,
+            &band,
+            start,
+            end.unwrap_or(start),
+            keep_old,
+            cli.override_quota,
+        )
+        .into(),
+        Some(Command::ExportManifest { start, end, output }) => {
+            run_export_manifest(&config, start, end, &output).into()
+        }
+        Some(Command::VerifyManifest {
+            manifest,
+            base_path,
+            fetch_list,
+        }) => run_verify_manifest(
+            &config,
+            &manifest,
+            base_path.as_deref(),
+            fetch_list.as_deref(),
+        ),
+        Some(Command::Verify {
+            fetch_list,
+            quarantine,
+        }) => run_verify(&config, fetch_list.as_deref(), quarantine),
+        Some(Command::DiffRemote {
+            start,
+            end,
+            snapshot,
+            update,
+        }) => run_diff_remote(&config, start, end.unwrap_or(start), &snapshot, update).into(),
+        Some(Command::Promote {
+            runs,
+            skip_validation,
+        }) => run_promote(&config, &runs, !skip_validation).into(),
+        Some(Command::Prune {
+            keep_days,
+            dry_run,
+            yes,
+        }) => run_prune(&config, keep_days, dry_run, yes).into(),
+        Some(Command::MigrateLayout { yes }) => run_migrate_layout(&config, yes).into(),
+        Some(Command::Audit {
+            hours,
+            grace_minutes,
+        }) => run_audit(&config, hours, grace_minutes).into(),
+        Some(Command::Repair {
+            start,
+            end,
+            dry_run,
+        }) => run_repair(
+            &config,
+            start,
+            end.unwrap_or(start),
+            dry_run,
+            cli.override_quota,
+        )
+        .into(),
+        Some(Command::Check { start, end, output }) => {
+            run_check(&config, start, end.unwrap_or(start), output.as_deref()).into()
+        }
+        #[cfg(feature = "sqlite")]
+        Some(Command::History {
+            since,
+            until,
+            path,
+            failed,
+            limit,
+        }) => run_history(
+            &config,
+            &HistoryQuery {
+                since,
+                until,
+                path_contains: path,
+                status: failed.then_some(HistoryStatus::Failed),
+                limit,
+            },
+        )
+        .into(),
+        Some(Command::Available { month }) => match run_available(&config, month) {
+            Ok(report) => {
+                report.print_report();
+                ExitStatus::Success.into()
+            }
+            Err(e) => {
+                tracing::error!("查询远程可用数据失败: {}", e);
+                ExitStatus::from_error(e.as_ref()).into()
+            }
+        },
+        Some(Command::RemoteLs { path }) => {
+            print_json(run_remote_ls(&config, &path), "列出远程目录失败").into()
+        }
+        Some(Command::RemoteStat { path }) => {
+            print_json(run_remote_stat(&config, &path), "查询远程路径失败").into()
+        }
+        Some(Command::Estimate {
+            start,
+            end,
+            bands,
+            segments,
+            areas,
+            live,
+            sample_hours,
+        }) => {
+            let areas = if areas.is_empty() {
+                config.download.areas.clone()
+            } else {
+                areas
+            };
+            let sample_hours = live.then_some(sample_hours);
+            run_estimate(&config, start, end, bands, segments, areas, sample_hours).into()
+        }
+        None if cli.follow => run_follow(&config, cli.override_quota).into(),
+        None => run_download(
+            &config,
+            cli.override_quota,
+            cli.resume_token.as_ref(),
+            cli.retry_from_file.as_deref(),
+            cli.dry_run,
+        )
+        .into(),
+    }
+}
+
+/// 命令是否需要连接服务器；不带子命令时为下载
+fn connects_to_server(command: Option<&Command>) -> bool {
+    match command {
+        None
+        | Some(Command::Doctor)
+        | Some(Command::Speedtest { .. })
+        | Some(Command::Refetch { .. })
+        | Some(Command::Repair { .. })
+        | Some(Command::DiffRemote { .. })
+        | Some(Command::Available { .. })
+        | Some(Command::RemoteLs { .. })
+        | Some(Command::RemoteStat { .. }) => true,
+        Some(Command::Estimate { live, .. }) => *live,
+        Some(_) => false,
+    }
+}
+
+/// 将查询结果以 JSON 输出到标准输出，失败时记录错误
+fn print_json<T: serde::Serialize>(result: Result<T, Box<dyn Error>>, context: &str) -> ExitStatus {
+    match result.and_then(|value| Ok(serde_json::to_string_pretty(&value)?)) {
+        Ok(json) => {
+            println!("{}", json);
+            ExitStatus::Success
+        }
+        Err(e) => {
+            tracing::error!("{}: {}", context, e);
+            ExitStatus::from_error(e.as_ref())
+        }
+    }
+}
+
+/// 命令是否将结果以 JSON 输出到标准输出
+fn prints_json(command: Option<&Command>) -> bool {
+    matches!(
+        command,
+        Some(Command::RemoteLs { .. }) | Some(Command::RemoteStat { .. })
+    )
+}
+
+/// 加载并验证配置，失败时提示交互式设置
+fn load_config(config_path: &str, profile: Option<&str>) -> Option<Config> {
+    let mut config = match Config::load_or_create(config_path) {
         Ok(config) => config,
         Err(e) => {
             println!("配置加载失败: {}", e);
@@ -41,54 +715,1700 @@ fn main() {
                     }
                     Err(e) => {
                         eprintln!("交互式配置失败: {}", e);
-                        return;
+                        return None;
                     }
                 }
             } else {
                 println!("请手动编辑配置文件后重新运行程序");
-                return;
+                return None;
             }
         }
     };
 
+    if let Some(profile) = profile {
+        if let Err(e) = config.apply_profile(profile) {
+            eprintln!("配置档无效: {}", e);
+            return None;
+        }
+        println!("使用配置档: {}", profile);
+    }
+
     // 验证配置
     if let Err(e) = config.validate() {
         eprintln!("配置验证失败: {}", e);
-        return;
+        return None;
+    }
+
+    Some(config)
+}
+
+fn run_download(
+    config: &Config,
+    override_quota: bool,
+    resume_token: Option<&ResumeToken>,
+    retry_from_file: Option<&str>,
+    dry_run: bool,
+) -> ExitStatus {
+    log_config(config);
+
+    if !dry_run && !quota_allows_run(config, override_quota) {
+        return ExitStatus::QuotaExceeded;
     }
 
-    println!("使用配置:");
-    println!("  服务器: {}", config.get_host_with_port());
-    println!("  用户名: {}", config.server.username);
-    println!("  线程数: {}", config.download.num_threads);
-    println!("  下载目录: {}", config.download.base_path);
+    let Some((product, auxiliary)) = configured_products(config) else {
+        return ExitStatus::ConfigError;
+    };
 
-    // 获取下载时间列表
-    let download_time_list = get_download_time_list();
-    println!("下载时间列表: {:?}", download_time_list);
+    let retry_files = match retry_from_file.map(|path| read_failed_list(Path::new(path))) {
+        Some(Ok(files)) => Some(files),
+        Some(Err(e)) => {
+            tracing::error!(
+                "读取重试列表失败 {}: {}",
+                retry_from_file.unwrap_or_default(),
+                e
+            );
+            return ExitStatus::ConfigError;
+        }
+        None => None,
+    };
 
-    // 创建本地存储配置
-    let storage = LocalFileStorage::new(&config.download.base_path)
-        .with_time_organization(config.download.organize_by_time);
+    // 获取下载时间列表：重试列表中文件所属的时次，有续传令牌时从令牌记录的时次继续，其次使用配置的时间段，否则交互输入
+    let mut download_time_list = match resume_token {
+        _ if retry_files.is_some() => {
+            retry_time_list(retry_files.as_deref().unwrap_or_default(), &product)
+        }
+        Some(token) if token.product != product.name => {
+            tracing::error!(
+                "续传令牌属于产品 {}，与当前配置的产品 {} 不一致",
+                token.product,
+                product.name
+            );
+            return ExitStatus::ConfigError;
+        }
+        Some(token) => {
+            match token.time_list_aligned(product.cadence_minutes, product.offset_minutes) {
+                Ok(list) => {
+                    tracing::info!("从续传令牌继续: {} 至 {}", token.next, token.end);
+                    list
+                }
+                Err(e) => {
+                    tracing::error!("续传令牌无效: {}", e);
+                    return ExitStatus::ConfigError;
+                }
+            }
+        }
+        None if !config.time.is_unset() => match config.time.time_list(&product) {
+            Ok(list) => {
+                tracing::info!(
+                    "使用配置的时间段: {} 个时次（{} 至 {}）",
+                    list.len(),
+                    list.first().map(ToString::to_string).unwrap_or_default(),
+                    list.last().map(ToString::to_string).unwrap_or_default()
+                );
+                list
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+                return ExitStatus::ConfigError;
+            }
+        },
+        None => {
+            match get_download_time_list_aligned(product.cadence_minutes, product.offset_minutes) {
+                Ok(list) => list,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return ExitStatus::Aborted;
+                }
+            }
+        }
+    };
+    skip_fldk_housekeeping(config, &mut download_time_list);
+    tracing::debug!("下载时间列表: {:?}", download_time_list);
+
+    let storage = build_storage(config);
+    if !local_layout_current(config, &storage) {
+        return ExitStatus::ConfigError;
+    }
+    let options = build_options(config, &product);
+    // 按列表重新下载时不按配置的波段筛选
+    let (options, bands) = match retry_files {
+        Some(files) => (options.with_only_files(files), Vec::new()),
+        None => (options, config.download.bands.clone()),
+    };
+    if dry_run {
+        return run_dry_run(config, &download_time_list, &bands, &options, &storage);
+    }
+    let options = with_event_socket(config, options).with_shutdown(handle_interrupts());
+    if let Some(max_secs) = config.download.start_jitter_secs {
+        wait_start_jitter(max_secs, &options.shutdown);
+    }
+    download_slots(
+        config,
+        &auxiliary,
+        &download_time_list,
+        bands,
+        &options,
+        &storage,
+        true,
+    )
+}
+
+/// 持续跟踪中实时下载（各轮）与夜间修补分享带宽与连接数时的名称
+const REALTIME_JOB: &str = "realtime";
+const REPAIR_JOB: &str = "repair";
+
+/// 持续跟踪：从时次边界起每隔 `follow.interval_minutes` 分钟下载最近 `follow.lookback_hours` 小时内的时次，
+/// 尚未发布的时次不等待，由下一轮下载；设置了 `follow.repair_at` 时每天到点后在一轮结束时于后台开始夜间修补，
+/// 与之后的各轮同时进行，按 `follow.realtime_weight` 与 `follow.repair_weight` 分享带宽与连接数。
+/// 收到中断请求后当前一轮结束即退出（等待进行中的修补停止），返回最后一轮的结果
+fn run_follow(config: &Config, override_quota: bool) -> ExitStatus {
+    log_config(config);
+    let Some((product, auxiliary)) = configured_products(config) else {
+        return ExitStatus::ConfigError;
+    };
+    let lookback = chrono::Duration::hours(config.follow.lookback_hours as i64);
+    tracing::info!(
+        "持续跟踪：每 {} 分钟检查最近 {} 小时内的时次，按 Ctrl+C 停止",
+        config.follow.interval_minutes,
+        config.follow.lookback_hours
+    );
+    if !local_layout_current(config, &build_storage(config)) {
+        return ExitStatus::ConfigError;
+    }
+
+    let shutdown = handle_interrupts();
+    // 总带宽限制与工作线程数由实时下载与夜间修补按权重分享，权重可通过控制接口调整
+    let shares = Arc::new(
+        JobShares::new(
+            config
+                .download
+                .max_bandwidth_mbps
+                .map(mbps_to_bytes_per_sec),
+            config.download.num_threads,
+        )
+        .with_job(REALTIME_JOB, config.follow.realtime_weight, true)
+        .with_job(REPAIR_JOB, config.follow.repair_weight, false),
+    );
+    // 事件套接字、指标端点与中断处理各轮共用，其余参数每轮重新建立
+    let daemon = with_metrics_endpoint(
+        config,
+        Arc::clone(&shares),
+        with_event_socket(config, DownloadOptions::new(config.download.num_threads)),
+    )
+    .with_shutdown(Arc::clone(&shutdown));
+    // 配置已验证
+    let repair_time = config.follow.repair_time().unwrap_or_default();
+    let mut next_repair = repair_time.map(|time| next_daily(time, Utc::now().naive_utc()));
+    if let Some(next_repair) = next_repair {
+        tracing::info!(
+            "夜间修补：每天 {} (UTC) 核对最近 {} 天的时次，下一次在 {}",
+            repair_time.unwrap_or_default().format("%H:%M"),
+            config.follow.repair_days,
+            next_repair.format("%Y-%m-%d %H:%M")
+        );
+    }
+    thread::scope(|scope| {
+        let mut status = ExitStatus::Success;
+        // 在后台进行的夜间修补
+        let mut repair: Option<thread::ScopedJoinHandle<'_, ExitStatus>> = None;
+        for round in 1.. {
+            let started = Utc::now().naive_utc();
+            if quota_allows_run(config, override_quota) {
+                // 运行时长上限从建立参数时开始计算，随机等待计入本轮
+                let lease = shares.start(REALTIME_JOB);
+                let options = round_options(config, &product, &daemon, lease.as_ref());
+                if let Some(max_secs) = config.download.start_jitter_secs
+                    && !wait_start_jitter(max_secs, &shutdown)
+                {
+                    break;
+                }
+                let end = product.period_start(&Utc::now().naive_utc());
+                let start = product.period_start(
+                    &(end - lookback + chrono::Duration::minutes(product.cadence_minutes)),
+                );
+                let mut download_time_list = product.time_list(start, end).unwrap_or_default();
+                skip_fldk_housekeeping(config, &mut download_time_list);
+                tracing::info!(
+                    "第 {} 轮：检查 {} 至 {} 的 {} 个时次",
+                    round,
+                    start.format("%Y-%m-%d %H:%M"),
+                    end.format("%Y-%m-%d %H:%M"),
+                    download_time_list.len()
+                );
+                status = download_slots(
+                    config,
+                    &auxiliary,
+                    &download_time_list,
+                    config.download.bands.clone(),
+                    &options,
+                    &build_storage(config),
+                    false,
+                );
+            } else {
+                status = ExitStatus::QuotaExceeded;
+            }
+            if let Some(repaired) = repair
+                .take_if(|handle| handle.is_finished())
+                .map(join_repair)
+                && repaired != ExitStatus::Success
+            {
+                status = repaired;
+            }
+            if let (Some(time), Some(due)) = (repair_time, next_repair)
+                && Utc::now().naive_utc() >= due
+                && !shutdown.requested()
+            {
+                next_repair = Some(next_daily(time, Utc::now().naive_utc()));
+                if repair.is_some() {
+                    tracing::warn!("上一次夜间修补仍在进行，跳过本次修补");
+                } else if quota_allows_run(config, override_quota) {
+                    let lease = shares.start(REPAIR_JOB);
+                    let mut options = round_options(config, &product, &daemon, lease.as_ref());
+                    // 状态文件与终端进度条留给同时进行的各轮
+                    options.status_interval = None;
+                    options.progress_display = None;
+                    tracing::info!("夜间修补在后台开始，使用 {} 个连接", options.num_threads);
+                    repair = Some(scope.spawn(move || {
+                        let _lease = lease;
+                        run_nightly_repair(config, &options)
+                    }));
+                }
+            }
+            if shutdown.requested() {
+                break;
+            }
+            let next = next_round_start(&product, config.follow.interval_minutes, started);
+            let wait = (next - Utc::now().naive_utc()).to_std().unwrap_or_default();
+            tracing::info!("下一轮将在 {} 后开始", format_duration(wait));
+            if !shutdown.sleep(wait) {
+                break;
+            }
+        }
+        if let Some(handle) = repair {
+            tracing::info!("等待夜间修补停止...");
+            let repaired = join_repair(handle);
+            if repaired != ExitStatus::Success {
+                status = repaired;
+            }
+        }
+        tracing::info!("持续跟踪已停止");
+        status
+    })
+}
+
+/// 等待后台的夜间修补结束，修补线程 panic 时视为中止
+fn join_repair(handle: thread::ScopedJoinHandle<'_, ExitStatus>) -> ExitStatus {
+    handle.join().unwrap_or_else(|_| {
+        tracing::error!("夜间修补线程 panic");
+        ExitStatus::Aborted
+    })
+}
+
+/// 持续跟踪中一轮下载或夜间修补的参数：按配置重新建立，运行时长上限从本轮开始计算，重试预算每轮重新计算；
+/// 事件套接字、指标与中断请求沿用 `daemon` 的。工作线程数与带宽限制按 `lease` 分得的份额
+fn round_options(
+    config: &Config,
+    product: &ProductType,
+    daemon: &DownloadOptions,
+    lease: Option<&JobLease>,
+) -> DownloadOptions {
+    let mut options = build_options(config, product)
+        .with_poll_unpublished(false)
+        .with_shutdown(Arc::clone(&daemon.shutdown));
+    if let Some(events) = &daemon.events {
+        options = options.with_events(Arc::clone(events));
+    }
+    if let Some(metrics) = &daemon.live_metrics {
+        options = options.with_live_metrics(Arc::clone(metrics));
+    }
+    if let Some(lease) = lease {
+        options.num_threads = lease.connections;
+        if let Some(limiter) = &lease.limiter {
+            options = options.with_bandwidth_limit(Arc::clone(limiter));
+        }
+    }
+    options
+}
+
+/// `started` 开始的一轮之后下一轮的开始时刻：从 `started` 所在时次的开始起每隔 `interval_minutes`
+/// 分钟一轮，各轮固定在时次边界上而不随每轮耗时与随机等待漂移；该时刻已过时立即开始
+fn next_round_start(
+    product: &ProductType,
+    interval_minutes: u64,
+    started: NaiveDateTime,
+) -> NaiveDateTime {
+    let interval = chrono::Duration::minutes(interval_minutes as i64);
+    let mut next = product.period_start(&started) + interval;
+    while next <= started {
+        next += interval;
+    }
+    next
+}
+
+/// `now` 之后下一个 `time`（UTC）的时刻
+fn next_daily(time: NaiveTime, now: NaiveDateTime) -> NaiveDateTime {
+    let today = now.date().and_time(time);
+    if today > now {
+        today
+    } else {
+        today + chrono::Duration::days(1)
+    }
+}
+
+/// 夜间修补：核对最近 `follow.repair_days` 天的本地归档（跟踪回看范围内的时次由常规的一轮处理），
+/// 重新下载不完整时次缺少的文件；仍未补齐的时次由下一次修补继续
+fn run_nightly_repair(config: &Config, options: &DownloadOptions) -> ExitStatus {
+    let (start, end) = audit_window(
+        config.follow.repair_days * 24,
+        config.follow.lookback_hours * 60,
+    );
+    let storage = build_storage(config);
+    let report = match audit_archive(config, &storage, start, end) {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("夜间修补核对归档失败: {}", e);
+            return ExitStatus::Aborted;
+        }
+    };
+    if report.is_complete() {
+        tracing::info!(
+            "夜间修补：{} 至 {} 的 {} 个时次均已完整",
+            report.start.format("%Y-%m-%d %H:%M"),
+            report.end.format("%Y-%m-%d %H:%M"),
+            report.checked_slots
+        );
+        return ExitStatus::Success;
+    }
+    report.print_report();
+    tracing::info!(
+        "夜间修补：补齐 {} 个不完整的时次",
+        report.incomplete_slots().len()
+    );
+    download_missing(config, &report, options, &storage, false)
+}
+
+/// 只下载核对结果中不完整的时次所缺的文件：按波段拆分的产品只列出这些时次的远程目录并下载缺少的分段，
+/// 其他产品下载这些时次中本地没有的文件；不下载辅助文件
+fn download_missing(
+    config: &Config,
+    report: &AuditReport,
+    options: &DownloadOptions,
+    storage: &LocalFileStorage,
+    dry_run: bool,
+) -> ExitStatus {
+    let slots = report.incomplete_slots();
+    let options = if options.product.has_bands {
+        options
+            .clone()
+            .with_only_segments(report.missing_segment_keys())
+    } else {
+        options.clone()
+    };
+    // 已按分段筛选，不再按波段筛选；整时次提交时也不会将本次无需下载的波段视为远程缺少
+    let bands = Vec::new();
+    if dry_run {
+        return run_dry_run(config, &slots, &bands, &options, storage);
+    }
+    download_slots(config, &[], &slots, bands, &options, storage, false)
+}
+
+/// 输出本次运行使用的服务器与下载目录
+fn log_config(config: &Config) {
+    tracing::info!("使用配置:");
+    tracing::info!("  服务器: {}", config.get_host_with_port());
+    tracing::info!("  用户名: {}", config.server.username);
+    tracing::info!("  线程数: {}", config.download.num_threads);
+    tracing::info!("  下载目录: {}", config.download.base_path);
+    if let Some(backend) = STORAGE_BACKEND.get() {
+        tracing::info!("  存储后端: {} ({})", backend.name(), backend.location(""));
+    }
+}
+
+/// 配置的数据产品与辅助文件，配置无效时输出错误并返回 `None`
+fn configured_products(config: &Config) -> Option<(ProductType, Vec<ProductType>)> {
+    match (config.product(), config.auxiliary_products()) {
+        (Ok(product), Ok(auxiliary)) => {
+            tracing::info!("  数据产品: {}", product.name);
+            Some((product, auxiliary))
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!("{}", e);
+            report_message(&e);
+            None
+        }
+    }
+}
+
+/// 只下载全圆盘时去掉例行维护时次
+fn skip_fldk_housekeeping(config: &Config, download_time_list: &mut Vec<NaiveDateTime>) {
+    if config.download.areas.iter().all(|area| area == "FLDK") {
+        let skipped = skip_housekeeping_slots(download_time_list);
+        if skipped > 0 {
+            tracing::info!("跳过例行维护时次 {} 个（无全圆盘观测）", skipped);
+        }
+    }
+}
+
+/// 下载时次列表中的主产品与辅助文件，写入失败文件列表、监控指标与运行报告，并按配置删除超出保留期的数据。
+/// `resumable` 时回补未全部完成则输出续传令牌
+fn download_slots(
+    config: &Config,
+    auxiliary: &[ProductType],
+    download_time_list: &[NaiveDateTime],
+    bands: Vec<String>,
+    options: &DownloadOptions,
+    storage: &LocalFileStorage,
+    resumable: bool,
+) -> ExitStatus {
+    let product = &options.product;
+    // 运行编号：使用入站暂存区时与运行目录同名
+    let report = ReportContext {
+        run_id: storage
+            .spool_path
+            .as_ref()
+            .and(storage.staging_path.as_ref())
+            .and_then(|run_dir| run_dir.file_name())
+            .map_or_else(new_run_id, |name| name.to_string_lossy().to_string()),
+        started_at: Utc::now(),
+        slots: download_time_list
+            .first()
+            .copied()
+            .zip(download_time_list.last().copied()),
+    };
+    if storage.spool_path.is_some()
+        && let Some(run_dir) = &storage.staging_path
+    {
+        tracing::info!("入站暂存目录: {}", run_dir.display());
+    }
 
     // 执行下载
-    println!("开始下载可见光波段数据...");
-    match download_visible_bands_streaming(
+    let result = if product.has_bands {
+        if config.download.bands.is_empty() {
+            tracing::info!("开始下载所有波段数据...");
+        } else {
+            tracing::info!(
+                "开始下载波段 {} 的数据...",
+                config.download.bands.join(", ")
+            );
+        }
+        download_fldk_files_streaming(
+            download_time_list.to_vec(),
+            bands,
+            options.clone(),
+            &config.get_host_with_port(),
+            &config.server.username,
+            &config.server.credentials(),
+            storage.clone(),
+        )
+    } else {
+        tracing::info!("开始下载 {} 数据...", product.name);
+        download_all_bands_streaming(
+            download_time_list.to_vec(),
+            options.clone(),
+            &config.get_host_with_port(),
+            &config.server.username,
+            &config.server.credentials(),
+            storage.clone(),
+        )
+    };
+    let mut pending_slots = BTreeSet::new();
+    // 主产品与辅助文件各自的统计，用于写入监控指标
+    let mut run_stats: Vec<(String, DownloadStats)> = Vec::new();
+    let mut status = match result {
+        Ok(stats) => {
+            tracing::info!("下载完成！");
+            tracing::info!("成功下载: {} 个文件", stats.downloaded_files);
+            tracing::info!("已存在: {} 个文件", stats.existing_files);
+            tracing::info!("下载失败: {} 个文件", stats.failed_files);
+            tracing::info!("总下载量: {}", format_bytes(stats.total_bytes));
+            if let Some(path) = &config.download.daily_summary_path {
+                match stats.write_daily_summary(Path::new(path)) {
+                    Ok(()) => tracing::info!("每日汇总已写入 {}", path),
+                    Err(e) => tracing::warn!("写入每日汇总失败 {}: {}", path, e),
+                }
+            }
+            pending_slots.extend(stats.pending_slots.iter().copied());
+            let status = ExitStatus::from_stats(&stats);
+            if status == ExitStatus::ConnectionError {
+                report_message("所有文件均因连接失败未能下载");
+            }
+            if !stats.short_slots.is_empty() {
+                report_message(&format!(
+                    "{} 个时次的文件数少于预期",
+                    stats.short_slots.len()
+                ));
+            }
+            if let Some(minutes) = config.download.latency_alert_minutes {
+                check_slot_latency(&stats, minutes);
+            }
+            if !stats.post_processing.failed.is_empty() {
+                report_message(&format!(
+                    "{} 个文件后处理失败",
+                    stats.post_processing.failed.len()
+                ));
+            }
+            run_stats.push((product.name.clone(), stats));
+            status
+        }
+        Err(e) => {
+            tracing::error!("下载失败: {}", e);
+            report_error(e.as_ref());
+            let status = ExitStatus::from_error(e.as_ref());
+            write_metrics(config, &run_stats, status);
+            let completeness = report_completeness(config, options, storage, download_time_list);
+            write_run_report(config, &report, &run_stats, completeness.as_ref(), status);
+            return status;
+        }
+    };
+
+    // 下载辅助文件；运行被中断时留待下次
+    if !auxiliary.is_empty() && !options.shutdown.requested() {
+        match download_auxiliary_files(
+            download_time_list,
+            auxiliary,
+            options,
+            &config.get_host_with_port(),
+            &config.server.username,
+            &config.server.credentials(),
+            storage,
+        ) {
+            Ok(stats) => {
+                tracing::info!(
+                    "辅助文件下载完成，成功: {}, 失败: {}",
+                    stats.downloaded_files,
+                    stats.failed_files
+                );
+                pending_slots.extend(stats.pending_slots.iter().copied());
+                status = status.max(ExitStatus::from_stats(&stats));
+                run_stats.push(("auxiliary".to_string(), stats));
+            }
+            Err(e) => {
+                tracing::error!("辅助文件下载失败: {}", e);
+                report_error(e.as_ref());
+                status = status.max(ExitStatus::from_error(e.as_ref()));
+            }
+        }
+    }
+
+    // 仍失败的文件写入下载目录，可用 --retry-from-file 只重新下载这些文件
+    let failed_list = Path::new(&config.download.base_path).join(FAILED_LIST_FILE);
+    let all_stats = run_stats
+        .iter()
+        .fold(DownloadStats::new(), |mut total, (_, stats)| {
+            total.merge(stats);
+            total
+        });
+    send_notification(&product.name, report.slots, &all_stats);
+    match all_stats.write_failed_list(&failed_list) {
+        Ok(()) if !all_stats.failed.is_empty() => tracing::info!(
+            "{} 个失败的文件已写入 {}，可使用 --retry-from-file {} 重新下载",
+            all_stats.failed.len(),
+            failed_list.display(),
+            failed_list.display()
+        ),
+        Ok(()) => {}
+        Err(e) => tracing::warn!("写入失败文件列表失败 {}: {}", failed_list.display(), e),
+    }
+
+    // 回补未全部完成时输出续传令牌，下次运行从最早的未完成时次继续
+    if resumable
+        && let (Some(&next), Some(&end)) = (pending_slots.first(), download_time_list.last())
+    {
+        let token = ResumeToken {
+            product: product.name.clone(),
+            next,
+            end,
+        };
+        tracing::info!("回补尚未完成，续传令牌: {}", token);
+        tracing::info!("下次运行可使用 --resume-token {} 继续", token);
+    }
+
+    if options.shutdown.requested() {
+        status = status.max(ExitStatus::Aborted);
+    }
+
+    // 运行结束后再次输出本月下载量
+    check_monthly_quota(config);
+
+    if !options.shutdown.requested()
+        && config.retention.auto_prune
+        && let Some(keep_days) = config.retention.keep_days
+    {
+        tracing::info!("删除超出保留期（{} 天）的数据...", keep_days);
+        match plan_prune(storage, keep_days).and_then(|plan| {
+            plan.print_report();
+            plan.execute(storage)
+        }) {
+            Ok(removed) => tracing::info!("已删除 {} 个文件", removed),
+            Err(e) => tracing::warn!("删除过期数据失败: {}", e),
+        }
+    }
+
+    write_metrics(config, &run_stats, status);
+    let completeness = report_completeness(config, options, storage, download_time_list);
+    write_run_report(config, &report, &run_stats, completeness.as_ref(), status);
+    status
+}
+
+/// 重试列表中文件所属的时次（按产品的发布时段取整），无法从文件名确定时次的文件跳过
+fn retry_time_list(files: &[String], product: &ProductType) -> Vec<NaiveDateTime> {
+    let mut slots = BTreeSet::new();
+    for file in files {
+        let filename = file.rsplit('/').next().unwrap_or(file);
+        match slot_from_filename(filename) {
+            Some(slot) => {
+                slots.insert(product.period_start(&slot));
+            }
+            None => tracing::warn!("无法从文件名确定观测时次，跳过: {}", file),
+        }
+    }
+    tracing::info!(
+        "重新下载列表中的 {} 个文件，涉及 {} 个时次",
+        files.len(),
+        slots.len()
+    );
+    slots.into_iter().collect()
+}
+
+/// 实时时次从观测到下载完成的延迟超过 `minutes` 分钟时警告并上报
+fn check_slot_latency(stats: &DownloadStats, minutes: u64) {
+    let late = stats.late_slots(Duration::from_secs(minutes * 60));
+    let Some(worst) = late.iter().max_by_key(|slot| slot.latency) else {
+        return;
+    };
+    let message = format!(
+        "实时数据延迟：{} 个时次从观测到下载完成超过 {} 分钟，最大为 {} {} 的 {}",
+        late.len(),
+        minutes,
+        worst.datetime.format("%Y-%m-%d %H:%M"),
+        worst.area,
+        format_duration(worst.latency)
+    );
+    tracing::warn!("{}", message);
+    report_message(&message);
+}
+
+/// 列出下载计划后退出，不传输数据
+fn run_dry_run(
+    config: &Config,
+    download_time_list: &[NaiveDateTime],
+    bands: &[String],
+    options: &DownloadOptions,
+    storage: &LocalFileStorage,
+) -> ExitStatus {
+    let bands = if options.product.has_bands {
+        bands
+    } else {
+        &[]
+    };
+    match plan_download(
         download_time_list,
-        config.download.num_threads,
+        bands,
+        options,
         &config.get_host_with_port(),
         &config.server.username,
-        &config.server.password,
+        &config.server.credentials(),
         storage,
     ) {
-        Ok(stats) => {
-            println!("下载完成！");
-            println!("成功下载: {} 个文件", stats.downloaded_files);
-            println!("下载失败: {} 个文件", stats.failed_files);
-            println!("总下载量: {} 字节", stats.total_bytes);
+        Ok(plan) => {
+            plan.print_report();
+            ExitStatus::Success
+        }
+        Err(e) => {
+            tracing::error!("列出下载计划失败: {}", e);
+            report_error(e.as_ref());
+            ExitStatus::from_error(e.as_ref())
+        }
+    }
+}
+
+/// 运行报告中与统计无关的部分
+struct ReportContext {
+    run_id: String,
+    started_at: DateTime<Utc>,
+    slots: Option<(NaiveDateTime, NaiveDateTime)>,
+}
+
+/// 配置了 `report_file` 时检查运行结束时请求时次的波段完整性，未配置波段时检查全部波段；
+/// 不按波段拆分的产品与写入存储后端时不检查
+fn report_completeness(
+    config: &Config,
+    options: &DownloadOptions,
+    storage: &LocalFileStorage,
+    download_time_list: &[NaiveDateTime],
+) -> Option<BandCompletenessReport> {
+    if config.download.report_file.is_none()
+        || !options.product.has_bands
+        || options.backend.is_some()
+    {
+        return None;
+    }
+    let bands = if config.download.bands.is_empty() {
+        himawari::BANDS.iter().map(ToString::to_string).collect()
+    } else {
+        config.download.bands.clone()
+    };
+    Some(storage.check_band_completeness(download_time_list, &options.areas, &bands))
+}
+
+/// 配置了 `archive_reports` 时将本次运行的报告与失败文件列表写入归档，配置了 `report_file` 时
+/// 另将报告写入该文件
+fn write_run_report(
+    config: &Config,
+    context: &ReportContext,
+    run_stats: &[(String, DownloadStats)],
+    completeness: Option<&BandCompletenessReport>,
+    status: ExitStatus,
+) {
+    if !config.download.archive_reports && config.download.report_file.is_none() {
+        return;
+    }
+    let report = RunReport {
+        run_id: context.run_id.clone(),
+        started_at: context.started_at,
+        slots: context.slots,
+        products: run_stats
+            .iter()
+            .map(|(product, stats)| (product.clone(), stats))
+            .collect(),
+        completeness,
+        exit_code: status as u8,
+    };
+    if config.download.archive_reports {
+        match report.write_to_archive(Path::new(&config.download.base_path)) {
+            Ok(dir) => tracing::info!("运行报告已写入 {}", dir.display()),
+            Err(e) => tracing::warn!("写入运行报告失败: {}", e),
+        }
+    }
+    if let Some(path) = &config.download.report_file {
+        match report.write_report_file(Path::new(path)) {
+            Ok(()) => tracing::info!("报告文件已写入 {}", path),
+            Err(e) => tracing::warn!("写入报告文件失败 {}: {}", path, e),
+        }
+    }
+}
+
+/// 配置了 `metrics_textfile_path` 时写入本次运行的 Prometheus 指标
+fn write_metrics(config: &Config, run_stats: &[(String, DownloadStats)], status: ExitStatus) {
+    let Some(path) = &config.download.metrics_textfile_path else {
+        return;
+    };
+    let metrics = RunMetrics {
+        products: run_stats
+            .iter()
+            .map(|(product, stats)| (product.clone(), stats))
+            .collect(),
+        exit_code: status as u8,
+    };
+    match metrics.write(Path::new(path)) {
+        Ok(()) => tracing::info!("监控指标已写入 {}", path),
+        Err(e) => tracing::warn!("写入监控指标失败 {}: {}", path, e),
+    }
+}
+
+/// 导出本地归档某段时间内的校验清单
+fn run_export_manifest(
+    config: &Config,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    output: &str,
+) -> ExitStatus {
+    let product = match config.product() {
+        Ok(product) => product,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return ExitStatus::ConfigError;
         }
+    };
+    let root = Path::new(&config.download.base_path);
+    let renamed = match Manifest::load(root) {
+        Ok(downloads) => downloads.renamed_files(),
         Err(e) => {
-            eprintln!("下载失败: {}", e);
+            tracing::warn!("读取下载清单失败，文件名重写过的文件可能无法识别: {}", e);
+            HashMap::new()
         }
+    };
+    let manifest =
+        match export_archive(root, &product, &config.download.areas, start, end, &renamed) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                tracing::error!("生成校验清单失败: {}", e);
+                return ExitStatus::from_error(e.as_ref());
+            }
+        };
+    if let Err(e) = manifest.save(Path::new(output)) {
+        tracing::error!("写入校验清单失败 {}: {}", output, e);
+        return ExitStatus::from_error(e.as_ref());
+    }
+    println!(
+        "校验清单已写入 {}：{} 个文件，共 {}",
+        output,
+        manifest.files.len(),
+        format_bytes(manifest.total_bytes)
+    );
+    ExitStatus::Success
+}
+
+/// 按校验清单核对收到的归档，可选地写出需要从服务器补齐的文件列表；存在不一致时返回失败
+fn run_verify_manifest(
+    config: &Config,
+    manifest_path: &str,
+    base_path: Option<&str>,
+    fetch_list: Option<&str>,
+) -> ExitCode {
+    let manifest = match ArchiveManifest::load(Path::new(manifest_path)) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            tracing::error!("读取校验清单失败 {}: {}", manifest_path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let root = base_path.unwrap_or(&config.download.base_path);
+    println!(
+        "核对 {} 个文件（{} {} 至 {}）",
+        manifest.files.len(),
+        manifest.product,
+        manifest.start,
+        manifest.end
+    );
+    let report = manifest.verify(Path::new(root));
+    report.print_report();
+
+    if let Some(fetch_list) = fetch_list {
+        let Some(product) = ProductType::find(&manifest.product, &config.products) else {
+            tracing::error!("未知的产品类型 {}，无法生成补齐列表", manifest.product);
+            return ExitCode::FAILURE;
+        };
+        let items = report.fetch_list(
+            &manifest,
+            &product,
+            config.remote_layout().as_ref(),
+            &config.download.areas,
+        );
+        let mut lines = String::new();
+        for item in &items {
+            match &item.remote_path {
+                Some(remote_path) => {
+                    lines.push_str(remote_path);
+                    lines.push('\n');
+                }
+                None => tracing::warn!("无法确定远程路径，未写入补齐列表: {}", item.path),
+            }
+        }
+        if let Err(e) = std::fs::write(fetch_list, lines) {
+            tracing::error!("写入补齐列表失败 {}: {}", fetch_list, e);
+            return ExitCode::FAILURE;
+        }
+        println!("补齐列表已写入 {}：{} 个文件", fetch_list, items.len());
+        let slots: BTreeSet<NaiveDateTime> = items.iter().filter_map(|item| item.slot).collect();
+        if let (Some(first), Some(last)) = (slots.first(), slots.last()) {
+            println!("缺口涉及 {} 个时次，{} 至 {}", slots.len(), first, last);
+        }
+    }
+
+    if report.all_passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// 按下载清单核对本地文件的校验和，按需写入补齐列表并将损坏的文件移入隔离区
+fn run_verify(config: &Config, fetch_list: Option<&str>, quarantine: bool) -> ExitCode {
+    if !config.storage.is_local() {
+        tracing::error!("使用 [storage] 存储后端时没有可核对的本地文件");
+        return ExitCode::FAILURE;
+    }
+    let storage = build_storage(config);
+    let report = match verify_archive(&storage) {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("读取下载清单失败: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    report.print_report();
+
+    if let Some(fetch_list) = fetch_list {
+        let paths = report.fetch_list();
+        let lines: String = paths.iter().map(|path| format!("{}\n", path)).collect();
+        if let Err(e) = std::fs::write(fetch_list, lines) {
+            tracing::error!("写入补齐列表失败 {}: {}", fetch_list, e);
+            return ExitCode::FAILURE;
+        }
+        println!("补齐列表已写入 {}：{} 个文件", fetch_list, paths.len());
+    }
+    if quarantine && !report.all_passed() {
+        match report.quarantine(&storage) {
+            Ok(moved) => println!(
+                "已将 {} 个损坏的文件移入隔离区，之后的运行将重新下载缺失与损坏的文件",
+                moved
+            ),
+            Err(e) => {
+                tracing::error!("移入隔离区失败: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if report.all_passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// 列出远程文件并与快照比较；快照不存在或指定了 `update` 时保存当前列表
+fn run_diff_remote(
+    config: &Config,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    snapshot_path: &str,
+    update: bool,
+) -> ExitStatus {
+    let current = match run_snapshot(config, start, end) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            tracing::error!("列出远程文件失败: {}", e);
+            return ExitStatus::from_error(e.as_ref());
+        }
+    };
+    println!(
+        "当前远程列表: {} 个目录，{} 个文件",
+        current.directories.len(),
+        current.files.len()
+    );
+
+    let path = Path::new(snapshot_path);
+    let save = if path.exists() {
+        let previous = match RemoteSnapshot::load(path) {
+            Ok(previous) => previous,
+            Err(e) => {
+                tracing::error!("读取快照失败 {}: {}", snapshot_path, e);
+                return ExitStatus::ConfigError;
+            }
+        };
+        if previous.product != current.product {
+            tracing::error!(
+                "快照属于产品 {}，与当前配置的产品 {} 不一致",
+                previous.product,
+                current.product
+            );
+            return ExitStatus::ConfigError;
+        }
+        println!(
+            "与 {} 的快照比较",
+            previous.taken_at.format("%Y-%m-%d %H:%M:%S")
+        );
+        previous.diff(&current).print_report();
+        update
+    } else {
+        true
+    };
+
+    if save {
+        if let Err(e) = current.save(path) {
+            tracing::error!("保存快照失败 {}: {}", snapshot_path, e);
+            return ExitStatus::from_error(e.as_ref());
+        }
+        println!("快照已保存到 {}", snapshot_path);
+    }
+    ExitStatus::Success
+}
+
+/// 检查入站暂存区并将完整的时次移入归档
+fn run_promote(config: &Config, runs: &[String], validate: bool) -> ExitStatus {
+    if config.download.spool_path.is_none() {
+        tracing::error!("未配置入站暂存区 download.spool_path");
+        return ExitStatus::ConfigError;
     }
+    let storage = build_storage(config);
+    match promote(&storage, runs, validate) {
+        Ok(report) => {
+            report.print_report();
+            if report.all_promoted() {
+                ExitStatus::Success
+            } else {
+                ExitStatus::PartialFailure
+            }
+        }
+        Err(e) => {
+            tracing::error!("移入归档失败: {}", e);
+            ExitStatus::Aborted
+        }
+    }
+}
+
+/// 列出并（确认后）删除超出保留期的数据
+fn run_prune(config: &Config, keep_days: Option<u64>, dry_run: bool, yes: bool) -> ExitStatus {
+    let Some(keep_days) = keep_days.or(config.retention.keep_days) else {
+        tracing::error!("未设置保留天数，请使用 --keep-days 或配置 retention.keep_days");
+        return ExitStatus::ConfigError;
+    };
+    let storage = build_storage(config);
+    let plan = match plan_prune(&storage, keep_days) {
+        Ok(plan) => plan,
+        Err(e) => {
+            tracing::error!("读取下载目录失败: {}", e);
+            return ExitStatus::Aborted;
+        }
+    };
+    plan.print_report();
+    if dry_run || plan.directories.is_empty() {
+        return ExitStatus::Success;
+    }
+    if !yes && !config.retention.auto_prune {
+        tracing::error!(
+            "未删除任何文件：确认删除请加 --yes，或在配置中设置 retention.auto_prune = true"
+        );
+        return ExitStatus::ConfigError;
+    }
+    match plan.execute(&storage) {
+        Ok(removed) => {
+            println!("已删除 {} 个文件", removed);
+            ExitStatus::Success
+        }
+        Err(e) => {
+            tracing::error!("删除过期数据失败: {}", e);
+            ExitStatus::Aborted
+        }
+    }
+}
+
+/// 列出并（确认后）将已下载的文件移到当前布局中的位置
+fn run_migrate_layout(config: &Config, yes: bool) -> ExitStatus {
+    if !config.storage.is_local() {
+        tracing::error!("使用 [storage] 存储后端时没有需要迁移的本地文件");
+        return ExitStatus::ConfigError;
+    }
+    let storage = build_storage(config);
+    let plan = match plan_migration(&storage) {
+        Ok(plan) => plan,
+        Err(e) => {
+            tracing::error!("读取下载清单失败: {}", e);
+            return ExitStatus::Aborted;
+        }
+    };
+    plan.print_report();
+    if plan.is_empty() {
+        return ExitStatus::Success;
+    }
+    if !yes {
+        println!("未移动任何文件：确认移动请加 --yes");
+        return ExitStatus::Success;
+    }
+    match plan.execute(&storage) {
+        Ok(moved) => {
+            println!("已移动 {} 个文件", moved);
+            ExitStatus::Success
+        }
+        Err(e) => {
+            tracing::error!("迁移本地布局失败: {}", e);
+            ExitStatus::Aborted
+        }
+    }
+}
+
+/// 核对最近 `hours` 小时、观测后已超过 `grace_minutes` 分钟的时次，存在不完整的时次时警告并上报
+fn run_audit(config: &Config, hours: u64, grace_minutes: u64) -> ExitStatus {
+    let (start, end) = audit_window(hours, grace_minutes);
+    let report = match audit_archive(config, &build_storage(config), start, end) {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("归档自检失败: {}", e);
+            return ExitStatus::Aborted;
+        }
+    };
+    report.print_report();
+    if report.is_complete() {
+        return ExitStatus::Success;
+    }
+    let message = format!(
+        "归档自检：{} 最近 {} 小时内有 {} 个时次在观测 {} 分钟后仍不完整",
+        report.product,
+        hours,
+        report.incomplete.len(),
+        grace_minutes
+    );
+    tracing::warn!("{}", message);
+    report_message(&message);
+    ExitStatus::PartialFailure
+}
+
+/// 核对 `start` 到 `end` 之间的本地归档并只下载缺少的文件；`dry_run` 时只列出需要下载的文件
+fn run_repair(
+    config: &Config,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    dry_run: bool,
+    override_quota: bool,
+) -> ExitStatus {
+    if !config.storage.is_local() {
+        tracing::error!("使用 [storage] 存储后端时无法核对本地归档");
+        return ExitStatus::ConfigError;
+    }
+    let product = match config.product() {
+        Ok(product) => product,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return ExitStatus::ConfigError;
+        }
+    };
+    if !dry_run && !quota_allows_run(config, override_quota) {
+        return ExitStatus::QuotaExceeded;
+    }
+    log_config(config);
+    let storage = build_storage(config);
+    if !local_layout_current(config, &storage) {
+        return ExitStatus::ConfigError;
+    }
+    let report = match audit_archive(config, &storage, start, end) {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("核对本地归档失败: {}", e);
+            return ExitStatus::Aborted;
+        }
+    };
+    report.print_report();
+    if report.is_complete() {
+        println!("本地归档完整，无需下载");
+        return ExitStatus::Success;
+    }
+    let options = build_options(config, &product);
+    if dry_run {
+        return download_missing(config, &report, &options, &storage, true);
+    }
+    let options = with_event_socket(config, options).with_shutdown(handle_interrupts());
+    download_missing(config, &report, &options, &storage, false)
+}
+
+/// 逐时次、波段与分段核对 `start` 到 `end` 之间的本地归档，按需将缺少的分段写入 CSV
+fn run_check(
+    config: &Config,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    output: Option<&str>,
+) -> ExitStatus {
+    let report = match audit_archive(config, &build_storage(config), start, end) {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("核对本地归档失败: {}", e);
+            return ExitStatus::Aborted;
+        }
+    };
+    report.print_report();
+    if let Some(output) = output {
+        if let Err(e) = report.write_csv(Path::new(output)) {
+            tracing::error!("写入核对结果失败 {}: {}", output, e);
+            return ExitStatus::Aborted;
+        }
+        println!("缺少的文件已写入 {}", output);
+    }
+    if report.is_complete() {
+        ExitStatus::Success
+    } else {
+        ExitStatus::PartialFailure
+    }
+}
+
+/// 估算时间段内的文件数与数据量；指定了 `sample_hours` 时按服务器上最近一段时间的文件大小估算
+fn run_estimate(
+    config: &Config,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    bands: Vec<String>,
+    segments: Vec<u32>,
+    areas: Vec<String>,
+    sample_hours: Option<u64>,
+) -> ExitStatus {
+    let product = match config.product() {
+        Ok(product) => product,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return ExitStatus::ConfigError;
+        }
+    };
+    if let Some(band) = bands.iter().find(|band| !himawari::is_valid_band(band)) {
+        tracing::error!("无效的波段: {}", band);
+        return ExitStatus::ConfigError;
+    }
+    if let Some(area) = areas.iter().find(|area| !himawari::is_valid_area(area)) {
+        tracing::error!("无效的观测区域: {}", area);
+        return ExitStatus::ConfigError;
+    }
+    if let Some(segment) = segments.iter().find(|segment| !(1..=10).contains(*segment)) {
+        tracing::error!("无效的分段编号: {}，全圆盘分段为 1-10", segment);
+        return ExitStatus::ConfigError;
+    }
+    let sizes = match sample_hours {
+        Some(hours) => match sample_file_sizes(config, &areas, hours) {
+            Ok(sampled) => FileSizes::Sampled(sampled),
+            Err(e) => {
+                tracing::error!("读取服务器上的文件大小失败: {}", e);
+                return ExitStatus::from_error(e.as_ref());
+            }
+        },
+        None => FileSizes::Typical,
+    };
+    let selection = EstimateSelection {
+        product,
+        bands: if bands.is_empty() {
+            config.download.bands.clone()
+        } else {
+            bands
+        },
+        segments,
+        areas,
+        start,
+        end,
+        files_per_slot: config.download.expected_files_per_slot.unwrap_or(1),
+    };
+    let estimate = match estimate_archive(&selection, &sizes) {
+        Ok(estimate) => estimate,
+        Err(e) => {
+            tracing::error!("估算失败: {}", e);
+            return ExitStatus::ConfigError;
+        }
+    };
+    estimate.print_report();
+    if let Ok(free) = fs4::available_space(&config.download.base_path) {
+        println!(
+            "下载目录 {} 所在磁盘可用 {}",
+            config.download.base_path,
+            format_bytes(free)
+        );
+        if free < estimate.bytes {
+            println!("可用空间不足以存放估算的数据量");
+        }
+    }
+    ExitStatus::Success
+}
+
+/// 列出下载历史中符合条件的记录
+#[cfg(feature = "sqlite")]
+fn run_history(config: &Config, query: &HistoryQuery) -> ExitStatus {
+    let base_path = Path::new(&config.download.base_path);
+    if !base_path.join(HISTORY_FILE).exists() {
+        tracing::error!(
+            "{} 中没有下载历史，请在配置中设置 download.history = true 后运行下载",
+            base_path.display()
+        );
+        return ExitStatus::ConfigError;
+    }
+    let entries = match DownloadHistory::open(base_path).and_then(|history| history.query(query)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::error!("查询下载历史失败: {}", e);
+            return ExitStatus::Aborted;
+        }
+    };
+    for entry in &entries {
+        let time = entry.recorded_at.format("%Y-%m-%d %H:%M:%S");
+        match entry.status {
+            HistoryStatus::Downloaded => println!(
+                "{}  {}  {}  {} -> {}",
+                time,
+                entry.status,
+                format_bytes(entry.size),
+                entry.remote_path,
+                entry.local_path.display()
+            ),
+            HistoryStatus::Failed => println!(
+                "{}  {}  {}: {}",
+                time,
+                entry.status,
+                entry.remote_path,
+                entry.error.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+    println!("共 {} 条记录", entries.len());
+    ExitStatus::Success
+}
+
+/// 删除（或重命名保留）某个波段在时间段内的本地文件后重新下载
+fn run_refetch(
+    config: &Config,
+    band: &str,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    keep_old: bool,
+    override_quota: bool,
+) -> ExitStatus {
+    if !himawari::is_valid_band(band) {
+        tracing::error!("{} 不是有效的 AHI 波段", band);
+        return ExitStatus::ConfigError;
+    }
+    let product = match config.product() {
+        Ok(product) if product.has_bands => product,
+        Ok(product) => {
+            tracing::error!("产品 {} 不按波段分文件，无法按波段重新下载", product.name);
+            return ExitStatus::ConfigError;
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return ExitStatus::ConfigError;
+        }
+    };
+    let mut download_time_list = match product.time_list(start, end) {
+        Ok(list) => list,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return ExitStatus::ConfigError;
+        }
+    };
+    if config.download.areas.iter().all(|area| area == "FLDK") {
+        skip_housekeeping_slots(&mut download_time_list);
+    }
+
+    if !quota_allows_run(config, override_quota) {
+        return ExitStatus::QuotaExceeded;
+    }
+
+    let storage = build_storage(config);
+    if !local_layout_current(config, &storage) {
+        return ExitStatus::ConfigError;
+    }
+    let mut retired = 0;
+    for datetime in &download_time_list {
+        for area in &config.download.areas {
+            match storage.retire_band(datetime, area, band, keep_old) {
+                Ok(files) => retired += files.len(),
+                Err(e) => {
+                    tracing::warn!("移除旧文件失败 {} {} {}: {}", datetime, area, band, e);
+                    return ExitStatus::Aborted;
+                }
+            }
+        }
+    }
+    tracing::info!(
+        "已{} {} 个旧文件，开始重新下载波段 {} 的 {} 个时次",
+        if keep_old {
+            "重命名保留"
+        } else {
+            "删除"
+        },
+        retired,
+        band,
+        download_time_list.len()
+    );
+
+    let result = download_single_band_streaming(
+        download_time_list,
+        band,
+        with_event_socket(config, build_options(config, &product))
+            .with_shutdown(handle_interrupts()),
+        &config.get_host_with_port(),
+        &config.server.username,
+        &config.server.credentials(),
+        storage,
+    );
+    match result {
+        Ok(stats) => ExitStatus::from_stats(&stats),
+        Err(e) => {
+            tracing::error!("重新下载失败: {}", e);
+            report_error(e.as_ref());
+            ExitStatus::from_error(e.as_ref())
+        }
+    }
+}
+
+/// 响应 Ctrl+C 与 SIGTERM：第一次不再开始新文件，第二次中止进行中的传输并保留临时文件
+fn handle_interrupts() -> Arc<Shutdown> {
+    let shutdown = Arc::new(Shutdown::new());
+    #[cfg(unix)]
+    if let Err(e) = install_signal_handlers(Arc::clone(&shutdown)) {
+        tracing::warn!("{}，Ctrl+C 将直接结束进程", e);
+    }
+    shutdown
+}
+
+/// 下载清单中记录的文件是否都在当前本地布局对应的位置。`organize_by_time`、`area_subdirectories` 或
+/// `filename_template` 改变后已有文件需要换位置，此时拒绝下载，须先运行 `migrate-layout` 移动文件或恢复原来的设置
+fn local_layout_current(config: &Config, storage: &LocalFileStorage) -> bool {
+    if !config.storage.is_local() {
+        return true;
+    }
+    let plan = match plan_migration(storage) {
+        Ok(plan) => plan,
+        Err(e) => {
+            tracing::error!("读取下载清单失败: {}", e);
+            return false;
+        }
+    };
+    let Some(first) = plan.moves.first() else {
+        return true;
+    };
+    tracing::error!(
+        "本地布局已改变：{} 个已下载的文件不在当前配置对应的位置（例如 {} -> {}）。\
+         请先运行 migrate-layout --yes 移动这些文件，或恢复原来的 organize_by_time、area_subdirectories 与 filename_template 设置",
+        plan.moves.len(),
+        first.from.display(),
+        first.to.display()
+    );
+    false
+}
+
+/// 按配置创建本地存储
+fn build_storage(config: &Config) -> LocalFileStorage {
+    let storage = LocalFileStorage::new(&config.download.base_path)
+        .with_time_organization(config.download.organize_by_time)
+        .with_area_subdirectories(config.download.area_subdirectories)
+        .with_extensions(config.server.files.extensions.clone())
+        .with_orphan_temp_age(Duration::from_secs(
+            config.download.orphan_temp_minutes * 60,
+        ))
+        .with_resume_incomplete(config.download.resume_incomplete)
+        .with_decompressed(config.download.decompress);
+    // 模板已在加载配置时校验
+    let storage = match config
+        .download
+        .filename_template
+        .as_deref()
+        .map(FilenameTemplate::parse)
+    {
+        Some(Ok(template)) => storage.with_filename_template(template),
+        _ => storage,
+    };
+    match (&config.download.staging_path, &config.download.spool_path) {
+        (Some(staging_path), _) => storage.with_staging(staging_path),
+        (None, Some(spool_path)) => storage
+            .with_spool(spool_path, &new_run_id())
+            .with_promote_after_run(config.download.auto_promote),
+        (None, None) => storage,
+    }
+}
+
+/// 按配置创建下载参数
+fn build_options(config: &Config, product: &ProductType) -> DownloadOptions {
+    let options =
+        DownloadOptions::new(config.download.num_threads)
+            .with_buffer_size(config.download.buffer_size_kb * 1024)
+            .with_band_priority(config.download.band_priority.clone())
+            .with_areas(config.download.areas.clone())
+            .with_resolutions(config.download.resolutions.clone())
+            .with_product(product.clone())
+            .with_layout(config.remote_layout())
+            .with_retry_budget(Arc::new(RetryBudget::new(
+                config.download.retry_budget,
+                Duration::from_secs(config.download.min_reconnect_interval_secs),
+            )))
+            .with_inter_file_delay(Duration::from_millis(config.download.inter_file_delay_ms))
+            .with_parallel_strategy(config.download.parallel_by)
+            .with_transport(config.server.transport)
+            .with_channels_per_session(config.server.channels_per_session)
+            .with_write_metadata(config.download.write_metadata)
+            .with_checksum_algorithm(config.download.checksum_algorithm)
+            .with_validate_downloads(config.download.validate_downloads)
+            .with_superseded_policy(config.download.on_superseded)
+            .with_duplicate_policy(config.download.on_duplicate)
+            .with_conflict_policy(config.download.on_conflict)
+            .with_file_results(config.download.report_file.is_some())
+            .with_progress(config.progress_reporting())
+            .with_unpublished_wait(Duration::from_secs(
+                config.download.unpublished_wait_minutes * 60,
+            ))
+            .with_destinations(
+                config
+                    .destinations
+                    .iter()
+                    .map(DestinationConfig::build)
+                    .collect(),
+            )
+            .with_pipeline(config.pipeline().unwrap_or_default())
+            .with_pipeline_threads(config.download.pipeline_threads.unwrap_or_else(|| {
+                thread::available_parallelism().map_or(1, |threads| threads.get())
+            }))
+            .with_final_retry(config.download.retry_failed_at_end);
+    #[cfg(feature = "sqlite")]
+    let options = if config.download.history {
+        match DownloadHistory::open(Path::new(&config.download.base_path)) {
+            Ok(history) => options.with_history(Arc::new(history)),
+            Err(e) => {
+                tracing::warn!("打开下载历史失败，本次运行不记录: {}", e);
+                options
+            }
+        }
+    } else {
+        options
+    };
+    let options = match PROGRESS_BARS.get() {
+        Some(bars) => options.with_progress_display(bars.clone()),
+        None => options,
+    };
+    let options = match STORAGE_BACKEND.get() {
+        Some(backend) => options.with_backend(backend.clone()),
+        None => options,
+    };
+    let options = match config.download.max_buffer_size_kb {
+        Some(kb) => options.with_max_buffer_size(kb * 1024),
+        None => options,
+    };
+    let options = options.with_buffer_budget(Arc::new(BufferBudget::new(
+        config
+            .download
+            .max_buffer_memory_mb
+            .map(|mb| mb * 1024 * 1024),
+    )));
+    let options = match config.download.max_bandwidth_mbps {
+        Some(mbps) => options.with_bandwidth_limit(Arc::new(RateLimiter::from_mbps(mbps))),
+        None => options,
+    };
+    let options = match config.download.max_thread_bandwidth_mbps {
+        Some(mbps) => options.with_thread_bandwidth_limit(mbps_to_bytes_per_sec(mbps)),
+        None => options,
+    };
+    let options = match config.download.max_run_duration_secs {
+        Some(secs) => options.with_max_run_duration(Duration::from_secs(secs)),
+        None => options,
+    };
+    let options = match config.download.status_interval_secs {
+        Some(secs) => options.with_status_interval(Duration::from_secs(secs)),
+        None => options,
+    };
+    let options = match config.download.stall_timeout_secs {
+        Some(secs) => options.with_stall_timeout(Duration::from_secs(secs)),
+        None => options,
+    };
+    let options = match config.download.max_file_duration_secs {
+        Some(secs) => options.with_max_file_duration(Duration::from_secs(secs)),
+        None => options,
+    };
+    match config.download.expected_files_per_slot {
+        Some(expected) => options.with_expected_files_per_slot(expected),
+        None => options,
+    }
+}
+
+/// 配置了 `event_socket` 时创建事件套接字，推送本次运行的事件
+fn with_event_socket(config: &Config, options: DownloadOptions) -> DownloadOptions {
+    let Some(path) = &config.download.event_socket else {
+        return options;
+    };
+    match EventSocket::bind(Path::new(path)) {
+        Ok(events) => {
+            tracing::info!("运行事件推送到 {}", path);
+            options.with_events(Arc::new(events))
+        }
+        Err(e) => {
+            tracing::warn!("创建事件套接字失败，本次运行不推送事件 {}: {}", path, e);
+            options
+        }
+    }
+}
+
+/// 配置了 `follow.metrics_listen` 时在该地址上提供 `/metrics` 端点，指标在各轮之间累计；
+/// 同一地址上的 `/shares` 用于查看与调整 `shares` 的权重
+fn with_metrics_endpoint(
+    config: &Config,
+    shares: Arc<JobShares>,
+    options: DownloadOptions,
+) -> DownloadOptions {
+    let Some(addr) = &config.follow.metrics_listen else {
+        return options;
+    };
+    let metrics = Arc::new(LiveMetrics::default());
+    match serve_metrics(addr, Arc::clone(&metrics), Some(shares)) {
+        Ok(()) => {
+            tracing::info!("Prometheus 指标端点: http://{}/metrics", addr);
+            options.with_live_metrics(metrics)
+        }
+        Err(e) => {
+            tracing::warn!("监听指标端点失败，不提供指标 {}: {}", addr, e);
+            options
+        }
+    }
+}
+
+/// 随机等待 0 到 `max_secs` 秒后再开始下载；等待时间计入运行时长上限。
+/// 期间收到中断请求时提前返回，返回是否完整等待
+fn wait_start_jitter(max_secs: u64, shutdown: &Shutdown) -> bool {
+    if max_secs == 0 {
+        return true;
+    }
+    // 标准库的 RandomState 每次创建使用随机的密钥，足以为各站点选取不同的等待时间
+    let random = RandomState::new().build_hasher().finish();
+    let jitter = Duration::from_millis(random % (max_secs * 1000 + 1));
+    tracing::info!("随机等待 {} 后开始下载", format_duration(jitter));
+    shutdown.sleep(jitter)
+}
+
+/// 本月下载量超过上限时，只有指定了 `--override-quota` 才允许开始新的运行
+fn quota_allows_run(config: &Config, override_quota: bool) -> bool {
+    if check_monthly_quota(config) {
+        return true;
+    }
+    if override_quota {
+        tracing::info!("已指定 --override-quota，忽略每月下载量上限继续运行");
+        true
+    } else {
+        tracing::error!("本月下载量已超过上限，拒绝开始新的运行；如需继续请使用 --override-quota");
+        false
+    }
+}
+
+/// 输出本月（UTC）下载量，接近上限时警告；返回是否仍在上限之内，未设置上限时总是返回 true
+fn check_monthly_quota(config: &Config) -> bool {
+    let Some(quota_gb) = config.download.monthly_quota_gb else {
+        return true;
+    };
+    let used = match Manifest::load(Path::new(&config.download.base_path)) {
+        Ok(manifest) => manifest.monthly_usage(Utc::now().date_naive()),
+        Err(e) => {
+            tracing::warn!("读取下载清单失败，无法统计本月下载量: {}", e);
+            return true;
+        }
+    };
+    let quota = quota_gb * 1024 * 1024 * 1024;
+    tracing::info!(
+        "本月下载量: {} / {}",
+        format_bytes(used),
+        format_bytes(quota)
+    );
+    if used >= quota {
+        tracing::warn!("本月下载量已达到上限 {}", format_bytes(quota));
+        false
+    } else {
+        if used as f64 >= quota as f64 * QUOTA_WARNING_RATIO {
+            tracing::warn!(
+                "本月下载量已达到上限的 {:.0}%",
+                used as f64 / quota as f64 * 100.0
+            );
+        }
+        true
+    }
+}
+
+/// 运行结果达到 `[email]` 的通知条件时发送邮件（需启用 `email` 特性）；发送失败只提示
+fn send_notification(
+    product: &str,
+    slots: Option<(NaiveDateTime, NaiveDateTime)>,
+    stats: &DownloadStats,
+) {
+    #[cfg(feature = "email")]
+    if let Some(notifier) = NOTIFIER.get() {
+        match notifier.notify_run(product, slots, stats) {
+            Ok(true) => tracing::info!("已发送邮件通知"),
+            Ok(false) => {}
+            Err(e) => tracing::warn!("发送邮件通知失败: {}", e),
+        }
+    }
+    #[cfg(not(feature = "email"))]
+    let _ = (product, slots, stats);
+}
+
+/// 上报导致运行失败的错误（需启用 `sentry` 特性并配置 DSN）
+fn report_error(error: &(dyn Error + 'static)) {
+    #[cfg(feature = "sentry")]
+    himawari_hsd_downloader::reporting::report_error(error);
+    #[cfg(not(feature = "sentry"))]
+    let _ = error;
+}
+
+/// 上报没有对应错误值的运行失败
+fn report_message(message: &str) {
+    #[cfg(feature = "sentry")]
+    himawari_hsd_downloader::reporting::report_message(message);
+    #[cfg(not(feature = "sentry"))]
+    let _ = message;
 }
@@ -0,0 +1,296 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+/// 下载清单文件名，保存在下载目录下
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// 本进程中正在使用的共享清单，按清单文件路径索引
+static SHARED: Mutex<BTreeMap<PathBuf, Weak<Mutex<Manifest>>>> = Mutex::new(BTreeMap::new());
+
+/// 已下载文件的记录
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManifestEntry {
+    pub local_path: PathBuf,
+    pub size: u64,
+    pub downloaded_at: DateTime<Utc>,
+    /// 下载完成时计算的 SHA-256，供 `verify` 命令核对；较早版本记录的文件没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// 发现远程文件被更新（重新处理或改名）时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupersededPolicy {
+    /// 只在清单中标记，不下载新版本
+    #[default]
+    Flag,
+    /// 下载新版本，旧文件重命名为 `<文件名>.superseded-<时间>` 保留
+    Keep,
+    /// 下载新版本，删除旧文件
+    Replace,
+}
+
+/// 被远程新版本取代的本地文件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SupersededRecord {
+    /// 本地文件对应的远程路径
+    pub remote_path: String,
+    pub local_path: PathBuf,
+    pub local_size: u64,
+    /// 新版本的远程路径，同名更新时与 `remote_path` 相同
+    pub new_remote_path: String,
+    pub new_size: u64,
+    pub detected_at: DateTime<Utc>,
+    pub policy: SupersededPolicy,
+}
+
+/// 大小正确但内容校验失败、被移入隔离区的文件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineRecord {
+    pub remote_path: String,
+    pub quarantine_path: PathBuf,
+    pub size: u64,
+    pub reason: String,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// 文件写入某个额外存储目的地的结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplicaStatus {
+    pub ok: bool,
+    /// 最近一次写入失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 服务器上不存在的远程目录
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MissingDirectory {
+    pub slot: NaiveDateTime,
+    pub first_detected_at: DateTime<Utc>,
+    pub last_checked_at: DateTime<Utc>,
+}
+
+/// 本地下载清单，记录已下载的文件、被取代的旧版本、被隔离的文件、永久缺失的目录、各存储目的地的写入状态、每日下载量
+/// 与中断时保留的临时文件，
+/// 保存为 `<base_path>/manifest.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(skip)]
+    path: PathBuf,
+    /// 按远程路径索引的已下载文件
+    #[serde(default)]
+    pub files: BTreeMap<String, ManifestEntry>,
+    #[serde(default)]
+    pub superseded: Vec<SupersededRecord>,
+    /// 移入隔离区的文件，按发现顺序排列
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quarantined: Vec<QuarantineRecord>,
+    /// 按远程目录索引的永久缺失目录
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub missing_directories: BTreeMap<String, MissingDirectory>,
+    /// 按远程路径、目的地名称索引的写入状态
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub replicas: BTreeMap<String, BTreeMap<String, ReplicaStatus>>,
+    /// 按日期（UTC）统计的下载字节数
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub usage: BTreeMap<NaiveDate, u64>,
+    /// 运行被中断时保留、下次运行断点续传的临时文件，启动清理时不删除
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub interrupted: BTreeSet<PathBuf>,
+}
+
+impl Manifest {
+    /// 读取下载目录中的清单，不存在时返回空清单
+    pub fn load(base_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = base_path.join(MANIFEST_FILE_NAME);
+        let mut manifest: Manifest = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            Manifest::default()
+        };
+        manifest.set_path(base_path);
+        Ok(manifest)
+    }
+
+    /// 读取下载目录中的清单供本进程中同时进行的运行共用：同一目录的清单已被其他运行打开时返回同一份，
+    /// 各自保存时都带有对方的记录，不会互相覆盖（例如持续跟踪的一轮下载与夜间修补同时进行）
+    pub fn open_shared(base_path: &Path) -> Result<Arc<Mutex<Self>>, Box<dyn std::error::Error>> {
+        let path = base_path.join(MANIFEST_FILE_NAME);
+        let mut shared = SHARED.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.retain(|_, manifest| manifest.strong_count() > 0);
+        if let Some(manifest) = shared.get(&path).and_then(Weak::upgrade) {
+            return Ok(manifest);
+        }
+        let manifest = Arc::new(Mutex::new(Self::load(base_path)?));
+        shared.insert(path, Arc::downgrade(&manifest));
+        Ok(manifest)
+    }
+
+    /// 设置清单所在的下载目录
+    pub fn set_path(&mut self, base_path: &Path) {
+        self.path = base_path.join(MANIFEST_FILE_NAME);
+    }
+
+    /// 写回清单文件（先写临时文件再替换）
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+
+    pub fn record_download(
+        &mut self,
+        remote_path: &str,
+        local_path: &Path,
+        size: u64,
+        sha256: Option<String>,
+    ) {
+        self.files.insert(
+            remote_path.to_string(),
+            ManifestEntry {
+                local_path: local_path.to_path_buf(),
+                size,
+                downloaded_at: Utc::now(),
+                sha256,
+            },
+        );
+    }
+
+    /// 记录运行被中断时保留的临时文件
+    pub fn record_interrupted(&mut self, temp_path: &Path) {
+        self.interrupted.insert(temp_path.to_path_buf());
+    }
+
+    /// 去掉已续传完成（或已被删除）的临时文件记录
+    pub fn clear_finished_interrupted(&mut self) {
+        self.interrupted.retain(|temp_path| temp_path.exists());
+    }
+
+    /// 移除本地文件已被删除的下载记录及其写入目的地的记录，返回移除的记录数
+    pub fn forget_files(&mut self, local_paths: &HashSet<PathBuf>) -> usize {
+        let forgotten: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(_, entry)| local_paths.contains(&entry.local_path))
+            .map(|(remote_path, _)| remote_path.clone())
+            .collect();
+        for remote_path in &forgotten {
+            self.files.remove(remote_path);
+            self.replicas.remove(remote_path);
+        }
+        forgotten.len()
+    }
+
+    /// 本地文件名与远程文件名不同（按文件名模板重写过）的文件：本地路径到远程文件名
+    pub fn renamed_files(&self) -> HashMap<PathBuf, String> {
+        self.files
+            .iter()
+            .filter_map(|(remote_path, entry)| {
+                let remote_name = remote_path.rsplit('/').next().unwrap_or(remote_path);
+                let local_name = entry.local_path.file_name()?.to_string_lossy();
+                (local_name != remote_name)
+                    .then(|| (entry.local_path.clone(), remote_name.to_string()))
+            })
+            .collect()
+    }
+
+    /// 已下载文件按远程文件名索引的本地位置。改变目录组织方式或文件名模板后，
+    /// 之前下载的文件不在按新布局生成的路径上，据此仍能找到
+    pub fn locations_by_filename(&self) -> HashMap<String, PathBuf> {
+        self.files
+            .iter()
+            .map(|(remote_path, entry)| {
+                let remote_name = remote_path.rsplit('/').next().unwrap_or(remote_path);
+                (remote_name.to_string(), entry.local_path.clone())
+            })
+            .collect()
+    }
+
+    /// 记录被取代的文件；同一版本重复发现时只保留第一次的记录，返回是否为新记录
+    pub fn record_superseded(&mut self, record: SupersededRecord) -> bool {
+        let known = self.superseded.iter().any(|existing| {
+            existing.remote_path == record.remote_path
+                && existing.new_remote_path == record.new_remote_path
+                && existing.new_size == record.new_size
+        });
+        if !known {
+            self.superseded.push(record);
+        }
+        !known
+    }
+
+    pub fn record_quarantine(&mut self, record: QuarantineRecord) {
+        self.quarantined.push(record);
+    }
+
+    /// 记录服务器上不存在的目录，已记录的只更新最近检查时间
+    pub fn record_missing_directory(&mut self, remote_dir: &str, slot: NaiveDateTime) {
+        let now = Utc::now();
+        self.missing_directories
+            .entry(remote_dir.to_string())
+            .and_modify(|missing| missing.last_checked_at = now)
+            .or_insert(MissingDirectory {
+                slot,
+                first_detected_at: now,
+                last_checked_at: now,
+            });
+    }
+
+    /// 目录已能列出时移除其缺失记录
+    pub fn clear_missing_directory(&mut self, remote_dir: &str) {
+        self.missing_directories.remove(remote_dir);
+    }
+
+    /// 记录文件写入某个目的地的结果
+    pub fn record_replica(
+        &mut self,
+        remote_path: &str,
+        destination: &str,
+        result: &Result<(), String>,
+    ) {
+        self.replicas
+            .entry(remote_path.to_string())
+            .or_default()
+            .insert(
+                destination.to_string(),
+                ReplicaStatus {
+                    ok: result.is_ok(),
+                    error: result.as_ref().err().cloned(),
+                    updated_at: Utc::now(),
+                },
+            );
+    }
+
+    /// 文件是否已成功写入某个目的地
+    pub fn replicated(&self, remote_path: &str, destination: &str) -> bool {
+        self.replicas
+            .get(remote_path)
+            .and_then(|replicas| replicas.get(destination))
+            .is_some_and(|status| status.ok)
+    }
+
+    /// 将下载的字节数计入当天（UTC）的下载量
+    pub fn record_usage(&mut self, bytes: u64) {
+        *self.usage.entry(Utc::now().date_naive()).or_default() += bytes;
+    }
+
+    /// `date` 所在自然月的下载字节数
+    pub fn monthly_usage(&self, date: NaiveDate) -> u64 {
+        self.usage
+            .iter()
+            .filter(|(day, _)| day.year() == date.year() && day.month() == date.month())
+            .map(|(_, bytes)| bytes)
+            .sum()
+    }
+}
@@ -0,0 +1,443 @@
+//! Prometheus 指标：运行结束时按 node_exporter textfile collector 的格式写入文件，
+//! 由 cron 启动的单次运行也能被监控，无需常驻进程；持续跟踪时也可以在 HTTP 端点 `/metrics` 上提供
+//! 进程启动以来的累计指标，同一端口上的 `/shares` 用于查看与调整同时运行的下载分享带宽与连接数的权重
+
+use crate::download::{DownloadStats, JobShares};
+use crate::events::RunEvent;
+use crate::himawari::HsdFilename;
+use chrono::{NaiveDateTime, Utc};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write as _};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// 指标名前缀
+const PREFIX: &str = "hsd_downloader";
+
+/// 一次运行中各产品（主产品与辅助文件）的下载统计与运行的退出码
+pub struct RunMetrics<'a> {
+    pub products: Vec<(String, &'a DownloadStats)>,
+    pub exit_code: u8,
+}
+
+impl RunMetrics<'_> {
+    /// 生成 Prometheus 文本格式的指标
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        gauge(
+            &mut out,
+            "last_run_timestamp_seconds",
+            "运行结束的 Unix 时间",
+            [(String::new(), Utc::now().timestamp() as f64)],
+        );
+        gauge(
+            &mut out,
+            "last_run_exit_code",
+            "运行的退出码，0 表示全部成功",
+            [(String::new(), f64::from(self.exit_code))],
+        );
+        self.per_product(
+            &mut out,
+            "last_run_duration_seconds",
+            "下载耗时",
+            |stats| stats.elapsed_time.as_secs_f64(),
+        );
+        let files = self.products.iter().flat_map(|(product, stats)| {
+            [
+                ("total", stats.total_files),
+                ("existing", stats.existing_files),
+                ("downloaded", stats.downloaded_files),
+                ("skipped", stats.skipped_files),
+                ("failed", stats.failed_files),
+            ]
+            .map(|(state, count)| {
+                (
+                    format!("product=\"{}\",state=\"{}\"", escape(product), state),
+                    count as f64,
+                )
+            })
+        });
+        gauge(&mut out, "files", "本次运行请求的文件数，按状态划分", files);
+        self.per_product(
+            &mut out,
+            "downloaded_bytes",
+            "本次运行下载的字节数",
+            |stats| stats.total_bytes as f64,
+        );
+        let failures = self.products.iter().flat_map(|(product, stats)| {
+            stats.failures.iter().map(move |(kind, count)| {
+                (
+                    format!("product=\"{}\",kind=\"{}\"", escape(product), kind.name()),
+                    *count as f64,
+                )
+            })
+        });
+        gauge(
+            &mut out,
+            "failures",
+            "下载失败的文件数，按原因划分",
+            failures,
+        );
+        self.per_product(
+            &mut out,
+            "completeness_ratio",
+            "本地已有文件占请求文件的比例",
+            completeness,
+        );
+        self.per_product(
+            &mut out,
+            "pending_slots",
+            "仍有文件未下载或目录尚未发布的时次数",
+            |stats| stats.pending_slots.len() as f64,
+        );
+        self.per_product(
+            &mut out,
+            "short_slots",
+            "文件数少于预期的时次数",
+            |stats| stats.short_slots.len() as f64,
+        );
+        self.per_product(
+            &mut out,
+            "post_processing_failures",
+            "后处理失败的文件数，原始数据已下载",
+            |stats| stats.post_processing.failed.len() as f64,
+        );
+        self.per_product(
+            &mut out,
+            "post_processing_duration_seconds",
+            "从第一个文件进入后处理队列到最后一个文件处理完的时长",
+            |stats| stats.post_processing.elapsed.as_secs_f64(),
+        );
+        self.per_product(
+            &mut out,
+            "post_processing_busy_seconds",
+            "各后处理线程处理文件的耗时之和",
+            |stats| stats.post_processing.busy_time.as_secs_f64(),
+        );
+        self.per_product(
+            &mut out,
+            "post_processing_queue_peak",
+            "后处理队列中同时等待的文件数的峰值",
+            |stats| stats.post_processing.peak_queue as f64,
+        );
+        // 本次运行没有下载完成的时次时不输出样本，避免把“没有数据”报告为零延迟
+        let latest_latency = self.products.iter().filter_map(|(product, stats)| {
+            let latest = stats.latest_slot_latency()?;
+            Some((
+                format!("product=\"{}\"", escape(product)),
+                latest.latency.as_secs_f64(),
+            ))
+        });
+        gauge(
+            &mut out,
+            "slot_latency_seconds",
+            "观测时间最新的已完成时次从观测到下载完成的延迟",
+            latest_latency,
+        );
+        let max_latency = self.products.iter().filter_map(|(product, stats)| {
+            Some((
+                format!("product=\"{}\"", escape(product)),
+                stats.max_slot_latency()?.as_secs_f64(),
+            ))
+        });
+        gauge(
+            &mut out,
+            "slot_latency_max_seconds",
+            "本次运行完成的时次中从观测到下载完成的最大延迟",
+            max_latency,
+        );
+        out
+    }
+
+    /// 写入指标文件：先写临时文件再改名，collector 不会读到写了一半的文件
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, self.render())?;
+        fs::rename(&temp_path, path)
+    }
+
+    fn per_product(
+        &self,
+        out: &mut String,
+        name: &str,
+        help: &str,
+        value: impl Fn(&DownloadStats) -> f64,
+    ) {
+        let samples = self
+            .products
+            .iter()
+            .map(|(product, stats)| (format!("product=\"{}\"", escape(product)), value(stats)));
+        gauge(out, name, help, samples);
+    }
+}
+
+/// 常驻进程的累计指标：由运行事件更新，跨多轮运行累计，供 `/metrics` 端点读取
+#[derive(Debug, Default)]
+pub struct LiveMetrics {
+    bytes: AtomicU64,
+    downloaded: AtomicU64,
+    skipped: AtomicU64,
+    failed: AtomicU64,
+    active: AtomicU64,
+    /// 各波段已下载的观测时间最新的文件的时间线开始时间
+    latest_by_band: Mutex<BTreeMap<String, NaiveDateTime>>,
+}
+
+impl LiveMetrics {
+    /// 按一个运行事件更新计数；重试前失败的传输同样计入失败数
+    pub fn record(&self, event: &RunEvent) {
+        match event {
+            RunEvent::FileStarted { .. } => {
+                self.active.fetch_add(1, Ordering::Relaxed);
+            }
+            RunEvent::FileFinished { file, bytes, .. } => {
+                self.finish_transfer();
+                if *bytes == 0 {
+                    self.skipped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                self.bytes.fetch_add(*bytes, Ordering::Relaxed);
+                self.downloaded.fetch_add(1, Ordering::Relaxed);
+                let filename = file.rsplit('/').next().unwrap_or(file);
+                if let Some(name) = HsdFilename::parse(filename) {
+                    let mut latest = self
+                        .latest_by_band
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    let entry = latest.entry(name.band).or_insert(name.datetime);
+                    *entry = (*entry).max(name.datetime);
+                }
+            }
+            RunEvent::FileFailed { .. } => {
+                self.finish_transfer();
+                self.failed.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_transfer(&self) {
+        let _ = self
+            .active
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |active| {
+                active.checked_sub(1)
+            });
+    }
+
+    /// 生成 Prometheus 文本格式的指标
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed) as f64;
+        counter(
+            &mut out,
+            "downloaded_bytes_total",
+            "进程启动以来下载的字节数",
+            [(String::new(), load(&self.bytes))],
+        );
+        counter(
+            &mut out,
+            "files_total",
+            "进程启动以来处理完的文件数，按结果划分",
+            [
+                ("downloaded", &self.downloaded),
+                ("skipped", &self.skipped),
+                ("failed", &self.failed),
+            ]
+            .map(|(state, value)| (format!("state=\"{}\"", state), load(value))),
+        );
+        gauge(
+            &mut out,
+            "active_transfers",
+            "正在下载的文件数",
+            [(String::new(), load(&self.active))],
+        );
+        // 还没有下载过的波段不输出样本，避免把“没有数据”报告为零延迟
+        let now = Utc::now().naive_utc();
+        let lag: Vec<(String, f64)> = self
+            .latest_by_band
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(band, latest)| {
+                (
+                    format!("band=\"{}\"", escape(band)),
+                    (now - *latest).num_seconds() as f64,
+                )
+            })
+            .collect();
+        gauge(
+            &mut out,
+            "band_lag_seconds",
+            "各波段已下载的最新观测时间距当前的秒数",
+            lag,
+        );
+        out
+    }
+}
+
+/// 连接建立后读取请求的超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 在后台线程中监听 `addr`，对 `GET /metrics` 返回 `metrics` 的当前值。给出 `shares` 时还提供控制接口：
+/// `GET /shares` 以 JSON 返回各下载的权重与当前份额，`PUT /shares/<名称>?weight=<权重>` 调整权重。
+/// 其他路径返回 404。请求逐个处理，只供抓取指标与运维调整，不作为通用 HTTP 服务，也不做认证
+pub fn serve_metrics(
+    addr: &str,
+    metrics: Arc<LiveMetrics>,
+    shares: Option<Arc<JobShares>>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Err(e) = answer_scrape(stream, &metrics, shares.as_deref()) {
+                tracing::debug!("响应指标请求失败: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn answer_scrape(
+    mut stream: TcpStream,
+    metrics: &LiveMetrics,
+    shares: Option<&JobShares>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // 读完请求头，客户端在收到响应前不会因连接被关闭而报错
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next(), parts.next());
+    let (path, query) = target
+        .map(|target| target.split_once('?').unwrap_or((target, "")))
+        .unwrap_or_default();
+    let (status, content_type, body) = match (method, path, shares) {
+        (Some("GET"), "/metrics", _) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            metrics.render(),
+        ),
+        (Some("GET"), "/shares", Some(shares)) => share_listing(shares),
+        (Some("PUT"), path, Some(shares)) if path.starts_with("/shares/") => {
+            let name = &path["/shares/".len()..];
+            let weight = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("weight="))
+                .and_then(|weight| weight.parse::<u32>().ok());
+            match weight {
+                None => (
+                    "400 Bad Request",
+                    "text/plain; charset=utf-8",
+                    "weight must be a non-negative integer\n".to_string(),
+                ),
+                Some(weight) if shares.set_weight(name, weight) => {
+                    tracing::info!("{} 的权重已调整为 {}", name, weight);
+                    share_listing(shares)
+                }
+                Some(_) => not_found(),
+            }
+        }
+        _ => not_found(),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// 各下载的权重与当前份额
+fn share_listing(shares: &JobShares) -> (&'static str, &'static str, String) {
+    let body = serde_json::to_string_pretty(&shares.snapshot()).unwrap_or_default() + "\n";
+    ("200 OK", "application/json", body)
+}
+
+fn not_found() -> (&'static str, &'static str, String) {
+    (
+        "404 Not Found",
+        "text/plain; charset=utf-8",
+        "not found\n".to_string(),
+    )
+}
+
+/// 本地已有文件（已存在、已下载与跳过的）占请求文件的比例，没有请求文件时为 1
+fn completeness(stats: &DownloadStats) -> f64 {
+    if stats.total_files == 0 {
+        return 1.0;
+    }
+    let present = stats.existing_files + stats.downloaded_files + stats.skipped_files;
+    present as f64 / stats.total_files as f64
+}
+
+/// 写入一个 gauge 的说明、类型与各样本，标签为空的样本不带标签
+fn gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    metric(out, "gauge", name, help, samples);
+}
+
+/// 写入一个只增不减的 counter
+fn counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    metric(out, "counter", name, help, samples);
+}
+
+fn metric(
+    out: &mut String,
+    kind: &str,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{}_{} {}", PREFIX, name, value);
+        } else {
+            let _ = writeln!(out, "{}_{}{{{}}} {}", PREFIX, name, labels, value);
+        }
+    }
+}
+
+/// 转义标签值中的反斜杠、双引号与换行
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
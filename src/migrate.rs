@@ -0,0 +1,160 @@
+//! 本地布局迁移：改变 `organize_by_time`、观测区域子目录或文件名模板后，将下载清单中记录的文件移到按新布局
+//! 生成的位置并更新清单，之后的运行按新布局即可找到这些文件，不再重新下载
+
+use crate::manifest::Manifest;
+use crate::storage::{LocalFileStorage, decompressed_path, promote_file};
+use crate::units::format_bytes;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// 一个需要移动的文件
+#[derive(Debug, Clone)]
+pub struct LayoutMove {
+    pub remote_path: String,
+    /// 清单中记录的当前位置
+    pub from: PathBuf,
+    /// 按当前布局生成的位置
+    pub to: PathBuf,
+    pub size: u64,
+}
+
+/// 按当前布局计算出的迁移计划
+#[derive(Debug, Clone, Default)]
+pub struct MigrationPlan {
+    pub moves: Vec<LayoutMove>,
+    /// 新位置已有其他文件而不移动的文件
+    pub conflicts: Vec<LayoutMove>,
+    /// 文件已在新位置、只需更新清单记录的远程路径与新位置
+    pub relinked: Vec<(String, PathBuf)>,
+    /// 清单记录的位置与新位置都没有文件的远程路径
+    pub missing: Vec<String>,
+}
+
+impl MigrationPlan {
+    pub fn total_bytes(&self) -> u64 {
+        self.moves.iter().map(|file| file.size).sum()
+    }
+
+    /// 是否没有需要移动的文件或更新的记录
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty() && self.relinked.is_empty()
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "需要移动 {} 个文件，共 {}；{} 条记录只需更新清单",
+            self.moves.len(),
+            format_bytes(self.total_bytes()),
+            self.relinked.len()
+        );
+        // 按原目录与新目录分组列出
+        let mut directories: BTreeMap<(PathBuf, PathBuf), (usize, u64)> = BTreeMap::new();
+        for file in &self.moves {
+            let key = (
+                file.from.parent().unwrap_or(&file.from).to_path_buf(),
+                file.to.parent().unwrap_or(&file.to).to_path_buf(),
+            );
+            let group = directories.entry(key).or_default();
+            group.0 += 1;
+            group.1 += file.size;
+        }
+        for ((from, to), (files, bytes)) in directories {
+            println!(
+                "  {} -> {}  {} 个文件, {}",
+                from.display(),
+                to.display(),
+                files,
+                format_bytes(bytes)
+            );
+        }
+        if !self.conflicts.is_empty() {
+            println!(
+                "新位置已有其他文件，不移动 {} 个文件:",
+                self.conflicts.len()
+            );
+            for file in &self.conflicts {
+                println!("  {} -> {}", file.from.display(), file.to.display());
+            }
+        }
+        if !self.missing.is_empty() {
+            println!("{} 条清单记录的文件已不存在，不做处理", self.missing.len());
+        }
+    }
+
+    /// 移动计划中的文件（连同来源信息文件），更新清单中的位置并清理留下的空目录，返回移动的文件数
+    pub fn execute(&self, storage: &LocalFileStorage) -> Result<usize, Box<dyn Error>> {
+        let mut manifest = Manifest::load(&storage.base_path)?;
+        let mut moved = 0;
+        for file in &self.moves {
+            if let Err(e) = promote_file(&file.from, &file.to) {
+                tracing::warn!(
+                    "移动文件失败 {} -> {}: {}",
+                    file.from.display(),
+                    file.to.display(),
+                    e
+                );
+                continue;
+            }
+            if let Some(entry) = manifest.files.get_mut(&file.remote_path) {
+                entry.local_path = file.to.clone();
+            }
+            moved += 1;
+        }
+        for (remote_path, local_path) in &self.relinked {
+            if let Some(entry) = manifest.files.get_mut(remote_path) {
+                entry.local_path = local_path.clone();
+            }
+        }
+        manifest.save()?;
+        storage.remove_empty_directories()?;
+        Ok(moved)
+    }
+}
+
+/// 比较下载清单中记录的位置与按当前布局生成的位置，列出需要移动的文件。
+///
+/// 只处理清单中有记录的文件；暂存目录、入站暂存区与未完成的下载不在此列
+pub fn plan_migration(storage: &LocalFileStorage) -> Result<MigrationPlan, Box<dyn Error>> {
+    let manifest = Manifest::load(&storage.base_path)?;
+    let mut plan = MigrationPlan::default();
+    for (remote_path, entry) in &manifest.files {
+        let mut source = entry.local_path.clone();
+        let mut target = storage.generate_local_path(remote_path);
+        // 下载后解压并删除了压缩文件的，移动解压后的文件
+        if storage.decompressed
+            && !source.exists()
+            && let (Some(decompressed), Some(decompressed_target)) =
+                (decompressed_path(&source), decompressed_path(&target))
+            && decompressed.exists()
+        {
+            source = decompressed;
+            target = decompressed_target;
+        }
+        if source == target {
+            continue;
+        }
+        let size = fs::metadata(&source)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+        match (size, target.exists()) {
+            (Some(size), false) => plan.moves.push(LayoutMove {
+                remote_path: remote_path.clone(),
+                from: source,
+                to: target,
+                size,
+            }),
+            (Some(size), true) => plan.conflicts.push(LayoutMove {
+                remote_path: remote_path.clone(),
+                from: source,
+                to: target,
+                size,
+            }),
+            (None, true) => plan.relinked.push((remote_path.clone(), target)),
+            (None, false) => plan.missing.push(remote_path.clone()),
+        }
+    }
+    Ok(plan)
+}
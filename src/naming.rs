@@ -0,0 +1,146 @@
+//! 本地文件名模板：保存时按模板重写远程文件名，例如去掉 `HS_` 前缀、扩展名改为小写，
+//! 或改用合作方的命名方式
+
+use crate::himawari::HsdFilename;
+use std::error::Error;
+
+/// 只能用于 HSD 文件名的占位符；非 HSD 文件使用这些占位符时保持原文件名
+const HSD_FIELDS: &[&str] = &[
+    "satellite",
+    "YYYY",
+    "MM",
+    "DD",
+    "hh",
+    "mm",
+    "band",
+    "area",
+    "resolution",
+    "segment",
+    "total",
+];
+
+/// 任何文件名都可使用的占位符
+const GENERIC_FIELDS: &[&str] = &["filename", "stem", "ext"];
+
+/// 模板中的一段：原样文字，或带可选大小写转换的占位符
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field { name: String, case: Case },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Case {
+    Keep,
+    Lower,
+    Upper,
+}
+
+/// 本地文件名模板，例如 `{satellite}_{YYYY}{MM}{DD}_{hh}{mm}_{band}_{area}_S{segment}{total}{ext:lower}`
+///
+/// 占位符：`{filename}` 远程文件名，`{stem}` 第一个 `.` 之前的部分，`{ext}` 自第一个 `.` 起的扩展名；
+/// HSD 文件另有 `{satellite}` `{YYYY}` `{MM}` `{DD}` `{hh}` `{mm}` `{band}` `{area}` `{resolution}`
+/// `{segment}` `{total}`。占位符后加 `:lower` 或 `:upper` 转换大小写。
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilenameTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+impl FilenameTemplate {
+    pub fn parse(template: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("文件名模板中的 {{ 没有对应的 }}: {}", template))?;
+            let field = &rest[open + 1..open + close];
+            let (name, case) = match field.split_once(':') {
+                Some((name, "lower")) => (name, Case::Lower),
+                Some((name, "upper")) => (name, Case::Upper),
+                Some((_, modifier)) => {
+                    return Err(format!("文件名模板中未知的转换 :{}", modifier).into());
+                }
+                None => (field, Case::Keep),
+            };
+            if !GENERIC_FIELDS.contains(&name) && !HSD_FIELDS.contains(&name) {
+                return Err(format!("文件名模板中未知的占位符 {{{}}}", name).into());
+            }
+            parts.push(Part::Field {
+                name: name.to_string(),
+                case,
+            });
+            rest = &rest[open + close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        if parts.is_empty() {
+            return Err("文件名模板不能为空".into());
+        }
+        if parts.iter().any(|part| match part {
+            Part::Literal(text) => text.contains(['/', '\\', '}']),
+            Part::Field { .. } => false,
+        }) {
+            return Err(format!("文件名模板不能包含路径分隔符或多余的 }}: {}", template).into());
+        }
+        Ok(Self {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// 按模板重写文件名；模板使用 HSD 占位符而文件名不是 HSD 格式时保持原文件名
+    pub fn apply(&self, filename: &str) -> String {
+        let hsd = HsdFilename::parse(filename);
+        let (stem, ext) = filename
+            .find('.')
+            .map_or((filename, ""), |dot| filename.split_at(dot));
+        let mut renamed = String::new();
+        for part in &self.parts {
+            let (name, case) = match part {
+                Part::Literal(text) => {
+                    renamed.push_str(text);
+                    continue;
+                }
+                Part::Field { name, case } => (name.as_str(), *case),
+            };
+            let value = match (name, &hsd) {
+                ("filename", _) => filename.to_string(),
+                ("stem", _) => stem.to_string(),
+                ("ext", _) => ext.to_string(),
+                ("satellite", Some(hsd)) => hsd.satellite.clone(),
+                ("YYYY", Some(hsd)) => hsd.datetime.format("%Y").to_string(),
+                ("MM", Some(hsd)) => hsd.datetime.format("%m").to_string(),
+                ("DD", Some(hsd)) => hsd.datetime.format("%d").to_string(),
+                ("hh", Some(hsd)) => hsd.datetime.format("%H").to_string(),
+                ("mm", Some(hsd)) => hsd.datetime.format("%M").to_string(),
+                ("band", Some(hsd)) => hsd.band.clone(),
+                ("area", Some(hsd)) => hsd.area.clone(),
+                ("resolution", Some(hsd)) => hsd.resolution.clone(),
+                ("segment", Some(hsd)) => format!("{:02}", hsd.segment),
+                ("total", Some(hsd)) => format!("{:02}", hsd.total_segments),
+                _ => return filename.to_string(),
+            };
+            match case {
+                Case::Keep => renamed.push_str(&value),
+                Case::Lower => renamed.push_str(&value.to_lowercase()),
+                Case::Upper => renamed.push_str(&value.to_uppercase()),
+            }
+        }
+        if renamed.is_empty() {
+            filename.to_string()
+        } else {
+            renamed
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! 邮件通知（`email` 特性）：一次运行下载失败的文件过多或预期的时次没有文件时通过 SMTP 发信，
+//! 无人值守的归档机器出现问题时及时通知值守人员
+
+use crate::config::{EmailConfig, SmtpSecurity};
+use crate::download::DownloadStats;
+use chrono::NaiveDateTime;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use std::error::Error;
+use std::fmt::Write;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// 邮件中最多列出的失败文件数
+const MAX_LISTED_FILES: usize = 20;
+
+/// 连接 SMTP 服务器与收发命令的超时
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 一次运行中需要通知的问题
+#[derive(Debug, Clone, Default)]
+pub struct RunAlert {
+    /// 失败的文件数，未超过阈值时为空
+    pub failed_files: Option<usize>,
+    /// 目录已列出但没有匹配文件、或目录过了发布等待期仍不存在的时次
+    pub empty_slots: Vec<NaiveDateTime>,
+}
+
+impl RunAlert {
+    /// 按通知条件检查一次运行的统计，没有需要通知的问题时返回 `None`
+    pub fn check(config: &EmailConfig, stats: &DownloadStats) -> Option<Self> {
+        let failed_files =
+            (stats.failed_files > config.failed_files_threshold).then_some(stats.failed_files);
+        let mut empty_slots = Vec::new();
+        if config.notify_empty_slots {
+            empty_slots.extend(stats.discovery.empty_slots.iter().copied());
+            empty_slots.extend(
+                stats
+                    .discovery
+                    .missing_directories
+                    .iter()
+                    .map(|(_, slot)| *slot),
+            );
+            empty_slots.sort();
+            empty_slots.dedup();
+        }
+        (failed_files.is_some() || !empty_slots.is_empty()).then_some(Self {
+            failed_files,
+            empty_slots,
+        })
+    }
+
+    pub fn subject(&self, product: &str) -> String {
+        let mut problems = Vec::new();
+        if let Some(failed_files) = self.failed_files {
+            problems.push(format!("{} 个文件下载失败", failed_files));
+        }
+        if !self.empty_slots.is_empty() {
+            problems.push(format!("{} 个时次没有文件", self.empty_slots.len()));
+        }
+        format!("[HSD 下载] {}: {}", product, problems.join("，"))
+    }
+
+    /// 邮件正文：运行概况、失败原因与部分失败文件、没有文件的时次
+    pub fn body(
+        &self,
+        product: &str,
+        slots: Option<(NaiveDateTime, NaiveDateTime)>,
+        stats: &DownloadStats,
+    ) -> String {
+        let mut body = String::new();
+        let _ = writeln!(body, "产品: {}", product);
+        if let Some((first, last)) = slots {
+            let _ = writeln!(
+                body,
+                "时次: {} 至 {}",
+                first.format("%Y-%m-%d %H:%M"),
+                last.format("%Y-%m-%d %H:%M")
+            );
+        }
+        let _ = writeln!(
+            body,
+            "文件: 共 {} 个，下载 {} 个，已存在 {} 个，失败 {} 个",
+            stats.total_files, stats.downloaded_files, stats.existing_files, stats.failed_files
+        );
+        if self.failed_files.is_some() {
+            let grouped: Vec<String> = stats
+                .failures
+                .iter()
+                .map(|(kind, count)| format!("{} × {}", count, kind))
+                .collect();
+            let _ = writeln!(body, "\n失败原因: {}", grouped.join(", "));
+            for file in stats.failed.iter().take(MAX_LISTED_FILES) {
+                let _ = writeln!(
+                    body,
+                    "  {} ({}): {}",
+                    file.remote_path, file.kind, file.error
+                );
+            }
+            if stats.failed.len() > MAX_LISTED_FILES {
+                let _ = writeln!(
+                    body,
+                    "  …另有 {} 个文件",
+                    stats.failed.len() - MAX_LISTED_FILES
+                );
+            }
+        }
+        if !self.empty_slots.is_empty() {
+            let _ = writeln!(body, "\n没有文件的时次:");
+            for slot in &self.empty_slots {
+                let _ = writeln!(body, "  {}", slot.format("%Y-%m-%d %H:%M"));
+            }
+        }
+        body
+    }
+}
+
+/// 按 `[email]` 配置发送通知，两封之间至少间隔 `min_interval_minutes` 分钟
+#[derive(Debug)]
+pub struct Notifier {
+    config: EmailConfig,
+    last_sent: Mutex<Option<Instant>>,
+}
+
+impl Notifier {
+    /// 未配置 SMTP 服务器时返回 `None`
+    pub fn new(config: &EmailConfig) -> Option<Self> {
+        (!config.is_disabled()).then(|| Self {
+            config: config.clone(),
+            last_sent: Mutex::new(None),
+        })
+    }
+
+    /// 一次运行的结果达到通知条件、且距上一封通知已超过最短间隔时发信，返回是否发送
+    pub fn notify_run(
+        &self,
+        product: &str,
+        slots: Option<(NaiveDateTime, NaiveDateTime)>,
+        stats: &DownloadStats,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(alert) = RunAlert::check(&self.config, stats) else {
+            return Ok(false);
+        };
+        // 在锁内判断间隔并占下本次发送，发信前释放锁，SMTP 阻塞时不会卡住其他调用
+        let min_interval = Duration::from_secs(self.config.min_interval_minutes * 60);
+        let (previous, reserved) = {
+            let mut last_sent = self
+                .last_sent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if last_sent.is_some_and(|sent| sent.elapsed() < min_interval) {
+                tracing::info!(
+                    "距上一封通知不足 {} 分钟，本次不发送: {}",
+                    self.config.min_interval_minutes,
+                    alert.subject(product)
+                );
+                return Ok(false);
+            }
+            let reserved = Instant::now();
+            (last_sent.replace(reserved), reserved)
+        };
+        if let Err(e) = self.send(&alert.subject(product), &alert.body(product, slots, stats)) {
+            // 发送失败时交还占下的发送机会，下一次运行仍可通知
+            let mut last_sent = self
+                .last_sent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if *last_sent == Some(reserved) {
+                *last_sent = previous;
+            }
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// 向所有收件人发送一封纯文本邮件
+    pub fn send(&self, subject: &str, body: &str) -> Result<(), Box<dyn Error>> {
+        let config = &self.config;
+        let host = config.smtp_host.as_deref().unwrap_or_default();
+        let mut message = Message::builder()
+            .from(
+                config
+                    .from
+                    .parse()
+                    .map_err(|e| format!("发件人地址无效 {}: {}", config.from, e))?,
+            )
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &config.to {
+            message = message.to(to
+                .parse()
+                .map_err(|e| format!("收件人地址无效 {}: {}", to, e))?);
+        }
+        let message = message.body(body.to_string())?;
+
+        let mut transport = match config.security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(host)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(host)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(host),
+        }
+        .port(
+            config
+                .smtp_port
+                .unwrap_or_else(|| config.security.default_port()),
+        )
+        .timeout(Some(SMTP_TIMEOUT));
+        if let Some(username) = &config.username {
+            transport = transport.credentials(Credentials::new(
+                username.clone(),
+                config.password.clone().unwrap_or_default(),
+            ));
+        }
+        transport
+            .build()
+            .send(&message)
+            .map_err(|e| format!("通过 {} 发送邮件失败: {}", host, e))?;
+        Ok(())
+    }
+}
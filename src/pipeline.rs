@@ -0,0 +1,145 @@
+//! 下载后处理流水线：文件进入下载目录后，按配置的顺序依次执行各处理步骤，每一步处理上一步产生的文件
+
+use crate::destination::{shell, shell_quote};
+use bzip2::read::MultiBzDecoder;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// 内置处理步骤的名称
+pub const BUILTIN_STAGES: [&str; 1] = ["decompress"];
+
+/// 处理步骤
+///
+/// 新的处理步骤只需实现该 trait 并通过 `DownloadOptions::with_pipeline` 传入。
+pub trait PipelineStage: Debug + Send + Sync {
+    /// 步骤名称，用于日志
+    fn name(&self) -> &str;
+
+    /// 处理一个文件，返回交给下一步处理的文件
+    fn process(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>>;
+}
+
+/// 解压 bzip2 压缩的文件：`<文件名>.bz2` 解压为同一目录下的 `<文件名>`；其他文件原样交给下一步
+///
+/// 默认保留压缩文件（下次运行据此判断文件已存在）。`remove_archive` 为 true 时，数据流完整解码、
+/// 解压的文件已写入磁盘后删除压缩文件，下次运行按解压后的文件判断（见 `LocalFileStorage::with_decompressed`）
+#[derive(Debug, Clone, Default)]
+pub struct Decompress {
+    pub remove_archive: bool,
+}
+
+impl PipelineStage for Decompress {
+    fn name(&self) -> &str {
+        "decompress"
+    }
+
+    fn process(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let Some(filename) = path.file_name().map(|name| name.to_string_lossy()) else {
+            return Ok(path.to_path_buf());
+        };
+        let Some(decompressed) = filename.strip_suffix(".bz2") else {
+            return Ok(path.to_path_buf());
+        };
+        let target = path.with_file_name(decompressed);
+        // 先写临时文件再改名，不会留下解压了一半的文件
+        let temp = path.with_file_name(format!("{}.decompressing", decompressed));
+        let result = File::create(&temp).and_then(|mut output| {
+            let mut decoder = MultiBzDecoder::new(BufReader::new(File::open(path)?));
+            io::copy(&mut decoder, &mut output)?;
+            output.sync_all()
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp);
+            return Err(format!("解压失败: {}", e).into());
+        }
+        fs::rename(&temp, &target)?;
+        if self.remove_archive {
+            fs::remove_file(path)?;
+        }
+        Ok(target)
+    }
+}
+
+/// 外部命令，例如 `python convert.py {path}`
+///
+/// `{path}` 替换为当前文件的路径，命令退出码非 0 视为失败；文件原样交给下一步。
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub command: String,
+}
+
+impl PipelineStage for Hook {
+    fn name(&self) -> &str {
+        "hook"
+    }
+
+    fn process(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let command = self
+            .command
+            .replace("{path}", &shell_quote(&path.to_string_lossy()));
+        let output = shell(&command).output()?;
+        if output.status.success() {
+            Ok(path.to_path_buf())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(format!("命令退出状态 {}: {}", output.status, stderr.trim()).into())
+        }
+    }
+}
+
+/// 配置文件中的处理步骤：内置步骤写名称，例如 `"decompress"`；外部命令写作 `{ hook = "命令" }`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum StageConfig {
+    Builtin(String),
+    Hook { hook: String },
+}
+
+impl StageConfig {
+    pub fn build(&self) -> Result<Arc<dyn PipelineStage>, String> {
+        match self {
+            StageConfig::Builtin(name) => match name.as_str() {
+                "decompress" => Ok(Arc::new(Decompress::default())),
+                _ => Err(format!(
+                    "未知的处理步骤 {}，内置步骤有: {}；外部命令写作 {{ hook = \"命令\" }}",
+                    name,
+                    BUILTIN_STAGES.join(", ")
+                )),
+            },
+            StageConfig::Hook { hook } => Ok(Arc::new(Hook {
+                command: hook.clone(),
+            })),
+        }
+    }
+}
+
+/// 失败的处理步骤
+#[derive(Debug, Clone)]
+pub struct StageFailure {
+    /// 步骤名称
+    pub stage: String,
+    pub error: String,
+}
+
+impl fmt::Display for StageFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.stage, self.error)
+    }
+}
+
+/// 对 `path` 依次执行各步骤；某一步失败时不再执行后续步骤，返回失败的步骤
+pub fn run_pipeline(stages: &[Arc<dyn PipelineStage>], path: &Path) -> Result<(), StageFailure> {
+    let mut current = path.to_path_buf();
+    for stage in stages {
+        current = stage.process(&current).map_err(|e| StageFailure {
+            stage: stage.name().to_string(),
+            error: e.to_string(),
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+//! 常用类型的统一导入：`use himawari_hsd_downloader::prelude::*;`
+
+pub use crate::config::Config;
+pub use crate::destination::{Destination, DestinationConfig};
+pub use crate::download::{
+    DownloadOptions, DownloadStats, ParallelStrategy, download_all_bands_streaming,
+    download_auxiliary_files, download_fldk_files_streaming, download_single_band_streaming,
+    download_visible_bands_streaming,
+};
+pub use crate::failure::FailureKind;
+pub use crate::layout::{LayoutConfig, RemoteLayout};
+pub use crate::product::ProductType;
+pub use crate::retry_budget::RetryBudget;
+pub use crate::storage::{BandCompletenessReport, LocalFileStorage};
+pub use crate::timelist::get_download_time_list;
+pub use crate::transport::{RemoteConnection, Transport};
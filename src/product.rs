@@ -0,0 +1,172 @@
+use crate::himawari;
+use crate::timelist::time_list_aligned;
+use chrono::{Duration, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// 数据产品类型：远程目录模板、文件名模式与观测间隔
+///
+/// 模板中可使用占位符 `{YYYY}` `{MM}` `{DD}` `{hh}` `{mm}`（观测时间）与 `{area}`（观测区域），
+/// 文件名模式另外支持通配符 `*` 与 `?`。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProductType {
+    pub name: String,
+    pub path_template: String,
+    pub filename_pattern: String,
+    pub cadence_minutes: i64,
+    /// 时次相对当天 0 点的偏移（分钟），例如每 30 分钟一次、在整点与半点后 5 分钟的镜像产品为 5
+    #[serde(default)]
+    pub offset_minutes: i64,
+    /// 文件是否按波段拆分（NetCDF 格点产品一个文件包含所有波段）
+    #[serde(default)]
+    pub has_bands: bool,
+}
+
+impl ProductType {
+    /// HSD 标准数据
+    pub fn hsd() -> Self {
+        Self {
+            name: "hsd".to_string(),
+            path_template: "/jma/hsd/{YYYY}{MM}/{DD}/{hh}/".to_string(),
+            filename_pattern: "HS_*_{YYYY}{MM}{DD}_{hh}{mm}_*_{area}_*.DAT.bz2".to_string(),
+            cadence_minutes: himawari::TIMELINE_MINUTES,
+            offset_minutes: 0,
+            has_bands: true,
+        }
+    }
+
+    /// AHI L1 格点 NetCDF 产品
+    pub fn netcdf_l1() -> Self {
+        Self {
+            name: "netcdf_l1".to_string(),
+            path_template: "/jma/netcdf/{YYYY}{MM}/{DD}/".to_string(),
+            filename_pattern: "NC_H*_{YYYY}{MM}{DD}_{hh}{mm}_R21_{area}.*.nc".to_string(),
+            cadence_minutes: 10,
+            offset_minutes: 0,
+            has_bands: false,
+        }
+    }
+
+    /// L2 云属性产品
+    pub fn l2_cloud() -> Self {
+        Self {
+            name: "l2_cloud".to_string(),
+            path_template: "/pub/himawari/L2/CLP/010/{YYYY}{MM}/{DD}/{hh}/".to_string(),
+            filename_pattern: "NC_H*_{YYYY}{MM}{DD}_{hh}{mm}_L2CLP010_{area}.*.nc".to_string(),
+            cadence_minutes: 10,
+            offset_minutes: 0,
+            has_bands: false,
+        }
+    }
+
+    /// 内置产品类型
+    pub fn presets() -> Vec<Self> {
+        vec![Self::hsd(), Self::netcdf_l1(), Self::l2_cloud()]
+    }
+
+    /// 按名称查找产品类型，自定义产品优先于内置产品
+    pub fn find(name: &str, custom: &[ProductType]) -> Option<Self> {
+        custom
+            .iter()
+            .cloned()
+            .chain(Self::presets())
+            .find(|product| product.name == name)
+    }
+
+    /// 指定观测时间对应的远程目录
+    pub fn remote_directory(&self, datetime: &NaiveDateTime) -> String {
+        expand_template(&self.path_template, datetime, "")
+    }
+
+    /// 观测时间所属发布时段的开始时间（从当天 0 点后 `offset_minutes` 分钟起按观测间隔向下取整）
+    pub fn period_start(&self, datetime: &NaiveDateTime) -> NaiveDateTime {
+        let midnight = datetime.date().and_time(NaiveTime::MIN);
+        let elapsed = (*datetime - midnight).num_minutes();
+        midnight
+            + Duration::minutes(
+                elapsed - (elapsed - self.offset_minutes).rem_euclid(self.cadence_minutes),
+            )
+    }
+
+    /// `start` 到 `end`（含）之间该产品的时次
+    pub fn time_list(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<NaiveDateTime>, String> {
+        time_list_aligned(start, end, self.cadence_minutes, self.offset_minutes)
+    }
+
+    /// 配置的时间段端点须对齐的时间线（分钟）：观测间隔与 10 分钟观测时间线的最大公约数，
+    /// 同样按 `offset_minutes` 偏移；原生产品即 10 分钟时间线
+    pub fn timeline_minutes(&self) -> i64 {
+        let (mut a, mut b) = (self.cadence_minutes, himawari::TIMELINE_MINUTES);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    }
+
+    /// 时间是否位于 [`ProductType::timeline_minutes`] 的时间线上
+    pub fn is_on_timeline(&self, datetime: &NaiveDateTime) -> bool {
+        let minute_of_day = i64::from(datetime.hour() * 60 + datetime.minute());
+        datetime.second() == 0
+            && (minute_of_day - self.offset_minutes).rem_euclid(self.timeline_minutes()) == 0
+    }
+
+    /// 将文件名模式中自第一个 `.` 起的扩展名替换为 `extension`，用于扩展名与标准不同的镜像
+    pub fn with_extension(&self, extension: &str) -> Self {
+        let stem = self
+            .filename_pattern
+            .find('.')
+            .map_or(self.filename_pattern.as_str(), |dot| {
+                &self.filename_pattern[..dot]
+            });
+        Self {
+            filename_pattern: format!("{}{}", stem, extension),
+            ..self.clone()
+        }
+    }
+
+    /// 文件名是否属于该产品指定时间与观测区域
+    pub fn matches(&self, filename: &str, datetime: &NaiveDateTime, area: &str) -> bool {
+        let pattern = expand_template(&self.filename_pattern, datetime, area);
+        glob_match(&pattern, filename)
+    }
+}
+
+/// 展开模板中的时间与区域占位符
+pub fn expand_template(template: &str, datetime: &NaiveDateTime, area: &str) -> String {
+    template
+        .replace("{YYYY}", &datetime.format("%Y").to_string())
+        .replace("{MM}", &datetime.format("%m").to_string())
+        .replace("{DD}", &datetime.format("%d").to_string())
+        .replace("{hh}", &datetime.format("%H").to_string())
+        .replace("{mm}", &datetime.format("%M").to_string())
+        .replace("{area}", area)
+}
+
+/// 简单通配符匹配：`*` 匹配任意长度字符，`?` 匹配单个字符
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
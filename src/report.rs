@@ -0,0 +1,226 @@
+//! 运行报告：运行结束时将 JSON 与 HTML 报告及失败文件列表写入归档中的 `reports/YYYY/MM/DD/`，
+//! 归档被拷贝到别处时来源记录随数据一起带走；也可写入指定的报告文件，供下游流程读取
+
+use crate::download::{DownloadStats, FILE_RESULTS_CSV_HEADER};
+use crate::storage::BandCompletenessReport;
+use crate::units::{format_bytes, format_duration};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::{Value, json};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 运行报告所在的目录，位于下载目录下
+pub const REPORTS_DIR: &str = "reports";
+
+/// 一次运行的报告
+pub struct RunReport<'a> {
+    /// 运行编号，用作报告文件名
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    /// 请求的第一个与最后一个时次
+    pub slots: Option<(NaiveDateTime, NaiveDateTime)>,
+    /// 各产品（主产品与辅助文件）的下载统计
+    pub products: Vec<(String, &'a DownloadStats)>,
+    /// 运行结束时请求时次的波段完整性，只写入 JSON 报告
+    pub completeness: Option<&'a BandCompletenessReport>,
+    pub exit_code: u8,
+}
+
+impl RunReport<'_> {
+    pub fn to_json(&self) -> Value {
+        let products: Vec<Value> = self
+            .products
+            .iter()
+            .map(|(product, stats)| {
+                let mut value = stats.to_json();
+                value["product"] = json!(product);
+                value
+            })
+            .collect();
+        let mut report = json!({
+            "run_id": self.run_id,
+            "version": env!("CARGO_PKG_VERSION"),
+            "started_at": self.started_at.to_rfc3339(),
+            "finished_at": Utc::now().to_rfc3339(),
+            "first_slot": self.slots.map(|(first, _)| first.to_string()),
+            "last_slot": self.slots.map(|(_, last)| last.to_string()),
+            "exit_code": self.exit_code,
+            "products": products,
+        });
+        if let Some(completeness) = self.completeness {
+            report["completeness"] = completeness.to_json();
+        }
+        report
+    }
+
+    /// 各产品的逐文件结果，每个文件一行，第一列为产品
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("product,{}\n", FILE_RESULTS_CSV_HEADER);
+        for (product, stats) in &self.products {
+            for row in stats.file_result_rows() {
+                let _ = writeln!(csv, "{},{}", csv_field(product), row);
+            }
+        }
+        csv
+    }
+
+    /// 供人阅读的 HTML 报告
+    pub fn render_html(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<!DOCTYPE html>\n<html lang=\"zh\">\n<head><meta charset=\"utf-8\"><title>运行报告 {}</title></head>\n<body>",
+            escape(&self.run_id)
+        );
+        let _ = writeln!(out, "<h1>运行报告 {}</h1>", escape(&self.run_id));
+        let _ = writeln!(
+            out,
+            "<p>开始时间: {}<br>退出码: {}",
+            self.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.exit_code
+        );
+        if let Some((first, last)) = self.slots {
+            let _ = write!(
+                out,
+                "<br>时次: {} 至 {}",
+                first.format("%Y-%m-%d %H:%M"),
+                last.format("%Y-%m-%d %H:%M")
+            );
+        }
+        let _ = writeln!(out, "</p>");
+
+        for (product, stats) in &self.products {
+            let _ = writeln!(out, "<h2>{}</h2>\n<table>", escape(product));
+            for (label, value) in [
+                ("总文件数", stats.total_files.to_string()),
+                ("已存在", stats.existing_files.to_string()),
+                ("成功下载", stats.downloaded_files.to_string()),
+                ("跳过", stats.skipped_files.to_string()),
+                ("失败", stats.failed_files.to_string()),
+                ("结束前重试", stats.retried_at_end.to_string()),
+                ("重试成功", stats.recovered_at_end.to_string()),
+                ("下载量", format_bytes(stats.total_bytes)),
+                ("耗时", format_duration(stats.elapsed_time)),
+                ("未完成的时次", stats.pending_slots.len().to_string()),
+                ("文件数不足的时次", stats.short_slots.len().to_string()),
+                ("后处理完成", stats.post_processing.processed.to_string()),
+                ("后处理失败", stats.post_processing.failed.len().to_string()),
+                ("后处理耗时", format_duration(stats.post_processing.elapsed)),
+                (
+                    "后处理队列峰值",
+                    stats.post_processing.peak_queue.to_string(),
+                ),
+            ] {
+                let _ = writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", label, value);
+            }
+            let _ = writeln!(out, "</table>");
+            if !stats.failed.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "<h3>失败的文件</h3>\n<table>\n<tr><th>远程路径</th><th>原因</th><th>错误</th></tr>"
+                );
+                for file in &stats.failed {
+                    let _ = writeln!(
+                        out,
+                        "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        escape(&file.remote_path),
+                        file.kind,
+                        escape(&file.error)
+                    );
+                }
+                let _ = writeln!(out, "</table>");
+            }
+            if !stats.post_processing.failed.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "<h3>后处理失败的文件</h3>\n<p>原始数据已保留在归档中。</p>\n<table>\n<tr><th>远程路径</th><th>步骤</th><th>错误</th></tr>"
+                );
+                for failure in &stats.post_processing.failed {
+                    let _ = writeln!(
+                        out,
+                        "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        escape(&failure.remote_path),
+                        escape(&failure.stage),
+                        escape(&failure.error)
+                    );
+                }
+                let _ = writeln!(out, "</table>");
+            }
+        }
+        let _ = writeln!(out, "</body>\n</html>");
+        out
+    }
+
+    /// 失败文件的远程路径，每行一个
+    pub fn failed_list(&self) -> String {
+        self.products
+            .iter()
+            .flat_map(|(_, stats)| &stats.failed)
+            .map(|file| format!("{}\n", file.remote_path))
+            .collect()
+    }
+
+    /// 写入 `<base_path>/reports/YYYY/MM/DD/<运行编号>.json` 与 `.html`，有失败文件时另写
+    /// `<运行编号>-failed.txt`；日期为运行开始的日期（UTC）。返回报告所在的目录
+    pub fn write_to_archive(&self, base_path: &Path) -> io::Result<PathBuf> {
+        let dir = base_path
+            .join(REPORTS_DIR)
+            .join(self.started_at.format("%Y/%m/%d").to_string());
+        fs::create_dir_all(&dir)?;
+        let json = serde_json::to_string_pretty(&self.to_json()).map_err(io::Error::other)?;
+        fs::write(dir.join(format!("{}.json", self.run_id)), json)?;
+        fs::write(
+            dir.join(format!("{}.html", self.run_id)),
+            self.render_html(),
+        )?;
+        let failed = self.failed_list();
+        if !failed.is_empty() {
+            fs::write(dir.join(format!("{}-failed.txt", self.run_id)), failed)?;
+        }
+        Ok(dir)
+    }
+
+    /// 写入 `path`：扩展名为 `.csv` 时写入逐文件结果，否则写入 JSON 报告。先写临时文件再替换，
+    /// 读取方不会读到写了一半的报告
+    pub fn write_report_file(&self, path: &Path) -> io::Result<()> {
+        let content = if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+        {
+            self.to_csv()
+        } else {
+            serde_json::to_string_pretty(&self.to_json()).map_err(io::Error::other)?
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+/// CSV 字段：含逗号、引号或换行时加引号，内部的引号双写
+pub fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// 转义 HTML 中的特殊字符
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,72 @@
+//! Sentry 兼容的错误上报（`sentry` 特性）
+
+use crate::config::Config;
+use sentry::protocol::{Context, Value};
+
+/// 替换敏感配置项时使用的占位符
+const REDACTED: &str = "[已隐藏]";
+
+/// 按配置启用错误上报，未配置 DSN 时返回 `None`；守卫销毁前会等待未发送的事件
+///
+/// 启用后 panic 会被自动上报，上报内容附带隐藏了密码和 DSN 的配置。
+pub fn init(
+    config: &Config,
+) -> Result<Option<sentry::ClientInitGuard>, Box<dyn std::error::Error>> {
+    let Some(dsn) = &config.error_reporting.dsn else {
+        return Ok(None);
+    };
+    let dsn: sentry::types::Dsn = dsn
+        .parse()
+        .map_err(|e| format!("错误上报地址（DSN）无效: {}", e))?;
+
+    let mut options = sentry::ClientOptions::default();
+    options.dsn = Some(dsn);
+    options.release = sentry::release_name!();
+    options.environment = config.error_reporting.environment.clone().map(Into::into);
+    let guard = sentry::init(options);
+
+    let redacted = redacted_config(config);
+    sentry::configure_scope(|scope| {
+        scope.set_context("config", Context::Other(redacted));
+    });
+
+    tracing::info!("已启用错误上报");
+    Ok(Some(guard))
+}
+
+/// 上报导致运行失败的错误
+pub fn report_error(error: &(dyn std::error::Error + 'static)) {
+    sentry::capture_error(error);
+}
+
+/// 上报没有对应错误值的运行失败
+pub fn report_message(message: &str) {
+    sentry::capture_message(message, sentry::Level::Error);
+}
+
+/// 将配置转换为上报用的键值表，隐藏密码、私钥口令、上报地址与 SMTP 密码
+fn redacted_config(config: &Config) -> std::collections::BTreeMap<String, Value> {
+    let Ok(Value::Object(mut map)) = serde_json::to_value(config) else {
+        return Default::default();
+    };
+    if let Some(Value::Object(server)) = map.get_mut("server") {
+        server.insert("password".to_string(), REDACTED.into());
+        if server
+            .get("passphrase")
+            .is_some_and(|passphrase| !passphrase.is_null())
+        {
+            server.insert("passphrase".to_string(), REDACTED.into());
+        }
+    }
+    if let Some(Value::Object(reporting)) = map.get_mut("error_reporting")
+        && reporting.contains_key("dsn")
+    {
+        reporting.insert("dsn".to_string(), REDACTED.into());
+    }
+    if let Some(Value::Object(email)) = map.get_mut("email")
+        && email.contains_key("password")
+    {
+        email.insert("password".to_string(), REDACTED.into());
+    }
+    map.into_iter().collect()
+}
@@ -0,0 +1,200 @@
+//! 归档保留期：删除观测时间早于保留天数的时次，删除前可先列出将要删除的目录与数据量
+
+use crate::himawari::HsdFilename;
+use crate::manifest::Manifest;
+use crate::report::REPORTS_DIR;
+use crate::sidecar::sidecar_path;
+use crate::storage::{LocalFileStorage, QUARANTINE_DIR};
+use crate::units::format_bytes;
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::PoisonError;
+
+/// 将被删除的一个目录（按时间组织时即一个时次的 `YYYY/MM/DD/HH` 目录）
+#[derive(Debug, Clone)]
+pub struct PruneDirectory {
+    pub path: PathBuf,
+    /// 目录中最新一个文件的观测时间
+    pub newest: NaiveDateTime,
+    /// 将被删除的文件（不含来源信息文件）
+    pub files: Vec<PathBuf>,
+    /// 将被删除的字节数（含来源信息文件）
+    pub bytes: u64,
+}
+
+/// 按保留期计算出的删除计划
+#[derive(Debug, Clone)]
+pub struct PrunePlan {
+    /// 观测时间早于该时间的文件将被删除
+    pub cutoff: NaiveDateTime,
+    pub directories: Vec<PruneDirectory>,
+}
+
+impl PrunePlan {
+    pub fn total_files(&self) -> usize {
+        self.directories.iter().map(|dir| dir.files.len()).sum()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.directories.iter().map(|dir| dir.bytes).sum()
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "观测时间早于 {} 的数据：{} 个目录，{} 个文件，共 {}",
+            self.cutoff.format("%Y-%m-%d %H:%M"),
+            self.directories.len(),
+            self.total_files(),
+            format_bytes(self.total_bytes())
+        );
+        for dir in &self.directories {
+            println!(
+                "  {}  {} 个文件, {}",
+                dir.path.display(),
+                dir.files.len(),
+                format_bytes(dir.bytes)
+            );
+        }
+    }
+
+    /// 删除计划中的文件及其来源信息文件，从下载清单中移除对应记录并清理留下的空目录，返回删除的文件数
+    pub fn execute(&self, storage: &LocalFileStorage) -> Result<usize, Box<dyn Error>> {
+        let mut removed = HashSet::new();
+        for dir in &self.directories {
+            for file in &dir.files {
+                if let Err(e) = fs::remove_file(file) {
+                    tracing::warn!("删除文件失败 {}: {}", file.display(), e);
+                    continue;
+                }
+                let sidecar = sidecar_path(file);
+                if sidecar.exists() {
+                    fs::remove_file(sidecar)?;
+                }
+                removed.insert(file.clone());
+            }
+        }
+
+        // 与同时进行的下载共用清单，之后它们保存清单时不会写回已删除的文件
+        let manifest = Manifest::open_shared(&storage.base_path)?;
+        let mut manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+        if manifest.forget_files(&removed) > 0 {
+            manifest.save()?;
+        }
+        storage.remove_empty_directories()?;
+        Ok(removed.len())
+    }
+}
+
+/// 列出下载目录中观测时间早于 `keep_days` 天前的文件，按所在目录分组。
+///
+/// HSD 文件按文件名中的观测时间判断（文件名按模板重写过的按下载清单中的远程文件名），其他文件按
+/// `YYYY/MM/DD/HH` 目录判断；两者都无法确定时间的文件不会被删除。隔离区、运行报告、暂存目录、
+/// 入站暂存区、未完成的下载与来源信息文件不在此列
+pub fn plan_prune(storage: &LocalFileStorage, keep_days: u64) -> Result<PrunePlan, Box<dyn Error>> {
+    let cutoff = Utc::now().naive_utc() - Duration::days(keep_days as i64);
+    let renamed = Manifest::load(&storage.base_path)
+        .map(|manifest| manifest.renamed_files())
+        .unwrap_or_default();
+
+    let mut excluded = vec![
+        storage.base_path.join(QUARANTINE_DIR),
+        storage.base_path.join(REPORTS_DIR),
+    ];
+    excluded.extend(storage.staging_path.iter().cloned());
+    excluded.extend(storage.spool_path.iter().cloned());
+
+    let mut files = Vec::new();
+    collect_files(&storage.base_path, &excluded, &mut files)?;
+
+    let mut directories: BTreeMap<PathBuf, PruneDirectory> = BTreeMap::new();
+    for file in files {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        if name.ends_with(&storage.temp_suffix) || name.ends_with(".meta.json") {
+            continue;
+        }
+        let Some(observed) = observation_time(storage, &file, &renamed) else {
+            continue;
+        };
+        if observed >= cutoff {
+            continue;
+        }
+        let sidecar = sidecar_path(&file);
+        let bytes = [&file, &sidecar]
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum::<u64>();
+        let parent = file.parent().unwrap_or(&storage.base_path).to_path_buf();
+        let dir = directories
+            .entry(parent.clone())
+            .or_insert_with(|| PruneDirectory {
+                path: parent,
+                newest: observed,
+                files: Vec::new(),
+                bytes: 0,
+            });
+        dir.newest = dir.newest.max(observed);
+        dir.files.push(file);
+        dir.bytes += bytes;
+    }
+
+    Ok(PrunePlan {
+        cutoff,
+        directories: directories.into_values().collect(),
+    })
+}
+
+/// 文件的观测时间：HSD 文件名中的时间，否则为所在的 `YYYY/MM/DD/HH` 目录的时间
+fn observation_time(
+    storage: &LocalFileStorage,
+    file: &Path,
+    renamed: &HashMap<PathBuf, String>,
+) -> Option<NaiveDateTime> {
+    let local_name = file.file_name()?.to_string_lossy();
+    let filename = renamed
+        .get(file)
+        .map_or(local_name.as_ref(), String::as_str);
+    if let Some(hsd) = HsdFilename::parse(filename) {
+        return Some(hsd.datetime);
+    }
+    if !storage.organize_by_time {
+        return None;
+    }
+    let parts: Vec<String> = file
+        .parent()?
+        .strip_prefix(&storage.base_path)
+        .ok()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let [.., year, month, day, hour] = parts.as_slice() else {
+        return None;
+    };
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)?
+        .and_hms_opt(hour.parse().ok()?, 0, 0)
+}
+
+/// 递归列出 `dir` 下的文件，跳过 `excluded` 中的目录
+pub(crate) fn collect_files(
+    dir: &Path,
+    excluded: &[PathBuf],
+    files: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() && !excluded.contains(&path) {
+            collect_files(&path, excluded, files)?;
+        } else if file_type.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 单个主机在本次运行中的重试记录
+#[derive(Debug, Clone, Default)]
+pub struct HostRetryState {
+    pub failures: usize,
+    pub retries_used: usize,
+    pub connect_attempts: usize,
+    last_connect: Option<Instant>,
+}
+
+/// 按主机统计失败次数，并限制一次运行中的总重试次数与重连间隔，避免重试风暴导致账户被封禁
+#[derive(Debug, Default)]
+pub struct RetryBudget {
+    /// 每个主机允许的总重试次数，`None` 表示不限制
+    max_retries: Option<usize>,
+    /// 同一主机两次建立连接之间的最小间隔
+    min_reconnect_interval: Duration,
+    hosts: Mutex<HashMap<String, HostRetryState>>,
+}
+
+impl RetryBudget {
+    pub fn new(max_retries: Option<usize>, min_reconnect_interval: Duration) -> Self {
+        Self {
+            max_retries,
+            min_reconnect_interval,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 记录一次失败
+    pub fn record_failure(&self, host: &str) {
+        let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        hosts.entry(host.to_string()).or_default().failures += 1;
+    }
+
+    /// 申请一次重试机会，预算耗尽时返回 false
+    pub fn try_acquire_retry(&self, host: &str) -> bool {
+        let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = hosts.entry(host.to_string()).or_default();
+        if let Some(max_retries) = self.max_retries
+            && state.retries_used >= max_retries
+        {
+            return false;
+        }
+        state.retries_used += 1;
+        true
+    }
+
+    /// 等待直到距离上次连接该主机已超过最小间隔，然后登记本次连接
+    pub fn wait_for_connect_slot(&self, host: &str) {
+        loop {
+            let wait = {
+                let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+                let state = hosts.entry(host.to_string()).or_default();
+                let wait = state
+                    .last_connect
+                    .map(|last| self.min_reconnect_interval.saturating_sub(last.elapsed()))
+                    .unwrap_or(Duration::ZERO);
+                if wait.is_zero() {
+                    state.last_connect = Some(Instant::now());
+                    state.connect_attempts += 1;
+                    return;
+                }
+                wait
+            };
+            thread::sleep(wait);
+        }
+    }
+
+    /// 各主机的重试记录
+    pub fn snapshot(&self) -> Vec<(String, HostRetryState)> {
+        let hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut snapshot: Vec<_> = hosts
+            .iter()
+            .map(|(host, state)| (host.clone(), state.clone()))
+            .collect();
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        snapshot
+    }
+
+    pub fn print_summary(&self) {
+        for (host, state) in self.snapshot() {
+            if state.failures == 0 {
+                continue;
+            }
+            let budget = self
+                .max_retries
+                .map(|max| max.to_string())
+                .unwrap_or_else(|| "不限".to_string());
+            tracing::info!(
+                "主机 {}: 失败 {} 次, 已用重试 {}/{}, 连接 {} 次",
+                host,
+                state.failures,
+                state.retries_used,
+                budget,
+                state.connect_attempts
+            );
+        }
+    }
+}
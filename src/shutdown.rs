@@ -0,0 +1,104 @@
+//! 中断请求：第一次收到 Ctrl+C（SIGINT）或 SIGTERM 时工作线程不再开始新文件，进行中的文件继续完成；
+//! 再次收到时中止进行中的传输，已写入的部分保留在临时文件中，下次运行断点续传
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 等待期间检查中断请求的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 本次运行收到的中断请求
+#[derive(Debug, Default)]
+pub struct Shutdown {
+    requests: AtomicUsize,
+}
+
+impl Shutdown {
+    pub const fn new() -> Self {
+        Self {
+            requests: AtomicUsize::new(0),
+        }
+    }
+
+    /// 记录一次中断请求，返回累计的请求次数；只使用原子操作，可在信号处理函数中调用
+    pub fn request(&self) -> usize {
+        self.requests.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// 是否已请求停止：不再开始新文件
+    pub fn requested(&self) -> bool {
+        self.requests.load(Ordering::SeqCst) >= 1
+    }
+
+    /// 是否已请求中止：进行中的传输也立即停止
+    pub fn aborting(&self) -> bool {
+        self.requests.load(Ordering::SeqCst) >= 2
+    }
+
+    /// 等待 `duration`，期间收到中断请求时提前返回；返回是否完整等待
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let end = Instant::now() + duration;
+        loop {
+            if self.requested() {
+                return false;
+            }
+            let remaining = end.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return true;
+            }
+            thread::sleep(remaining.min(POLL_INTERVAL));
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "cli"))]
+mod signals {
+    use super::Shutdown;
+    use std::sync::{Arc, OnceLock};
+
+    static TARGET: OnceLock<Arc<Shutdown>> = OnceLock::new();
+
+    const STOPPING: &[u8] =
+        "\n收到中断信号，进行中的文件完成后退出；再次按 Ctrl+C 立即中止\n".as_bytes();
+    const ABORTING: &[u8] =
+        "\n再次收到中断信号，中止进行中的传输，临时文件保留以便续传\n".as_bytes();
+
+    extern "C" fn on_signal(signal: libc::c_int) {
+        let Some(shutdown) = TARGET.get() else {
+            return;
+        };
+        // 信号处理函数中不能加锁，提示直接写入标准错误
+        let message = if shutdown.request() >= 2 {
+            // 恢复默认处理，第三次信号立即结束进程
+            unsafe { libc::signal(signal, libc::SIG_DFL) };
+            ABORTING
+        } else {
+            STOPPING
+        };
+        unsafe { libc::write(libc::STDERR_FILENO, message.as_ptr().cast(), message.len()) };
+    }
+
+    pub fn install(shutdown: Arc<Shutdown>) -> Result<(), String> {
+        if TARGET.set(shutdown).is_err() {
+            return Err("中断信号处理已安装".to_string());
+        }
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+                return Err(format!(
+                    "安装信号 {} 的处理函数失败: {}",
+                    signal,
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 安装 SIGINT 与 SIGTERM 的处理函数，收到信号时向 `shutdown` 记录中断请求；进程内只能安装一次
+#[cfg(all(unix, feature = "cli"))]
+pub fn install_signal_handlers(shutdown: std::sync::Arc<Shutdown>) -> Result<(), String> {
+    signals::install(shutdown)
+}
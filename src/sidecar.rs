@@ -0,0 +1,64 @@
+use crate::checksum::{Checksum, ChecksumAlgorithm};
+use crate::transport::RemoteMetadata;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 下载文件的来源信息，写入文件旁的 `<文件名>.meta.json`，便于下游追溯数据来源
+#[derive(Debug, Clone, Serialize)]
+pub struct FileMetadata {
+    pub source_host: String,
+    pub remote_path: String,
+    /// 远程文件修改时间
+    pub remote_mtime: Option<DateTime<Utc>>,
+    pub size: u64,
+    /// 本地文件的校验和，写作 `"sha256": "<十六进制>"` 等，键名为所用的算法
+    #[serde(flatten)]
+    pub checksum: Checksum,
+    pub downloaded_at: DateTime<Utc>,
+    /// 下载成功前的重试次数
+    pub retries: usize,
+}
+
+impl FileMetadata {
+    /// 根据已下载的本地文件生成来源信息，校验和按 `algorithm` 计算
+    pub fn new(
+        source_host: &str,
+        remote_path: &str,
+        remote: &RemoteMetadata,
+        local_path: &Path,
+        retries: usize,
+        algorithm: ChecksumAlgorithm,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            source_host: source_host.to_string(),
+            remote_path: remote_path.to_string(),
+            remote_mtime: remote
+                .modified
+                .and_then(|secs| DateTime::from_timestamp(secs as i64, 0)),
+            size: fs::metadata(local_path)?.len(),
+            checksum: Checksum::of_file(local_path, algorithm)?,
+            downloaded_at: Utc::now(),
+            retries,
+        })
+    }
+
+    /// 写入 `<local_path>.meta.json`
+    pub fn write_sidecar(&self, local_path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let path = sidecar_path(local_path);
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+/// 数据文件对应的来源信息文件路径
+pub fn sidecar_path(local_path: &Path) -> PathBuf {
+    let mut path = local_path.as_os_str().to_owned();
+    path.push(".meta.json");
+    PathBuf::from(path)
+}
+
+// 旧的函数路径，保留一个版本
+pub use crate::checksum::sha256_file;
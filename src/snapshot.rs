@@ -0,0 +1,226 @@
+//! 远程目录列表快照：保存某段时间内服务器上的文件及其属性，之后与当前列表比较，
+//! 了解数据提供方新增、删除或重新处理了哪些文件
+
+use crate::config::Config;
+use crate::layout::RemoteLayout;
+use crate::product::ProductType;
+use crate::transport::RemoteConnection;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// 快照中一个远程文件的属性
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct SnapshotEntry {
+    pub size: u64,
+    /// 修改时间（Unix 时间戳，秒），服务器未提供时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+}
+
+/// 某段时间内的远程文件列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSnapshot {
+    pub product: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub taken_at: DateTime<Utc>,
+    /// 成功列出的远程目录；未列出的目录中的文件不参与比较
+    pub directories: BTreeSet<String>,
+    /// 按远程路径索引的文件
+    pub files: BTreeMap<String, SnapshotEntry>,
+}
+
+impl RemoteSnapshot {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// 与较新的快照比较；只比较两次都成功列出的目录
+    pub fn diff(&self, newer: &RemoteSnapshot) -> SnapshotDiff {
+        let both_listed = |path: &str| {
+            self.directories.contains(parent(path)) && newer.directories.contains(parent(path))
+        };
+        let mut diff = SnapshotDiff::default();
+        for (path, entry) in &newer.files {
+            match self.files.get(path) {
+                None if both_listed(path) => diff.appeared.push((path.clone(), *entry)),
+                Some(old) if old != entry => diff.changed.push((path.clone(), *old, *entry)),
+                _ => {}
+            }
+        }
+        for (path, entry) in &self.files {
+            if !newer.files.contains_key(path) && both_listed(path) {
+                diff.disappeared.push((path.clone(), *entry));
+            }
+        }
+        diff.unlisted = self
+            .directories
+            .symmetric_difference(&newer.directories)
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+/// 两次快照之间的差异
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    pub appeared: Vec<(String, SnapshotEntry)>,
+    pub disappeared: Vec<(String, SnapshotEntry)>,
+    /// 大小或修改时间改变的文件：旧属性、新属性
+    pub changed: Vec<(String, SnapshotEntry, SnapshotEntry)>,
+    /// 只在其中一次快照中成功列出的目录，其中的文件未参与比较
+    pub unlisted: Vec<String>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty() && self.disappeared.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn print_report(&self) {
+        println!("=== 远程列表差异 ===");
+        println!(
+            "新增 {} 个，消失 {} 个，变化 {} 个",
+            self.appeared.len(),
+            self.disappeared.len(),
+            self.changed.len()
+        );
+        for (path, entry) in &self.appeared {
+            println!("  + {} ({} bytes)", path, entry.size);
+        }
+        for (path, entry) in &self.disappeared {
+            println!("  - {} ({} bytes)", path, entry.size);
+        }
+        for (path, old, new) in &self.changed {
+            println!(
+                "  ~ {} ({} -> {} bytes{})",
+                path,
+                old.size,
+                new.size,
+                if old.modified != new.modified {
+                    "，修改时间改变"
+                } else {
+                    ""
+                }
+            );
+        }
+        if !self.unlisted.is_empty() {
+            println!(
+                "{} 个目录只在其中一次快照中成功列出，未参与比较:",
+                self.unlisted.len()
+            );
+            for dir in &self.unlisted {
+                println!("  {}", dir);
+            }
+        }
+        if self.is_empty() {
+            println!("远程文件没有变化");
+        }
+    }
+}
+
+/// 远程路径所在的目录，不含末尾的 `/`（部分后端列目录时会产生 `dir//name`）
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/')
+        .map_or("", |(dir, _)| dir.trim_end_matches('/'))
+}
+
+/// 列出某段时间内属于该产品与观测区域的远程文件；同一目录只列出一次，读取失败的目录不计入快照
+pub fn take_snapshot(
+    connection: &dyn RemoteConnection,
+    product: &ProductType,
+    layout: &dyn RemoteLayout,
+    areas: &[String],
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<RemoteSnapshot, Box<dyn Error>> {
+    let mut snapshot = RemoteSnapshot {
+        product: product.name.clone(),
+        start,
+        end,
+        taken_at: Utc::now(),
+        directories: BTreeSet::new(),
+        files: BTreeMap::new(),
+    };
+    let mut slots_by_directory: BTreeMap<String, Vec<NaiveDateTime>> = BTreeMap::new();
+    for slot in product.time_list(start, end)? {
+        slots_by_directory
+            .entry(layout.directory(product, &slot))
+            .or_default()
+            .push(slot);
+    }
+
+    for (remote_dir, slots) in &slots_by_directory {
+        let entries = match connection.list_directory_entries(remote_dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("读取目录失败 {}: {}", remote_dir, e);
+                continue;
+            }
+        };
+        for entry in entries {
+            let filename = entry.path.rsplit('/').next().unwrap_or(&entry.path);
+            let matched = slots.iter().any(|slot| {
+                areas
+                    .iter()
+                    .any(|area| layout.matches(product, filename, slot, area))
+            });
+            if matched {
+                // 统一为 `目录/文件名`，不同后端保存的快照可以互相比较
+                snapshot.files.insert(
+                    format!("{}/{}", parent(&entry.path), filename),
+                    SnapshotEntry {
+                        size: entry.metadata.size,
+                        modified: entry.metadata.modified,
+                    },
+                );
+            }
+        }
+        // 目录中没有匹配的文件时也记录为已列出，之后出现的文件才能计为新增
+        snapshot
+            .directories
+            .insert(remote_dir.trim_end_matches('/').to_string());
+    }
+    Ok(snapshot)
+}
+
+/// 按配置连接服务器，列出当前产品某段时间内的远程文件
+pub fn run_snapshot(
+    config: &Config,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<RemoteSnapshot, Box<dyn Error>> {
+    let product = config.product()?;
+    let layout = config.remote_layout();
+    let connection = config.server.transport.connect(
+        &config.get_host_with_port(),
+        &config.server.username,
+        &config.server.credentials(),
+    )?;
+    take_snapshot(
+        connection.as_ref(),
+        &product,
+        layout.as_ref(),
+        &config.download.areas,
+        start,
+        end,
+    )
+}
@@ -0,0 +1,267 @@
+use crate::config::Config;
+use crate::download::{
+    BufferSizer, DownloadOptions, ProgressReporting, download_file_with_resume,
+    list_remote_files_in_directory,
+};
+use crate::timelist::{PUBLISH_DELAY_MINUTES, latest_published_slot};
+use crate::transport::ConnectionTag;
+use crate::units::{format_bytes, format_duration, format_rate};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Barrier, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 测速参数
+#[derive(Debug, Clone)]
+pub struct SpeedtestOptions {
+    /// 用于测速的波段
+    pub band: String,
+    /// 每轮下载的样本文件数
+    pub sample_files: usize,
+    /// 待比较的并发线程数
+    pub thread_counts: Vec<usize>,
+    /// 待比较的缓冲区大小（字节）
+    pub buffer_sizes: Vec<usize>,
+}
+
+impl Default for SpeedtestOptions {
+    fn default() -> Self {
+        Self {
+            band: "B01".to_string(),
+            sample_files: 4,
+            thread_counts: vec![1, 2, 4, 8],
+            buffer_sizes: vec![32 * 1024, 256 * 1024, 1024 * 1024],
+        }
+    }
+}
+
+/// 单轮测速结果
+#[derive(Debug, Clone)]
+pub struct SpeedtestTrial {
+    pub num_threads: usize,
+    pub buffer_size: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    pub failed_files: usize,
+}
+
+impl SpeedtestTrial {
+    /// 吞吐量（MB/s）
+    pub fn throughput_mbps(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.bytes as f64 / self.elapsed.as_secs_f64() / 1024.0 / 1024.0
+    }
+}
+
+/// 测速报告
+#[derive(Debug, Clone, Default)]
+pub struct SpeedtestReport {
+    pub sample_files: Vec<String>,
+    pub trials: Vec<SpeedtestTrial>,
+}
+
+impl SpeedtestReport {
+    /// 吞吐量最高且没有失败文件的一轮
+    pub fn best(&self) -> Option<&SpeedtestTrial> {
+        self.trials
+            .iter()
+            .filter(|trial| trial.failed_files == 0)
+            .max_by(|a, b| a.throughput_mbps().total_cmp(&b.throughput_mbps()))
+    }
+
+    pub fn print_report(&self) {
+        println!("=== 测速结果 ===");
+        println!("样本文件: {} 个", self.sample_files.len());
+        println!(
+            "{:>6} {:>10} {:>10} {:>12} {:>6}",
+            "线程", "缓冲区", "耗时", "吞吐量", "失败"
+        );
+        for trial in &self.trials {
+            println!(
+                "{:>8} {:>11} {:>10} {:>14} {:>8}",
+                trial.num_threads,
+                format_bytes(trial.buffer_size as u64),
+                format_duration(trial.elapsed),
+                format_rate(trial.bytes, trial.elapsed),
+                trial.failed_files
+            );
+        }
+        if let Some(best) = self.best() {
+            println!(
+                "建议配置: num_threads = {}, buffer_size_kb = {}",
+                best.num_threads,
+                best.buffer_size / 1024
+            );
+        }
+    }
+}
+
+/// 以不同并发数和缓冲区大小下载样本文件到临时目录，比较实际吞吐量
+pub fn run_speedtest(
+    config: &Config,
+    options: &SpeedtestOptions,
+) -> Result<SpeedtestReport, Box<dyn std::error::Error>> {
+    let host = config.get_host_with_port();
+    let username = &config.server.username;
+    let credentials = config.server.credentials();
+
+    // 选取最近一个已发布时次的样本文件
+    let slot = latest_published_slot(PUBLISH_DELAY_MINUTES);
+    let product = config.product()?;
+    let layout = config.remote_layout();
+    let remote_dir = layout.directory(&product, &slot);
+    let connection = config
+        .server
+        .transport
+        .connect(&host, username, &credentials)?;
+    let listing = DownloadOptions::new(1)
+        .with_product(product)
+        .with_layout(Arc::clone(&layout))
+        .with_areas(config.download.areas.clone())
+        .with_resolutions(config.download.resolutions.clone());
+    let mut sample_files: Vec<String> = list_remote_files_in_directory(
+        connection.as_ref(),
+        &remote_dir,
+        &slot,
+        &listing,
+        std::slice::from_ref(&options.band),
+    )?
+    .into_iter()
+    .filter(|entry| layout.is_ready(entry))
+    .map(|entry| entry.path)
+    .collect();
+    sample_files.sort();
+    sample_files.truncate(options.sample_files);
+    if sample_files.is_empty() {
+        return Err(format!("在 {} 中未找到波段 {} 的样本文件", remote_dir, options.band).into());
+    }
+    tracing::info!("测速样本: {} 个文件 ({})", sample_files.len(), remote_dir);
+
+    let work_dir = std::env::temp_dir().join(format!("hsd_speedtest_{}", std::process::id()));
+    fs::create_dir_all(&work_dir)?;
+
+    let mut report = SpeedtestReport {
+        sample_files: sample_files.clone(),
+        trials: Vec::new(),
+    };
+
+    for &num_threads in &options.thread_counts {
+        for &buffer_size in &options.buffer_sizes {
+            tracing::info!(
+                "测速: {} 线程, {} KB 缓冲区...",
+                num_threads,
+                buffer_size / 1024
+            );
+            let trial = run_trial(config, &sample_files, num_threads, buffer_size, &work_dir);
+            clear_directory(&work_dir)?;
+            report.trials.push(trial);
+        }
+    }
+
+    fs::remove_dir_all(&work_dir)?;
+    Ok(report)
+}
+
+fn run_trial(
+    config: &Config,
+    sample_files: &[String],
+    num_threads: usize,
+    buffer_size: usize,
+    work_dir: &Path,
+) -> SpeedtestTrial {
+    let queue = Arc::new(Mutex::new(
+        sample_files.iter().cloned().collect::<VecDeque<_>>(),
+    ));
+    let results = Arc::new(Mutex::new((0u64, 0usize)));
+    // 所有线程建立连接后再统一计时，连接开销不计入吞吐量
+    let barrier = Arc::new(Barrier::new(num_threads + 1));
+    let mut handles = Vec::new();
+
+    for worker in 0..num_threads {
+        let queue = Arc::clone(&queue);
+        let results = Arc::clone(&results);
+        let barrier = Arc::clone(&barrier);
+        let transport = config.server.transport;
+        let host = config.get_host_with_port();
+        let username = config.server.username.clone();
+        let credentials = config.server.credentials();
+        let work_dir = work_dir.to_path_buf();
+        let transfer_options = DownloadOptions::new(1).with_progress(ProgressReporting::Off);
+
+        handles.push(thread::spawn(move || {
+            let tag = ConnectionTag::next(format!("speedtest{}", worker));
+            let connection = transport.connect_tagged(&host, &username, &credentials, &tag);
+            barrier.wait();
+            let connection = match connection {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("测速连接 {} 失败: {}", tag, e);
+                    lock(&results).1 += lock(&queue).drain(..).count();
+                    return;
+                }
+            };
+
+            loop {
+                let Some(remote_path) = lock(&queue).pop_front() else {
+                    break;
+                };
+                let final_path = sample_path(&work_dir, &remote_path);
+                let mut temp_path = final_path.clone().into_os_string();
+                temp_path.push(".downloading");
+
+                match download_file_with_resume(
+                    connection.as_ref(),
+                    &remote_path,
+                    None,
+                    Path::new(&temp_path),
+                    &final_path,
+                    &mut BufferSizer::fixed(buffer_size),
+                    &transfer_options,
+                ) {
+                    Ok(transfer) => lock(&results).0 += transfer.bytes,
+                    Err(e) => {
+                        tracing::warn!("测速下载失败 {}: {}", remote_path, e);
+                        lock(&results).1 += 1;
+                    }
+                }
+            }
+        }));
+    }
+
+    barrier.wait();
+    let start_time = Instant::now();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    let elapsed = start_time.elapsed();
+
+    let (bytes, failed_files) = *lock(&results);
+    SpeedtestTrial {
+        num_threads,
+        buffer_size,
+        bytes,
+        elapsed,
+        failed_files,
+    }
+}
+
+/// 获取锁；其他测速线程 panic 后仍继续使用其中的数据
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn sample_path(work_dir: &Path, remote_path: &str) -> PathBuf {
+    let filename = Path::new(remote_path).file_name().unwrap();
+    work_dir.join(filename)
+}
+
+fn clear_directory(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(dir)? {
+        fs::remove_file(entry?.path())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,244 @@
+//! 入站暂存区：每次运行的下载先写入 `<spool_path>/<运行编号>/`（布局与归档相同），
+//! 由 `promote` 检查完整性与内容后按时次移入归档，外部数据在被接收之前不会进入归档
+
+use crate::himawari::HsdFilename;
+use crate::manifest::Manifest;
+use crate::sidecar::sidecar_path;
+use crate::storage::{LocalFileStorage, promote_file};
+use crate::validation::validate_file;
+use chrono::Utc;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 生成本次运行的编号（UTC 开始时间），同时用作入站暂存区中运行目录的名称
+pub fn new_run_id() -> String {
+    Utc::now().format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// 保留在暂存区、未移入归档的时次
+#[derive(Debug, Clone)]
+pub struct HeldSlot {
+    /// 时次与观测区域；无法识别时次的文件为其相对路径
+    pub slot: String,
+    pub reason: String,
+}
+
+/// 一次移入归档的结果
+#[derive(Debug, Default)]
+pub struct PromoteReport {
+    pub runs: usize,
+    pub promoted_slots: usize,
+    pub promoted_files: usize,
+    pub held: Vec<HeldSlot>,
+}
+
+impl PromoteReport {
+    /// 是否所有时次都已移入归档
+    pub fn all_promoted(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "检查 {} 个运行目录：{} 个时次（{} 个文件）已移入归档，{} 个时次保留在暂存区",
+            self.runs,
+            self.promoted_slots,
+            self.promoted_files,
+            self.held.len()
+        );
+        for held in &self.held {
+            println!("  {}: {}", held.slot, held.reason);
+        }
+    }
+}
+
+/// 暂存区中的一个文件，同一相对路径可能在多个运行目录中各有一份
+#[derive(Default)]
+struct SpooledFile {
+    /// 按运行编号排序，最后一份为最新
+    copies: Vec<PathBuf>,
+    remote_name: String,
+}
+
+/// 检查入站暂存区中的运行目录（`runs` 为空时检查全部），将完整的时次移入归档。
+///
+/// 时次按观测时间与区域划分，同一时次的文件可以分布在不同的运行目录中；时次中每个波段的分段齐全、
+/// 文件不为空且没有正在下载的文件时才视为完整，`validate` 为 true 时另外校验文件内容。
+/// 文件名无法识别时次的文件逐个检查。同一文件在多个运行目录中都有时移入最新的一份，其余删除
+pub fn promote(
+    storage: &LocalFileStorage,
+    runs: &[String],
+    validate: bool,
+) -> Result<PromoteReport, Box<dyn Error>> {
+    if storage.spool_path.is_none() {
+        return Err("未配置入站暂存区 spool_path".into());
+    }
+    let mut run_dirs = storage.spooled_runs();
+    if let Some(run) = runs
+        .iter()
+        .find(|run| !run_dirs.iter().any(|dir| dir.ends_with(run.as_str())))
+    {
+        return Err(format!("入站暂存区中没有运行目录 {}", run).into());
+    }
+    if !runs.is_empty() {
+        run_dirs.retain(|dir| runs.iter().any(|run| dir.ends_with(run.as_str())));
+    }
+
+    // 文件名按模板重写过时，依据下载清单找回远程文件名以识别时次
+    let renamed = Manifest::load(&storage.base_path)
+        .map(|manifest| manifest.renamed_files())
+        .unwrap_or_default();
+
+    let mut files: BTreeMap<PathBuf, SpooledFile> = BTreeMap::new();
+    let mut downloading: BTreeSet<PathBuf> = BTreeSet::new();
+    for run in &run_dirs {
+        collect_files(storage, run, run, &mut files, &mut downloading)?;
+    }
+
+    let mut slots: BTreeMap<String, Vec<(&PathBuf, &SpooledFile)>> = BTreeMap::new();
+    for (relative, file) in files.iter_mut() {
+        let archived = storage.base_path.join(relative);
+        file.remote_name = renamed.get(&archived).cloned().unwrap_or_else(|| {
+            relative
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        });
+    }
+    for (relative, file) in &files {
+        slots
+            .entry(slot_label(relative, &file.remote_name))
+            .or_default()
+            .push((relative, file));
+    }
+    let mut downloading_slots: HashMap<String, usize> = HashMap::new();
+    for relative in &downloading {
+        let name = relative.file_name().unwrap_or_default().to_string_lossy();
+        let remote_name = renamed
+            .get(&storage.base_path.join(relative))
+            .cloned()
+            .unwrap_or_else(|| name.to_string());
+        *downloading_slots
+            .entry(slot_label(relative, &remote_name))
+            .or_default() += 1;
+    }
+
+    let mut report = PromoteReport {
+        runs: run_dirs.len(),
+        ..PromoteReport::default()
+    };
+    for (slot, slot_files) in slots {
+        let reason = match downloading_slots.get(&slot) {
+            Some(count) => Some(format!("仍有 {} 个文件未下载完成", count)),
+            None => check_slot(&slot_files, validate),
+        };
+        if let Some(reason) = reason {
+            report.held.push(HeldSlot { slot, reason });
+            continue;
+        }
+
+        let result = slot_files.iter().try_for_each(|(relative, file)| {
+            let (latest, older) = file.copies.split_last().expect("至少有一份");
+            promote_file(latest, &storage.base_path.join(relative))?;
+            for copy in older {
+                fs::remove_file(copy)?;
+                let sidecar = sidecar_path(copy);
+                if sidecar.exists() {
+                    fs::remove_file(sidecar)?;
+                }
+            }
+            Ok::<(), std::io::Error>(())
+        });
+        match result {
+            Ok(()) => {
+                tracing::info!("时次 {} 已移入归档 ({} 个文件)", slot, slot_files.len());
+                report.promoted_slots += 1;
+                report.promoted_files += slot_files.len();
+            }
+            Err(e) => report.held.push(HeldSlot {
+                slot,
+                reason: format!("移入归档失败: {}", e),
+            }),
+        }
+    }
+
+    storage.remove_empty_directories()?;
+    Ok(report)
+}
+
+/// 递归收集运行目录中的文件，跳过来源信息文件；正在下载的临时文件记入 `downloading`
+fn collect_files(
+    storage: &LocalFileStorage,
+    run: &Path,
+    dir: &Path,
+    files: &mut BTreeMap<PathBuf, SpooledFile>,
+    downloading: &mut BTreeSet<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_symlink() {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(storage, run, &path, files, downloading)?;
+            continue;
+        }
+        let relative = path.strip_prefix(run)?.to_path_buf();
+        let name = relative.to_string_lossy().to_string();
+        if name.ends_with(".meta.json") {
+            continue;
+        }
+        match name.strip_suffix(&storage.temp_suffix) {
+            Some(downloaded) => {
+                downloading.insert(PathBuf::from(downloaded));
+            }
+            None => files.entry(relative).or_default().copies.push(path),
+        }
+    }
+    Ok(())
+}
+
+/// 时次标签：HSD 文件为观测时间与区域，其余文件为相对路径
+fn slot_label(relative: &Path, remote_name: &str) -> String {
+    match HsdFilename::parse(remote_name) {
+        Some(hsd) => format!("{} {}", hsd.datetime.format("%Y-%m-%d %H:%M"), hsd.area),
+        None => relative.display().to_string(),
+    }
+}
+
+/// 检查时次是否可以移入归档，返回不能移入的原因
+fn check_slot(files: &[(&PathBuf, &SpooledFile)], validate: bool) -> Option<String> {
+    let mut segments: BTreeMap<String, (u32, BTreeSet<u32>)> = BTreeMap::new();
+    for (_, file) in files {
+        if let Some(hsd) = HsdFilename::parse(&file.remote_name) {
+            let band = segments
+                .entry(hsd.band.clone())
+                .or_insert_with(|| (hsd.total_segments, BTreeSet::new()));
+            band.1.insert(hsd.segment);
+        }
+    }
+    for (band, (total, present)) in &segments {
+        let missing: Vec<u32> = (1..=*total)
+            .filter(|segment| !present.contains(segment))
+            .collect();
+        if !missing.is_empty() {
+            return Some(format!("波段 {} 缺少分段 {:?}", band, missing));
+        }
+    }
+
+    for (_, file) in files {
+        let Some(latest) = file.copies.last() else {
+            continue;
+        };
+        if fs::metadata(latest).map_or(0, |metadata| metadata.len()) == 0 {
+            return Some(format!("文件为空: {}", latest.display()));
+        }
+        if validate && let Err(reason) = validate_file(latest, &file.remote_name) {
+            return Some(format!("{} 内容校验失败: {}", latest.display(), reason));
+        }
+    }
+    None
+}
@@ -0,0 +1,201 @@
+use crate::download::SlotLatency;
+use crate::transport::ConnectionTag;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 运行状态文件名，写在下载目录下
+pub const STATUS_FILE_NAME: &str = "status.json";
+
+/// 单个工作线程的状态
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorkerStatus {
+    /// 连接标识，例如 `w3.g1`，与 SSH 客户端标识串及日志中的一致
+    pub connection: Option<String>,
+    /// 正在下载的远程文件，空闲或已退出时为空
+    pub current_file: Option<String>,
+    /// 开始下载当前文件的时间
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+/// 写入 `status.json` 的运行状态快照
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusSnapshot {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    /// 状态文件最后一次写入的时间
+    pub updated_at: DateTime<Utc>,
+    /// 最后一次开始或完成文件的时间；心跳仍在更新而该时间长期不变时，说明下载已卡住
+    pub last_progress_at: DateTime<Utc>,
+    pub bytes_done: u64,
+    pub files_done: usize,
+    pub files_failed: usize,
+    /// 队列中尚未领取的文件数
+    pub queue_remaining: usize,
+    pub workers: BTreeMap<usize, WorkerStatus>,
+    /// 本次运行中已下载完成的观测时间最新的时次，及其完成时距观测时间的延迟（秒）
+    pub latest_slot: Option<NaiveDateTime>,
+    pub latest_slot_latency_secs: Option<f64>,
+    /// 运行是否已结束
+    pub finished: bool,
+}
+
+/// 下载过程中由各线程共同更新的运行状态
+#[derive(Debug)]
+pub struct RunStatus {
+    path: PathBuf,
+    snapshot: Mutex<StatusSnapshot>,
+}
+
+impl RunStatus {
+    pub fn new(base_path: &Path, queue_remaining: usize) -> Self {
+        let now = Utc::now();
+        Self {
+            path: base_path.join(STATUS_FILE_NAME),
+            snapshot: Mutex::new(StatusSnapshot {
+                pid: std::process::id(),
+                started_at: now,
+                updated_at: now,
+                last_progress_at: now,
+                bytes_done: 0,
+                files_done: 0,
+                files_failed: 0,
+                queue_remaining,
+                workers: BTreeMap::new(),
+                latest_slot: None,
+                latest_slot_latency_secs: None,
+                finished: false,
+            }),
+        }
+    }
+
+    /// 加锁读写状态；某个工作线程持锁时 panic 不影响其余线程继续更新
+    fn snapshot(&self) -> MutexGuard<'_, StatusSnapshot> {
+        self.snapshot.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 工作线程领取任务后更新队列剩余数
+    pub fn set_queue_remaining(&self, queue_remaining: usize) {
+        self.snapshot().queue_remaining = queue_remaining;
+    }
+
+    /// 记录工作线程建立的连接
+    pub fn worker_connected(&self, worker: usize, tag: &ConnectionTag) {
+        let mut snapshot = self.snapshot();
+        snapshot.workers.entry(worker).or_default().connection = Some(tag.to_string());
+    }
+
+    /// 记录工作线程开始下载某个文件
+    pub fn start_file(&self, worker: usize, remote_path: &str) {
+        let mut snapshot = self.snapshot();
+        let now = Utc::now();
+        snapshot.last_progress_at = now;
+        let status = snapshot.workers.entry(worker).or_default();
+        status.current_file = Some(remote_path.to_string());
+        status.started_at = Some(now);
+    }
+
+    /// 记录工作线程完成当前文件，`bytes` 为空表示下载失败
+    pub fn finish_file(&self, worker: usize, bytes: Option<u64>) {
+        let mut snapshot = self.snapshot();
+        snapshot.last_progress_at = Utc::now();
+        match bytes {
+            Some(bytes) => {
+                snapshot.files_done += 1;
+                snapshot.bytes_done += bytes;
+            }
+            None => snapshot.files_failed += 1,
+        }
+        let status = snapshot.workers.entry(worker).or_default();
+        status.current_file = None;
+        status.started_at = None;
+    }
+
+    /// 记录下载完成的时次，保留观测时间最新的一个
+    pub fn slot_completed(&self, slot: &SlotLatency) {
+        let mut snapshot = self.snapshot();
+        if snapshot
+            .latest_slot
+            .is_none_or(|latest| slot.datetime >= latest)
+        {
+            snapshot.latest_slot = Some(slot.datetime);
+            snapshot.latest_slot_latency_secs = Some(slot.latency.as_secs_f64());
+        }
+    }
+
+    /// 记录工作线程退出
+    pub fn worker_exited(&self, worker: usize) {
+        self.snapshot().workers.remove(&worker);
+    }
+
+    /// 将当前状态写入状态文件（先写临时文件再替换，避免读到半个文件）
+    pub fn write(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let content = {
+            let mut snapshot = self.snapshot();
+            snapshot.updated_at = Utc::now();
+            serde_json::to_string_pretty(&*snapshot)?
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn mark_finished(&self) {
+        let mut snapshot = self.snapshot();
+        snapshot.finished = true;
+        snapshot.workers.clear();
+    }
+}
+
+/// 后台定期写入状态文件的心跳线程，销毁时写入最终状态并停止
+pub struct Heartbeat {
+    status: Arc<RunStatus>,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    pub fn start(status: Arc<RunStatus>, interval: Duration) -> Self {
+        let (stop, stop_rx) = mpsc::channel();
+        let writer = Arc::clone(&status);
+        let handle = thread::spawn(move || {
+            loop {
+                if let Err(e) = writer.write() {
+                    tracing::warn!("写入状态文件失败: {}", e);
+                }
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        });
+        Self {
+            status,
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        self.status.mark_finished();
+        if let Err(e) = self.status.write() {
+            tracing::warn!("写入状态文件失败: {}", e);
+        }
+    }
+}
@@ -0,0 +1,829 @@
+use crate::himawari::{self, HsdFilename};
+use crate::naming::FilenameTemplate;
+use crate::report::csv_field;
+use chrono::{NaiveDateTime, Utc};
+use serde_json::{Value, json};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// 下载状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadStatus {
+    NotStarted,
+    Downloading,
+    Completed,
+    Failed,
+}
+
+/// 文件下载记录
+#[derive(Debug, Clone)]
+pub struct FileDownloadRecord {
+    pub remote_path: String,
+    pub local_path: PathBuf,
+    pub temp_path: PathBuf,
+    pub expected_size: Option<u64>,
+    pub downloaded_size: u64,
+    pub status: DownloadStatus,
+    pub retry_count: usize,
+    pub last_modified: Option<String>,
+}
+
+/// 清理未完成下载的结果
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    /// 已删除的临时文件
+    pub removed: Vec<PathBuf>,
+    /// 最近仍有写入而保留的临时文件，可能属于另一个正在运行的实例
+    pub kept: Vec<PathBuf>,
+    /// 留待本次运行断点续传的临时文件：上次运行被中断时保留的，启用 `resume_incomplete` 时为全部
+    pub resumable: Vec<PathBuf>,
+}
+
+/// 隔离区目录名，位于下载目录下，存放大小正确但内容校验失败的文件
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// 本地文件存储结构
+#[derive(Debug, Clone)]
+pub struct LocalFileStorage {
+    pub base_path: PathBuf,
+    pub organize_by_time: bool,
+    pub temp_suffix: String,
+    /// 按观测区域（FLDK、R301 等）分别存放到子目录
+    pub separate_areas: bool,
+    /// 整时次提交模式下的暂存目录，下载先写入此处，时次完整后再移入归档
+    pub staging_path: Option<PathBuf>,
+    /// 入站暂存区根目录；启用时暂存目录为其下本次运行的 `<运行编号>/` 子目录，
+    /// 之前运行留下的、尚未移入归档的文件同样视为已下载
+    pub spool_path: Option<PathBuf>,
+    /// 运行结束时自动将完整的时次从暂存目录移入归档；为 false 时留待 `promote` 处理
+    pub promote_after_run: bool,
+    /// 保存时重写文件名的模板，未设置时保持远程文件名
+    pub filename_template: Option<FilenameTemplate>,
+    /// HSD 分段文件可能使用的扩展名（例如镜像提供已解压的 `.DAT`），为空时只使用标准的 `.DAT.bz2`
+    pub extensions: Vec<String>,
+    /// 未完成的下载文件超过该时长没有写入才视为遗留并在运行开始时删除；为零时全部删除
+    pub orphan_temp_age: Duration,
+    /// 运行开始时保留全部未完成的下载文件，由下载时断点续传，不按 `orphan_temp_age` 删除
+    pub resume_incomplete: bool,
+    /// 下载清单中按远程文件名记录的本地位置，按当前布局找不到文件时据此查找（本地布局改变前下载的文件）
+    pub recorded_locations: Arc<HashMap<String, PathBuf>>,
+    /// 下载后解压并删除了 `.bz2` 压缩文件：压缩文件不在时，同一位置解压后的文件视为已下载
+    pub decompressed: bool,
+}
+
+impl LocalFileStorage {
+    pub fn new(base_path: &str) -> Self {
+        Self {
+            base_path: PathBuf::from(base_path),
+            organize_by_time: true,
+            temp_suffix: ".downloading".to_string(),
+            separate_areas: false,
+            staging_path: None,
+            spool_path: None,
+            promote_after_run: true,
+            filename_template: None,
+            extensions: Vec::new(),
+            orphan_temp_age: Duration::from_secs(60 * 60),
+            resume_incomplete: false,
+            recorded_locations: Arc::default(),
+            decompressed: false,
+        }
+    }
+
+    pub fn with_time_organization(mut self, organize_by_time: bool) -> Self {
+        self.organize_by_time = organize_by_time;
+        self
+    }
+
+    pub fn with_temp_suffix(mut self, suffix: &str) -> Self {
+        self.temp_suffix = suffix.to_string();
+        self
+    }
+
+    pub fn with_area_subdirectories(mut self, separate_areas: bool) -> Self {
+        self.separate_areas = separate_areas;
+        self
+    }
+
+    pub fn with_staging(mut self, staging_path: &str) -> Self {
+        self.staging_path = Some(PathBuf::from(staging_path));
+        self
+    }
+
+    /// 启用入站暂存区：本次运行的下载写入 `<spool_path>/<run_id>/`
+    pub fn with_spool(mut self, spool_path: &str, run_id: &str) -> Self {
+        let spool_path = PathBuf::from(spool_path);
+        self.staging_path = Some(spool_path.join(run_id));
+        self.spool_path = Some(spool_path);
+        self
+    }
+
+    pub fn with_promote_after_run(mut self, promote_after_run: bool) -> Self {
+        self.promote_after_run = promote_after_run;
+        self
+    }
+
+    pub fn with_filename_template(mut self, template: FilenameTemplate) -> Self {
+        self.filename_template = Some(template);
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn with_orphan_temp_age(mut self, orphan_temp_age: Duration) -> Self {
+        self.orphan_temp_age = orphan_temp_age;
+        self
+    }
+
+    pub fn with_resume_incomplete(mut self, resume_incomplete: bool) -> Self {
+        self.resume_incomplete = resume_incomplete;
+        self
+    }
+
+    /// 设置下载清单中记录的本地位置，见 [`crate::manifest::Manifest::locations_by_filename`]
+    pub fn with_recorded_locations(mut self, locations: HashMap<String, PathBuf>) -> Self {
+        self.recorded_locations = Arc::new(locations);
+        self
+    }
+
+    pub fn with_decompressed(mut self, decompressed: bool) -> Self {
+        self.decompressed = decompressed;
+        self
+    }
+
+    /// 下载清单中记录的远程文件的本地位置
+    fn recorded_location(&self, remote_path: &str) -> Option<PathBuf> {
+        let filename = remote_path.rsplit('/').next()?;
+        self.recorded_locations.get(filename).cloned()
+    }
+
+    /// 应有的 HSD 分段按每个可接受的扩展名得到的文件名；未设置扩展名时只有标准文件名
+    fn segment_filenames(&self, expected: &HsdFilename) -> Vec<String> {
+        if self.extensions.is_empty() {
+            return vec![expected.to_string()];
+        }
+        self.extensions
+            .iter()
+            .map(|extension| {
+                HsdFilename {
+                    extension: extension.clone(),
+                    ..expected.clone()
+                }
+                .to_string()
+            })
+            .collect()
+    }
+
+    /// 远程文件名对应的本地文件名（按模板重写）
+    pub fn local_filename(&self, filename: &str) -> String {
+        match &self.filename_template {
+            Some(template) => template.apply(filename),
+            None => filename.to_string(),
+        }
+    }
+
+    /// 生成本地文件路径；目录按远程文件名确定，文件名按模板重写
+    pub fn generate_local_path(&self, remote_path: &str) -> PathBuf {
+        let filename = Path::new(remote_path)
+            .file_name()
+            .unwrap()
+            .to_string_lossy();
+
+        let parts = self.parse_filename(&filename);
+        let local_filename = self.local_filename(&filename);
+        let base_path = match parts.as_ref().and_then(|parts| parts.area.as_ref()) {
+            Some(area) if self.separate_areas => self.base_path.join(area),
+            _ => self.base_path.clone(),
+        };
+
+        if self.organize_by_time
+            && let Some(parts) = parts
+        {
+            return base_path
+                .join(&parts.year)
+                .join(&parts.month)
+                .join(&parts.day)
+                .join(&parts.hour)
+                .join(local_filename);
+        }
+
+        base_path.join(local_filename)
+    }
+
+    /// 文件在归档中的相对路径，以 `/` 分隔，用作存储后端中的对象键
+    pub fn archive_key(&self, remote_path: &str) -> String {
+        let local_path = self.generate_local_path(remote_path);
+        let relative = local_path
+            .strip_prefix(&self.base_path)
+            .unwrap_or(&local_path);
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// 生成暂存目录中的对应路径，未启用暂存时为空
+    pub fn generate_staging_path(&self, remote_path: &str) -> Option<PathBuf> {
+        let staging_path = self.staging_path.as_ref()?;
+        let local_path = self.generate_local_path(remote_path);
+        let relative = local_path
+            .strip_prefix(&self.base_path)
+            .unwrap_or(Path::new(local_path.file_name().unwrap()));
+        Some(staging_path.join(relative))
+    }
+
+    /// 暂存目录与入站暂存区中之前各次运行目录里的对应路径，本次运行的暂存目录在前
+    pub fn generate_staged_paths(&self, remote_path: &str) -> Vec<PathBuf> {
+        let Some(staged) = self.generate_staging_path(remote_path) else {
+            return Vec::new();
+        };
+        let mut paths = vec![staged.clone()];
+        if let Some(staging_path) = &self.staging_path
+            && let Ok(relative) = staged.strip_prefix(staging_path)
+        {
+            paths.extend(
+                self.spooled_runs()
+                    .into_iter()
+                    .filter(|run| run != staging_path)
+                    .map(|run| run.join(relative)),
+            );
+        }
+        paths
+    }
+
+    /// 入站暂存区中的运行目录，按运行编号（即时间）排序
+    pub fn spooled_runs(&self) -> Vec<PathBuf> {
+        let Some(spool_path) = &self.spool_path else {
+            return Vec::new();
+        };
+        let Ok(entries) = fs::read_dir(spool_path) else {
+            return Vec::new();
+        };
+        let mut runs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && !path.is_symlink())
+            .collect();
+        runs.sort();
+        runs
+    }
+
+    /// 下载写入的位置：启用暂存时为暂存目录，否则直接写入归档
+    pub fn generate_download_path(&self, remote_path: &str) -> PathBuf {
+        self.generate_staging_path(remote_path)
+            .unwrap_or_else(|| self.generate_local_path(remote_path))
+    }
+
+    /// 查找已下载完成的本地文件（归档或暂存目录中大小大于 0 的文件），返回路径与大小；
+    /// 都没有时查找下载清单中记录的位置，下载后解压过的再查找解压后的文件
+    pub fn find_stored(&self, remote_path: &str) -> Option<(PathBuf, u64)> {
+        let candidates = self.with_decompressed_paths(
+            std::iter::once(self.generate_local_path(remote_path))
+                .chain(self.generate_staged_paths(remote_path))
+                .chain(self.recorded_location(remote_path))
+                .collect(),
+        );
+        candidates.into_iter().find_map(|path| {
+            let size = fs::metadata(&path).ok()?.len();
+            (size > 0).then_some((path, size))
+        })
+    }
+
+    /// 下载后解压过时，在候选路径之后加上各自解压后的路径
+    fn with_decompressed_paths(&self, mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        if self.decompressed {
+            let decompressed: Vec<PathBuf> = paths
+                .iter()
+                .filter_map(|path| decompressed_path(path))
+                .collect();
+            paths.extend(decompressed);
+        }
+        paths
+    }
+
+    /// `find_stored` 找到的是解压后的文件（压缩文件已删除），其大小不能与远程文件比较
+    pub fn is_decompressed_copy(&self, stored: &Path, remote_path: &str) -> bool {
+        self.decompressed
+            && remote_path.ends_with(".bz2")
+            && !stored.to_string_lossy().ends_with(".bz2")
+    }
+
+    /// 查找同一时次、波段、区域与分段但文件名不同的本地旧版本（例如卫星编号或分辨率标记改变），
+    /// 返回旧文件路径与大小
+    pub fn find_previous_version(&self, remote_path: &str) -> Option<(PathBuf, u64)> {
+        let filename = Path::new(remote_path)
+            .file_name()?
+            .to_string_lossy()
+            .to_string();
+        let parsed = HsdFilename::parse(&filename)?;
+        let key = parsed.observation_key();
+        let local_filename = self.local_filename(&filename);
+        std::iter::once(self.generate_local_path(remote_path))
+            .chain(self.generate_staged_paths(remote_path))
+            .filter_map(|path| fs::read_dir(path.parent()?).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .find_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name == local_filename
+                    || HsdFilename::parse(&name).is_none_or(|other| other.observation_key() != key)
+                {
+                    return None;
+                }
+                let size = entry.metadata().ok()?.len();
+                (size > 0).then(|| (entry.path(), size))
+            })
+    }
+
+    /// 将暂存目录（或入站暂存区中之前的运行目录）中的文件（连同来源信息文件）移入归档，
+    /// 文件不在暂存目录时不做处理
+    pub fn commit_staged(&self, remote_path: &str) -> std::io::Result<()> {
+        let Some(staged) = self
+            .generate_staged_paths(remote_path)
+            .into_iter()
+            .find(|path| path.exists())
+        else {
+            return Ok(());
+        };
+        promote_file(&staged, &self.generate_local_path(remote_path))
+    }
+
+    /// 内容校验失败的文件移入隔离区的位置：`<base_path>/quarantine/<归档中的相对路径>.<时间>`，
+    /// 同一文件多次被隔离时互不覆盖
+    pub fn generate_quarantine_path(&self, remote_path: &str) -> PathBuf {
+        let local_path = self.generate_local_path(remote_path);
+        let relative = local_path
+            .strip_prefix(&self.base_path)
+            .unwrap_or(Path::new(local_path.file_name().unwrap()));
+        let mut quarantined = self
+            .base_path
+            .join(QUARANTINE_DIR)
+            .join(relative)
+            .into_os_string();
+        quarantined.push(format!(".{}", Utc::now().format("%Y%m%d%H%M%S")));
+        PathBuf::from(quarantined)
+    }
+
+    /// 生成临时文件路径
+    pub fn generate_temp_path(&self, local_path: &Path) -> PathBuf {
+        let mut temp_path = local_path.to_path_buf();
+        let mut filename = temp_path.file_name().unwrap().to_string_lossy().to_string();
+        filename.push_str(&self.temp_suffix);
+        temp_path.set_file_name(filename);
+        temp_path
+    }
+
+    /// 清理之前运行遗留的未完成下载文件，并删除因此（或因之前的运行）留下的空日期目录；
+    /// `orphan_temp_age` 内仍有写入的文件与 `interrupted` 中记录的中断时保留的文件不删除；
+    /// 启用 `resume_incomplete` 时全部保留，只删除空目录
+    pub fn cleanup_incomplete_downloads(
+        &self,
+        interrupted: &BTreeSet<PathBuf>,
+    ) -> Result<CleanupReport, Box<dyn std::error::Error>> {
+        let mut incomplete_files = Vec::new();
+        self.cleanup_directory(&self.base_path, &mut incomplete_files)?;
+        if let Some(staging_root) = self.spool_path.as_ref().or(self.staging_path.as_ref())
+            && !staging_root.starts_with(&self.base_path)
+        {
+            self.cleanup_directory(staging_root, &mut incomplete_files)?;
+        }
+
+        // 另一个实例正在写入的文件修改时间不断更新，只删除超过 orphan_temp_age 没有写入的文件
+        let now = SystemTime::now();
+        let mut report = CleanupReport::default();
+        for file in incomplete_files {
+            if self.resume_incomplete || interrupted.contains(&file) {
+                report.resumable.push(file);
+                continue;
+            }
+            let idle = fs::metadata(&file)
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| now.duration_since(modified).unwrap_or_default());
+            match idle {
+                Ok(idle) if idle < self.orphan_temp_age => report.kept.push(file),
+                Ok(_) => report.removed.push(file),
+                // 文件已被其他实例移走
+                Err(_) => {}
+            }
+        }
+
+        if !report.removed.is_empty() {
+            tracing::info!("发现 {} 个未完成的下载文件:", report.removed.len());
+            for file in &report.removed {
+                tracing::info!("  删除: {}", file.display());
+                if let Err(e) = fs::remove_file(file) {
+                    tracing::warn!("删除文件失败 {}: {}", file.display(), e);
+                }
+            }
+        }
+        if !report.kept.is_empty() {
+            tracing::info!(
+                "保留 {} 个 {} 分钟内仍有写入的下载文件（可能属于其他正在运行的实例）:",
+                report.kept.len(),
+                self.orphan_temp_age.as_secs() / 60
+            );
+            for file in &report.kept {
+                tracing::info!("  保留: {}", file.display());
+            }
+        }
+        if !report.resumable.is_empty() {
+            tracing::info!(
+                "保留 {} 个未完成的下载文件，将断点续传",
+                report.resumable.len()
+            );
+        }
+
+        let removed = self.remove_empty_directories()?;
+        if !removed.is_empty() {
+            tracing::info!("删除 {} 个空目录", removed.len());
+        }
+
+        Ok(report)
+    }
+
+    /// 移除某时次、区域与波段已保存的全部分段（归档与暂存目录中的文件及其来源信息文件），
+    /// 以便重新下载；`keep_old` 为 true 时将旧文件（连同来源信息文件）重命名为
+    /// `<文件名>.superseded-<时间>` 保留。
+    /// 返回被移除的文件
+    pub fn retire_band(
+        &self,
+        datetime: &NaiveDateTime,
+        area: &str,
+        band: &str,
+        keep_old: bool,
+    ) -> std::io::Result<Vec<PathBuf>> {
+        let suffix = format!(".superseded-{}", Utc::now().format("%Y%m%d%H%M%S"));
+        let mut retired = Vec::new();
+        let filenames = HsdFilename::expected(datetime, band, area)
+            .iter()
+            .flat_map(|expected| self.segment_filenames(expected))
+            .collect::<Vec<_>>();
+        for filename in filenames {
+            let candidates = std::iter::once(self.generate_local_path(&filename))
+                .chain(self.generate_staged_paths(&filename));
+            for path in candidates.filter(|path| path.exists()) {
+                let sidecar = crate::sidecar::sidecar_path(&path);
+                if keep_old {
+                    let mut kept = path.clone().into_os_string();
+                    kept.push(&suffix);
+                    let kept = PathBuf::from(kept);
+                    fs::rename(&path, &kept)?;
+                    if sidecar.exists() {
+                        fs::rename(&sidecar, crate::sidecar::sidecar_path(&kept))?;
+                    }
+                } else {
+                    fs::remove_file(&path)?;
+                    if sidecar.exists() {
+                        fs::remove_file(&sidecar)?;
+                    }
+                }
+                retired.push(path);
+            }
+        }
+        Ok(retired)
+    }
+
+    /// 删除下载目录与暂存目录下的空目录链（例如只剩空的 `YYYY/MM/DD/HH`），
+    /// 入站暂存区中已清空的运行目录一并删除；下载目录、暂存目录与入站暂存区本身保留，
+    /// 返回被删除的目录
+    pub fn remove_empty_directories(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let mut removed = Vec::new();
+        let staging_root = self.spool_path.as_ref().or(self.staging_path.as_ref());
+        for root in std::iter::once(&self.base_path).chain(staging_root) {
+            if root.is_dir() {
+                for entry in fs::read_dir(root)? {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        remove_if_empty(&path, &mut removed)?;
+                    }
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    fn cleanup_directory(
+        &self,
+        dir: &Path,
+        incomplete_files: &mut Vec<PathBuf>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !dir.exists() {
+            return Ok(());
+        }
+
+        let entries = fs::read_dir(dir)?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_dir() {
+                self.cleanup_directory(&path, incomplete_files)?;
+            } else if let Some(filename) = path.file_name() {
+                let filename_str = filename.to_string_lossy();
+                if filename_str.ends_with(&self.temp_suffix) {
+                    incomplete_files.push(path);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 检查波段数据完整性，每个观测区域分别统计；全圆盘的例行维护时次没有数据，不参与检查
+    pub fn check_band_completeness(
+        &self,
+        download_list: &[NaiveDateTime],
+        areas: &[String],
+        bands: &[String],
+    ) -> BandCompletenessReport {
+        let mut report = BandCompletenessReport::new();
+
+        for datetime in download_list {
+            for area in areas {
+                if area == "FLDK" && himawari::is_housekeeping_slot(datetime) {
+                    continue;
+                }
+                report
+                    .time_slots
+                    .push(self.check_time_slot(datetime, area, bands));
+            }
+        }
+
+        report
+    }
+
+    fn check_time_slot(
+        &self,
+        datetime: &NaiveDateTime,
+        area: &str,
+        bands: &[String],
+    ) -> TimeSlotReport {
+        let mut time_report = TimeSlotReport {
+            datetime: *datetime,
+            area: area.to_string(),
+            bands: Vec::new(),
+        };
+
+        for band in bands {
+            // 波段的全部分段（任一可接受的扩展名）都已下载才算完整；大小为各分段之和，路径为第一个分段。
+            // 按当前布局找不到的分段再查下载清单中记录的位置
+            let segments: Vec<PathBuf> = HsdFilename::expected(datetime, band, area)
+                .iter()
+                .map(|expected| {
+                    let filenames = self.segment_filenames(expected);
+                    let paths = self.with_decompressed_paths(
+                        filenames
+                            .iter()
+                            .map(|filename| self.generate_local_path(filename))
+                            .chain(
+                                filenames
+                                    .iter()
+                                    .filter_map(|filename| self.recorded_location(filename)),
+                            )
+                            .collect(),
+                    );
+                    paths
+                        .iter()
+                        .find(|path| path.exists())
+                        .unwrap_or(&paths[0])
+                        .clone()
+                })
+                .collect();
+            let exists = segments.iter().all(|path| path.exists());
+            let size = if exists {
+                segments
+                    .iter()
+                    .map(|path| fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+                    .sum()
+            } else {
+                0
+            };
+
+            time_report.bands.push(BandStatus {
+                band: band.clone(),
+                exists,
+                size,
+                path: segments[0].clone(),
+            });
+        }
+
+        time_report
+    }
+
+    fn parse_filename(&self, filename: &str) -> Option<FilenameParts> {
+        // HS_H09_20250717_0900_B03_FLDK_R05_S0101.DAT.bz2
+        let parts: Vec<&str> = filename.split('_').collect();
+        if parts.len() >= 4 {
+            let datetime_str = parts[2];
+            let time_str = parts[3];
+
+            if datetime_str.len() == 8 && time_str.len() == 4 {
+                return Some(FilenameParts {
+                    year: datetime_str[0..4].to_string(),
+                    month: datetime_str[4..6].to_string(),
+                    day: datetime_str[6..8].to_string(),
+                    hour: time_str[0..2].to_string(),
+                    area: parts.get(5).map(|area| area.to_string()),
+                });
+            }
+        }
+        None
+    }
+}
+
+/// `.bz2` 文件解压后的路径，其他文件为空
+pub(crate) fn decompressed_path(path: &Path) -> Option<PathBuf> {
+    let filename = path.file_name()?.to_string_lossy();
+    Some(path.with_file_name(filename.strip_suffix(".bz2")?))
+}
+
+/// 先处理子目录，再在目录为空时删除它，返回目录是否已删除
+fn remove_if_empty(dir: &Path, removed: &mut Vec<PathBuf>) -> std::io::Result<bool> {
+    let mut empty = true;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // 不跟随符号链接，避免删除链接目标中的目录
+        if path.is_symlink() || !path.is_dir() || !remove_if_empty(&path, removed)? {
+            empty = false;
+        }
+    }
+    if empty {
+        fs::remove_dir(dir)?;
+        removed.push(dir.to_path_buf());
+    }
+    Ok(empty)
+}
+
+/// 将暂存的文件连同来源信息文件移到归档中的位置
+pub(crate) fn promote_file(staged: &Path, archived: &Path) -> std::io::Result<()> {
+    if let Some(parent) = archived.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_file(staged, archived)?;
+
+    let staged_sidecar = crate::sidecar::sidecar_path(staged);
+    if staged_sidecar.exists() {
+        move_file(&staged_sidecar, &crate::sidecar::sidecar_path(archived))?;
+    }
+    Ok(())
+}
+
+/// 移动文件；暂存目录与归档不在同一文件系统时退回为复制后删除
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+#[derive(Debug)]
+struct FilenameParts {
+    year: String,
+    month: String,
+    day: String,
+    hour: String,
+    area: Option<String>,
+}
+
+/// 波段状态
+#[derive(Debug, Clone)]
+pub struct BandStatus {
+    pub band: String,
+    pub exists: bool,
+    pub size: u64,
+    pub path: PathBuf,
+}
+
+/// 时间段报告
+#[derive(Debug, Clone)]
+pub struct TimeSlotReport {
+    pub datetime: NaiveDateTime,
+    pub area: String,
+    pub bands: Vec<BandStatus>,
+}
+
+impl TimeSlotReport {
+    /// 该时次所有波段是否齐全
+    pub fn is_complete(&self) -> bool {
+        self.bands.iter().all(|band| band.exists)
+    }
+}
+
+/// 波段完整性报告
+#[derive(Debug, Clone)]
+pub struct BandCompletenessReport {
+    pub time_slots: Vec<TimeSlotReport>,
+}
+
+impl Default for BandCompletenessReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BandCompletenessReport {
+    pub fn new() -> Self {
+        Self {
+            time_slots: Vec::new(),
+        }
+    }
+
+    /// 按观测区域汇总：(区域, 完整时次数, 总时次数)
+    pub fn area_summary(&self) -> Vec<(String, usize, usize)> {
+        let mut summary: Vec<(String, usize, usize)> = Vec::new();
+        for slot in &self.time_slots {
+            let index = match summary.iter().position(|(area, _, _)| *area == slot.area) {
+                Some(index) => index,
+                None => {
+                    summary.push((slot.area.clone(), 0, 0));
+                    summary.len() - 1
+                }
+            };
+            if slot.is_complete() {
+                summary[index].1 += 1;
+            }
+            summary[index].2 += 1;
+        }
+        summary
+    }
+
+    pub fn print_report(&self) {
+        tracing::info!("=== 波段数据完整性报告 ===");
+        for slot in &self.time_slots {
+            tracing::info!(
+                "时间: {} 区域: {}",
+                slot.datetime.format("%Y-%m-%d %H:%M"),
+                slot.area
+            );
+            for band in &slot.bands {
+                let status = if band.exists { "✓" } else { "✗" };
+                tracing::info!("  {} {}: {} bytes", status, band.band, band.size);
+            }
+        }
+        for (area, complete, total) in self.area_summary() {
+            tracing::info!("区域 {}: 完整时次 {}/{}", area, complete, total);
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let time_slots: Vec<Value> = self
+            .time_slots
+            .iter()
+            .map(|slot| {
+                let bands: Vec<Value> = slot
+                    .bands
+                    .iter()
+                    .map(|band| {
+                        json!({
+                            "band": band.band,
+                            "exists": band.exists,
+                            "size": band.size,
+                            "path": band.path,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "datetime": slot.datetime.to_string(),
+                    "area": slot.area,
+                    "complete": slot.is_complete(),
+                    "bands": bands,
+                })
+            })
+            .collect();
+        let areas: Vec<Value> = self
+            .area_summary()
+            .into_iter()
+            .map(|(area, complete, total)| {
+                json!({ "area": area, "complete_slots": complete, "total_slots": total })
+            })
+            .collect();
+        json!({ "areas": areas, "time_slots": time_slots })
+    }
+
+    /// 每个时次、观测区域与波段一行
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("datetime,area,band,exists,size,path\n");
+        for slot in &self.time_slots {
+            for band in &slot.bands {
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{}\n",
+                    slot.datetime,
+                    slot.area,
+                    band.band,
+                    band.exists,
+                    band.size,
+                    csv_field(&band.path.to_string_lossy())
+                ));
+            }
+        }
+        csv
+    }
+}
@@ -0,0 +1,69 @@
+//! OpenTelemetry 追踪导出（`otel` 特性）
+
+use crate::config::TelemetryConfig;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// 追踪导出器；销毁时导出剩余的 span
+pub struct TelemetryGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("导出追踪数据失败: {}", e);
+        }
+    }
+}
+
+/// 按配置注册只含 OTLP 追踪导出的全局 subscriber，未配置接收地址时返回 `None`
+pub fn init(
+    config: &TelemetryConfig,
+) -> Result<Option<TelemetryGuard>, Box<dyn std::error::Error>> {
+    let Some((guard, layer)) = layer(config)? else {
+        return Ok(None);
+    };
+    tracing_subscriber::registry().with(layer).try_init()?;
+    if let Some(endpoint) = &config.otlp_endpoint {
+        println!("追踪数据将导出到: {}", endpoint);
+    }
+    Ok(Some(guard))
+}
+
+/// 按配置创建 OTLP 追踪导出层，供与其他层（如日志输出）组合后注册；未配置接收地址时返回 `None`
+#[allow(clippy::type_complexity)]
+pub fn layer<S>(
+    config: &TelemetryConfig,
+) -> Result<Option<(TelemetryGuard, OpenTelemetryLayer<S, SdkTracer>)>, Box<dyn std::error::Error>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build();
+
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    let layer = tracing_opentelemetry::layer().with_tracer(tracer);
+    Ok(Some((TelemetryGuard { provider }, layer)))
+}
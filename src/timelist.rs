@@ -0,0 +1,299 @@
+use crate::himawari;
+use chrono::{Duration, NaiveDateTime, Timelike, Utc};
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::{fmt, io};
+
+const DATE_FMT: &str = r#"%Y-%m-%d %H:%M:%S"#;
+/// 数据从观测到在服务器上发布的大致延迟（分钟）
+pub const PUBLISH_DELAY_MINUTES: i64 = 30;
+
+/// 交互式输入时间段，并按产品观测间隔 `cadence_minutes` 生成下载时间列表；
+/// 未输入、格式无效、结束时间早于开始时间或晚于当前时间时返回错误
+pub fn get_download_time_list(cadence_minutes: i64) -> Result<Vec<NaiveDateTime>, String> {
+    get_download_time_list_aligned(cadence_minutes, 0)
+}
+
+/// 与 [`get_download_time_list`] 相同，时次为当天 0 点起 `offset_minutes` 分钟后、每隔 `cadence_minutes` 分钟一个
+pub fn get_download_time_list_aligned(
+    cadence_minutes: i64,
+    offset_minutes: i64,
+) -> Result<Vec<NaiveDateTime>, String> {
+    let current_time = Utc::now();
+    println!("Current UTC Time: {}", current_time.format(DATE_FMT));
+
+    let current_time = current_time.naive_utc();
+
+    let download_period =
+        input_time()?.ok_or_else(|| format!("未输入有效的下载时间，格式应为 {}", DATE_FMT))?;
+    if download_period.start_time > download_period.end_time {
+        return Err("结束时间早于开始时间".to_string());
+    }
+    if download_period.end_time > current_time {
+        return Err("输入的时间晚于当前时间".to_string());
+    }
+    println!("Download Period: {}", download_period);
+
+    let download_time_list =
+        generate_download_time_list(&download_period, cadence_minutes, offset_minutes)
+            .map_err(|e| format!("生成下载时间列表失败: {}", e))?;
+    if download_time_list.is_empty() {
+        return Err("输入的时间段内没有观测时次".to_string());
+    }
+    Ok(download_time_list)
+}
+
+/// 最近一个应当已经发布的观测时次（当前时间回退 `publish_delay_minutes` 后向下取整到 10 分钟）
+pub fn latest_published_slot(publish_delay_minutes: i64) -> NaiveDateTime {
+    let time = Utc::now().naive_utc() - Duration::minutes(publish_delay_minutes);
+    let minute = time.minute() - time.minute() % himawari::TIMELINE_MINUTES as u32;
+    strip_seconds(time.with_minute(minute).unwrap())
+}
+
+/// 解析 `YYYY-MM-DD HH:MM:SS` 格式的 UTC 时间
+pub fn parse_time(input: &str) -> Result<NaiveDateTime, String> {
+    convert_input_str_to_naive_date_time(input.trim())
+        .map_err(|_| format!("无效的时间 {}，格式应为 YYYY-MM-DD HH:MM:SS", input))
+}
+
+/// 生成 `start` 到 `end`（含）之间按 `cadence_minutes` 对齐的观测时次
+pub fn time_list_between(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    cadence_minutes: i64,
+) -> Result<Vec<NaiveDateTime>, String> {
+    time_list_aligned(start, end, cadence_minutes, 0)
+}
+
+/// 生成 `start` 到 `end`（含）之间的时次：当天 0 点起 `offset_minutes` 分钟后、每隔 `cadence_minutes` 分钟一个，
+/// 例如每 30 分钟一次、在整点与半点后 5 分钟发布的镜像为 `(30, 5)`
+pub fn time_list_aligned(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    cadence_minutes: i64,
+    offset_minutes: i64,
+) -> Result<Vec<NaiveDateTime>, String> {
+    if cadence_minutes <= 0 {
+        return Err(format!("时次间隔 {} 分钟无效", cadence_minutes));
+    }
+    if start > end {
+        return Err("结束时间早于开始时间".to_string());
+    }
+    let period = DownloadTime {
+        start_time: start,
+        end_time: end,
+    };
+    generate_download_time_list(&period, cadence_minutes, offset_minutes)
+        .map_err(ToString::to_string)
+}
+
+/// 从文件名中的 `YYYYMMDD_hhmm` 部分（HSD 与 NetCDF 文件名中的观测时间）取出观测时次
+pub fn slot_from_filename(filename: &str) -> Option<NaiveDateTime> {
+    let parts: Vec<&str> = filename.split('_').collect();
+    parts.windows(2).find_map(|pair| {
+        let (date, time) = (pair[0], pair[1].get(..4)?);
+        if date.len() != 8 || !date.bytes().chain(time.bytes()).all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        NaiveDateTime::parse_from_str(&format!("{}{}", date, time), "%Y%m%d%H%M").ok()
+    })
+}
+
+/// 续传令牌中时间的格式
+const TOKEN_TIME_FMT: &str = "%Y%m%dT%H%M";
+
+/// 续传令牌：记录一次回补的产品、下一个未完成的时次与原定的结束时次，
+/// 格式为 `<产品>:<下一个时次>:<结束时次>`，例如 `hsd:20240105T0300:20240131T2350`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeToken {
+    pub product: String,
+    pub next: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl ResumeToken {
+    /// 从令牌中的下一个时次继续生成原定时间段内的下载时间列表
+    pub fn time_list(&self, cadence_minutes: i64) -> Result<Vec<NaiveDateTime>, String> {
+        time_list_between(self.next, self.end, cadence_minutes)
+    }
+
+    /// 与 [`ResumeToken::time_list`] 相同，时次按 `offset_minutes` 偏移，见 [`time_list_aligned`]
+    pub fn time_list_aligned(
+        &self,
+        cadence_minutes: i64,
+        offset_minutes: i64,
+    ) -> Result<Vec<NaiveDateTime>, String> {
+        time_list_aligned(self.next, self.end, cadence_minutes, offset_minutes)
+    }
+}
+
+impl fmt::Display for ResumeToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.product,
+            self.next.format(TOKEN_TIME_FMT),
+            self.end.format(TOKEN_TIME_FMT)
+        )
+    }
+}
+
+impl FromStr for ResumeToken {
+    type Err = String;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "无效的续传令牌 {}，格式应为 <产品>:<YYYYMMDDTHHMM>:<YYYYMMDDTHHMM>",
+                token
+            )
+        };
+        let mut parts = token.trim().rsplitn(3, ':');
+        let (Some(end), Some(next), Some(product)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let parse =
+            |time| NaiveDateTime::parse_from_str(time, TOKEN_TIME_FMT).map_err(|_| invalid());
+        let (next, end) = (parse(next)?, parse(end)?);
+        if product.is_empty() || next > end {
+            return Err(invalid());
+        }
+        Ok(Self {
+            product: product.to_string(),
+            next,
+            end,
+        })
+    }
+}
+
+/// 去掉全圆盘的例行维护时次（这些时次没有全圆盘观测），返回去掉的时次数
+pub fn skip_housekeeping_slots(download_time_list: &mut Vec<NaiveDateTime>) -> usize {
+    let before = download_time_list.len();
+    download_time_list.retain(|datetime| !himawari::is_housekeeping_slot(datetime));
+    before - download_time_list.len()
+}
+
+struct DownloadTime {
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+}
+
+impl fmt::Display for DownloadTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Download start time: {}\nDownload end time: {}",
+            self.start_time, self.end_time
+        )
+        .expect("Failed to format DownloadTime for display");
+        Ok(())
+    }
+}
+
+fn convert_input_str_to_naive_date_time(
+    input_time: &str,
+) -> Result<NaiveDateTime, chrono::ParseError> {
+    NaiveDateTime::parse_from_str(input_time, DATE_FMT)
+}
+
+/// 从标准输入读取一行，读取失败时返回错误信息
+fn read_input_line() -> Result<String, String> {
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| format!("读取输入失败: {}", e))?;
+    Ok(line)
+}
+
+fn return_naive_date_time() -> Result<Option<NaiveDateTime>, String> {
+    let download_time = read_input_line()?;
+
+    let download_time = download_time.trim();
+    if download_time.is_empty() {
+        return Ok(None);
+    }
+    Ok(convert_input_str_to_naive_date_time(download_time).ok())
+}
+
+fn input_time() -> Result<Option<DownloadTime>, String> {
+    println!("Input download start time(UTC Time): ({})", DATE_FMT);
+    let Some(start_time) = return_naive_date_time()? else {
+        return Ok(None);
+    };
+
+    println!(
+        "Input download end time(UTC Time): ({})(Use start time instead if input nothing.)",
+        DATE_FMT
+    );
+    let end_time = return_naive_date_time()?.unwrap_or(start_time); // if end_time is nothing, we will use the start time.
+
+    let download_period = DownloadTime {
+        start_time,
+        end_time,
+    };
+    Ok(Some(download_period))
+}
+
+fn generate_download_time_list(
+    original_time_period: &DownloadTime,
+    cadence_minutes: i64,
+    offset_minutes: i64,
+) -> Result<Vec<NaiveDateTime>, &str> {
+    let mut start_time = original_time_period.start_time;
+
+    // 将开始时间对齐到当天第一个时次（0 点后 offset_minutes 分钟）之后的整数倍间隔
+    let max_count = cadence_minutes;
+    let mut count = 0;
+    let correct_start_min = loop {
+        let minute_of_day = (start_time.hour() * 60 + start_time.minute()) as i64;
+        if count >= max_count {
+            break None;
+        } else if (minute_of_day - offset_minutes).rem_euclid(cadence_minutes) == 0 {
+            break Some(strip_seconds(start_time));
+        } else {
+            start_time += Duration::minutes(1);
+            count += 1
+        }
+    };
+
+    match correct_start_min {
+        Some(correct_start_min) => {
+            let download_time_list: Vec<NaiveDateTime> = generate_intervals(
+                correct_start_min,
+                original_time_period.end_time,
+                cadence_minutes,
+            );
+            Ok(download_time_list)
+        }
+        None => Err("Failed to generate download time list."),
+    }
+}
+
+fn strip_seconds(dt: NaiveDateTime) -> NaiveDateTime {
+    dt.with_second(0)
+        .and_then(|dt| dt.with_nanosecond(0))
+        .unwrap()
+}
+
+fn generate_intervals(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    step_minutes: i64,
+) -> Vec<NaiveDateTime> {
+    // 每 step_minutes 分钟一个间隔
+    let step = Duration::minutes(step_minutes);
+    // 计算总间隔数
+    let total_minutes = (end - start).num_minutes();
+    let count = (total_minutes / step_minutes) + 1; // 包含两端
+
+    let mut times = Vec::with_capacity(count as usize);
+
+    let mut current = start;
+    while current <= end {
+        times.push(current);
+        current += step;
+    }
+    times
+}
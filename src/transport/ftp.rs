@@ -0,0 +1,670 @@
+//! FTP 与显式 TLS 的 FTPS（`AUTH TLS`）传输：被动模式传输数据，服务器支持 `MLSD` 时列目录一并取得文件属性，
+//! 否则用 `NLST` 列目录、`SIZE`/`MDTM` 查询属性；以 `REST` 命令断点续传。
+//!
+//! 每个通道是一条独立登录的控制连接，同一时刻只进行一次传输。FTPS 的证书按系统的根证书验证
+//! （可用环境变量 `SSL_CERT_FILE` 指定证书文件），数据连接同样加密并复用控制连接的 TLS 会话。
+
+use super::{
+    ConnectError, ConnectStage, Credentials, RemoteConnection, RemoteEntry, RemoteMetadata,
+    RemoteSession,
+};
+use crate::failure::{DownloadError, FailureKind};
+use chrono::NaiveDateTime;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// 未指定超时时等待服务器响应与数据的时间
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 连接 `host`（`主机:端口`）并登录；`secure` 时先以 `AUTH TLS` 加密控制连接。
+/// `client_id` 去掉 `SSH-2.0-` 前缀后以 `CLNT` 命令告知服务器，服务器不支持时忽略
+pub(super) fn connect(
+    tcp: TcpStream,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    timeout: Option<Duration>,
+    client_id: Option<&str>,
+    secure: bool,
+) -> Result<Arc<dyn RemoteSession>, ConnectError> {
+    let password = match credentials {
+        Credentials::Password(password) => password,
+        Credentials::PublicKey { .. } => {
+            return Err(ConnectError::new(
+                ConnectStage::Auth,
+                host,
+                "FTP 传输只支持密码认证",
+            ));
+        }
+    };
+    let tls = if secure {
+        Some(tls_settings(host).map_err(|e| ConnectError::new(ConnectStage::Handshake, host, e))?)
+    } else {
+        None
+    };
+    let settings = Arc::new(FtpSettings {
+        host: host.to_string(),
+        username: if username.is_empty() {
+            "anonymous".to_string()
+        } else {
+            username.to_string()
+        },
+        password: password.clone(),
+        client_id: client_id.map(|id| id.trim_start_matches("SSH-2.0-").to_string()),
+        timeout: timeout.unwrap_or(DEFAULT_TIMEOUT),
+        tls,
+    });
+    let control = Control::login(tcp, &settings)?;
+    Ok(Arc::new(FtpSession {
+        settings,
+        idle: Mutex::new(Some(control)),
+    }))
+}
+
+/// 按系统根证书验证服务器证书的 TLS 配置；所有连接共用同一配置，数据连接可复用控制连接的 TLS 会话
+fn tls_settings(host: &str) -> Result<TlsSettings, Box<dyn Error>> {
+    let mut roots = RootCertStore::empty();
+    let (added, _) =
+        roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    if added == 0 {
+        return Err("没有可用的系统根证书，无法验证 FTPS 服务器证书".into());
+    }
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let name = host
+        .rsplit_once(':')
+        .map_or(host, |(name, _)| name)
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    Ok(TlsSettings {
+        config: Arc::new(config),
+        server_name: ServerName::try_from(name.to_string())?,
+    })
+}
+
+#[derive(Clone)]
+struct TlsSettings {
+    config: Arc<ClientConfig>,
+    server_name: ServerName<'static>,
+}
+
+impl TlsSettings {
+    fn wrap(&self, tcp: TcpStream) -> io::Result<Stream> {
+        let connection = ClientConnection::new(self.config.clone(), self.server_name.clone())
+            .map_err(io::Error::other)?;
+        Ok(Stream::Tls(Box::new(StreamOwned::new(connection, tcp))))
+    }
+}
+
+/// 登录所需的参数，通道的控制连接断开后据此重新登录
+struct FtpSettings {
+    host: String,
+    username: String,
+    password: String,
+    client_id: Option<String>,
+    timeout: Duration,
+    tls: Option<TlsSettings>,
+}
+
+struct FtpSession {
+    settings: Arc<FtpSettings>,
+    /// 登录时检查认证所用的控制连接，由第一个通道接管
+    idle: Mutex<Option<Control>>,
+}
+
+impl RemoteSession for FtpSession {
+    fn open_channel(&self) -> Result<Box<dyn RemoteConnection>, Box<dyn Error>> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let control = match idle {
+            Some(control) => control,
+            None => Control::reconnect(&self.settings)?,
+        };
+        Ok(Box::new(FtpConnection {
+            settings: self.settings.clone(),
+            control: Mutex::new(Some(control)),
+        }))
+    }
+}
+
+/// 控制连接或数据连接，FTPS 时经过 TLS
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Stream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Stream::Plain(tcp) => tcp,
+            Stream::Tls(tls) => &tls.sock,
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(tcp) => tcp.read(buf),
+            Stream::Tls(tls) => tls.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(tcp) => tcp.write(buf),
+            Stream::Tls(tls) => tls.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(tcp) => tcp.flush(),
+            Stream::Tls(tls) => tls.flush(),
+        }
+    }
+}
+
+/// 服务器的应答：三位应答码与（多行时合并的）文本
+struct Reply {
+    code: u16,
+    text: String,
+}
+
+/// 已登录的控制连接
+struct Control {
+    stream: BufReader<Stream>,
+    /// 服务器是否支持 `MLSD`
+    mlsd: bool,
+    /// 读写失败后连接状态未知，不再使用
+    broken: bool,
+}
+
+impl Control {
+    /// 重新建立 TCP 连接并登录
+    fn reconnect(settings: &FtpSettings) -> Result<Self, ConnectError> {
+        let tcp = TcpStream::connect(&settings.host)
+            .map_err(|e| ConnectError::new(ConnectStage::Tcp, &settings.host, e))?;
+        Self::login(tcp, settings)
+    }
+
+    fn login(tcp: TcpStream, settings: &FtpSettings) -> Result<Self, ConnectError> {
+        let host = settings.host.as_str();
+        let handshake = |e: Box<dyn Error>| ConnectError::new(ConnectStage::Handshake, host, e);
+        set_timeouts(&tcp, settings.timeout)
+            .map_err(|e| ConnectError::new(ConnectStage::Tcp, host, e))?;
+        let mut control = Control {
+            stream: BufReader::new(Stream::Plain(tcp)),
+            mlsd: false,
+            broken: false,
+        };
+
+        // 120 表示服务器稍后就绪
+        let mut greeting = control.reply().map_err(|e| handshake(e.into()))?;
+        while greeting.code == 120 {
+            greeting = control.reply().map_err(|e| handshake(e.into()))?;
+        }
+        if greeting.code != 220 {
+            return Err(handshake(
+                format!("服务器拒绝连接: {} {}", greeting.code, greeting.text).into(),
+            ));
+        }
+
+        if let Some(tls) = &settings.tls {
+            control.expect("AUTH TLS", &[234]).map_err(handshake)?;
+            let Stream::Plain(tcp) = control.stream.into_inner() else {
+                unreachable!("控制连接尚未加密");
+            };
+            control.stream = BufReader::new(tls.wrap(tcp).map_err(|e| handshake(e.into()))?);
+            control.expect("PBSZ 0", &[200]).map_err(handshake)?;
+            control.expect("PROT P", &[200]).map_err(handshake)?;
+        }
+
+        let auth = |e: Box<dyn Error>| ConnectError::new(ConnectStage::Auth, host, e);
+        let user = control
+            .command(&format!("USER {}", settings.username))
+            .map_err(|e| handshake(e.into()))?;
+        match user.code {
+            230 => {}
+            331 => {
+                control
+                    .expect(&format!("PASS {}", settings.password), &[230, 202])
+                    .map_err(|e| match FailureKind::classify(e.as_ref()) {
+                        FailureKind::AuthFailed => auth("用户名或密码错误".into()),
+                        _ => handshake(e),
+                    })?;
+            }
+            530 => return Err(auth(format!("服务器拒绝用户: {}", user.text).into())),
+            code => return Err(handshake(format!("USER: {} {}", code, user.text).into())),
+        }
+
+        control.expect("TYPE I", &[200]).map_err(handshake)?;
+        if let Some(client_id) = &settings.client_id {
+            control
+                .command(&format!("CLNT {}", client_id))
+                .map_err(|e| handshake(e.into()))?;
+        }
+        let features = control.command("FEAT").map_err(|e| handshake(e.into()))?;
+        control.mlsd = features.code == 211
+            && features.text.lines().any(|line| {
+                line.trim().eq_ignore_ascii_case("MLSD") || line.trim().starts_with("MLST")
+            });
+        Ok(control)
+    }
+
+    fn tcp(&self) -> &TcpStream {
+        self.stream.get_ref().tcp()
+    }
+
+    /// 读取一个应答，多行应答的各行以换行合并
+    fn reply(&mut self) -> io::Result<Reply> {
+        let result = self.read_reply();
+        self.broken |= result.is_err();
+        result
+    }
+
+    fn read_reply(&mut self) -> io::Result<Reply> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "服务器关闭了控制连接",
+            ));
+        }
+        let code = line
+            .get(..3)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("无效的应答: {}", line.trim_end()),
+                )
+            })?;
+        let mut text = line.get(4..).unwrap_or_default().trim_end().to_string();
+        // 多行应答以 `123-` 开始，以 `123 ` 结束
+        if line.as_bytes().get(3) == Some(&b'-') {
+            let end = format!("{} ", code);
+            loop {
+                line.clear();
+                if self.stream.read_line(&mut line)? == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "多行应答未结束时连接关闭",
+                    ));
+                }
+                if line.starts_with(&end) {
+                    text.push('\n');
+                    text.push_str(line[4..].trim_end());
+                    break;
+                }
+                text.push('\n');
+                text.push_str(line.trim_end());
+            }
+        }
+        Ok(Reply { code, text })
+    }
+
+    fn command(&mut self, command: &str) -> io::Result<Reply> {
+        let stream = self.stream.get_mut();
+        let result = stream
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .and_then(|()| stream.flush());
+        self.broken |= result.is_err();
+        result?;
+        self.reply()
+    }
+
+    /// 发送命令，应答码不在 `codes` 中时按应答码返回可分类的错误
+    fn expect(&mut self, command: &str, codes: &[u16]) -> Result<Reply, Box<dyn Error>> {
+        let reply = self.command(command)?;
+        if codes.contains(&reply.code) {
+            return Ok(reply);
+        }
+        // 不在错误信息中显示密码
+        let shown = match command.split_once(' ') {
+            Some(("PASS", _)) => "PASS ***",
+            _ => command,
+        };
+        Err(reply_error(shown, &reply))
+    }
+
+    /// 以被动模式打开数据连接，优先使用 `EPSV`；连接到控制连接的服务器地址，不使用应答中可能是内网的地址
+    fn open_data(&mut self, settings: &FtpSettings) -> Result<Stream, Box<dyn Error>> {
+        let reply = self.command("EPSV")?;
+        let port = if reply.code == 229 {
+            parse_epsv(&reply.text)
+        } else {
+            parse_pasv(&self.expect("PASV", &[227])?.text)
+        }
+        .ok_or_else(|| format!("无法解析被动模式应答: {}", reply.text))?;
+        let address = SocketAddr::new(self.tcp().peer_addr()?.ip(), port);
+        let tcp = TcpStream::connect_timeout(&address, settings.timeout)?;
+        set_timeouts(&tcp, settings.timeout)?;
+        Ok(match &settings.tls {
+            Some(tls) => tls.wrap(tcp)?,
+            None => Stream::Plain(tcp),
+        })
+    }
+
+    /// 数据传输结束后读取服务器的完成应答
+    fn finish_transfer(&mut self, command: &str) -> Result<(), Box<dyn Error>> {
+        let reply = self.reply()?;
+        if matches!(reply.code, 226 | 250) {
+            Ok(())
+        } else {
+            Err(reply_error(command, &reply))
+        }
+    }
+
+    /// 执行列目录类命令，返回数据连接上的全部文本行
+    fn list(
+        &mut self,
+        settings: &FtpSettings,
+        command: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut data = self.open_data(settings)?;
+        self.expect(command, &[125, 150])?;
+        let mut listing = Vec::new();
+        read_to_end(&mut data, &mut listing)?;
+        drop(data);
+        self.finish_transfer(command)?;
+        Ok(String::from_utf8_lossy(&listing)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+fn set_timeouts(tcp: &TcpStream, timeout: Duration) -> io::Result<()> {
+    tcp.set_read_timeout(Some(timeout))?;
+    tcp.set_write_timeout(Some(timeout))
+}
+
+/// 读到数据连接结束；服务器未发送 TLS close_notify 就关闭连接时同样视为结束，是否完整由完成应答确认
+fn read_to_end(data: &mut Stream, buffer: &mut Vec<u8>) -> io::Result<()> {
+    match data.read_to_end(buffer) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+        result => result.map(|_| ()),
+    }
+}
+
+/// 按应答码返回可分类的错误
+fn reply_error(command: &str, reply: &Reply) -> Box<dyn Error> {
+    let message = format!("{}: {} {}", command, reply.code, reply.text);
+    match reply.code {
+        530 => Box::new(DownloadError {
+            kind: FailureKind::AuthFailed,
+            message,
+        }),
+        550 => Box::new(io::Error::new(io::ErrorKind::NotFound, message)),
+        421 | 425 | 426 => Box::new(io::Error::new(io::ErrorKind::ConnectionReset, message)),
+        _ => message.into(),
+    }
+}
+
+/// `229 Entering Extended Passive Mode (|||6446|)`
+fn parse_epsv(text: &str) -> Option<u16> {
+    let start = text.find('(')?;
+    let end = text[start..].find(')')? + start;
+    text[start + 1..end]
+        .trim_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .ok()
+}
+
+/// `227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)`
+fn parse_pasv(text: &str) -> Option<u16> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let numbers: Vec<u16> = text[start..]
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .take(6)
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [_, _, _, _, high, low] if high < 256 && low < 256 => Some(high * 256 + low),
+        _ => None,
+    }
+}
+
+/// `MDTM` 与 `MLSD` 的 `modify` 时间（`YYYYMMDDhhmmss[.sss]`，UTC）
+fn parse_time(value: &str) -> Option<u64> {
+    let time = NaiveDateTime::parse_from_str(value.get(..14)?, "%Y%m%d%H%M%S").ok()?;
+    u64::try_from(time.and_utc().timestamp()).ok()
+}
+
+/// `MLSD` 的一行：`type=file;size=1024;modify=20240101000512; 文件名`
+struct Fact {
+    name: String,
+    kind: String,
+    size: Option<u64>,
+    modified: Option<u64>,
+}
+
+fn parse_mlsd_line(line: &str) -> Option<Fact> {
+    let (facts, name) = line.split_once(' ')?;
+    let mut fact = Fact {
+        name: name.to_string(),
+        kind: String::new(),
+        size: None,
+        modified: None,
+    };
+    for item in facts.split(';') {
+        let Some((key, value)) = item.split_once('=') else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "type" => fact.kind = value.to_ascii_lowercase(),
+            "size" => fact.size = value.parse().ok(),
+            "modify" => fact.modified = parse_time(value),
+            _ => {}
+        }
+    }
+    Some(fact)
+}
+
+struct FtpConnection {
+    settings: Arc<FtpSettings>,
+    /// 控制连接，读写失败后置空，下次使用时重新登录
+    control: Mutex<Option<Control>>,
+}
+
+impl FtpConnection {
+    fn control(&self) -> MutexGuard<'_, Option<Control>> {
+        self.control.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 在控制连接上执行操作，连接已断开时先重新登录；操作中读写失败的连接随之丢弃
+    fn with_control<T>(
+        &self,
+        operation: impl FnOnce(&mut Control) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut guard = self.control();
+        let control = match guard.take() {
+            Some(control) => guard.insert(control),
+            None => guard.insert(Control::reconnect(&self.settings)?),
+        };
+        let result = operation(control);
+        if control.broken {
+            *guard = None;
+        }
+        result
+    }
+
+    fn list_facts(&self, dir: &str) -> Result<Vec<(String, Fact)>, Box<dyn Error>> {
+        let dir = dir.trim_end_matches('/');
+        let lines = self.with_control(|control| {
+            control.list(&self.settings, &format!("MLSD {}", command_path(dir)?))
+        })?;
+        Ok(lines
+            .iter()
+            .filter_map(|line| parse_mlsd_line(line))
+            .filter(|fact| !matches!(fact.kind.as_str(), "cdir" | "pdir"))
+            .map(|fact| (format!("{}/{}", dir, fact.name), fact))
+            .collect())
+    }
+
+    fn supports_mlsd(&self) -> Result<bool, Box<dyn Error>> {
+        self.with_control(|control| Ok(control.mlsd))
+    }
+}
+
+/// 路径中不能有换行，否则会被当作另一条命令
+fn command_path(path: &str) -> Result<&str, Box<dyn Error>> {
+    if path.contains(['\r', '\n']) {
+        return Err(format!("远程路径包含换行: {:?}", path).into());
+    }
+    Ok(if path.is_empty() { "/" } else { path })
+}
+
+impl RemoteConnection for FtpConnection {
+    fn list_directory(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        if self.supports_mlsd()? {
+            let mut paths: Vec<String> = self
+                .list_facts(dir)?
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect();
+            paths.sort();
+            return Ok(paths);
+        }
+        let dir = dir.trim_end_matches('/');
+        let lines = self.with_control(|control| {
+            control.list(&self.settings, &format!("NLST {}", command_path(dir)?))
+        })?;
+        // 服务器可能返回文件名或完整路径
+        let mut paths: Vec<String> = lines
+            .iter()
+            .filter_map(|line| line.trim_end().rsplit('/').next())
+            .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+            .map(|name| format!("{}/{}", dir, name))
+            .collect();
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
+
+    /// 支持 `MLSD` 时使用其返回的属性；否则逐个查询，无法取得大小的条目（子目录）不列出
+    fn list_directory_entries(&self, dir: &str) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+        if !self.supports_mlsd()? {
+            let mut entries = Vec::new();
+            for path in self.list_directory(dir)? {
+                match self.metadata(&path) {
+                    Ok(metadata) => entries.push(RemoteEntry { path, metadata }),
+                    Err(e) if FailureKind::classify(e.as_ref()) == FailureKind::RemoteMissing => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            return Ok(entries);
+        }
+        let mut entries = Vec::new();
+        for (path, fact) in self.list_facts(dir)? {
+            let metadata = match fact.size {
+                Some(size) => RemoteMetadata {
+                    size,
+                    modified: fact.modified,
+                },
+                None if fact.kind == "dir" => RemoteMetadata {
+                    size: 0,
+                    modified: fact.modified,
+                },
+                None => self.metadata(&path)?,
+            };
+            entries.push(RemoteEntry { path, metadata });
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &str) -> Result<RemoteMetadata, Box<dyn Error>> {
+        self.with_control(|control| {
+            let path = command_path(path)?;
+            let size = control.expect(&format!("SIZE {}", path), &[213])?;
+            let size = size
+                .text
+                .trim()
+                .parse()
+                .map_err(|_| format!("SIZE {}: 无效的大小 {}", path, size.text))?;
+            // MDTM 不是所有服务器都支持
+            let modified = control.command(&format!("MDTM {}", path))?;
+            let modified = (modified.code == 213)
+                .then(|| parse_time(modified.text.trim()))
+                .flatten();
+            Ok(RemoteMetadata { size, modified })
+        })
+    }
+
+    fn open_at(&self, path: &str, offset: u64) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        let command = format!("RETR {}", command_path(path)?);
+        let data = self.with_control(|control| {
+            let data = control.open_data(&self.settings)?;
+            if offset > 0 {
+                control.expect(&format!("REST {}", offset), &[350])?;
+            }
+            control.expect(&command, &[125, 150])?;
+            Ok(data)
+        })?;
+        Ok(Box::new(FtpReader {
+            connection: self,
+            data: Some(data),
+            command,
+        }))
+    }
+}
+
+/// 正在传输的文件；读完后确认服务器的完成应答，中途放弃时丢弃控制连接，下次使用时重新登录
+struct FtpReader<'a> {
+    connection: &'a FtpConnection,
+    data: Option<Stream>,
+    command: String,
+}
+
+impl Read for FtpReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(data) = &mut self.data else {
+            return Ok(0);
+        };
+        let read = match data.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => 0,
+            result => result?,
+        };
+        if read == 0 && !buf.is_empty() {
+            self.data = None;
+            let mut guard = self.connection.control();
+            let result = match guard.as_mut() {
+                Some(control) => control.finish_transfer(&self.command),
+                None => Err("控制连接已断开".into()),
+            };
+            if let Err(e) = result {
+                *guard = None;
+                return Err(match e.downcast::<io::Error>() {
+                    Ok(e) => *e,
+                    Err(e) => io::Error::other(e.to_string()),
+                });
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for FtpReader<'_> {
+    fn drop(&mut self) {
+        if self.data.take().is_some() {
+            *self.connection.control() = None;
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! HTTP(S) 传输：通过 Web 服务器的目录索引页列出文件，用 HEAD 请求查询文件属性，以 Range 请求断点续传；
+//! 支持 Basic 认证。连接由 HTTP 客户端管理，同一会话上的通道共享连接池
+
+use super::{
+    ConnectError, ConnectStage, Credentials, RemoteConnection, RemoteMetadata, RemoteSession,
+};
+use crate::failure::{DownloadError, FailureKind};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::DateTime;
+use std::error::Error;
+use std::io::{self, Read};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+use ureq::http::Response;
+use ureq::{Agent, Body};
+
+/// 未指定超时时建立连接的等待时间
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 未指定超时时等待响应头的时间
+const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 连接 `host`（`主机:端口`）并检查认证。`tcp` 只用于确认端口可达，之后的请求由 HTTP 客户端另行建立连接；
+/// `client_id` 去掉 `SSH-2.0-` 前缀后作为 `User-Agent`
+pub(super) fn connect(
+    tcp: TcpStream,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    timeout: Option<Duration>,
+    client_id: Option<&str>,
+    secure: bool,
+) -> Result<Arc<dyn RemoteSession>, ConnectError> {
+    drop(tcp);
+    let password = match credentials {
+        Credentials::Password(password) => password,
+        Credentials::PublicKey { .. } => {
+            return Err(ConnectError::new(
+                ConnectStage::Auth,
+                host,
+                "HTTP 传输只支持密码认证",
+            ));
+        }
+    };
+    let authorization = (!username.is_empty()).then(|| {
+        format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", username, password))
+        )
+    });
+    let session = HttpSession {
+        agent: agent(timeout, client_id),
+        base_url: format!("{}://{}", if secure { "https" } else { "http" }, host),
+        authorization,
+    };
+
+    // 请求根目录确认服务可用且凭据有效；根目录不允许列出（404、403 等）不影响下载
+    let response = session
+        .request("HEAD", "/", None)
+        .map_err(|e| ConnectError::new(connect_stage(&e), host, e))?;
+    if response.status().as_u16() == 401 {
+        return Err(ConnectError::new(
+            ConnectStage::Auth,
+            host,
+            "用户名或密码错误（HTTP 401）",
+        ));
+    }
+    Ok(Arc::new(session))
+}
+
+/// HTTP 客户端；`client_id` 去掉 `SSH-2.0-` 前缀后作为 `User-Agent`，HTTP 错误状态作为普通响应返回
+pub(super) fn agent(timeout: Option<Duration>, client_id: Option<&str>) -> Agent {
+    let user_agent = client_id
+        .map(|id| id.trim_start_matches("SSH-2.0-").to_string())
+        .unwrap_or_else(|| format!("HSD_Downloader/{}", env!("CARGO_PKG_VERSION")));
+    Agent::config_builder()
+        .http_status_as_error(false)
+        .user_agent(user_agent)
+        .timeout_connect(Some(timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)))
+        .timeout_recv_response(Some(timeout.unwrap_or(DEFAULT_RESPONSE_TIMEOUT)))
+        .build()
+        .into()
+}
+
+/// 请求失败发生在建立连接的哪个阶段
+pub(super) fn connect_stage(error: &ureq::Error) -> ConnectStage {
+    match error {
+        ureq::Error::Io(_)
+        | ureq::Error::HostNotFound
+        | ureq::Error::ConnectionFailed
+        | ureq::Error::Timeout(_) => ConnectStage::Tcp,
+        _ => ConnectStage::Handshake,
+    }
+}
+
+#[derive(Clone)]
+struct HttpSession {
+    agent: Agent,
+    /// `http(s)://主机:端口`
+    base_url: String,
+    /// Basic 认证的 `Authorization` 头，用户名为空时不认证
+    authorization: Option<String>,
+}
+
+impl HttpSession {
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        range_from: Option<u64>,
+    ) -> Result<Response<Body>, ureq::Error> {
+        let mut request = ureq::http::Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.base_url, encode_path(path)))
+            // 按字节偏移续传，不接受压缩后的内容
+            .header("accept-encoding", "identity");
+        if let Some(authorization) = &self.authorization {
+            request = request.header("authorization", authorization);
+        }
+        if let Some(offset) = range_from {
+            request = request.header("range", format!("bytes={}-", offset));
+        }
+        self.agent.run(request.body(())?)
+    }
+}
+
+impl RemoteSession for HttpSession {
+    fn open_channel(&self) -> Result<Box<dyn RemoteConnection>, Box<dyn Error>> {
+        Ok(Box::new(HttpConnection {
+            session: self.clone(),
+        }))
+    }
+}
+
+struct HttpConnection {
+    session: HttpSession,
+}
+
+impl HttpConnection {
+    /// 发送请求，HTTP 状态表示失败时按状态码返回可分类的错误
+    fn expect_success(
+        &self,
+        method: &str,
+        path: &str,
+        range_from: Option<u64>,
+    ) -> Result<Response<Body>, Box<dyn Error>> {
+        let response = self.session.request(method, path, range_from)?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        Err(status_error(method, path, response.status().as_u16()))
+    }
+}
+
+/// 按 HTTP 状态码返回可分类的错误
+pub(super) fn status_error(method: &str, path: &str, status: u16) -> Box<dyn Error> {
+    let message = format!("{} {}: HTTP {}", method, path, status);
+    match status {
+        401 => Box::new(DownloadError {
+            kind: FailureKind::AuthFailed,
+            message,
+        }),
+        403 => Box::new(io::Error::new(io::ErrorKind::PermissionDenied, message)),
+        404 | 410 => Box::new(io::Error::new(io::ErrorKind::NotFound, message)),
+        408 | 504 => Box::new(io::Error::new(io::ErrorKind::TimedOut, message)),
+        _ => message.into(),
+    }
+}
+
+impl RemoteConnection for HttpConnection {
+    fn list_directory(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let dir = dir.trim_end_matches('/');
+        let mut response = self.expect_success("GET", &format!("{}/", dir), None)?;
+        let html = response.body_mut().read_to_string()?;
+        let mut files: Vec<String> = index_links(&html)
+            .filter_map(|href| {
+                let name = href.rsplit('/').next()?;
+                // 子目录、上级目录与排序链接不是文件
+                if name.is_empty() || href.ends_with('/') || href.contains('?') {
+                    return None;
+                }
+                let name = percent_decode(name);
+                (name != "." && name != "..").then(|| format!("{}/{}", dir, name))
+            })
+            .collect();
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    fn metadata(&self, path: &str) -> Result<RemoteMetadata, Box<dyn Error>> {
+        head_metadata(&self.expect_success("HEAD", path, None)?, path)
+    }
+
+    fn open_at(&self, path: &str, offset: u64) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        let response = self.expect_success("GET", path, (offset > 0).then_some(offset))?;
+        body_from(response, path, offset)
+    }
+}
+
+/// `HEAD` 响应中的文件大小与修改时间
+pub(super) fn head_metadata(
+    response: &Response<Body>,
+    path: &str,
+) -> Result<RemoteMetadata, Box<dyn Error>> {
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let size = header("content-length")
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("HEAD {}: 响应没有 Content-Length", path))?;
+    let modified = header("last-modified")
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .and_then(|time| u64::try_from(time.timestamp()).ok());
+    Ok(RemoteMetadata { size, modified })
+}
+
+/// 自 `offset` 起的 `Range` 请求的响应内容；服务器不支持 Range 时返回完整文件，跳过已下载的部分
+pub(super) fn body_from(
+    response: Response<Body>,
+    path: &str,
+    offset: u64,
+) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let partial = response.status().as_u16() == 206;
+    let mut reader = response.into_body().into_reader();
+    if offset > 0 && !partial {
+        tracing::debug!(
+            "服务器不支持 Range 请求，从头读取并跳过 {} 字节: {}",
+            offset,
+            path
+        );
+        let skipped = io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+        if skipped < offset {
+            return Err(format!("读取 {} 时文件短于续传起点 {} 字节", path, offset).into());
+        }
+    }
+    Ok(Box::new(reader))
+}
+
+/// 目录索引页中所有链接的 `href`
+fn index_links(html: &str) -> impl Iterator<Item = String> + '_ {
+    html.split("href=").skip(1).filter_map(|rest| {
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let rest = &rest[1..];
+        let end = rest.find(quote)?;
+        Some(rest[..end].replace("&amp;", "&"))
+    })
+}
+
+/// 请求路径中的字符按 URL 规则编码，`/` 保留
+pub(super) fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// 解码链接中的 `%XX`
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%'
+            && let Some(byte) = value
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
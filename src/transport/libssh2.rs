@@ -0,0 +1,159 @@
+use super::{
+    ConnectError, ConnectStage, Credentials, RemoteConnection, RemoteEntry, RemoteMetadata,
+    RemoteSession,
+};
+use ssh2::{File, Session, Sftp};
+use std::cell::Cell;
+use std::error::Error;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 基于 libssh2 的 SSH 会话，各 SFTP 通道共享同一会话
+struct Libssh2Session {
+    session: Session,
+    timeout: Option<Duration>,
+}
+
+/// 基于 libssh2 的 SFTP 连接
+struct Libssh2Connection {
+    // 会话需与 SFTP 通道同时存活
+    session: Session,
+    sftp: Sftp,
+    read_timeout: Cell<Option<Duration>>,
+}
+
+/// 读取远程文件；每次读取前按连接当前的读超时设置会话超时，读完恢复为不限时，
+/// 临近时限时设置的很短的超时不会留给之后的打开文件等操作
+struct Libssh2Reader<'a> {
+    session: &'a Session,
+    file: File,
+    timeout: &'a Cell<Option<Duration>>,
+}
+
+impl Read for Libssh2Reader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(timeout) = self.timeout.get() else {
+            return self.file.read(buf);
+        };
+        self.session.set_timeout(timeout.as_millis() as u32);
+        let result = self.file.read(buf);
+        self.session.set_timeout(0);
+        result
+    }
+}
+
+pub(super) fn connect(
+    tcp: TcpStream,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    timeout: Option<Duration>,
+    client_id: Option<&str>,
+) -> Result<Arc<dyn RemoteSession>, ConnectError> {
+    let mut session =
+        Session::new().map_err(|e| ConnectError::new(ConnectStage::Handshake, host, e))?;
+    if let Some(client_id) = client_id {
+        session
+            .set_banner(client_id)
+            .map_err(|e| ConnectError::new(ConnectStage::Handshake, host, e))?;
+    }
+    session.set_tcp_stream(tcp);
+    if let Some(timeout) = timeout {
+        session.set_timeout(timeout.as_millis() as u32);
+    }
+    session
+        .handshake()
+        .map_err(|e| ConnectError::new(ConnectStage::Handshake, host, e))?;
+    match credentials {
+        Credentials::Password(password) => session.userauth_password(username, password),
+        Credentials::PublicKey {
+            private_key,
+            passphrase,
+        } => session.userauth_pubkey_file(username, None, private_key, passphrase.as_deref()),
+    }
+    .map_err(|e| ConnectError::new(ConnectStage::Auth, host, e))?;
+    if !session.authenticated() {
+        return Err(ConnectError::new(
+            ConnectStage::Auth,
+            host,
+            "服务器未接受认证",
+        ));
+    }
+    // 超时仅用于建立连接，大文件传输不受限制
+    session.set_timeout(0);
+    Ok(Arc::new(Libssh2Session { session, timeout }))
+}
+
+impl RemoteSession for Libssh2Session {
+    fn open_channel(&self) -> Result<Box<dyn RemoteConnection>, Box<dyn Error>> {
+        if let Some(timeout) = self.timeout {
+            self.session.set_timeout(timeout.as_millis() as u32);
+        }
+        let sftp = self.session.sftp();
+        if self.timeout.is_some() {
+            self.session.set_timeout(0);
+        }
+        Ok(Box::new(Libssh2Connection {
+            session: self.session.clone(),
+            sftp: sftp?,
+            read_timeout: Cell::new(None),
+        }))
+    }
+}
+
+impl RemoteConnection for Libssh2Connection {
+    fn list_directory(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self
+            .sftp
+            .readdir(Path::new(dir))?
+            .into_iter()
+            .map(|(path, _stat)| path.to_string_lossy().to_string())
+            .collect())
+    }
+
+    /// 使用 `readdir` 一并返回的属性，服务器未给出大小时才单独查询
+    fn list_directory_entries(&self, dir: &str) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+        self.sftp
+            .readdir(Path::new(dir))?
+            .into_iter()
+            .map(|(path, stat)| {
+                let path = path.to_string_lossy().to_string();
+                let metadata = match stat.size {
+                    Some(size) => RemoteMetadata {
+                        size,
+                        modified: stat.mtime,
+                    },
+                    None => self.metadata(&path)?,
+                };
+                Ok(RemoteEntry { path, metadata })
+            })
+            .collect()
+    }
+
+    fn metadata(&self, path: &str) -> Result<RemoteMetadata, Box<dyn Error>> {
+        let stat = self.sftp.stat(Path::new(path))?;
+        Ok(RemoteMetadata {
+            size: stat.size.unwrap_or(0),
+            modified: stat.mtime,
+        })
+    }
+
+    fn open_at(&self, path: &str, offset: u64) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        let mut file = self.sftp.open(Path::new(path))?;
+        if offset > 0 {
+            file.seek(SeekFrom::Start(offset))?;
+        }
+        Ok(Box::new(Libssh2Reader {
+            session: &self.session,
+            file,
+            timeout: &self.read_timeout,
+        }))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) {
+        self.read_timeout.set(timeout);
+    }
+}
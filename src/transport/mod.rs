@@ -0,0 +1,576 @@
+//! 远程传输后端：建立连接、列出目录与按偏移读取文件；SFTP 之外也可通过 HTTP(S)、FTP(S)
+//! 或匿名访问的公开 S3 存储桶取得数据
+
+use crate::failure::FailureKind;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+#[cfg(feature = "ftp")]
+mod ftp;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "sftp")]
+mod libssh2;
+#[cfg(feature = "russh")]
+mod russh;
+#[cfg(feature = "http")]
+mod s3;
+
+/// 已建立的远程连接
+pub trait RemoteConnection {
+    /// 列出目录中的文件，返回完整的远程路径
+    fn list_directory(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// 列出目录中的文件及其属性；默认逐个查询属性，后端可在列目录时一并返回
+    fn list_directory_entries(&self, dir: &str) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+        self.list_directory(dir)?
+            .into_iter()
+            .map(|path| {
+                Ok(RemoteEntry {
+                    metadata: self.metadata(&path)?,
+                    path,
+                })
+            })
+            .collect()
+    }
+
+    /// 远程文件的大小与修改时间
+    fn metadata(&self, path: &str) -> Result<RemoteMetadata, Box<dyn Error>>;
+
+    /// 从指定偏移开始读取远程文件
+    fn open_at(&self, path: &str, offset: u64) -> Result<Box<dyn Read + '_>, Box<dyn Error>>;
+
+    /// 设置之后读取远程文件时等待数据的最长时间，超时的读取返回错误；为空时一直等待。
+    /// 对已打开的远程文件的后续读取同样生效。默认不设置，由后端自身的超时决定（FTP 数据连接的读超时、HTTP 客户端的超时）
+    fn set_read_timeout(&self, timeout: Option<Duration>) {
+        let _ = timeout;
+    }
+}
+
+/// 已完成认证的 SSH 会话，可在其上打开多个 SFTP 通道
+pub trait RemoteSession: Send + Sync {
+    /// 在会话上打开一个新的 SFTP 通道
+    fn open_channel(&self) -> Result<Box<dyn RemoteConnection>, Box<dyn Error>>;
+}
+
+/// 远程文件属性
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemoteMetadata {
+    /// 文件大小（字节）
+    pub size: u64,
+    /// 修改时间（Unix 时间戳，秒），服务器未提供时为 `None`
+    pub modified: Option<u64>,
+}
+
+/// 目录中的远程文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteEntry {
+    /// 完整的远程路径
+    pub path: String,
+    pub metadata: RemoteMetadata,
+}
+
+/// SSH 认证凭据
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    Password(String),
+    /// 私钥文件认证，`passphrase` 为私钥的保护口令
+    PublicKey {
+        private_key: PathBuf,
+        passphrase: Option<String>,
+    },
+}
+
+/// 传输实现：SSH/SFTP、HTTP(S)、FTP(S) 或公开的 S3 存储桶
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    /// 基于系统 libssh2/OpenSSL（`sftp` 特性）
+    Libssh2,
+    /// 纯 Rust 实现，可静态编译（`russh` 特性）
+    Russh,
+    /// HTTP，通过 Web 服务器的目录索引页列出文件，Basic 认证（`http` 特性）
+    Http,
+    /// HTTPS，同 [`Transport::Http`]（`http` 特性）
+    Https,
+    /// FTP，被动模式传输，`REST` 命令续传（`ftp` 特性）
+    Ftp,
+    /// 显式 TLS 的 FTPS（`AUTH TLS`），控制连接与数据连接均加密（`ftp` 特性）
+    Ftps,
+    /// 匿名访问的公开 S3 存储桶，例如 AWS 开放数据计划的 `noaa-himawari9`（`http` 特性）
+    S3,
+}
+
+impl Default for Transport {
+    /// 优先使用 libssh2，仅编译了纯 Rust 实现时使用 russh
+    fn default() -> Self {
+        if cfg!(feature = "sftp") || !cfg!(feature = "russh") {
+            Transport::Libssh2
+        } else {
+            Transport::Russh
+        }
+    }
+}
+
+impl Transport {
+    /// 该实现是否已编译进当前程序
+    pub fn is_available(&self) -> bool {
+        match self {
+            Transport::Libssh2 => cfg!(feature = "sftp"),
+            Transport::Russh => cfg!(feature = "russh"),
+            Transport::Http | Transport::Https | Transport::S3 => cfg!(feature = "http"),
+            Transport::Ftp | Transport::Ftps => cfg!(feature = "ftp"),
+        }
+    }
+
+    /// 启用该实现所需的 Cargo 特性
+    pub fn feature(&self) -> &'static str {
+        match self {
+            Transport::Libssh2 => "sftp",
+            Transport::Russh => "russh",
+            Transport::Http | Transport::Https | Transport::S3 => "http",
+            Transport::Ftp | Transport::Ftps => "ftp",
+        }
+    }
+
+    /// 是否通过 HTTP(S) 访问服务器：只支持密码认证，没有 SSH 会话与 SFTP 通道
+    pub fn is_http(&self) -> bool {
+        matches!(self, Transport::Http | Transport::Https)
+    }
+
+    /// 是否匿名访问，不需要用户名与密码
+    pub fn is_anonymous(&self) -> bool {
+        *self == Transport::S3
+    }
+
+    /// 是否通过 FTP(S) 访问服务器：只支持密码认证，每个通道是一条独立登录的控制连接
+    pub fn is_ftp(&self) -> bool {
+        matches!(self, Transport::Ftp | Transport::Ftps)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transport::Libssh2 => "libssh2",
+            Transport::Russh => "russh",
+            Transport::Http => "http",
+            Transport::Https => "https",
+            Transport::Ftp => "ftp",
+            Transport::Ftps => "ftps",
+            Transport::S3 => "s3",
+        }
+    }
+
+    /// 连接 `host`（`主机:端口`），完成认证并打开 SFTP 通道
+    pub fn connect(
+        &self,
+        host: &str,
+        username: &str,
+        credentials: &Credentials,
+    ) -> Result<Box<dyn RemoteConnection>, ConnectError> {
+        let tcp =
+            TcpStream::connect(host).map_err(|e| ConnectError::new(ConnectStage::Tcp, host, e))?;
+        self.connect_stream(tcp, host, username, credentials, None)
+    }
+
+    /// 与 [`Transport::connect`] 相同，并在 SSH 客户端标识串中带上连接标识，
+    /// 服务器端的会话记录可据此与本地日志对应
+    pub fn connect_tagged(
+        &self,
+        host: &str,
+        username: &str,
+        credentials: &Credentials,
+        tag: &ConnectionTag,
+    ) -> Result<Box<dyn RemoteConnection>, ConnectError> {
+        let tcp =
+            TcpStream::connect(host).map_err(|e| ConnectError::new(ConnectStage::Tcp, host, e))?;
+        self.establish(
+            tcp,
+            host,
+            username,
+            credentials,
+            None,
+            Some(&tag.client_id()),
+        )
+    }
+
+    /// 在已建立的 TCP 连接上完成 SSH 握手、认证并打开 SFTP 通道，`timeout` 限制建立连接各步骤的等待时间
+    #[allow(unused_variables)]
+    pub fn connect_stream(
+        &self,
+        tcp: TcpStream,
+        host: &str,
+        username: &str,
+        credentials: &Credentials,
+        timeout: Option<Duration>,
+    ) -> Result<Box<dyn RemoteConnection>, ConnectError> {
+        self.establish(tcp, host, username, credentials, timeout, None)
+    }
+
+    /// 连接 `host` 并完成认证，返回可打开多个 SFTP 通道的会话；SSH 客户端标识串中带上连接标识。
+    /// `timeout` 限制 TCP 连接与之后各步骤的等待时间，为空时一直等待
+    pub fn connect_session(
+        &self,
+        host: &str,
+        username: &str,
+        credentials: &Credentials,
+        tag: &ConnectionTag,
+        timeout: Option<Duration>,
+    ) -> Result<Arc<dyn RemoteSession>, ConnectError> {
+        let tcp = connect_tcp(host, timeout)
+            .map_err(|e| ConnectError::new(ConnectStage::Tcp, host, e))?;
+        self.authenticate(
+            tcp,
+            host,
+            username,
+            credentials,
+            timeout,
+            Some(&tag.client_id()),
+        )
+    }
+
+    /// `client_id` 为完整的 SSH 客户端标识串（`SSH-2.0-...`），为空时使用后端默认值
+    fn establish(
+        &self,
+        tcp: TcpStream,
+        host: &str,
+        username: &str,
+        credentials: &Credentials,
+        timeout: Option<Duration>,
+        client_id: Option<&str>,
+    ) -> Result<Box<dyn RemoteConnection>, ConnectError> {
+        self.authenticate(tcp, host, username, credentials, timeout, client_id)?
+            .open_channel()
+            .map_err(|e| ConnectError::new(ConnectStage::Sftp, host, e))
+    }
+
+    /// 完成 SSH 握手与认证，`timeout` 同时限制之后在该会话上打开通道的等待时间
+    #[allow(unused_variables)]
+    fn authenticate(
+        &self,
+        tcp: TcpStream,
+        host: &str,
+        username: &str,
+        credentials: &Credentials,
+        timeout: Option<Duration>,
+        client_id: Option<&str>,
+    ) -> Result<Arc<dyn RemoteSession>, ConnectError> {
+        match self {
+            #[cfg(feature = "sftp")]
+            Transport::Libssh2 => {
+                libssh2::connect(tcp, host, username, credentials, timeout, client_id)
+            }
+            #[cfg(feature = "russh")]
+            Transport::Russh => {
+                russh::connect(tcp, host, username, credentials, timeout, client_id)
+            }
+            #[cfg(feature = "http")]
+            Transport::Http | Transport::Https => http::connect(
+                tcp,
+                host,
+                username,
+                credentials,
+                timeout,
+                client_id,
+                *self == Transport::Https,
+            ),
+            #[cfg(feature = "http")]
+            Transport::S3 => s3::connect(tcp, host, timeout, client_id),
+            #[cfg(feature = "ftp")]
+            Transport::Ftp | Transport::Ftps => ftp::connect(
+                tcp,
+                host,
+                username,
+                credentials,
+                timeout,
+                client_id,
+                *self == Transport::Ftps,
+            ),
+            #[allow(unreachable_patterns)]
+            _ => Err(ConnectError::new(
+                ConnectStage::Handshake,
+                host,
+                format!("未启用 {} 特性，无法使用 {}", self.feature(), self.name()),
+            )),
+        }
+    }
+}
+
+/// 建立到 `host`（`主机:端口`）的 TCP 连接；设置了 `timeout` 时依次尝试解析出的各个地址，每个地址最多等待 `timeout`
+fn connect_tcp(host: &str, timeout: Option<Duration>) -> std::io::Result<TcpStream> {
+    let Some(timeout) = timeout else {
+        return TcpStream::connect(host);
+    };
+    let mut last_error = None;
+    for address in host.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "无法解析服务器地址")))
+}
+
+/// 复用已认证的 SSH 会话：每个会话最多同时打开 `channels_per_session` 个 SFTP 通道，
+/// 已有会话的通道都在使用时才重新登录，减少对限制认证次数的服务器的登录次数
+pub struct SessionPool {
+    transport: Transport,
+    host: String,
+    username: String,
+    credentials: Credentials,
+    channels_per_session: usize,
+    sessions: Mutex<Vec<PooledSession>>,
+    /// 会话可容纳多个通道时登录依次进行，避免多个线程同时发现没有空闲会话而各自登录
+    login: Mutex<()>,
+}
+
+#[derive(Clone)]
+struct PooledSession {
+    tag: ConnectionTag,
+    session: Arc<dyn RemoteSession>,
+    /// 会话上正在使用的通道数
+    channels: Arc<AtomicUsize>,
+}
+
+/// 会话池打开的 SFTP 通道
+pub struct PooledConnection {
+    pub connection: Box<dyn RemoteConnection>,
+    /// 通道所在会话登录时的连接标识，即服务器端会话记录中的客户端标识
+    pub session: ConnectionTag,
+}
+
+impl SessionPool {
+    pub fn new(
+        transport: Transport,
+        host: &str,
+        username: &str,
+        credentials: &Credentials,
+        channels_per_session: usize,
+    ) -> Self {
+        Self {
+            transport,
+            host: host.to_string(),
+            username: username.to_string(),
+            credentials: credentials.clone(),
+            channels_per_session: channels_per_session.max(1),
+            sessions: Mutex::new(Vec::new()),
+            login: Mutex::new(()),
+        }
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// 已登录的会话数
+    pub fn sessions(&self) -> usize {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// 在有空闲通道的会话上打开 SFTP 通道；没有这样的会话，或已有会话无法再打开通道（例如连接已断开）时，
+    /// 以 `tag` 登录新的会话
+    pub fn connect(&self, tag: &ConnectionTag) -> Result<PooledConnection, ConnectError> {
+        self.connect_within(tag, None)
+    }
+
+    /// 与 [`SessionPool::connect`] 相同，登录新会话时最多等待 `timeout`
+    pub fn connect_within(
+        &self,
+        tag: &ConnectionTag,
+        timeout: Option<Duration>,
+    ) -> Result<PooledConnection, ConnectError> {
+        let _login = (self.channels_per_session > 1)
+            .then(|| self.login.lock().unwrap_or_else(PoisonError::into_inner));
+        while let Some(pooled) = self.reserve() {
+            match pooled.session.open_channel() {
+                Ok(connection) => return Ok(pooled.attach(connection)),
+                Err(_) => self.discard(&pooled),
+            }
+        }
+
+        let session = self.transport.connect_session(
+            &self.host,
+            &self.username,
+            &self.credentials,
+            tag,
+            timeout,
+        )?;
+        let connection = session
+            .open_channel()
+            .map_err(|e| ConnectError::new(ConnectStage::Sftp, &self.host, e))?;
+        let pooled = PooledSession {
+            tag: tag.clone(),
+            session,
+            channels: Arc::new(AtomicUsize::new(1)),
+        };
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(pooled.clone());
+        Ok(pooled.attach(connection))
+    }
+
+    /// 占用一个有空闲通道的会话
+    fn reserve(&self) -> Option<PooledSession> {
+        let sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        let pooled = sessions
+            .iter()
+            .find(|pooled| pooled.channels.load(Ordering::SeqCst) < self.channels_per_session)?;
+        pooled.channels.fetch_add(1, Ordering::SeqCst);
+        Some(pooled.clone())
+    }
+
+    /// 从池中移除以 `session` 登录的会话：使用它的通道发现连接已断开时调用，
+    /// 之后不会再在这个会话上打开通道。会话上其余已打开的通道不受影响
+    pub fn evict(&self, session: &ConnectionTag) {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|pooled| pooled.tag != *session);
+    }
+
+    /// 从池中移除无法再打开通道的会话，已打开的通道不受影响
+    fn discard(&self, pooled: &PooledSession) {
+        pooled.channels.fetch_sub(1, Ordering::SeqCst);
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|other| !Arc::ptr_eq(&other.channels, &pooled.channels));
+    }
+}
+
+impl PooledSession {
+    fn attach(self, connection: Box<dyn RemoteConnection>) -> PooledConnection {
+        PooledConnection {
+            connection: Box::new(PooledChannel {
+                inner: connection,
+                channels: self.channels,
+            }),
+            session: self.tag,
+        }
+    }
+}
+
+/// 关闭时归还所占会话通道数的 SFTP 通道
+struct PooledChannel {
+    inner: Box<dyn RemoteConnection>,
+    channels: Arc<AtomicUsize>,
+}
+
+impl RemoteConnection for PooledChannel {
+    fn list_directory(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        self.inner.list_directory(dir)
+    }
+
+    fn list_directory_entries(&self, dir: &str) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+        self.inner.list_directory_entries(dir)
+    }
+
+    fn metadata(&self, path: &str) -> Result<RemoteMetadata, Box<dyn Error>> {
+        self.inner.metadata(path)
+    }
+
+    fn open_at(&self, path: &str, offset: u64) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        self.inner.open_at(path, offset)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
+impl Drop for PooledChannel {
+    fn drop(&mut self) {
+        self.channels.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 连接标识：工作线程名与该线程名在本进程中第几次建立连接（代数），显示为 `w3.g1`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionTag {
+    pub worker: String,
+    pub generation: u32,
+}
+
+/// 各工作线程名已建立连接的次数
+static GENERATIONS: Mutex<BTreeMap<String, u32>> = Mutex::new(BTreeMap::new());
+
+impl ConnectionTag {
+    /// 为工作线程的下一次连接分配标识，同一线程名每次调用代数加 1
+    pub fn next(worker: impl Into<String>) -> Self {
+        let worker = worker.into();
+        let mut generations = GENERATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+        let generation = generations.entry(worker.clone()).or_default();
+        *generation += 1;
+        Self {
+            worker,
+            generation: *generation,
+        }
+    }
+
+    /// 发送给服务器的 SSH 客户端标识串；按 RFC 4253，软件版本部分不含空格与 `-`
+    pub fn client_id(&self) -> String {
+        format!(
+            "SSH-2.0-HSD_Downloader_{}_{}",
+            env!("CARGO_PKG_VERSION"),
+            self
+        )
+    }
+}
+
+impl fmt::Display for ConnectionTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.g{}", self.worker, self.generation)
+    }
+}
+
+/// 建立连接的各个阶段
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectStage {
+    Tcp,
+    Handshake,
+    Auth,
+    Sftp,
+}
+
+/// 建立连接失败，记录失败发生的阶段
+#[derive(Debug)]
+pub struct ConnectError {
+    pub stage: ConnectStage,
+    pub message: String,
+}
+
+impl ConnectError {
+    pub(crate) fn new(stage: ConnectStage, host: &str, error: impl fmt::Display) -> Self {
+        Self {
+            stage,
+            message: format!("连接 {} 失败: {}", host, error),
+        }
+    }
+
+    pub fn failure_kind(&self) -> FailureKind {
+        match self.stage {
+            ConnectStage::Auth => FailureKind::AuthFailed,
+            _ => FailureKind::NoConnection,
+        }
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for ConnectError {}
@@ -0,0 +1,231 @@
+use super::{
+    ConnectError, ConnectStage, Credentials, RemoteConnection, RemoteEntry, RemoteMetadata,
+    RemoteSession,
+};
+use russh::client::{self, Handle};
+use russh::keys::{PrivateKeyWithHashAlg, PublicKeyOrCertificate, load_secret_key};
+use russh_sftp::client::SftpSession;
+use russh_sftp::client::fs::File;
+use std::cell::Cell;
+use std::error::Error;
+use std::io::{self, Read, SeekFrom};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::runtime::Runtime;
+
+/// 纯 Rust 的 SSH 会话；每个会话自带运行时（一个后台线程处理会话的收发），对外提供同步接口，
+/// 各 SFTP 通道共享会话及其运行时
+struct RusshSession {
+    runtime: Arc<Runtime>,
+    handle: Arc<Handle<Client>>,
+    timeout: Option<Duration>,
+}
+
+/// 纯 Rust 的 SFTP 连接
+struct RusshConnection {
+    runtime: Arc<Runtime>,
+    // 会话需与 SFTP 通道同时存活
+    _handle: Arc<Handle<Client>>,
+    sftp: SftpSession,
+    read_timeout: Cell<Option<Duration>>,
+}
+
+struct Client;
+
+impl client::Handler for Client {
+    type Error = russh::Error;
+
+    // 与 libssh2 实现一致，不校验服务器主机密钥
+    async fn check_server_key(
+        &mut self,
+        _server_public_key: &PublicKeyOrCertificate,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+pub(super) fn connect(
+    tcp: TcpStream,
+    host: &str,
+    username: &str,
+    credentials: &Credentials,
+    timeout: Option<Duration>,
+    client_id: Option<&str>,
+) -> Result<Arc<dyn RemoteSession>, ConnectError> {
+    // 多个线程各自在自己的通道上等待时，由后台线程持续处理会话的收发
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .map_err(|e| ConnectError::new(ConnectStage::Handshake, host, e))?;
+
+    let establish = async {
+        tcp.set_nonblocking(true)
+            .map_err(|e| ConnectError::new(ConnectStage::Tcp, host, e))?;
+        let stream = tokio::net::TcpStream::from_std(tcp)
+            .map_err(|e| ConnectError::new(ConnectStage::Tcp, host, e))?;
+
+        let mut config = client::Config::default();
+        if let Some(client_id) = client_id {
+            config.client_id = russh::SshId::Standard(client_id.to_string().into());
+        }
+        let config = Arc::new(config);
+        let mut handle = client::connect_stream(config, stream, Client)
+            .await
+            .map_err(|e| ConnectError::new(ConnectStage::Handshake, host, e))?;
+
+        let auth = match credentials {
+            Credentials::Password(password) => handle
+                .authenticate_password(username, password)
+                .await
+                .map_err(|e| ConnectError::new(ConnectStage::Auth, host, e))?,
+            Credentials::PublicKey {
+                private_key,
+                passphrase,
+            } => {
+                let key = load_secret_key(private_key, passphrase.as_deref())
+                    .map_err(|e| ConnectError::new(ConnectStage::Auth, host, e))?;
+                // RSA 密钥按服务器支持的签名算法选择哈希
+                let hash_alg = handle
+                    .best_supported_rsa_hash()
+                    .await
+                    .map_err(|e| ConnectError::new(ConnectStage::Auth, host, e))?
+                    .flatten();
+                handle
+                    .authenticate_publickey(
+                        username,
+                        PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg),
+                    )
+                    .await
+                    .map_err(|e| ConnectError::new(ConnectStage::Auth, host, e))?
+            }
+        };
+        if !auth.success() {
+            return Err(ConnectError::new(
+                ConnectStage::Auth,
+                host,
+                "服务器未接受认证",
+            ));
+        }
+
+        Ok(handle)
+    };
+    let handle = runtime.block_on(async {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, establish)
+                .await
+                .map_err(|_| ConnectError::new(ConnectStage::Handshake, host, "建立连接超时"))?,
+            None => establish.await,
+        }
+    })?;
+
+    Ok(Arc::new(RusshSession {
+        runtime: Arc::new(runtime),
+        handle: Arc::new(handle),
+        timeout,
+    }))
+}
+
+impl RemoteSession for RusshSession {
+    fn open_channel(&self) -> Result<Box<dyn RemoteConnection>, Box<dyn Error>> {
+        let open = async {
+            let channel = self.handle.channel_open_session().await?;
+            channel.request_subsystem(true, "sftp").await?;
+            Ok::<_, Box<dyn Error>>(SftpSession::new(channel.into_stream()).await?)
+        };
+        let sftp = self.runtime.block_on(async {
+            match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, open)
+                    .await
+                    .map_err(|_| "打开 SFTP 通道超时")?,
+                None => open.await,
+            }
+        })?;
+        Ok(Box::new(RusshConnection {
+            runtime: Arc::clone(&self.runtime),
+            _handle: Arc::clone(&self.handle),
+            sftp,
+            read_timeout: Cell::new(None),
+        }))
+    }
+}
+
+impl RemoteConnection for RusshConnection {
+    fn list_directory(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let entries = self.runtime.block_on(self.sftp.read_dir(dir))?;
+        let dir = dir.trim_end_matches('/');
+        Ok(entries
+            .map(|entry| entry.file_name())
+            .filter(|name| name != "." && name != "..")
+            .map(|name| format!("{}/{}", dir, name))
+            .collect())
+    }
+
+    /// 使用 `read_dir` 一并返回的属性，服务器未给出大小时才单独查询
+    fn list_directory_entries(&self, dir: &str) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+        let entries = self.runtime.block_on(self.sftp.read_dir(dir))?;
+        let dir = dir.trim_end_matches('/');
+        entries
+            .filter(|entry| entry.file_name() != "." && entry.file_name() != "..")
+            .map(|entry| {
+                let path = format!("{}/{}", dir, entry.file_name());
+                let attributes = entry.metadata();
+                let metadata = match attributes.size {
+                    Some(size) => RemoteMetadata {
+                        size,
+                        modified: attributes.mtime.map(u64::from),
+                    },
+                    None => self.metadata(&path)?,
+                };
+                Ok(RemoteEntry { path, metadata })
+            })
+            .collect()
+    }
+
+    fn metadata(&self, path: &str) -> Result<RemoteMetadata, Box<dyn Error>> {
+        let attributes = self.runtime.block_on(self.sftp.metadata(path))?;
+        Ok(RemoteMetadata {
+            size: attributes.len(),
+            modified: attributes.mtime.map(u64::from),
+        })
+    }
+
+    fn open_at(&self, path: &str, offset: u64) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        let file = self.runtime.block_on(async {
+            let mut file = self.sftp.open(path).await?;
+            if offset > 0 {
+                file.seek(SeekFrom::Start(offset)).await?;
+            }
+            Ok::<_, Box<dyn Error>>(file)
+        })?;
+        Ok(Box::new(RusshReader {
+            runtime: &self.runtime,
+            file,
+            timeout: &self.read_timeout,
+        }))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) {
+        self.read_timeout.set(timeout);
+    }
+}
+
+/// 在连接的运行时上同步读取远程文件，每次读取按连接当前的读超时等待
+struct RusshReader<'a> {
+    runtime: &'a Runtime,
+    file: File,
+    timeout: &'a Cell<Option<Duration>>,
+}
+
+impl Read for RusshReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(timeout) = self.timeout.get() else {
+            return self.runtime.block_on(self.file.read(buf));
+        };
+        self.runtime
+            .block_on(tokio::time::timeout(timeout, self.file.read(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "等待远程数据超时"))?
+    }
+}
@@ -0,0 +1,217 @@
+//! 匿名访问的公开 S3 存储桶，例如 AWS 开放数据计划的 `noaa-himawari9`：以 `ListObjectsV2` 列出目录，
+//! 用 `HEAD` 请求查询对象属性，以 Range 请求断点续传。远程路径为 `/` 加对象键，目录为以 `/` 分隔的键前缀
+
+use super::http::{agent, body_from, connect_stage, encode_path, head_metadata, status_error};
+use super::{
+    ConnectError, ConnectStage, RemoteConnection, RemoteEntry, RemoteMetadata, RemoteSession,
+};
+use chrono::DateTime;
+use std::error::Error;
+use std::io::Read;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+use ureq::http::Response;
+use ureq::{Agent, Body};
+
+/// 连接 `host`（存储桶的访问地址，例如 `noaa-himawari9.s3.amazonaws.com:443`）并确认存储桶允许匿名列出；
+/// `tcp` 只用于确认端口可达
+pub(super) fn connect(
+    tcp: TcpStream,
+    host: &str,
+    timeout: Option<Duration>,
+    client_id: Option<&str>,
+) -> Result<Arc<dyn RemoteSession>, ConnectError> {
+    drop(tcp);
+    let bucket = PublicBucket {
+        agent: agent(timeout, client_id),
+        base_url: format!("https://{}", host),
+    };
+    let response = bucket
+        .request("GET", "/?list-type=2&max-keys=1", None)
+        .map_err(|e| ConnectError::new(connect_stage(&e), host, e))?;
+    match response.status().as_u16() {
+        200 => Ok(Arc::new(bucket)),
+        401 | 403 => Err(ConnectError::new(
+            ConnectStage::Auth,
+            host,
+            "存储桶不允许匿名列出（HTTP 403）",
+        )),
+        status => Err(ConnectError::new(
+            ConnectStage::Handshake,
+            host,
+            format!("列出存储桶失败（HTTP {}）", status),
+        )),
+    }
+}
+
+#[derive(Clone)]
+struct PublicBucket {
+    agent: Agent,
+    /// `https://主机:端口`
+    base_url: String,
+}
+
+impl PublicBucket {
+    /// 发送请求，`target` 为已编码的路径与查询串
+    fn request(
+        &self,
+        method: &str,
+        target: &str,
+        range_from: Option<u64>,
+    ) -> Result<Response<Body>, ureq::Error> {
+        let mut request = ureq::http::Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.base_url, target))
+            .header("accept-encoding", "identity");
+        if let Some(offset) = range_from {
+            request = request.header("range", format!("bytes={}-", offset));
+        }
+        self.agent.run(request.body(())?)
+    }
+
+    /// 发送对象请求，HTTP 状态表示失败时按状态码返回可分类的错误
+    fn object(
+        &self,
+        method: &str,
+        path: &str,
+        range_from: Option<u64>,
+    ) -> Result<Response<Body>, Box<dyn Error>> {
+        let response = self.request(method, &encode_path(path), range_from)?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        Err(status_error(method, path, response.status().as_u16()))
+    }
+
+    /// 列出目录 `dir` 下的对象与子目录（键前缀），按页取完
+    fn list(&self, dir: &str) -> Result<(Vec<RemoteEntry>, Vec<String>), Box<dyn Error>> {
+        let dir = dir.trim_matches('/');
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", dir)
+        };
+        let mut objects = Vec::new();
+        let mut prefixes = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut target = format!(
+                "/?list-type=2&delimiter=%2F&prefix={}",
+                encode_component(&prefix)
+            );
+            if let Some(token) = &continuation {
+                target.push_str(&format!("&continuation-token={}", encode_component(token)));
+            }
+            let mut response = self.request("GET", &target, None)?;
+            if !response.status().is_success() {
+                return Err(status_error(
+                    "LIST",
+                    &format!("/{}", prefix),
+                    response.status().as_u16(),
+                ));
+            }
+            let xml = response.body_mut().read_to_string()?;
+            for contents in elements(&xml, "Contents") {
+                let Some(key) = element(contents, "Key") else {
+                    continue;
+                };
+                // 目录占位对象
+                if key.ends_with('/') {
+                    continue;
+                }
+                let size = element(contents, "Size")
+                    .and_then(|size| size.parse().ok())
+                    .ok_or_else(|| format!("LIST /{}: 对象 {} 没有大小", prefix, key))?;
+                let modified = element(contents, "LastModified")
+                    .and_then(|time| DateTime::parse_from_rfc3339(&time).ok())
+                    .and_then(|time| u64::try_from(time.timestamp()).ok());
+                objects.push(RemoteEntry {
+                    path: format!("/{}", key),
+                    metadata: RemoteMetadata { size, modified },
+                });
+            }
+            for common in elements(&xml, "CommonPrefixes") {
+                if let Some(sub) = element(common, "Prefix") {
+                    prefixes.push(format!("/{}", sub.trim_end_matches('/')));
+                }
+            }
+            continuation = (element(&xml, "IsTruncated").as_deref() == Some("true"))
+                .then(|| element(&xml, "NextContinuationToken"))
+                .flatten();
+            if continuation.is_none() {
+                break;
+            }
+        }
+        Ok((objects, prefixes))
+    }
+}
+
+impl RemoteSession for PublicBucket {
+    fn open_channel(&self) -> Result<Box<dyn RemoteConnection>, Box<dyn Error>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl RemoteConnection for PublicBucket {
+    fn list_directory(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let (objects, prefixes) = self.list(dir)?;
+        let mut paths: Vec<String> = objects
+            .into_iter()
+            .map(|entry| entry.path)
+            .chain(prefixes)
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// 列出结果本身带有对象的大小与修改时间，不再逐个查询；子目录的大小记为 0
+    fn list_directory_entries(&self, dir: &str) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+        let (mut entries, prefixes) = self.list(dir)?;
+        entries.extend(prefixes.into_iter().map(|path| RemoteEntry {
+            path,
+            metadata: RemoteMetadata {
+                size: 0,
+                modified: None,
+            },
+        }));
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &str) -> Result<RemoteMetadata, Box<dyn Error>> {
+        head_metadata(&self.object("HEAD", path, None)?, path)
+    }
+
+    fn open_at(&self, path: &str, offset: u64) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        let response = self.object("GET", path, (offset > 0).then_some(offset))?;
+        body_from(response, path, offset)
+    }
+}
+
+/// 查询串中的值按 URL 规则编码，`/` 同样编码
+fn encode_component(value: &str) -> String {
+    encode_path(value).replace('/', "%2F")
+}
+
+/// 所有 `<tag>...</tag>` 元素的内容
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    xml.split(open.as_str())
+        .skip(1)
+        .filter_map(|rest| rest.find(close.as_str()).map(|end| &rest[..end]))
+        .collect()
+}
+
+/// 第一个 `<tag>...</tag>` 元素的内容，XML 转义已还原
+fn element(xml: &str, tag: &str) -> Option<String> {
+    elements(xml, tag).first().map(|value| {
+        value
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    })
+}
@@ -0,0 +1,60 @@
+//! 数据量、时长与速度的统一输出格式：默认为便于阅读的形式（`1.50 GB`、`1 小时 02 分 03 秒`、`12.30 MB/s`），
+//! 启用机器可读格式（命令行 `--raw-units`）时固定输出字节数、秒数与字节/秒，便于脚本解析
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static RAW_UNITS: AtomicBool = AtomicBool::new(false);
+
+const SIZE_UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+/// 切换本进程的输出格式，`raw` 为 true 时输出机器可读的形式
+pub fn set_raw_units(raw: bool) {
+    RAW_UNITS.store(raw, Ordering::Relaxed);
+}
+
+/// 是否输出机器可读的形式
+pub fn raw_units() -> bool {
+    RAW_UNITS.load(Ordering::Relaxed)
+}
+
+/// 数据量：按 1024 进位取合适的单位并保留两位小数，不足 1 KB 时为整字节数；机器可读时为 `<字节数> B`
+pub fn format_bytes(bytes: u64) -> String {
+    if raw_units() || bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < SIZE_UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, SIZE_UNITS[unit])
+}
+
+/// 时长：不足 1 分钟时为秒数，否则为时、分、秒；机器可读时为 `<秒数>s`
+pub fn format_duration(duration: Duration) -> String {
+    if raw_units() {
+        return format!("{:.3}s", duration.as_secs_f64());
+    }
+    let secs = duration.as_secs();
+    if secs < 60 {
+        return format!("{:.1} 秒", duration.as_secs_f64());
+    }
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if hours == 0 {
+        format!("{} 分 {:02} 秒", minutes, seconds)
+    } else {
+        format!("{} 小时 {:02} 分 {:02} 秒", hours, minutes, seconds)
+    }
+}
+
+/// 传输速度：`bytes` 字节在 `elapsed` 内传完的平均速度；机器可读时为 `<字节数> B/s`
+pub fn format_rate(bytes: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    let rate = if secs > 0.0 { bytes as f64 / secs } else { 0.0 };
+    if raw_units() {
+        return format!("{:.0} B/s", rate);
+    }
+    format!("{}/s", format_bytes(rate as u64))
+}
@@ -0,0 +1,259 @@
+//! 下载内容校验：大小正确的文件仍可能是损坏的压缩包、服务器返回的错误页面或被错放的其他文件，
+//! 移入归档前检查 bz2 数据流是否完整、HSD 文件头是否有效且与文件名一致
+
+use crate::himawari::{HsdFilename, SATELLITES, TIMELINE_MINUTES};
+use bzip2::read::MultiBzDecoder;
+use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// HSD 第 1 个头块（基本信息块）的长度
+const HSD_BASIC_BLOCK_LENGTH: u16 = 282;
+
+/// 记录波段编号的头块（定标信息块）
+const CALIBRATION_BLOCK: u8 = 5;
+
+/// 记录分段编号的头块（分段信息块）
+const SEGMENT_BLOCK: u8 = 7;
+
+/// 检查文件内容，返回不通过的原因；`filename` 为远程文件名，用于判断文件类型。
+/// `.bz2` 文件完整解压一遍（数据流 CRC 由解码器校验），`.DAT` 与 `.DAT.bz2` 另检查 HSD 文件头，
+/// 文件名符合 HSD 命名规则时核对文件头中的卫星、观测区域、观测时间、波段与分段；
+/// 其他文件不检查
+pub fn validate_file(path: &Path, filename: &str) -> Result<(), String> {
+    let compressed = filename.ends_with(".bz2");
+    let hsd = filename.trim_end_matches(".bz2").ends_with(".DAT");
+    if !compressed && !hsd {
+        return Ok(());
+    }
+
+    let file = File::open(path).map_err(|e| format!("无法读取文件: {}", e))?;
+    let mut reader: Box<dyn Read> = if compressed {
+        Box::new(MultiBzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    if hsd {
+        let header = read_hsd_header(&mut reader).map_err(|e| e.into_message(compressed))?;
+        if let Some(expected) = HsdFilename::parse(filename) {
+            check_filename(&header, &expected)?;
+        }
+    }
+    io::copy(&mut reader, &mut io::sink()).map_err(|e| read_error(compressed, e))?;
+    Ok(())
+}
+
+fn read_error(compressed: bool, error: io::Error) -> String {
+    if compressed {
+        format!("bz2 数据损坏: {}", error)
+    } else {
+        format!("读取失败: {}", error)
+    }
+}
+
+/// 读取文件头时的错误：读取失败按是否压缩给出原因，内容无效直接给出原因
+enum HeaderError {
+    Read(io::Error),
+    Invalid(String),
+}
+
+impl HeaderError {
+    fn into_message(self, compressed: bool) -> String {
+        match self {
+            HeaderError::Read(error) => read_error(compressed, error),
+            HeaderError::Invalid(reason) => reason,
+        }
+    }
+}
+
+impl From<io::Error> for HeaderError {
+    fn from(error: io::Error) -> Self {
+        HeaderError::Read(error)
+    }
+}
+
+impl From<String> for HeaderError {
+    fn from(reason: String) -> Self {
+        HeaderError::Invalid(reason)
+    }
+}
+
+/// HSD 文件头中用于核对文件名的内容
+#[derive(Debug)]
+struct HsdHeader {
+    satellite: String,
+    area: String,
+    /// 时间线开始时间，`hhmm`
+    timeline: u16,
+    observation_start: NaiveDateTime,
+    band: u16,
+    segment: u8,
+    total_segments: u8,
+}
+
+/// 文件头各数值字段的字节序
+#[derive(Clone, Copy)]
+struct ByteOrder {
+    big_endian: bool,
+}
+
+impl ByteOrder {
+    fn u16(self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    fn f64(self, bytes: &[u8]) -> f64 {
+        let mut array = [0u8; 8];
+        array.copy_from_slice(&bytes[..8]);
+        if self.big_endian {
+            f64::from_be_bytes(array)
+        } else {
+            f64::from_le_bytes(array)
+        }
+    }
+}
+
+/// 读取基本信息块，再依次读取后续头块直到分段信息块
+fn read_hsd_header(reader: &mut dyn Read) -> Result<HsdHeader, HeaderError> {
+    let mut basic = [0u8; HSD_BASIC_BLOCK_LENGTH as usize];
+    reader.read_exact(&mut basic[..22])?;
+    let order = check_basic_block(&basic[..22])?;
+    reader.read_exact(&mut basic[22..])?;
+
+    let total_blocks = order.u16(&basic[3..5]);
+    let mut band = None;
+    let mut segment = None;
+    for number in 2..=total_blocks.min(SEGMENT_BLOCK as u16) as u8 {
+        let block = read_block(reader, order, number)?;
+        match number {
+            CALIBRATION_BLOCK => band = Some(order.u16(&block[3..5])),
+            SEGMENT_BLOCK => segment = Some((block[4], block[3])),
+            _ => {}
+        }
+    }
+    let (Some(band), Some((segment, total_segments))) = (band, segment) else {
+        return Err(format!("HSD 文件头无效: 只有 {} 个头块", total_blocks).into());
+    };
+    Ok(HsdHeader {
+        satellite: text_field(&basic[6..22]),
+        area: text_field(&basic[38..42]),
+        timeline: order.u16(&basic[44..46]),
+        observation_start: modified_julian_date(order.f64(&basic[46..54]))?,
+        band,
+        segment,
+        total_segments,
+    })
+}
+
+/// 基本信息块的开头：块编号 1、块长度 282、字节序标记，随后为卫星名
+fn check_basic_block(header: &[u8]) -> Result<ByteOrder, String> {
+    if header[0] != 1 {
+        return Err(format!("HSD 文件头无效: 第一个头块编号为 {}", header[0]));
+    }
+    let order = match header[5] {
+        0 => ByteOrder { big_endian: false },
+        1 => ByteOrder { big_endian: true },
+        other => return Err(format!("HSD 文件头无效: 字节序标记为 {}", other)),
+    };
+    let block_length = order.u16(&header[1..3]);
+    if block_length != HSD_BASIC_BLOCK_LENGTH {
+        return Err(format!("HSD 文件头无效: 第一个头块长度为 {}", block_length));
+    }
+    if !header[6..].starts_with(b"Himawari") {
+        return Err("HSD 文件头无效: 卫星名不是 Himawari".to_string());
+    }
+    Ok(order)
+}
+
+/// 读取编号为 `number` 的头块（含块编号与块长度）
+fn read_block(reader: &mut dyn Read, order: ByteOrder, number: u8) -> Result<Vec<u8>, HeaderError> {
+    let mut block = vec![0u8; 3];
+    reader.read_exact(&mut block)?;
+    if block[0] != number {
+        return Err(format!("HSD 文件头无效: 第 {} 个头块编号为 {}", number, block[0]).into());
+    }
+    let length = order.u16(&block[1..3]) as usize;
+    // 定标信息块与分段信息块至少读到编号字段
+    if length < 5 {
+        return Err(format!("HSD 文件头无效: 第 {} 个头块长度为 {}", number, length).into());
+    }
+    block.resize(length, 0);
+    reader.read_exact(&mut block[3..])?;
+    Ok(block)
+}
+
+/// 去掉末尾填充的 NUL 后的文本字段
+fn text_field(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .trim()
+        .to_string()
+}
+
+/// 简化儒略日转为 UTC 时间
+fn modified_julian_date(days: f64) -> Result<NaiveDateTime, String> {
+    let epoch = NaiveDate::from_ymd_opt(1858, 11, 17)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap_or_default();
+    // 1858 年至 2200 年之间
+    if !days.is_finite() || !(0.0..125_000.0).contains(&days) {
+        return Err(format!("HSD 文件头无效: 观测开始时间为 {}", days));
+    }
+    Ok(epoch + Duration::milliseconds((days * 86_400_000.0).round() as i64))
+}
+
+/// 核对文件头与文件名中的卫星、观测区域、时间线、波段与分段；观测开始时间应在文件名的时间线内
+/// （前后各留 1 分钟余量）
+fn check_filename(header: &HsdHeader, expected: &HsdFilename) -> Result<(), String> {
+    let mismatch = |field: &str, actual: &dyn std::fmt::Display, wanted: &dyn std::fmt::Display| {
+        Err(format!(
+            "HSD 文件头与文件名不符: {}为 {}，文件名为 {}",
+            field, actual, wanted
+        ))
+    };
+    if let Some(satellite) = SATELLITES.iter().find(|s| s.id == expected.satellite)
+        && header.satellite != satellite.name
+    {
+        return mismatch("卫星", &header.satellite, &expected.satellite);
+    }
+    if header.area != expected.area {
+        return mismatch("观测区域", &header.area, &expected.area);
+    }
+    let timeline = expected.datetime.hour() * 100 + expected.datetime.minute();
+    if u32::from(header.timeline) != timeline {
+        return mismatch(
+            "时间线",
+            &format!("{:04}", header.timeline),
+            &format!("{:04}", timeline),
+        );
+    }
+    let earliest = expected.datetime - Duration::minutes(1);
+    let latest = expected.datetime + Duration::minutes(TIMELINE_MINUTES + 1);
+    if header.observation_start < earliest || header.observation_start > latest {
+        return mismatch(
+            "观测开始时间",
+            &header.observation_start.format("%Y-%m-%d %H:%M:%S"),
+            &expected.datetime.format("%Y-%m-%d %H:%M"),
+        );
+    }
+    let band = format!("B{:02}", header.band);
+    if band != expected.band {
+        return mismatch("波段", &band, &expected.band);
+    }
+    let segment = (u32::from(header.segment), u32::from(header.total_segments));
+    if segment != (expected.segment, expected.total_segments) {
+        return mismatch(
+            "分段",
+            &format!("{:02}/{:02}", segment.0, segment.1),
+            &format!("{:02}/{:02}", expected.segment, expected.total_segments),
+        );
+    }
+    Ok(())
+}
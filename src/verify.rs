@@ -0,0 +1,175 @@
+//! 本地归档自检：按下载清单中记录的 SHA-256 重新计算已下载文件的校验和，找出下载之后在磁盘上损坏的文件。
+//! 损坏的文件可移入隔离区并从清单中删除，之后的运行将重新下载
+
+use crate::checksum::{ChecksumAlgorithm, hash_files};
+use crate::manifest::{Manifest, QuarantineRecord};
+use crate::storage::{LocalFileStorage, decompressed_path};
+use chrono::Utc;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// 每处理多少个文件输出一次进度
+const PROGRESS_EVERY: usize = 500;
+
+/// 核对未通过的文件
+#[derive(Debug, Clone)]
+pub struct DamagedFile {
+    pub remote_path: String,
+    pub local_path: PathBuf,
+    pub reason: String,
+}
+
+/// 按下载清单核对本地归档的结果
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    /// 大小与校验和都一致的文件数
+    pub verified: usize,
+    /// 清单中没有记录校验和（较早版本下载）的文件数
+    pub unrecorded: usize,
+    /// 下载后已解压并删除压缩文件、无法核对的文件数
+    pub decompressed: usize,
+    /// 清单记录的位置没有文件的远程路径
+    pub missing: Vec<String>,
+    /// 大小或校验和与记录不一致的文件
+    pub corrupted: Vec<DamagedFile>,
+    pub unreadable: Vec<DamagedFile>,
+}
+
+impl IntegrityReport {
+    pub fn all_passed(&self) -> bool {
+        self.missing.is_empty() && self.corrupted.is_empty() && self.unreadable.is_empty()
+    }
+
+    /// 需要从服务器重新下载的文件的远程路径
+    pub fn fetch_list(&self) -> Vec<&str> {
+        self.missing
+            .iter()
+            .map(String::as_str)
+            .chain(self.corrupted.iter().map(|file| file.remote_path.as_str()))
+            .chain(self.unreadable.iter().map(|file| file.remote_path.as_str()))
+            .collect()
+    }
+
+    pub fn print_report(&self) {
+        println!("=== 本地归档自检结果 ===");
+        println!("校验通过: {} 个文件", self.verified);
+        if self.unrecorded > 0 {
+            println!("清单中没有校验和、未核对: {} 个文件", self.unrecorded);
+        }
+        if self.decompressed > 0 {
+            println!("已解压、未核对: {} 个文件", self.decompressed);
+        }
+        if !self.missing.is_empty() {
+            println!("缺失: {} 个文件", self.missing.len());
+            for remote_path in &self.missing {
+                println!("  {}", remote_path);
+            }
+        }
+        for (label, files) in [("损坏", &self.corrupted), ("无法读取", &self.unreadable)] {
+            if !files.is_empty() {
+                println!("{}: {} 个文件", label, files.len());
+                for file in files {
+                    println!("  {} ({})", file.local_path.display(), file.reason);
+                }
+            }
+        }
+        if self.all_passed() {
+            println!("本地文件与下载清单记录一致");
+        }
+    }
+
+    /// 将损坏的文件移入隔离区、记入清单并删除其下载记录，之后的运行将重新下载；缺失文件的记录一并删除。
+    /// 返回移入隔离区的文件数
+    pub fn quarantine(&self, storage: &LocalFileStorage) -> Result<usize, Box<dyn Error>> {
+        let mut manifest = Manifest::load(&storage.base_path)?;
+        let mut moved = 0;
+        for file in &self.corrupted {
+            let quarantine_path = storage.generate_quarantine_path(&file.remote_path);
+            let size = fs::metadata(&file.local_path).map_or(0, |metadata| metadata.len());
+            let result = quarantine_path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::rename(&file.local_path, &quarantine_path));
+            if let Err(e) = result {
+                tracing::warn!("移入隔离区失败 {}: {}", file.local_path.display(), e);
+                continue;
+            }
+            manifest.record_quarantine(QuarantineRecord {
+                remote_path: file.remote_path.clone(),
+                quarantine_path,
+                size,
+                reason: file.reason.clone(),
+                detected_at: Utc::now(),
+            });
+            manifest.files.remove(&file.remote_path);
+            moved += 1;
+        }
+        for remote_path in &self.missing {
+            manifest.files.remove(remote_path);
+        }
+        manifest.save()?;
+        Ok(moved)
+    }
+}
+
+/// 按下载清单中记录的大小与 SHA-256 核对本地文件，校验和按 CPU 核心数同时计算
+pub fn verify_archive(storage: &LocalFileStorage) -> Result<IntegrityReport, Box<dyn Error>> {
+    let manifest = Manifest::load(&storage.base_path)?;
+    let mut report = IntegrityReport::default();
+    let mut pending = Vec::new();
+    for (remote_path, entry) in &manifest.files {
+        let Some(sha256) = &entry.sha256 else {
+            report.unrecorded += 1;
+            continue;
+        };
+        match fs::metadata(&entry.local_path) {
+            Ok(metadata) if metadata.is_file() => {
+                if metadata.len() != entry.size {
+                    report.corrupted.push(DamagedFile {
+                        remote_path: remote_path.clone(),
+                        local_path: entry.local_path.clone(),
+                        reason: format!(
+                            "大小为 {} 字节，记录为 {} 字节",
+                            metadata.len(),
+                            entry.size
+                        ),
+                    });
+                } else {
+                    pending.push((remote_path, &entry.local_path, sha256));
+                }
+            }
+            _ if storage.decompressed
+                && decompressed_path(&entry.local_path).is_some_and(|path| path.is_file()) =>
+            {
+                report.decompressed += 1;
+            }
+            _ => report.missing.push(remote_path.clone()),
+        }
+    }
+
+    let paths: Vec<PathBuf> = pending
+        .iter()
+        .map(|(_, path, _)| path.to_path_buf())
+        .collect();
+    let checksums = hash_files(&paths, ChecksumAlgorithm::Sha256, |done| {
+        if done % PROGRESS_EVERY == 0 {
+            tracing::info!("已核对 {}/{} 个文件", done, paths.len());
+        }
+    });
+    for ((remote_path, local_path, sha256), checksum) in pending.into_iter().zip(checksums) {
+        let damaged = |reason: String| DamagedFile {
+            remote_path: remote_path.clone(),
+            local_path: local_path.clone(),
+            reason,
+        };
+        match checksum {
+            Ok(actual) if actual == *sha256 => report.verified += 1,
+            Ok(_) => report
+                .corrupted
+                .push(damaged("校验和与记录不一致".to_string())),
+            Err(e) => report.unreadable.push(damaged(e.to_string())),
+        }
+    }
+    Ok(report)
+}